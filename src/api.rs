@@ -0,0 +1,138 @@
+use async_trait::async_trait;
+use futures::stream::BoxStream;
+use futures::StreamExt;
+
+use crate::error::OllamaError;
+use crate::models::*;
+use crate::OllamaClient;
+
+/// Boxed stream of responses returned by the streaming endpoints.
+pub type ResponseStream<T> = BoxStream<'static, Result<T, OllamaError>>;
+
+/// The Ollama API surface, implemented by [`OllamaClient`] and [`crate::mock::MockOllama`].
+///
+/// Application code can depend on this trait instead of the concrete client so it
+/// can be tested offline or pointed at an alternative backend.
+#[async_trait]
+pub trait OllamaApi: Send + Sync {
+    /// Lists all locally available models.
+    async fn list_models(&self) -> Result<Vec<ModelInfo>, OllamaError>;
+
+    /// Shows information about a specific model.
+    async fn show_model(&self, model_name: &str) -> Result<ShowModelResponse, OllamaError>;
+
+    /// Pulls a model from the registry.
+    async fn pull_model(
+        &self,
+        model_name: &str,
+    ) -> Result<ResponseStream<PullResponse>, OllamaError>;
+
+    /// Pushes a model to the registry.
+    async fn push_model(
+        &self,
+        model_name: &str,
+    ) -> Result<ResponseStream<PushResponse>, OllamaError>;
+
+    /// Creates a new model.
+    async fn create_model(
+        &self,
+        request: CreateModelRequest,
+    ) -> Result<ResponseStream<CreateResponse>, OllamaError>;
+
+    /// Deletes a model.
+    async fn delete_model(&self, model_name: &str) -> Result<(), OllamaError>;
+
+    /// Copies a model under a new name.
+    async fn copy_model(&self, source: &str, destination: &str) -> Result<(), OllamaError>;
+
+    /// Generates a completion using a model.
+    async fn generate(
+        &self,
+        request: GenerateRequest,
+    ) -> Result<ResponseStream<GenerateResponse>, OllamaError>;
+
+    /// Chats with a model.
+    async fn chat(&self, request: ChatRequest)
+        -> Result<ResponseStream<ChatResponse>, OllamaError>;
+
+    /// Generates embeddings from a model.
+    async fn generate_embeddings(
+        &self,
+        request: EmbedRequest,
+    ) -> Result<EmbedResponse, OllamaError>;
+
+    /// Lists running models.
+    async fn list_running_models(&self) -> Result<Vec<RunningModelInfo>, OllamaError>;
+
+    /// Retrieves the Ollama version.
+    async fn get_version(&self) -> Result<String, OllamaError>;
+}
+
+#[async_trait]
+impl OllamaApi for OllamaClient {
+    async fn list_models(&self) -> Result<Vec<ModelInfo>, OllamaError> {
+        OllamaClient::list_models(self).await
+    }
+
+    async fn show_model(&self, model_name: &str) -> Result<ShowModelResponse, OllamaError> {
+        OllamaClient::show_model(self, model_name).await
+    }
+
+    async fn pull_model(
+        &self,
+        model_name: &str,
+    ) -> Result<ResponseStream<PullResponse>, OllamaError> {
+        Ok(OllamaClient::pull_model(self, model_name).await?.boxed())
+    }
+
+    async fn push_model(
+        &self,
+        model_name: &str,
+    ) -> Result<ResponseStream<PushResponse>, OllamaError> {
+        Ok(OllamaClient::push_model(self, model_name).await?.boxed())
+    }
+
+    async fn create_model(
+        &self,
+        request: CreateModelRequest,
+    ) -> Result<ResponseStream<CreateResponse>, OllamaError> {
+        Ok(OllamaClient::create_model(self, request).await?.boxed())
+    }
+
+    async fn delete_model(&self, model_name: &str) -> Result<(), OllamaError> {
+        OllamaClient::delete_model(self, model_name).await
+    }
+
+    async fn copy_model(&self, source: &str, destination: &str) -> Result<(), OllamaError> {
+        OllamaClient::copy_model(self, source, destination).await
+    }
+
+    async fn generate(
+        &self,
+        request: GenerateRequest,
+    ) -> Result<ResponseStream<GenerateResponse>, OllamaError> {
+        Ok(OllamaClient::generate(self, request).await?.boxed())
+    }
+
+    async fn chat(
+        &self,
+        request: ChatRequest,
+    ) -> Result<ResponseStream<ChatResponse>, OllamaError> {
+        Ok(OllamaClient::chat(self, request).await?.boxed())
+    }
+
+    async fn generate_embeddings(
+        &self,
+        request: EmbedRequest,
+    ) -> Result<EmbedResponse, OllamaError> {
+        OllamaClient::generate_embeddings(self, request).await
+    }
+
+    async fn list_running_models(&self) -> Result<Vec<RunningModelInfo>, OllamaError> {
+        OllamaClient::list_running_models(self).await
+    }
+
+    async fn get_version(&self) -> Result<String, OllamaError> {
+        OllamaClient::get_version(self).await
+    }
+}
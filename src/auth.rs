@@ -0,0 +1,213 @@
+//! Credentials attached to every request.
+//!
+//! An [`AuthProvider`] supplies headers for each request sent by
+//! [`OllamaClient`](crate::OllamaClient). When the server answers
+//! `401 Unauthorized`, the client calls [`AuthProvider::refresh`] once and
+//! retries; a second 401 fails with [`OllamaError::Unauthorized`].
+//!
+//! Built-in providers:
+//!
+//! - [`BearerToken`]: a static `Authorization: Bearer` token.
+//! - [`BasicAuth`]: HTTP basic authentication.
+//! - [`HeaderAuth`]: any fixed header, e.g. `X-Api-Key`.
+//! - [`RefreshingToken`]: a bearer token fetched by an async function and
+//!   fetched again when the server rejects it.
+
+use std::future::Future;
+
+use async_trait::async_trait;
+use futures::future::BoxFuture;
+use futures::FutureExt;
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue, AUTHORIZATION};
+use tokio::sync::RwLock;
+
+use crate::error::OllamaError;
+
+/// Headers for one request, and the generation of the credentials in them.
+#[derive(Debug, Clone, Default)]
+pub struct Credentials {
+    pub headers: HeaderMap,
+    /// Identifies the credentials when they are passed back to
+    /// [`AuthProvider::refresh`]. Providers that never refresh leave it at 0.
+    pub generation: u64,
+}
+
+impl From<HeaderMap> for Credentials {
+    fn from(headers: HeaderMap) -> Self {
+        Credentials {
+            headers,
+            generation: 0,
+        }
+    }
+}
+
+/// Supplies credentials for requests.
+#[async_trait]
+pub trait AuthProvider: Send + Sync {
+    /// Credentials added to the next request.
+    async fn headers(&self) -> Result<Credentials, OllamaError>;
+
+    /// Called after the server rejected the credentials of `generation`.
+    /// Returns whether newer credentials are available, in which case the
+    /// request is retried once.
+    async fn refresh(&self, generation: u64) -> Result<bool, OllamaError> {
+        let _ = generation;
+        Ok(false)
+    }
+}
+
+fn sensitive_value(value: &str) -> Result<HeaderValue, OllamaError> {
+    let mut value = HeaderValue::from_str(value)
+        .map_err(|_| OllamaError::Auth("credentials contain invalid characters".to_string()))?;
+    value.set_sensitive(true);
+    Ok(value)
+}
+
+fn single_header(name: HeaderName, value: HeaderValue) -> HeaderMap {
+    let mut headers = HeaderMap::new();
+    headers.insert(name, value);
+    headers
+}
+
+/// Sends a fixed `Authorization: Bearer <token>` header.
+#[derive(Clone)]
+pub struct BearerToken {
+    token: String,
+}
+
+impl BearerToken {
+    pub fn new(token: impl Into<String>) -> Self {
+        BearerToken {
+            token: token.into(),
+        }
+    }
+}
+
+#[async_trait]
+impl AuthProvider for BearerToken {
+    async fn headers(&self) -> Result<Credentials, OllamaError> {
+        let value = sensitive_value(&format!("Bearer {}", self.token))?;
+        Ok(single_header(AUTHORIZATION, value).into())
+    }
+}
+
+/// Sends `Authorization: Basic` credentials.
+#[derive(Clone)]
+pub struct BasicAuth {
+    username: String,
+    password: Option<String>,
+}
+
+impl BasicAuth {
+    pub fn new(username: impl Into<String>, password: Option<String>) -> Self {
+        BasicAuth {
+            username: username.into(),
+            password,
+        }
+    }
+}
+
+#[async_trait]
+impl AuthProvider for BasicAuth {
+    async fn headers(&self) -> Result<Credentials, OllamaError> {
+        let credentials = format!(
+            "{}:{}",
+            self.username,
+            self.password.as_deref().unwrap_or_default()
+        );
+        let value = sensitive_value(&format!(
+            "Basic {}",
+            crate::base64::encode(credentials.as_bytes())
+        ))?;
+        Ok(single_header(AUTHORIZATION, value).into())
+    }
+}
+
+/// Sends a fixed header, e.g. `X-Api-Key: <key>`.
+#[derive(Clone)]
+pub struct HeaderAuth {
+    name: String,
+    value: String,
+}
+
+impl HeaderAuth {
+    pub fn new(name: impl Into<String>, value: impl Into<String>) -> Self {
+        HeaderAuth {
+            name: name.into(),
+            value: value.into(),
+        }
+    }
+}
+
+#[async_trait]
+impl AuthProvider for HeaderAuth {
+    async fn headers(&self) -> Result<Credentials, OllamaError> {
+        let name = HeaderName::from_bytes(self.name.as_bytes())
+            .map_err(|_| OllamaError::Auth(format!("invalid header name '{}'", self.name)))?;
+        Ok(single_header(name, sensitive_value(&self.value)?).into())
+    }
+}
+
+type FetchToken = dyn Fn() -> BoxFuture<'static, Result<String, OllamaError>> + Send + Sync;
+
+/// A bearer token obtained from an async function, such as an OAuth client
+/// credentials exchange.
+///
+/// The token is fetched before the first request and cached. When the server
+/// rejects it, it is fetched again; concurrent requests share one refresh.
+pub struct RefreshingToken {
+    fetch: Box<FetchToken>,
+    token: RwLock<Option<(u64, String)>>,
+}
+
+impl RefreshingToken {
+    pub fn new<F, Fut>(fetch: F) -> Self
+    where
+        F: Fn() -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<String, OllamaError>> + Send + 'static,
+    {
+        RefreshingToken {
+            fetch: Box::new(move || fetch().boxed()),
+            token: RwLock::new(None),
+        }
+    }
+
+    /// Returns the cached token and its generation, fetching it if needed.
+    async fn current(&self) -> Result<(u64, String), OllamaError> {
+        if let Some(token) = self.token.read().await.clone() {
+            return Ok(token);
+        }
+        let mut token = self.token.write().await;
+        if let Some(token) = token.clone() {
+            return Ok(token);
+        }
+        let fetched = (0, (self.fetch)().await?);
+        *token = Some(fetched.clone());
+        Ok(fetched)
+    }
+}
+
+#[async_trait]
+impl AuthProvider for RefreshingToken {
+    async fn headers(&self) -> Result<Credentials, OllamaError> {
+        let (generation, token) = self.current().await?;
+        let value = sensitive_value(&format!("Bearer {}", token))?;
+        Ok(Credentials {
+            headers: single_header(AUTHORIZATION, value),
+            generation,
+        })
+    }
+
+    async fn refresh(&self, generation: u64) -> Result<bool, OllamaError> {
+        let mut token = self.token.write().await;
+        // Another request already replaced the rejected token
+        if token
+            .as_ref()
+            .is_some_and(|(current, _)| *current > generation)
+        {
+            return Ok(true);
+        }
+        *token = Some((generation + 1, (self.fetch)().await?));
+        Ok(true)
+    }
+}
@@ -0,0 +1,313 @@
+//! Batch jobs over JSONL request files.
+//!
+//! Each line of the input is a [`GenerateRequest`], [`ChatRequest`] or
+//! [`EmbedRequest`] with an `id`, told apart by their `messages` and `input`
+//! fields:
+//!
+//! ```text
+//! {"id": "q1", "model": "llama3.2", "prompt": "Why is the sky blue?"}
+//! {"id": "q2", "model": "llama3.2", "messages": [{"role": "user", "content": "Hi"}]}
+//! {"id": "q3", "model": "all-minilm", "input": ["first", "second"]}
+//! ```
+//!
+//! Lines without an id are identified by their line number. Each result is
+//! appended to the output as soon as it is ready, with streamed responses
+//! aggregated into one:
+//!
+//! ```text
+//! {"id": "q2", "response": {"model": "llama3.2", "message": {...}, ...}}
+//! {"id": "q1", "error": "API returned an error: ..."}
+//! ```
+//!
+//! The output doubles as the checkpoint: running a job again with the same
+//! output skips the ids that already have a response, so an interrupted job
+//! resumes where it stopped and failed requests are retried.
+
+use std::collections::HashSet;
+use std::fmt;
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+use futures::{stream, StreamExt, TryStreamExt};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::api::{OllamaApi, ResponseStream};
+use crate::error::OllamaError;
+use crate::interceptor::Streamed;
+use crate::models::*;
+
+/// A request of a batch job.
+#[derive(Debug, Clone)]
+pub enum BatchRequest {
+    Generate(GenerateRequest),
+    Chat(ChatRequest),
+    Embed(EmbedRequest),
+}
+
+/// One line of a batch input.
+#[derive(Debug, Clone)]
+pub struct BatchItem {
+    pub id: String,
+    pub request: BatchRequest,
+}
+
+impl BatchItem {
+    /// Parses a line of the input. `line_number` (1-based) is the id of lines
+    /// without one.
+    pub fn parse(line: &str, line_number: usize) -> Result<BatchItem, OllamaError> {
+        let mut value: Value = serde_json::from_str(line)?;
+        let id = line_id(&value, line_number);
+        if let Some(fields) = value.as_object_mut() {
+            fields.remove("id");
+        }
+        let request = if value.get("messages").is_some() {
+            BatchRequest::Chat(serde_json::from_value(value)?)
+        } else if value.get("input").is_some() {
+            BatchRequest::Embed(serde_json::from_value(value)?)
+        } else {
+            BatchRequest::Generate(serde_json::from_value(value)?)
+        };
+        Ok(BatchItem { id, request })
+    }
+}
+
+fn line_id(value: &Value, line_number: usize) -> String {
+    match value.get("id") {
+        Some(Value::String(id)) => id.clone(),
+        Some(Value::Number(id)) => id.to_string(),
+        _ => line_number.to_string(),
+    }
+}
+
+/// One line of a batch output.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct BatchResult {
+    pub id: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub response: Option<Value>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// Options for [`run_batch`].
+#[derive(Debug, Clone)]
+pub struct BatchOptions {
+    /// Maximum number of requests in flight at once.
+    pub concurrency: usize,
+}
+
+impl Default for BatchOptions {
+    fn default() -> Self {
+        BatchOptions { concurrency: 4 }
+    }
+}
+
+/// Summary of a finished batch job.
+#[derive(Debug, Clone, Default)]
+pub struct BatchReport {
+    /// Lines in the input.
+    pub total: usize,
+    /// Requests skipped because the output already had their response.
+    pub skipped: usize,
+    pub succeeded: usize,
+    /// Requests that failed, including input lines that could not be parsed.
+    pub failed: usize,
+    pub prompt_tokens: u64,
+    pub eval_tokens: u64,
+    pub elapsed: Duration,
+}
+
+impl BatchReport {
+    /// Requests run per second, excluding skipped ones.
+    pub fn requests_per_second(&self) -> f64 {
+        per_second((self.succeeded + self.failed) as f64, self.elapsed)
+    }
+
+    /// Generated tokens per second.
+    pub fn tokens_per_second(&self) -> f64 {
+        per_second(self.eval_tokens as f64, self.elapsed)
+    }
+}
+
+fn per_second(count: f64, elapsed: Duration) -> f64 {
+    match elapsed.as_secs_f64() {
+        secs if secs > 0.0 => count / secs,
+        _ => 0.0,
+    }
+}
+
+impl fmt::Display for BatchReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} succeeded, {} failed, {} skipped in {:.1}s ({:.2} requests/s); \
+             {} prompt tokens, {} generated tokens ({:.1} tokens/s)",
+            self.succeeded,
+            self.failed,
+            self.skipped,
+            self.elapsed.as_secs_f64(),
+            self.requests_per_second(),
+            self.prompt_tokens,
+            self.eval_tokens,
+            self.tokens_per_second()
+        )
+    }
+}
+
+/// Runs every request in the JSONL file `input` that has no response in
+/// `output` yet, up to `options.concurrency` at once, and appends the results
+/// to `output`. Failed requests are recorded in the output and do not stop
+/// the job.
+pub async fn run_batch<A: OllamaApi + ?Sized>(
+    api: &A,
+    input: &Path,
+    output: &Path,
+    options: BatchOptions,
+) -> Result<BatchReport, OllamaError> {
+    let start = Instant::now();
+    let input = fs::read_to_string(input)
+        .map_err(|e| OllamaError::Batch(format!("reading {}: {}", input.display(), e)))?;
+    let (completed, needs_newline) = read_checkpoint(output)?;
+    let mut file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(output)
+        .map_err(|e| OllamaError::Batch(format!("opening {}: {}", output.display(), e)))?;
+    if needs_newline {
+        // Terminate a line cut short by an interrupted run
+        write_line(&mut file, "")?;
+    }
+
+    let mut report = BatchReport::default();
+    let mut pending = Vec::new();
+    for (index, line) in input.lines().enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        }
+        report.total += 1;
+        match BatchItem::parse(line, index + 1) {
+            Ok(item) if completed.contains(&item.id) => report.skipped += 1,
+            Ok(item) => pending.push(item),
+            Err(error) => {
+                let id = serde_json::from_str(line)
+                    .map(|value| line_id(&value, index + 1))
+                    .unwrap_or_else(|_| (index + 1).to_string());
+                if completed.contains(&id) {
+                    report.skipped += 1;
+                } else {
+                    report.failed += 1;
+                    write_result(&mut file, id, Err(error))?;
+                }
+            }
+        }
+    }
+
+    let mut results = stream::iter(pending)
+        .map(|item| async move { (item.id, run_item(api, item.request).await) })
+        .buffer_unordered(options.concurrency.max(1));
+    while let Some((id, result)) = results.next().await {
+        match &result {
+            Ok((_, prompt_tokens, eval_tokens)) => {
+                report.succeeded += 1;
+                report.prompt_tokens += u64::from(*prompt_tokens);
+                report.eval_tokens += u64::from(*eval_tokens);
+            }
+            Err(_) => report.failed += 1,
+        }
+        write_result(&mut file, id, result.map(|(response, ..)| response))?;
+    }
+
+    report.elapsed = start.elapsed();
+    Ok(report)
+}
+
+/// Returns the ids with a response in an existing output, and whether its
+/// last line is unterminated.
+fn read_checkpoint(output: &Path) -> Result<(HashSet<String>, bool), OllamaError> {
+    let contents = match fs::read_to_string(output) {
+        Ok(contents) => contents,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => String::new(),
+        Err(e) => {
+            return Err(OllamaError::Batch(format!(
+                "reading {}: {}",
+                output.display(),
+                e
+            )))
+        }
+    };
+    let completed = contents
+        .lines()
+        .filter_map(|line| serde_json::from_str::<BatchResult>(line).ok())
+        .filter(|result| result.response.is_some())
+        .map(|result| result.id)
+        .collect();
+    Ok((completed, !contents.is_empty() && !contents.ends_with('\n')))
+}
+
+/// Runs one request, returning the response with its prompt and generated
+/// token counts.
+async fn run_item<A: OllamaApi + ?Sized>(
+    api: &A,
+    request: BatchRequest,
+) -> Result<(Value, u32, u32), OllamaError> {
+    match request {
+        BatchRequest::Generate(request) => {
+            let response = aggregate(api.generate(request).await?).await?;
+            let tokens = (
+                response.prompt_eval_count.unwrap_or(0),
+                response.eval_count.unwrap_or(0),
+            );
+            Ok((serde_json::to_value(response)?, tokens.0, tokens.1))
+        }
+        BatchRequest::Chat(request) => {
+            let response = aggregate(api.chat(request).await?).await?;
+            let tokens = (
+                response.prompt_eval_count.unwrap_or(0),
+                response.eval_count.unwrap_or(0),
+            );
+            Ok((serde_json::to_value(response)?, tokens.0, tokens.1))
+        }
+        BatchRequest::Embed(request) => {
+            let response = api.generate_embeddings(request).await?;
+            let prompt_tokens = response.prompt_eval_count.unwrap_or(0);
+            Ok((serde_json::to_value(response)?, prompt_tokens, 0))
+        }
+    }
+}
+
+/// Folds a streamed response into one.
+async fn aggregate<T: Streamed>(stream: ResponseStream<T>) -> Result<T::Aggregate, OllamaError> {
+    stream
+        .try_fold(T::Aggregate::default(), |mut aggregate, chunk| async move {
+            T::aggregate(&mut aggregate, &chunk);
+            Ok(aggregate)
+        })
+        .await
+}
+
+fn write_result(
+    file: &mut fs::File,
+    id: String,
+    result: Result<Value, OllamaError>,
+) -> Result<(), OllamaError> {
+    let (response, error) = match result {
+        Ok(response) => (Some(response), None),
+        Err(error) => (None, Some(error.to_string())),
+    };
+    let line = serde_json::to_string(&BatchResult {
+        id,
+        response,
+        error,
+    })?;
+    write_line(file, &line)
+}
+
+/// Writes `line` with a single write, so an interrupted run leaves at most
+/// one partial line.
+fn write_line(file: &mut fs::File, line: &str) -> Result<(), OllamaError> {
+    file.write_all(format!("{}\n", line).as_bytes())
+        .map_err(|e| OllamaError::Batch(format!("writing output: {}", e)))
+}
@@ -0,0 +1,39 @@
+//! OpenAI-compatible gateway in front of Ollama.
+//!
+//! Usage: `oxide-proxy [config.json]`. See [`ollama_oxide::server::ProxyConfig`]
+//! for the configuration format; without a file the proxy listens on
+//! `127.0.0.1:8080`, forwards to the server in `OLLAMA_HOST` (see
+//! [`ollama_oxide::config`]) and requires no key.
+
+use std::sync::Arc;
+
+use ollama_oxide::server::{router, ProxyConfig};
+use ollama_oxide::OllamaClient;
+
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let config: ProxyConfig = match std::env::args().nth(1) {
+        Some(path) => serde_json::from_str(&std::fs::read_to_string(path)?)?,
+        None => ProxyConfig::default(),
+    };
+    let listen = config
+        .listen
+        .clone()
+        .unwrap_or_else(|| "127.0.0.1:8080".to_string());
+    let client = match &config.ollama_url {
+        Some(url) => OllamaClient::new(url),
+        None => OllamaClient::from_env(),
+    };
+    if config.keys.is_empty() {
+        eprintln!("warning: no API keys configured, the proxy is open to anyone who can reach it");
+    }
+
+    let listener = tokio::net::TcpListener::bind(&listen).await?;
+    eprintln!(
+        "forwarding {} to {}",
+        listener.local_addr()?,
+        client.base_url()
+    );
+    axum::serve(listener, router(Arc::new(client), config)).await?;
+    Ok(())
+}
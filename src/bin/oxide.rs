@@ -0,0 +1,605 @@
+//! `oxide`: a command-line client for Ollama built on [`ollama_oxide`].
+//!
+//! Reads the server address from `--host` or `OLLAMA_HOST` and an optional
+//! bearer token from `OLLAMA_API_KEY`. Every command accepts `--json` for
+//! machine-readable output.
+
+use std::error::Error;
+use std::io::{BufRead, Write};
+use std::path::PathBuf;
+use std::process::ExitCode;
+
+use clap::{Parser, Subcommand, ValueEnum};
+use futures::{Stream, StreamExt, TryStreamExt};
+use indicatif::{ProgressBar, ProgressStyle};
+use ollama_oxide::batch::{self, BatchOptions};
+use ollama_oxide::config;
+use ollama_oxide::error::OllamaError;
+use ollama_oxide::modelfile::Modelfile;
+use ollama_oxide::models::*;
+use ollama_oxide::OllamaClient;
+use serde::Serialize;
+use serde_json::json;
+
+type CliResult = Result<(), Box<dyn Error>>;
+
+#[derive(Parser)]
+#[command(name = "oxide", version, about = "Command-line client for Ollama")]
+struct Cli {
+    /// Ollama server, e.g. `localhost:11434` or `http://gpu-box:11434`
+    #[arg(long, env = "OLLAMA_HOST", global = true)]
+    host: Option<String>,
+
+    /// Print JSON (or NDJSON for streams) instead of human-readable output
+    #[arg(long, global = true)]
+    json: bool,
+
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// List local models
+    #[command(alias = "ls")]
+    List,
+    /// List running models
+    Ps,
+    /// Show information about a model
+    Show {
+        model: String,
+        /// Show the Modelfile
+        #[arg(long)]
+        modelfile: bool,
+        /// Show the parameters
+        #[arg(long)]
+        parameters: bool,
+        /// Show the prompt template
+        #[arg(long)]
+        template: bool,
+        /// Show the system prompt
+        #[arg(long)]
+        system: bool,
+        /// Show the license
+        #[arg(long)]
+        license: bool,
+    },
+    /// Pull a model from a registry
+    Pull { model: String },
+    /// Push a model to a registry
+    Push { model: String },
+    /// Remove models
+    Rm {
+        #[arg(required = true)]
+        models: Vec<String>,
+    },
+    /// Copy a model
+    Cp { source: String, destination: String },
+    /// Create a model from a Modelfile
+    Create {
+        model: String,
+        /// Path to the Modelfile
+        #[arg(short, long, default_value = "Modelfile")]
+        file: PathBuf,
+    },
+    /// Chat with a model; without a prompt, start an interactive session
+    Run {
+        model: String,
+        prompt: Vec<String>,
+        /// System prompt for the session
+        #[arg(long)]
+        system: Option<String>,
+    },
+    /// Generate a completion; the prompt is read from stdin if not given
+    Generate { model: String, prompt: Vec<String> },
+    /// Embed inputs; one input per line is read from stdin if none are given
+    Embed {
+        model: String,
+        inputs: Vec<String>,
+        #[arg(long, value_enum, default_value_t = EmbedFormat::Json)]
+        format: EmbedFormat,
+    },
+    /// Run a JSONL file of generate, chat and embed requests; rerun with the
+    /// same output to resume an interrupted job
+    Batch {
+        /// Requests, one JSON object with an `id` per line
+        input: PathBuf,
+        /// Results are appended here, one JSON object per line
+        #[arg(short, long)]
+        output: PathBuf,
+        /// Maximum number of requests in flight at once
+        #[arg(short, long, default_value_t = 4)]
+        concurrency: usize,
+    },
+    /// Show the client and server versions
+    Version,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum EmbedFormat {
+    Json,
+    Csv,
+}
+
+#[tokio::main]
+async fn main() -> ExitCode {
+    let cli = Cli::parse();
+    let client = OllamaClient::new(&config::parse_host(&cli.host.unwrap_or_default()));
+    let client = match config::api_key_from_env() {
+        Some(key) => client.with_api_key(&key),
+        None => client,
+    };
+    match run(&client, cli.command, cli.json).await {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+async fn run(client: &OllamaClient, command: Command, json: bool) -> CliResult {
+    match command {
+        Command::List => {
+            let models = client.list_models().await?;
+            if json {
+                return print_json(&models);
+            }
+            let rows = models.iter().map(|model| {
+                [
+                    model.name.clone(),
+                    short_digest(&model.digest),
+                    human_size(model.size),
+                    model.modified_at.clone(),
+                ]
+            });
+            print_table(["NAME", "ID", "SIZE", "MODIFIED"], rows);
+        }
+        Command::Ps => {
+            let models = client.list_running_models().await?;
+            if json {
+                return print_json(&models);
+            }
+            let rows = models.iter().map(|model| {
+                [
+                    model.name.clone(),
+                    short_digest(&model.digest),
+                    human_size(model.size),
+                    processor(model.size, model.size_vram),
+                    model.expires_at.clone(),
+                ]
+            });
+            print_table(["NAME", "ID", "SIZE", "PROCESSOR", "UNTIL"], rows);
+        }
+        Command::Show {
+            model,
+            modelfile,
+            parameters,
+            template,
+            system,
+            license,
+        } => {
+            let info = client.show_model(&model).await?;
+            let field = match (modelfile, parameters, template, system, license) {
+                (true, ..) => Some(info.modelfile.clone()),
+                (_, true, ..) => Some(info.parameters.clone()),
+                (_, _, true, ..) => Some(info.template.clone()),
+                (.., true, _) => Some(info.system.clone()),
+                (.., true) => Some(info.license.clone()),
+                _ => None,
+            };
+            match (field, json) {
+                (Some(value), true) => print_json(&value)?,
+                (Some(value), false) => println!("{}", value.unwrap_or_default()),
+                (None, true) => print_json(&info)?,
+                (None, false) => print_model_info(&info),
+            }
+        }
+        Command::Pull { model } => {
+            let progress = client.pull_model(&model).await?.map_ok(|p| {
+                let progress = (p.status.clone(), p.digest.clone(), p.total, p.completed);
+                (serde_json::to_value(p).unwrap_or_default(), progress)
+            });
+            show_progress(progress, json).await?;
+        }
+        Command::Push { model } => {
+            let progress = client.push_model(&model).await?.map_ok(|p| {
+                let progress = (p.status.clone(), p.digest.clone(), p.total, p.completed);
+                (serde_json::to_value(p).unwrap_or_default(), progress)
+            });
+            show_progress(progress, json).await?;
+        }
+        Command::Rm { models } => {
+            for model in models {
+                client.delete_model(&model).await?;
+                if json {
+                    print_json(&json!({ "deleted": model }))?;
+                } else {
+                    println!("deleted '{}'", model);
+                }
+            }
+        }
+        Command::Cp {
+            source,
+            destination,
+        } => {
+            client.copy_model(&source, &destination).await?;
+            if json {
+                print_json(&json!({ "source": source, "destination": destination }))?;
+            } else {
+                println!("copied '{}' to '{}'", source, destination);
+            }
+        }
+        Command::Create { model, file } => {
+            let text = std::fs::read_to_string(&file)
+                .map_err(|e| format!("reading {}: {}", file.display(), e))?;
+            let request = Modelfile::parse(&text)?.into_create_request(&model);
+            let mut status = Box::pin(client.create_model(request).await?);
+            while let Some(update) = status.try_next().await? {
+                if json {
+                    print_json(&update)?;
+                } else if let Some(status) = update.status {
+                    println!("{}", status);
+                }
+            }
+        }
+        Command::Run {
+            model,
+            prompt,
+            system,
+        } => {
+            let mut history = Vec::new();
+            if let Some(system) = system {
+                history.push(message("system", system));
+            }
+            if prompt.is_empty() {
+                repl(client, &model, history).await?;
+            } else {
+                history.push(message("user", prompt.join(" ")));
+                stream_chat(client, &model, &history, json).await?;
+            }
+        }
+        Command::Generate { model, prompt } => {
+            let prompt = match prompt.is_empty() {
+                true => std::io::read_to_string(std::io::stdin())?,
+                false => prompt.join(" "),
+            };
+            let request = GenerateRequest {
+                model,
+                prompt,
+                ..Default::default()
+            };
+            let mut chunks = Box::pin(client.generate(request).await?);
+            let mut stdout = std::io::stdout();
+            while let Some(chunk) = chunks.try_next().await? {
+                if json {
+                    print_json(&chunk)?;
+                } else {
+                    write!(stdout, "{}", chunk.response)?;
+                    stdout.flush()?;
+                }
+            }
+            if !json {
+                println!();
+            }
+        }
+        Command::Embed {
+            model,
+            inputs,
+            format,
+        } => {
+            let inputs = match inputs.is_empty() {
+                true => std::io::stdin().lines().collect::<Result<Vec<_>, _>>()?,
+                false => inputs,
+            };
+            let response = client
+                .generate_multiple_embeddings(model, inputs.clone(), None, None, None)
+                .await?;
+            match (format, json) {
+                (EmbedFormat::Csv, false) => {
+                    for (input, embedding) in inputs.iter().zip(&response.embeddings) {
+                        let values: Vec<String> = embedding.iter().map(f32::to_string).collect();
+                        println!("{},{}", csv_field(input), values.join(","));
+                    }
+                }
+                _ => print_json(&json!({
+                    "model": response.model,
+                    "inputs": inputs,
+                    "embeddings": response.embeddings,
+                }))?,
+            }
+        }
+        Command::Batch {
+            input,
+            output,
+            concurrency,
+        } => {
+            let options = BatchOptions { concurrency };
+            let report = batch::run_batch(client, &input, &output, options).await?;
+            if json {
+                print_json(&json!({
+                    "total": report.total,
+                    "skipped": report.skipped,
+                    "succeeded": report.succeeded,
+                    "failed": report.failed,
+                    "prompt_tokens": report.prompt_tokens,
+                    "eval_tokens": report.eval_tokens,
+                    "elapsed_seconds": report.elapsed.as_secs_f64(),
+                    "requests_per_second": report.requests_per_second(),
+                    "tokens_per_second": report.tokens_per_second(),
+                }))?;
+            } else {
+                println!("{}", report);
+            }
+        }
+        Command::Version => {
+            let server = client.get_version().await?;
+            let client_version = env!("CARGO_PKG_VERSION");
+            if json {
+                print_json(&json!({ "client": client_version, "server": server }))?;
+            } else {
+                println!("client version is {}", client_version);
+                println!("server version is {}", server);
+            }
+        }
+    }
+    Ok(())
+}
+
+fn print_json<T: Serialize>(value: &T) -> CliResult {
+    println!("{}", serde_json::to_string(value)?);
+    Ok(())
+}
+
+fn print_table<const N: usize>(headers: [&str; N], rows: impl Iterator<Item = [String; N]>) {
+    let rows: Vec<[String; N]> = rows.collect();
+    let mut widths = headers.map(str::len);
+    for row in &rows {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.len());
+        }
+    }
+    let line = |cells: Vec<&str>| {
+        let padded: Vec<String> = cells
+            .iter()
+            .zip(widths)
+            .map(|(cell, width)| format!("{:<width$}", cell, width = width))
+            .collect();
+        println!("{}", padded.join("    ").trim_end());
+    };
+    line(headers.to_vec());
+    for row in &rows {
+        line(row.iter().map(String::as_str).collect());
+    }
+}
+
+fn short_digest(digest: &str) -> String {
+    digest
+        .trim_start_matches("sha256:")
+        .chars()
+        .take(12)
+        .collect()
+}
+
+fn human_size(bytes: u64) -> String {
+    const UNITS: [&str; 5] = ["B", "KB", "MB", "GB", "TB"];
+    let mut size = bytes as f64;
+    let mut unit = 0;
+    while size >= 1000.0 && unit < UNITS.len() - 1 {
+        size /= 1000.0;
+        unit += 1;
+    }
+    match unit {
+        0 => format!("{} B", bytes),
+        _ => format!("{:.1} {}", size, UNITS[unit]),
+    }
+}
+
+fn processor(size: u64, size_vram: u64) -> String {
+    if size == 0 || size_vram == 0 {
+        return "100% CPU".to_string();
+    }
+    if size_vram >= size {
+        return "100% GPU".to_string();
+    }
+    let gpu = size_vram * 100 / size;
+    format!("{}%/{}% CPU/GPU", 100 - gpu, gpu)
+}
+
+fn print_model_info(info: &ShowModelResponse) {
+    println!("  Model");
+    if let Some(architecture) = info.architecture() {
+        println!("    architecture        {}", architecture);
+    }
+    if let Some(details) = &info.details {
+        println!("    parameters          {}", details.parameter_size);
+        println!("    quantization        {}", details.quantization_level);
+    }
+    if let Some(context_length) = info.context_length() {
+        println!("    context length      {}", context_length);
+    }
+    if let Some(embedding_length) = info.embedding_length() {
+        println!("    embedding length    {}", embedding_length);
+    }
+    if let Some(capabilities) = info.capabilities.as_ref().filter(|c| !c.is_empty()) {
+        println!("\n  Capabilities");
+        for capability in capabilities {
+            println!("    {}", capability);
+        }
+    }
+    if let Some(parameters) = info.parameters.as_ref().filter(|p| !p.is_empty()) {
+        println!("\n  Parameters");
+        for line in parameters.lines() {
+            println!("    {}", line);
+        }
+    }
+    if let Some(system) = info.system.as_ref().filter(|s| !s.is_empty()) {
+        println!("\n  System\n    {}", system);
+    }
+}
+
+type Progress = (Option<String>, Option<String>, Option<u64>, Option<u64>);
+
+/// Shows pull or push progress as one bar per layer, or as NDJSON.
+async fn show_progress(
+    updates: impl Stream<Item = Result<(serde_json::Value, Progress), OllamaError>>,
+    json: bool,
+) -> CliResult {
+    let mut updates = Box::pin(updates);
+    let mut bar: Option<(String, ProgressBar)> = None;
+    let style =
+        ProgressStyle::with_template("{msg} {bar:40} {bytes}/{total_bytes} {bytes_per_sec} {eta}")?;
+
+    while let Some((raw, (status, digest, total, completed))) = updates.try_next().await? {
+        if json {
+            print_json(&raw)?;
+            continue;
+        }
+        match (digest, total) {
+            (Some(digest), Some(total)) => {
+                if bar.as_ref().is_none_or(|(current, _)| *current != digest) {
+                    if let Some((_, previous)) = bar.take() {
+                        previous.finish();
+                    }
+                    let progress = ProgressBar::new(total).with_style(style.clone());
+                    progress.set_message(format!(
+                        "{} {}",
+                        status.clone().unwrap_or_default(),
+                        short_digest(&digest)
+                    ));
+                    bar = Some((digest, progress));
+                }
+                if let Some((_, progress)) = &bar {
+                    progress.set_position(completed.unwrap_or(0));
+                }
+            }
+            _ => {
+                if let Some((_, previous)) = bar.take() {
+                    previous.finish();
+                }
+                if let Some(status) = status {
+                    println!("{}", status);
+                }
+            }
+        }
+    }
+    if let Some((_, progress)) = bar {
+        progress.finish();
+    }
+    Ok(())
+}
+
+fn message(role: &str, content: String) -> ChatMessage {
+    ChatMessage {
+        role: role.to_string(),
+        content,
+        ..Default::default()
+    }
+}
+
+/// Streams a chat reply to stdout and returns the assembled message.
+async fn stream_chat(
+    client: &OllamaClient,
+    model: &str,
+    history: &[ChatMessage],
+    json: bool,
+) -> Result<ChatMessage, Box<dyn Error>> {
+    let request = ChatRequest {
+        model: model.to_string(),
+        messages: history.to_vec(),
+        ..Default::default()
+    };
+    let mut chunks = Box::pin(client.chat(request).await?);
+    let mut reply = message("assistant", String::new());
+    let mut stdout = std::io::stdout();
+    while let Some(chunk) = chunks.next().await {
+        let chunk = chunk?;
+        if json {
+            print_json(&chunk)?;
+        } else {
+            write!(stdout, "{}", chunk.message.content)?;
+            stdout.flush()?;
+        }
+        reply.content.push_str(&chunk.message.content);
+    }
+    if !json {
+        println!();
+    }
+    Ok(reply)
+}
+
+/// Interactive chat. Input wrapped in `"""` may span several lines.
+async fn repl(client: &OllamaClient, model: &str, mut history: Vec<ChatMessage>) -> CliResult {
+    let stdin = std::io::stdin();
+    let mut lines = stdin.lock().lines();
+    let mut stdout = std::io::stdout();
+    println!("Type /? for help, /bye to exit.");
+
+    loop {
+        write!(stdout, ">>> ")?;
+        stdout.flush()?;
+        let Some(line) = lines.next().transpose()? else {
+            println!();
+            return Ok(());
+        };
+
+        let input = match line.trim_start().strip_prefix("\"\"\"") {
+            Some(first) => {
+                let mut input = first.to_string();
+                if let Some(end) = input.find("\"\"\"") {
+                    input.truncate(end);
+                } else {
+                    loop {
+                        write!(stdout, "... ")?;
+                        stdout.flush()?;
+                        let Some(next) = lines.next().transpose()? else {
+                            break;
+                        };
+                        input.push('\n');
+                        if let Some(end) = next.find("\"\"\"") {
+                            input.push_str(&next[..end]);
+                            break;
+                        }
+                        input.push_str(&next);
+                    }
+                }
+                input.trim().to_string()
+            }
+            None => line.trim().to_string(),
+        };
+
+        match input.as_str() {
+            "" => continue,
+            "/bye" | "/exit" => return Ok(()),
+            "/clear" => {
+                history.retain(|message| message.role == "system");
+                println!("Cleared session context");
+                continue;
+            }
+            "/?" | "/help" => {
+                println!("  /clear   Clear the session context");
+                println!("  /bye     Exit");
+                println!("  Use \"\"\" to begin and end a multi-line message.");
+                continue;
+            }
+            _ => {}
+        }
+
+        history.push(message("user", input));
+        match stream_chat(client, model, &history, false).await {
+            Ok(reply) => history.push(reply),
+            Err(e) => {
+                history.pop();
+                eprintln!("Error: {}", e);
+            }
+        }
+    }
+}
+
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
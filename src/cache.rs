@@ -0,0 +1,275 @@
+//! Embedding caches keyed by model, model digest, truncation and input text.
+//!
+//! Attach a cache with [`crate::OllamaClient::with_embedding_cache`]; the
+//! embedding methods then only send cache misses to Ollama and merge the
+//! results back in input order. Because the key includes the model digest,
+//! re-pulling a model with different weights naturally invalidates its entries.
+//! Digests are looked up with `list_models` and reused for 30 seconds, so a
+//! re-pull is noticed within that time.
+
+use std::collections::{BTreeMap, HashMap};
+use std::sync::{Mutex, MutexGuard};
+use std::time::{Duration, Instant};
+
+use sha2::{Digest, Sha256};
+
+use crate::error::OllamaError;
+use crate::models::*;
+use crate::OllamaClient;
+
+/// Identifies a cached embedding.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct CacheKey {
+    pub model: String,
+    pub digest: String,
+    pub truncate: Option<bool>,
+    /// Hex-encoded SHA-256 of the input text.
+    pub input_hash: String,
+}
+
+impl CacheKey {
+    pub fn new(model: &str, digest: &str, truncate: Option<bool>, input: &str) -> Self {
+        CacheKey {
+            model: model.to_string(),
+            digest: digest.to_string(),
+            truncate,
+            input_hash: format!("{:x}", Sha256::digest(input.as_bytes())),
+        }
+    }
+
+    /// Stable string form of the key, used by persistent backends.
+    pub fn encode(&self) -> String {
+        let truncate = match self.truncate {
+            Some(true) => "t",
+            Some(false) => "f",
+            None => "-",
+        };
+        format!(
+            "{}\u{0}{}\u{0}{}\u{0}{}",
+            self.model, self.digest, truncate, self.input_hash
+        )
+    }
+}
+
+/// Storage backend for cached embeddings.
+///
+/// Backends are best-effort: failures should be logged and treated as misses
+/// rather than failing the embedding request.
+pub trait EmbeddingCache: Send + Sync {
+    fn get(&self, key: &CacheKey) -> Option<Vec<f32>>;
+    fn put(&self, key: CacheKey, embedding: Vec<f32>);
+}
+
+/// In-memory least-recently-used cache.
+#[derive(Debug)]
+pub struct MemoryCache {
+    capacity: usize,
+    state: Mutex<LruState>,
+}
+
+#[derive(Debug, Default)]
+struct LruState {
+    tick: u64,
+    entries: HashMap<CacheKey, (Vec<f32>, u64)>,
+    recency: BTreeMap<u64, CacheKey>,
+}
+
+impl MemoryCache {
+    /// Creates a cache holding at most `capacity` embeddings.
+    pub fn new(capacity: usize) -> Self {
+        MemoryCache {
+            capacity,
+            state: Mutex::new(LruState::default()),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.state().entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    fn state(&self) -> MutexGuard<'_, LruState> {
+        crate::lock(&self.state)
+    }
+}
+
+impl LruState {
+    fn touch(&mut self, key: &CacheKey) {
+        self.tick += 1;
+        let tick = self.tick;
+        if let Some((_, used)) = self.entries.get_mut(key) {
+            self.recency.remove(used);
+            *used = tick;
+            self.recency.insert(tick, key.clone());
+        }
+    }
+}
+
+impl EmbeddingCache for MemoryCache {
+    fn get(&self, key: &CacheKey) -> Option<Vec<f32>> {
+        let mut state = self.state();
+        state.touch(key);
+        state
+            .entries
+            .get(key)
+            .map(|(embedding, _)| embedding.clone())
+    }
+
+    fn put(&self, key: CacheKey, embedding: Vec<f32>) {
+        if self.capacity == 0 {
+            return;
+        }
+        let mut state = self.state();
+        if let Some((_, used)) = state.entries.remove(&key) {
+            state.recency.remove(&used);
+        }
+        while state.entries.len() >= self.capacity {
+            let Some((_, oldest)) = state.recency.pop_first() else {
+                break;
+            };
+            state.entries.remove(&oldest);
+        }
+        state.tick += 1;
+        let tick = state.tick;
+        state.recency.insert(tick, key.clone());
+        state.entries.insert(key, (embedding, tick));
+    }
+}
+
+/// On-disk cache backed by a [`sled`] database.
+#[cfg(feature = "sled")]
+#[derive(Debug, Clone)]
+pub struct SledCache {
+    tree: sled::Tree,
+}
+
+#[cfg(feature = "sled")]
+impl SledCache {
+    /// Opens or creates a cache database at `path`.
+    pub fn open(path: impl AsRef<std::path::Path>) -> Result<Self, OllamaError> {
+        let db = sled::open(path).map_err(|e| OllamaError::Cache(e.to_string()))?;
+        let tree = db
+            .open_tree("embeddings")
+            .map_err(|e| OllamaError::Cache(e.to_string()))?;
+        Ok(Self::from_tree(tree))
+    }
+
+    /// Uses an existing tree, e.g. to share a database with other data.
+    pub fn from_tree(tree: sled::Tree) -> Self {
+        SledCache { tree }
+    }
+}
+
+#[cfg(feature = "sled")]
+impl EmbeddingCache for SledCache {
+    fn get(&self, key: &CacheKey) -> Option<Vec<f32>> {
+        match self.tree.get(key.encode()) {
+            Ok(Some(bytes)) => Some(
+                bytes
+                    .chunks_exact(4)
+                    .map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+                    .collect(),
+            ),
+            Ok(None) => None,
+            Err(e) => {
+                tracing::warn!(error = %e, "embedding cache read failed");
+                None
+            }
+        }
+    }
+
+    fn put(&self, key: CacheKey, embedding: Vec<f32>) {
+        let bytes: Vec<u8> = embedding.iter().flat_map(|x| x.to_le_bytes()).collect();
+        if let Err(e) = self.tree.insert(key.encode(), bytes) {
+            tracing::warn!(error = %e, "embedding cache write failed");
+        }
+    }
+}
+
+/// Embeds `request` through `cache`, sending only the misses to Ollama.
+pub(crate) async fn embed_with_cache(
+    client: &OllamaClient,
+    cache: &dyn EmbeddingCache,
+    request: EmbedRequest,
+) -> Result<EmbedResponse, OllamaError> {
+    let Some(digest) = model_digest(client, &request.model).await? else {
+        tracing::debug!(
+            model = request.model,
+            "model digest unknown, bypassing embedding cache"
+        );
+        return client.embed_uncached(request).await;
+    };
+
+    let inputs = match &request.input {
+        EmbedInput::Single(input) => vec![input.clone()],
+        EmbedInput::Multiple(inputs) => inputs.clone(),
+    };
+    let keys: Vec<CacheKey> = inputs
+        .iter()
+        .map(|input| CacheKey::new(&request.model, &digest, request.truncate, input))
+        .collect();
+    let mut embeddings: Vec<Option<Vec<f32>>> = keys.iter().map(|key| cache.get(key)).collect();
+    let misses: Vec<usize> = (0..inputs.len())
+        .filter(|&index| embeddings[index].is_none())
+        .collect();
+
+    let mut response = EmbedResponse {
+        model: request.model.clone(),
+        ..Default::default()
+    };
+    if !misses.is_empty() {
+        let input = match request.input {
+            EmbedInput::Single(input) => EmbedInput::Single(input),
+            EmbedInput::Multiple(_) => {
+                EmbedInput::Multiple(misses.iter().map(|&index| inputs[index].clone()).collect())
+            }
+        };
+        response = client
+            .embed_uncached(EmbedRequest { input, ..request })
+            .await?;
+        if response.embeddings.len() != misses.len() {
+            return Err(OllamaError::InvalidResponse(format!(
+                "expected {} embeddings, got {}",
+                misses.len(),
+                response.embeddings.len()
+            )));
+        }
+        for (&index, embedding) in misses.iter().zip(response.embeddings.drain(..)) {
+            cache.put(keys[index].clone(), embedding.clone());
+            embeddings[index] = Some(embedding);
+        }
+    }
+
+    response.embeddings = embeddings.into_iter().flatten().collect();
+    Ok(response)
+}
+
+/// How long a looked up model digest is reused before listing models again.
+const DIGEST_TTL: Duration = Duration::from_secs(30);
+
+/// Looks up the digest of a locally installed model, reusing a recent answer.
+async fn model_digest(client: &OllamaClient, model: &str) -> Result<Option<String>, OllamaError> {
+    if let Some((digest, fetched)) = crate::lock(&client.model_digests).get(model) {
+        if fetched.elapsed() < DIGEST_TTL {
+            return Ok(Some(digest.clone()));
+        }
+    }
+    let tagged = if model.contains(':') {
+        model.to_string()
+    } else {
+        format!("{}:latest", model)
+    };
+    let models = client.list_models().await?;
+    let digest = models
+        .into_iter()
+        .find(|info| info.name == model || info.name == tagged)
+        .map(|info| info.digest);
+    if let Some(digest) = &digest {
+        crate::lock(&client.model_digests)
+            .insert(model.to_string(), (digest.clone(), Instant::now()));
+    }
+    Ok(digest)
+}
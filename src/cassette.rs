@@ -0,0 +1,502 @@
+//! Record-and-replay of HTTP interactions for deterministic tests.
+//!
+//! In record mode every request made by the client is forwarded to the server
+//! and the response, including each streamed chunk and its timing, is written
+//! to a cassette file. In replay mode the client never touches the network:
+//! responses are served back from the cassette, matched on the normalized
+//! request body.
+//!
+//! ```no_run
+//! # async fn example() -> Result<(), ollama_oxide::error::OllamaError> {
+//! use ollama_oxide::cassette::Cassette;
+//! use ollama_oxide::OllamaClient;
+//!
+//! let cassette = Cassette::replay("tests/cassettes/chat.json")?.ignore_field("keep_alive");
+//! let client = OllamaClient::new("http://localhost:11434").with_cassette(cassette);
+//! # Ok(())
+//! # }
+//! ```
+
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use bytes::Bytes;
+use futures::{stream, StreamExt};
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue};
+use reqwest::{Client, Method, Response};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::error::OllamaError;
+use crate::{base64, lock};
+
+/// Contents of a cassette file.
+#[derive(Serialize, Deserialize, Debug, Default, Clone)]
+pub struct CassetteFile {
+    pub interactions: Vec<Interaction>,
+}
+
+/// A recorded request/response pair.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Interaction {
+    pub request: CassetteRequest,
+    pub response: CassetteResponse,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct CassetteRequest {
+    pub method: String,
+    pub path: String,
+    #[serde(default)]
+    pub headers: BTreeMap<String, String>,
+    pub body: Option<Value>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct CassetteResponse {
+    pub status: u16,
+    #[serde(default)]
+    pub headers: BTreeMap<String, String>,
+    pub chunks: Vec<CassetteChunk>,
+}
+
+/// A body chunk and the time elapsed since the previous chunk (or since the
+/// request was sent, for the first chunk).
+///
+/// Chunks are stored as text when they are valid UTF-8, and as base64 when
+/// not, e.g. when a chunk boundary falls inside a multi-byte character.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct CassetteChunk {
+    pub delay_ms: u64,
+    pub data: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub encoding: Option<ChunkEncoding>,
+}
+
+/// How the `data` of a [`CassetteChunk`] is encoded, if not as plain text.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ChunkEncoding {
+    Base64,
+}
+
+impl CassetteChunk {
+    pub fn new(delay_ms: u64, bytes: &[u8]) -> Self {
+        match std::str::from_utf8(bytes) {
+            Ok(text) => CassetteChunk {
+                delay_ms,
+                data: text.to_string(),
+                encoding: None,
+            },
+            Err(_) => CassetteChunk {
+                delay_ms,
+                data: base64::encode(bytes),
+                encoding: Some(ChunkEncoding::Base64),
+            },
+        }
+    }
+
+    /// The raw bytes of the chunk.
+    pub fn bytes(&self) -> Result<Vec<u8>, OllamaError> {
+        match self.encoding {
+            None => Ok(self.data.clone().into_bytes()),
+            Some(ChunkEncoding::Base64) => base64::decode(&self.data)
+                .ok_or_else(|| OllamaError::Cassette("invalid base64 chunk".to_string())),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CassetteMode {
+    /// Forward requests to the server and record the responses.
+    Record,
+    /// Serve responses from the cassette without touching the network.
+    Replay,
+}
+
+type RequestMatcher = dyn Fn(&CassetteRequest, &CassetteRequest) -> bool + Send + Sync;
+type HeaderRedactor = dyn Fn(&str, &str) -> Option<String> + Send + Sync;
+
+/// A record/replay layer for [`crate::OllamaClient`].
+pub struct Cassette {
+    mode: CassetteMode,
+    path: PathBuf,
+    file: Mutex<CassetteFile>,
+    used: Mutex<Vec<bool>>,
+    ignored_fields: Vec<String>,
+    matcher: Option<Box<RequestMatcher>>,
+    redactors: Vec<Box<HeaderRedactor>>,
+    replay_timing: bool,
+}
+
+impl Cassette {
+    /// Records interactions to `path`, replacing any existing cassette.
+    pub fn record(path: impl AsRef<Path>) -> Self {
+        Self::new(CassetteMode::Record, path.as_ref(), CassetteFile::default())
+    }
+
+    /// Replays interactions from the cassette at `path`.
+    pub fn replay(path: impl AsRef<Path>) -> Result<Self, OllamaError> {
+        let path = path.as_ref();
+        let contents = std::fs::read_to_string(path).map_err(|e| {
+            OllamaError::Cassette(format!("failed to read {}: {}", path.display(), e))
+        })?;
+        let file: CassetteFile = serde_json::from_str(&contents)?;
+        Ok(Self::new(CassetteMode::Replay, path, file))
+    }
+
+    fn new(mode: CassetteMode, path: &Path, file: CassetteFile) -> Self {
+        let used = vec![false; file.interactions.len()];
+        Cassette {
+            mode,
+            path: path.to_path_buf(),
+            file: Mutex::new(file),
+            used: Mutex::new(used),
+            ignored_fields: Vec::new(),
+            matcher: None,
+            redactors: Vec::new(),
+            replay_timing: false,
+        }
+    }
+
+    /// Ignores a request body field when matching. Nested fields use dots,
+    /// e.g. `options.seed`.
+    pub fn ignore_field(mut self, field: &str) -> Self {
+        self.ignored_fields.push(field.to_string());
+        self
+    }
+
+    /// Replaces body matching with a custom predicate over the normalized
+    /// recorded and incoming requests. Method and path must still match.
+    pub fn with_matcher(
+        mut self,
+        matcher: impl Fn(&CassetteRequest, &CassetteRequest) -> bool + Send + Sync + 'static,
+    ) -> Self {
+        self.matcher = Some(Box::new(matcher));
+        self
+    }
+
+    /// Adds a hook that rewrites header values before they are written to the
+    /// cassette. It receives the lowercase header name and value and returns
+    /// the value to store, or `None` to drop the header.
+    pub fn with_header_redactor(
+        mut self,
+        redactor: impl Fn(&str, &str) -> Option<String> + Send + Sync + 'static,
+    ) -> Self {
+        self.redactors.push(Box::new(redactor));
+        self
+    }
+
+    /// Stores `[REDACTED]` instead of the value of the named header.
+    pub fn redact_header(self, name: &str) -> Self {
+        let name = name.to_ascii_lowercase();
+        self.with_header_redactor(move |header, value| {
+            if header == name {
+                Some("[REDACTED]".to_string())
+            } else {
+                Some(value.to_string())
+            }
+        })
+    }
+
+    /// Replays streamed chunks with their recorded delays instead of all at once.
+    pub fn with_replay_timing(mut self, enabled: bool) -> Self {
+        self.replay_timing = enabled;
+        self
+    }
+
+    pub fn mode(&self) -> CassetteMode {
+        self.mode
+    }
+
+    /// Returns a copy of the recorded interactions.
+    pub fn interactions(&self) -> Vec<Interaction> {
+        lock(&self.file).interactions.clone()
+    }
+
+    /// Writes the cassette to disk. Called automatically after each recorded
+    /// interaction.
+    pub fn save(&self) -> Result<(), OllamaError> {
+        let contents = serde_json::to_string_pretty(&*lock(&self.file))?;
+        if let Some(parent) = self.path.parent() {
+            std::fs::create_dir_all(parent).map_err(|e| OllamaError::Cassette(e.to_string()))?;
+        }
+        std::fs::write(&self.path, contents).map_err(|e| {
+            OllamaError::Cassette(format!("failed to write {}: {}", self.path.display(), e))
+        })
+    }
+
+    /// Records or replays a single HTTP exchange.
+    pub(crate) async fn send(
+        self: &Arc<Self>,
+        client: &Client,
+        method: Method,
+        base_url: &str,
+        path: &str,
+        headers: HeaderMap,
+        body: Option<Value>,
+    ) -> Result<Response, OllamaError> {
+        match self.mode {
+            CassetteMode::Record => {
+                self.record_exchange(client, method, base_url, path, headers, body)
+                    .await
+            }
+            CassetteMode::Replay => self.replay_exchange(method, path, body),
+        }
+    }
+
+    async fn record_exchange(
+        self: &Arc<Self>,
+        client: &Client,
+        method: Method,
+        base_url: &str,
+        path: &str,
+        headers: HeaderMap,
+        body: Option<Value>,
+    ) -> Result<Response, OllamaError> {
+        let mut builder = client
+            .request(method.clone(), format!("{}{}", base_url, path))
+            .headers(headers);
+        if let Some(body) = &body {
+            builder = builder.json(body);
+        }
+        let request = builder.build()?;
+        let recorded_request = CassetteRequest {
+            method: method.to_string(),
+            path: path.to_string(),
+            headers: self.redact(request.headers()),
+            body,
+        };
+
+        let start = Instant::now();
+        let response = client.execute(request).await?;
+        let status = response.status();
+        let headers = response.headers().clone();
+        let mut recording = Recording {
+            cassette: self.clone(),
+            request: Some(recorded_request),
+            status: status.as_u16(),
+            headers: self.redact(&headers),
+            chunks: Vec::new(),
+            last_chunk: start,
+        };
+
+        let body = response.bytes_stream().map(move |chunk| {
+            if let Ok(bytes) = &chunk {
+                recording.push(bytes);
+            }
+            chunk
+        });
+        Ok(build_response(
+            status.as_u16(),
+            &headers,
+            reqwest::Body::wrap_stream(body),
+        ))
+    }
+
+    fn replay_exchange(
+        &self,
+        method: Method,
+        path: &str,
+        body: Option<Value>,
+    ) -> Result<Response, OllamaError> {
+        let incoming = CassetteRequest {
+            method: method.to_string(),
+            path: path.to_string(),
+            headers: BTreeMap::new(),
+            body,
+        };
+        let interaction = self.find_interaction(&incoming).ok_or_else(|| {
+            OllamaError::Cassette(format!(
+                "no recorded interaction matches {} {} with body {}",
+                incoming.method,
+                incoming.path,
+                incoming.body.clone().unwrap_or(Value::Null)
+            ))
+        })?;
+
+        let mut headers = HeaderMap::new();
+        for (name, value) in &interaction.response.headers {
+            if let (Ok(name), Ok(value)) = (
+                HeaderName::from_bytes(name.as_bytes()),
+                HeaderValue::from_str(value),
+            ) {
+                headers.insert(name, value);
+            }
+        }
+        let timing = self.replay_timing;
+        let chunks = interaction
+            .response
+            .chunks
+            .iter()
+            .map(|chunk| Ok((chunk.delay_ms, chunk.bytes()?)))
+            .collect::<Result<Vec<_>, OllamaError>>()?;
+        let chunks = stream::iter(chunks).then(move |(delay_ms, bytes)| async move {
+            if timing && delay_ms > 0 {
+                tokio::time::sleep(Duration::from_millis(delay_ms)).await;
+            }
+            Ok::<_, std::io::Error>(Bytes::from(bytes))
+        });
+        Ok(build_response(
+            interaction.response.status,
+            &headers,
+            reqwest::Body::wrap_stream(chunks),
+        ))
+    }
+
+    /// Finds the first unused matching interaction, falling back to the last
+    /// matching one so repeated identical requests keep replaying.
+    fn find_interaction(&self, incoming: &CassetteRequest) -> Option<Interaction> {
+        let file = lock(&self.file);
+        let mut used = lock(&self.used);
+        let incoming = self.normalize(incoming);
+        let matches: Vec<usize> = file
+            .interactions
+            .iter()
+            .enumerate()
+            .filter(|(_, interaction)| {
+                self.matches(&self.normalize(&interaction.request), &incoming)
+            })
+            .map(|(index, _)| index)
+            .collect();
+        let index = matches
+            .iter()
+            .copied()
+            .find(|&index| !used[index])
+            .or_else(|| matches.last().copied())?;
+        used[index] = true;
+        Some(file.interactions[index].clone())
+    }
+
+    fn matches(&self, recorded: &CassetteRequest, incoming: &CassetteRequest) -> bool {
+        if recorded.method != incoming.method || recorded.path != incoming.path {
+            return false;
+        }
+        match &self.matcher {
+            Some(matcher) => matcher(recorded, incoming),
+            None => recorded.body == incoming.body,
+        }
+    }
+
+    /// Drops ignored fields and `null` values from the request body.
+    fn normalize(&self, request: &CassetteRequest) -> CassetteRequest {
+        let mut request = request.clone();
+        if let Some(body) = &mut request.body {
+            for field in &self.ignored_fields {
+                remove_field(body, field);
+            }
+            strip_nulls(body);
+        }
+        request
+    }
+
+    fn redact(&self, headers: &HeaderMap) -> BTreeMap<String, String> {
+        headers
+            .iter()
+            .filter_map(|(name, value)| {
+                let name = name.as_str().to_string();
+                // Credentials from an `AuthProvider` are never written to disk
+                if value.is_sensitive() {
+                    return Some((name, "[REDACTED]".to_string()));
+                }
+                let mut value = Some(String::from_utf8_lossy(value.as_bytes()).to_string());
+                for redactor in &self.redactors {
+                    value = value.and_then(|value| redactor(&name, &value));
+                }
+                value.map(|value| (name, value))
+            })
+            .collect()
+    }
+
+    fn push(&self, interaction: Interaction) {
+        lock(&self.file).interactions.push(interaction);
+        if let Err(e) = self.save() {
+            tracing::warn!(error = %e, "failed to save cassette");
+        }
+    }
+}
+
+impl std::fmt::Debug for Cassette {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Cassette")
+            .field("mode", &self.mode)
+            .field("path", &self.path)
+            .field("ignored_fields", &self.ignored_fields)
+            .finish_non_exhaustive()
+    }
+}
+
+/// An in-progress recording, saved to the cassette when the response body is
+/// dropped (fully read or abandoned).
+struct Recording {
+    cassette: Arc<Cassette>,
+    request: Option<CassetteRequest>,
+    status: u16,
+    headers: BTreeMap<String, String>,
+    chunks: Vec<CassetteChunk>,
+    last_chunk: Instant,
+}
+
+impl Recording {
+    fn push(&mut self, bytes: &Bytes) {
+        let now = Instant::now();
+        let delay_ms = now.duration_since(self.last_chunk).as_millis() as u64;
+        self.chunks.push(CassetteChunk::new(delay_ms, bytes));
+        self.last_chunk = now;
+    }
+}
+
+impl Drop for Recording {
+    fn drop(&mut self) {
+        if let Some(request) = self.request.take() {
+            self.cassette.push(Interaction {
+                request,
+                response: CassetteResponse {
+                    status: self.status,
+                    headers: std::mem::take(&mut self.headers),
+                    chunks: std::mem::take(&mut self.chunks),
+                },
+            });
+        }
+    }
+}
+
+fn build_response(status: u16, headers: &HeaderMap, body: reqwest::Body) -> Response {
+    let mut response = http::Response::new(body);
+    *response.status_mut() = http::StatusCode::from_u16(status).unwrap_or(http::StatusCode::OK);
+    for (name, value) in headers {
+        // The body is re-chunked, so framing headers no longer apply
+        if name != http::header::CONTENT_LENGTH && name != http::header::TRANSFER_ENCODING {
+            response.headers_mut().append(name, value.clone());
+        }
+    }
+    Response::from(response)
+}
+
+fn remove_field(value: &mut Value, field: &str) {
+    match field.split_once('.') {
+        Some((head, rest)) => {
+            if let Some(child) = value.get_mut(head) {
+                remove_field(child, rest);
+            }
+        }
+        None => {
+            if let Value::Object(map) = value {
+                map.remove(field);
+            }
+        }
+    }
+}
+
+fn strip_nulls(value: &mut Value) {
+    match value {
+        Value::Object(map) => {
+            map.retain(|_, value| !value.is_null());
+            map.values_mut().for_each(strip_nulls);
+        }
+        Value::Array(items) => items.iter_mut().for_each(strip_nulls),
+        _ => {}
+    }
+}
@@ -0,0 +1,115 @@
+//! Client configuration from the environment.
+//!
+//! [`OllamaClient::from_env`](crate::OllamaClient::from_env) reads these
+//! variables:
+//!
+//! - `OLLAMA_HOST`: the server, parsed by [`parse_host`].
+//! - `OLLAMA_API_KEY`: sent as a bearer token, e.g. to a server behind an
+//!   authenticating reverse proxy.
+//! - `OTEL_INSTRUMENTATION_GENAI_CAPTURE_MESSAGE_CONTENT`: `true` records
+//!   prompts and completions on tracing spans; see [`crate::telemetry`].
+//!
+//! [`ModelStore::from_env`](crate::store::ModelStore::from_env) reads
+//! `OLLAMA_MODELS`, the model directory of the local server.
+//!
+//! Proxies are taken from `HTTP_PROXY`, `HTTPS_PROXY`, `ALL_PROXY` and
+//! `NO_PROXY` (or their lowercase forms) by the underlying HTTP client.
+//! Unix domain sockets are not supported.
+
+use std::path::PathBuf;
+
+/// Server used when `OLLAMA_HOST` is unset.
+pub const DEFAULT_HOST: &str = "http://127.0.0.1:11434";
+
+/// Port used when `OLLAMA_HOST` has neither a scheme nor a port.
+pub const DEFAULT_PORT: u16 = 11434;
+
+/// Turns an `OLLAMA_HOST` value into a base URL, following the Ollama CLI:
+///
+/// - the scheme defaults to `http`;
+/// - the port defaults to 11434, or to 80/443 when a scheme is given;
+/// - an empty host means `127.0.0.1`, and the wildcard addresses `0.0.0.0`
+///   and `::`, which servers bind to, are mapped to the loopback address.
+///
+/// ```
+/// use ollama_oxide::config::parse_host;
+///
+/// assert_eq!(parse_host("gpu-box"), "http://gpu-box:11434");
+/// assert_eq!(parse_host("0.0.0.0:8080"), "http://127.0.0.1:8080");
+/// assert_eq!(parse_host("https://ollama.example.com"), "https://ollama.example.com:443");
+/// ```
+pub fn parse_host(value: &str) -> String {
+    let value = value.trim();
+    if value.is_empty() {
+        return DEFAULT_HOST.to_string();
+    }
+    let (scheme, rest, default_port) = match value.split_once("://") {
+        Some(("https", rest)) => ("https", rest, 443),
+        Some((scheme, rest)) => (scheme, rest, 80),
+        None => ("http", value, DEFAULT_PORT),
+    };
+    let (authority, path) = match rest.find('/') {
+        Some(slash) => rest.split_at(slash),
+        None => (rest, ""),
+    };
+
+    // A colon after the closing bracket of an IPv6 literal, or the only colon
+    // in the authority, separates the port.
+    let (host, port) = match authority.rfind(':') {
+        Some(colon) if authority.starts_with('[') && authority[..colon].ends_with(']') => {
+            (&authority[..colon], Some(&authority[colon + 1..]))
+        }
+        Some(colon) if !authority.starts_with('[') && authority.matches(':').count() == 1 => {
+            (&authority[..colon], Some(&authority[colon + 1..]))
+        }
+        _ => (authority, None),
+    };
+    let port = port
+        .and_then(|port| port.parse::<u16>().ok())
+        .unwrap_or(default_port);
+    let host = match host.trim_start_matches('[').trim_end_matches(']') {
+        "" | "0.0.0.0" => "127.0.0.1".to_string(),
+        "::" => "[::1]".to_string(),
+        bare if bare.contains(':') => format!("[{}]", bare),
+        _ => host.to_string(),
+    };
+
+    format!(
+        "{}://{}:{}{}",
+        scheme,
+        host,
+        port,
+        path.trim_end_matches('/')
+    )
+}
+
+/// Base URL from `OLLAMA_HOST`, or [`DEFAULT_HOST`].
+pub fn host_from_env() -> String {
+    parse_host(&std::env::var("OLLAMA_HOST").unwrap_or_default())
+}
+
+/// Non-empty `OLLAMA_API_KEY`, if set.
+pub fn api_key_from_env() -> Option<String> {
+    std::env::var("OLLAMA_API_KEY")
+        .ok()
+        .map(|key| key.trim().to_string())
+        .filter(|key| !key.is_empty())
+}
+
+/// Whether `OTEL_INSTRUMENTATION_GENAI_CAPTURE_MESSAGE_CONTENT` is `true`.
+pub fn capture_content_from_env() -> bool {
+    std::env::var("OTEL_INSTRUMENTATION_GENAI_CAPTURE_MESSAGE_CONTENT")
+        .is_ok_and(|value| value.trim().eq_ignore_ascii_case("true"))
+}
+
+/// The model directory in `OLLAMA_MODELS`, or `~/.ollama/models` as used by
+/// the Ollama server.
+pub fn models_dir_from_env() -> PathBuf {
+    if let Some(dir) = std::env::var_os("OLLAMA_MODELS").filter(|dir| !dir.is_empty()) {
+        return PathBuf::from(dir);
+    }
+    let home = std::env::var_os("HOME")
+        .or_else(|| std::env::var_os("USERPROFILE"))
+        .unwrap_or_default();
+    PathBuf::from(home).join(".ollama").join("models")
+}
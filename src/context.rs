@@ -0,0 +1,265 @@
+//! Token estimation and context-window budgeting for chat histories.
+//!
+//! Ollama silently drops the oldest part of a prompt that does not fit in
+//! `num_ctx`. [`ContextBudget`] estimates how many tokens a history uses so it
+//! can be trimmed on the client, where the caller controls what is lost.
+
+use std::ops::Range;
+use std::sync::Arc;
+
+use crate::api::OllamaApi;
+use crate::error::OllamaError;
+use crate::models::*;
+
+/// Context length assumed when a model does not report one.
+pub const DEFAULT_CONTEXT_LENGTH: usize = 2048;
+
+/// Tokens added per message by typical chat templates (role markers and
+/// separators).
+pub const MESSAGE_OVERHEAD: usize = 4;
+
+/// Estimates the number of tokens in a piece of text.
+///
+/// Implemented for closures, so a real tokenizer can be plugged in with
+/// `ContextBudget::with_estimator(|text: &str| tokenizer.count(text))`.
+pub trait TokenEstimator: Send + Sync {
+    fn estimate(&self, text: &str) -> usize;
+}
+
+impl<F> TokenEstimator for F
+where
+    F: Fn(&str) -> usize + Send + Sync,
+{
+    fn estimate(&self, text: &str) -> usize {
+        self(text)
+    }
+}
+
+/// Estimates tokens from the character count. About four characters per
+/// token is a reasonable guess for English text with most tokenizers.
+#[derive(Debug, Clone, Copy)]
+pub struct HeuristicEstimator {
+    pub chars_per_token: f64,
+}
+
+impl Default for HeuristicEstimator {
+    fn default() -> Self {
+        HeuristicEstimator {
+            chars_per_token: 4.0,
+        }
+    }
+}
+
+impl TokenEstimator for HeuristicEstimator {
+    fn estimate(&self, text: &str) -> usize {
+        (text.chars().count() as f64 / self.chars_per_token).ceil() as usize
+    }
+}
+
+/// What to do with a history that does not fit in the context window.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum TrimStrategy {
+    /// Drop the oldest messages, including system prompts.
+    DropOldest,
+    /// Drop the oldest messages after the leading system prompt.
+    #[default]
+    KeepSystemPrompt,
+    /// Replace the oldest messages after the leading system prompt with a
+    /// summary generated by the model. Only [`crate::session::ChatSession`]
+    /// can summarize; [`ContextBudget::trim`] falls back to
+    /// [`TrimStrategy::KeepSystemPrompt`].
+    Summarize,
+}
+
+/// Tracks how much of a model's context window a chat history uses.
+#[derive(Clone)]
+pub struct ContextBudget {
+    context_length: usize,
+    num_ctx: Option<usize>,
+    reserve: usize,
+    estimator: Arc<dyn TokenEstimator>,
+    scale: Option<f64>,
+    strategy: TrimStrategy,
+}
+
+impl ContextBudget {
+    /// Creates a budget for a context window of `context_length` tokens.
+    pub fn new(context_length: usize) -> Self {
+        ContextBudget {
+            context_length,
+            num_ctx: None,
+            reserve: 512,
+            estimator: Arc::new(HeuristicEstimator::default()),
+            scale: None,
+            strategy: TrimStrategy::default(),
+        }
+    }
+
+    /// Creates a budget from the context length in the model's `model_info`,
+    /// or [`DEFAULT_CONTEXT_LENGTH`] if the model does not report one.
+    pub async fn for_model<A: OllamaApi + ?Sized>(
+        api: &A,
+        model: &str,
+    ) -> Result<Self, OllamaError> {
+        let context_length = api
+            .show_model(model)
+            .await?
+            .context_length()
+            .map_or(DEFAULT_CONTEXT_LENGTH, |length| length as usize);
+        Ok(Self::new(context_length))
+    }
+
+    /// Caps the window at the `num_ctx` sent with requests, which is usually
+    /// smaller than the model's trained context length.
+    pub fn with_num_ctx(mut self, num_ctx: Option<u32>) -> Self {
+        self.num_ctx = num_ctx.map(|n| n as usize);
+        self
+    }
+
+    /// Sets the tokens kept free for the model's reply. Defaults to 512.
+    pub fn with_reserve(mut self, reserve: usize) -> Self {
+        self.reserve = reserve;
+        self
+    }
+
+    /// Replaces the [`HeuristicEstimator`]. Resets any calibration.
+    pub fn with_estimator(mut self, estimator: impl TokenEstimator + 'static) -> Self {
+        self.estimator = Arc::new(estimator);
+        self.scale = None;
+        self
+    }
+
+    pub fn with_strategy(mut self, strategy: TrimStrategy) -> Self {
+        self.strategy = strategy;
+        self
+    }
+
+    pub fn strategy(&self) -> TrimStrategy {
+        self.strategy
+    }
+
+    /// The effective context window in tokens.
+    pub fn context_length(&self) -> usize {
+        match self.num_ctx {
+            Some(num_ctx) => num_ctx.min(self.context_length),
+            None => self.context_length,
+        }
+    }
+
+    /// Tokens available for the prompt once the reply is reserved.
+    pub fn available(&self) -> usize {
+        self.context_length().saturating_sub(self.reserve)
+    }
+
+    /// Correction factor learnt by [`ContextBudget::calibrate`], if any.
+    pub fn scale(&self) -> Option<f64> {
+        self.scale
+    }
+
+    fn raw_estimate(&self, message: &ChatMessage) -> usize {
+        let tool_calls: usize = message
+            .tool_calls
+            .iter()
+            .flatten()
+            .map(|call| {
+                self.estimator.estimate(&call.function.name)
+                    + self
+                        .estimator
+                        .estimate(&call.function.arguments.to_string())
+            })
+            .sum();
+        MESSAGE_OVERHEAD + self.estimator.estimate(&message.content) + tool_calls
+    }
+
+    /// Estimated tokens used by one message.
+    pub fn estimate_message(&self, message: &ChatMessage) -> usize {
+        let raw = self.raw_estimate(message);
+        match self.scale {
+            Some(scale) => (raw as f64 * scale).ceil() as usize,
+            None => raw,
+        }
+    }
+
+    /// Estimated tokens used by a history.
+    pub fn estimate(&self, messages: &[ChatMessage]) -> usize {
+        messages.iter().map(|m| self.estimate_message(m)).sum()
+    }
+
+    pub fn fits(&self, messages: &[ChatMessage]) -> bool {
+        self.estimate(messages) <= self.available()
+    }
+
+    /// Adjusts future estimates using the `prompt_eval_count` Ollama reported
+    /// for `messages`.
+    ///
+    /// Observations far from the estimate are ignored: Ollama only counts the
+    /// tokens it had to evaluate, so a prompt served mostly from its cache
+    /// reports far fewer tokens than it contains.
+    pub fn calibrate(&mut self, messages: &[ChatMessage], prompt_eval_count: u32) {
+        let raw: usize = messages.iter().map(|m| self.raw_estimate(m)).sum();
+        if raw == 0 || prompt_eval_count == 0 {
+            return;
+        }
+        let observed = prompt_eval_count as f64 / raw as f64;
+        if !(0.25..=4.0).contains(&observed) {
+            return;
+        }
+        self.scale = Some(match self.scale {
+            Some(scale) => (scale + observed) / 2.0,
+            None => observed,
+        });
+    }
+
+    /// Returns the range of messages to remove for the rest of `messages` to
+    /// fit. The last message is never removed.
+    ///
+    /// An assistant message with tool calls and the tool results that follow
+    /// it are removed together. If the results run up to the last message,
+    /// the whole group is kept instead.
+    pub fn overflow(&self, messages: &[ChatMessage]) -> Range<usize> {
+        self.overflow_leaving(messages, 0)
+    }
+
+    /// Like [`ContextBudget::overflow`], but leaves `room` tokens free, e.g.
+    /// for a summary of the removed messages.
+    pub fn overflow_leaving(&self, messages: &[ChatMessage], room: usize) -> Range<usize> {
+        let mut total = self.estimate(messages);
+        let available = self.available().saturating_sub(room);
+        let first = match self.strategy {
+            TrimStrategy::DropOldest => 0,
+            TrimStrategy::KeepSystemPrompt | TrimStrategy::Summarize => messages
+                .iter()
+                .take_while(|message| message.role == "system")
+                .count(),
+        };
+
+        let mut end = first;
+        for message in messages
+            .iter()
+            .take(messages.len().saturating_sub(1))
+            .skip(first)
+        {
+            if total <= available {
+                break;
+            }
+            total -= self.estimate_message(message);
+            end += 1;
+        }
+        let is_tool = |index: usize| messages.get(index).is_some_and(|m| m.role == "tool");
+        while end > first && end + 1 < messages.len() && is_tool(end) {
+            end += 1;
+        }
+        // The results reach the last message, so keep their tool call
+        while end > first && is_tool(end) {
+            end -= 1;
+        }
+        first..end
+    }
+
+    /// Removes messages according to the strategy until the history fits, and
+    /// returns them.
+    pub fn trim(&self, messages: &mut Vec<ChatMessage>) -> Vec<ChatMessage> {
+        let remove = self.overflow(messages);
+        messages.drain(remove).collect()
+    }
+}
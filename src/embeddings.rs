@@ -0,0 +1,302 @@
+//! Embedding helpers: batched requests, vector math and an in-memory index.
+
+use std::collections::HashMap;
+
+use futures::{stream, StreamExt, TryStreamExt};
+
+use crate::api::OllamaApi;
+use crate::error::OllamaError;
+use crate::models::*;
+
+/// Endpoint used to generate embeddings.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EmbedEndpoint {
+    /// `/api/embed`, which accepts batches of inputs.
+    Embed,
+    /// `/api/embeddings`, which embeds a single prompt per request.
+    Legacy,
+}
+
+impl EmbedEndpoint {
+    /// First Ollama version assumed to serve `/api/embed`.
+    pub const EMBED_MIN_VERSION: (u64, u64, u64) = (0, 3, 0);
+
+    /// Picks the endpoint for a server version string such as `0.5.7`.
+    /// Unparseable and development (`0.0.0`) versions use `/api/embed`.
+    pub fn for_version(version: &str) -> Self {
+        let mut parts = version
+            .trim_start_matches('v')
+            .split(['.', '-', '+'])
+            .map(|part| part.parse::<u64>().ok());
+        let parsed = (
+            parts.next().flatten(),
+            parts.next().flatten(),
+            parts.next().flatten(),
+        );
+        match parsed {
+            (Some(0), Some(0), Some(0)) => EmbedEndpoint::Embed,
+            (Some(major), Some(minor), Some(patch))
+                if (major, minor, patch) < Self::EMBED_MIN_VERSION =>
+            {
+                EmbedEndpoint::Legacy
+            }
+            _ => EmbedEndpoint::Embed,
+        }
+    }
+}
+
+/// Options for [`embed_batch`].
+#[derive(Debug, Clone)]
+pub struct EmbedBatchOptions {
+    /// Maximum number of inputs sent in a single `/api/embed` request.
+    pub chunk_size: usize,
+    /// Maximum number of requests in flight at once.
+    pub concurrency: usize,
+    pub truncate: Option<bool>,
+    pub options: Option<GenerateOptions>,
+    pub keep_alive: Option<String>,
+}
+
+impl Default for EmbedBatchOptions {
+    fn default() -> Self {
+        EmbedBatchOptions {
+            chunk_size: 64,
+            concurrency: 4,
+            truncate: None,
+            options: None,
+            keep_alive: None,
+        }
+    }
+}
+
+/// Embeds `inputs` in chunks of `options.chunk_size`, running up to
+/// `options.concurrency` requests at once. The returned vectors are in the
+/// same order as `inputs`.
+pub async fn embed_batch<A: OllamaApi + ?Sized>(
+    api: &A,
+    model: &str,
+    inputs: Vec<String>,
+    options: EmbedBatchOptions,
+) -> Result<Vec<Vec<f32>>, OllamaError> {
+    let chunks: Vec<Vec<String>> = inputs
+        .chunks(options.chunk_size.max(1))
+        .map(<[String]>::to_vec)
+        .collect();
+
+    let results: Vec<Vec<Vec<f32>>> = stream::iter(chunks)
+        .map(|chunk| {
+            let expected = chunk.len();
+            let request = EmbedRequest {
+                model: model.to_string(),
+                input: EmbedInput::Multiple(chunk),
+                truncate: options.truncate,
+                options: options.options.clone(),
+                keep_alive: options.keep_alive.clone(),
+            };
+            async move {
+                let response = api.generate_embeddings(request).await?;
+                if response.embeddings.len() != expected {
+                    return Err(OllamaError::InvalidResponse(format!(
+                        "expected {} embeddings, got {}",
+                        expected,
+                        response.embeddings.len()
+                    )));
+                }
+                Ok(response.embeddings)
+            }
+        })
+        // `buffered` yields results in submission order
+        .buffered(options.concurrency.max(1))
+        .try_collect()
+        .await?;
+
+    Ok(results.into_iter().flatten().collect())
+}
+
+/// Scales `vector` in place to unit L2 norm. Zero vectors are left unchanged.
+pub fn normalize_l2(vector: &mut [f32]) {
+    let norm = l2_norm(vector);
+    if norm > 0.0 {
+        vector.iter_mut().for_each(|x| *x /= norm);
+    }
+}
+
+/// Returns the L2 norm of `vector`.
+pub fn l2_norm(vector: &[f32]) -> f32 {
+    vector.iter().map(|x| x * x).sum::<f32>().sqrt()
+}
+
+/// Dot product of two vectors.
+///
+/// # Panics
+///
+/// Panics if the vectors have different lengths.
+pub fn dot_product(a: &[f32], b: &[f32]) -> f32 {
+    assert_eq!(a.len(), b.len(), "vectors must have the same dimensions");
+    a.iter().zip(b).map(|(x, y)| x * y).sum()
+}
+
+/// Cosine similarity of two vectors, or `0.0` if either is a zero vector.
+///
+/// # Panics
+///
+/// Panics if the vectors have different lengths.
+pub fn cosine_similarity(a: &[f32], b: &[f32]) -> f32 {
+    let norms = l2_norm(a) * l2_norm(b);
+    if norms == 0.0 {
+        0.0
+    } else {
+        dot_product(a, b) / norms
+    }
+}
+
+/// Scoring function used by [`EmbeddingIndex::search`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Similarity {
+    #[default]
+    Cosine,
+    DotProduct,
+}
+
+/// A vector stored in an [`EmbeddingIndex`].
+#[derive(Debug, Clone)]
+pub struct IndexEntry<M> {
+    pub id: String,
+    pub vector: Vec<f32>,
+    pub metadata: M,
+    norm: f32,
+}
+
+/// A search result from [`EmbeddingIndex::search`].
+#[derive(Debug, Clone)]
+pub struct SearchHit<'a, M> {
+    pub id: &'a str,
+    pub score: f32,
+    pub metadata: &'a M,
+}
+
+/// In-memory vector index with exact (brute-force) top-k search.
+#[derive(Debug, Clone)]
+pub struct EmbeddingIndex<M = ()> {
+    similarity: Similarity,
+    dimensions: Option<usize>,
+    entries: Vec<IndexEntry<M>>,
+    positions: HashMap<String, usize>,
+}
+
+impl<M> Default for EmbeddingIndex<M> {
+    fn default() -> Self {
+        Self::new(Similarity::default())
+    }
+}
+
+impl<M> EmbeddingIndex<M> {
+    /// Creates an empty index scored with `similarity`.
+    pub fn new(similarity: Similarity) -> Self {
+        EmbeddingIndex {
+            similarity,
+            dimensions: None,
+            entries: Vec::new(),
+            positions: HashMap::new(),
+        }
+    }
+
+    /// Inserts a vector, replacing any existing entry with the same id.
+    ///
+    /// All vectors in an index must have the same dimensions.
+    pub fn insert(
+        &mut self,
+        id: impl Into<String>,
+        vector: Vec<f32>,
+        metadata: M,
+    ) -> Result<(), OllamaError> {
+        let expected = *self.dimensions.get_or_insert(vector.len());
+        if vector.len() != expected {
+            return Err(OllamaError::DimensionMismatch(expected, vector.len()));
+        }
+
+        let id = id.into();
+        let entry = IndexEntry {
+            id: id.clone(),
+            norm: l2_norm(&vector),
+            vector,
+            metadata,
+        };
+        match self.positions.get(&id) {
+            Some(&position) => self.entries[position] = entry,
+            None => {
+                self.positions.insert(id, self.entries.len());
+                self.entries.push(entry);
+            }
+        }
+        Ok(())
+    }
+
+    /// Removes and returns the entry with `id`.
+    pub fn remove(&mut self, id: &str) -> Option<IndexEntry<M>> {
+        let position = self.positions.remove(id)?;
+        let entry = self.entries.swap_remove(position);
+        if let Some(moved) = self.entries.get(position) {
+            self.positions.insert(moved.id.clone(), position);
+        }
+        if self.entries.is_empty() {
+            self.dimensions = None;
+        }
+        Some(entry)
+    }
+
+    pub fn get(&self, id: &str) -> Option<&IndexEntry<M>> {
+        self.positions
+            .get(id)
+            .map(|&position| &self.entries[position])
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Dimensions of the stored vectors, once the first vector is inserted.
+    pub fn dimensions(&self) -> Option<usize> {
+        self.dimensions
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &IndexEntry<M>> {
+        self.entries.iter()
+    }
+
+    /// Returns the `k` entries most similar to `query`, best first.
+    pub fn search(&self, query: &[f32], k: usize) -> Result<Vec<SearchHit<'_, M>>, OllamaError> {
+        if let Some(expected) = self.dimensions {
+            if query.len() != expected {
+                return Err(OllamaError::DimensionMismatch(expected, query.len()));
+            }
+        }
+
+        let query_norm = l2_norm(query);
+        let mut hits: Vec<SearchHit<'_, M>> = self
+            .entries
+            .iter()
+            .map(|entry| {
+                let dot = dot_product(query, &entry.vector);
+                let score = match self.similarity {
+                    Similarity::DotProduct => dot,
+                    Similarity::Cosine if query_norm == 0.0 || entry.norm == 0.0 => 0.0,
+                    Similarity::Cosine => dot / (query_norm * entry.norm),
+                };
+                SearchHit {
+                    id: &entry.id,
+                    score,
+                    metadata: &entry.metadata,
+                }
+            })
+            .collect();
+
+        hits.sort_by(|a, b| b.score.total_cmp(&a.score));
+        hits.truncate(k);
+        Ok(hits)
+    }
+}
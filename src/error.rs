@@ -12,4 +12,28 @@ pub enum OllamaError {
     InvalidResponse(String),
     #[error("Timeout while waiting for response")]
     Timeout,
+    #[error("Client is overloaded: {0} requests already queued")]
+    Overloaded(usize),
+    #[error("Cassette error: {0}")]
+    Cassette(String),
+    #[error("Embedding dimension mismatch: expected {0}, got {1}")]
+    DimensionMismatch(usize, usize),
+    #[error("Embedding cache error: {0}")]
+    Cache(String),
+    #[error("Invalid Modelfile: {0}")]
+    Modelfile(String),
+    #[error("Unauthorized: {0}")]
+    Unauthorized(String),
+    #[error("Authentication failed: {0}")]
+    Auth(String),
+    #[error("Request rejected: {0}")]
+    Rejected(String),
+    #[error("Batch error: {0}")]
+    Batch(String),
+    #[error("Model store error: {0}")]
+    Store(String),
+    #[error("Invalid GGUF file: {0}")]
+    Gguf(String),
+    #[error("Template error: {0}")]
+    Template(String),
 }
\ No newline at end of file
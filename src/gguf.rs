@@ -0,0 +1,517 @@
+//! Metadata of GGUF model files, read without a server.
+//!
+//! [`GgufFile::open`] reads the header, the key-value metadata and the tensor
+//! info table through a buffered reader and stops where the tensor data
+//! begins, so multi-gigabyte files are not loaded. Versions 1 to 3 of the
+//! format are supported, in little-endian byte order.
+//!
+//! ```no_run
+//! use ollama_oxide::gguf::GgufFile;
+//!
+//! let file = GgufFile::open("model.gguf")?;
+//! println!("{:?} {:?}", file.architecture(), file.context_length());
+//! let details = file.details(); // family, parameter_size, quantization_level
+//! # Ok::<(), ollama_oxide::error::OllamaError>(())
+//! ```
+
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{BufReader, Read};
+use std::path::Path;
+
+use crate::error::OllamaError;
+use crate::models::ModelDetails;
+
+/// `GGUF` in little-endian byte order.
+pub const MAGIC: u32 = 0x4655_4747;
+
+/// Alignment of the tensor data when `general.alignment` is not set.
+pub const DEFAULT_ALIGNMENT: u64 = 32;
+
+// Lengths above these are treated as corruption rather than allocated
+const MAX_STRING_LEN: u64 = 1 << 30;
+const MAX_DIMENSIONS: u32 = 8;
+const MAX_ARRAY_DEPTH: u32 = 4;
+
+/// A metadata value.
+#[derive(Debug, Clone, PartialEq)]
+pub enum GgufValue {
+    U8(u8),
+    I8(i8),
+    U16(u16),
+    I16(i16),
+    U32(u32),
+    I32(i32),
+    U64(u64),
+    I64(i64),
+    F32(f32),
+    F64(f64),
+    Bool(bool),
+    String(String),
+    Array(Vec<GgufValue>),
+}
+
+impl GgufValue {
+    /// The value as an unsigned integer, if it is a non-negative integer.
+    pub fn as_u64(&self) -> Option<u64> {
+        match *self {
+            GgufValue::U8(v) => Some(v.into()),
+            GgufValue::U16(v) => Some(v.into()),
+            GgufValue::U32(v) => Some(v.into()),
+            GgufValue::U64(v) => Some(v),
+            GgufValue::I8(v) => u64::try_from(v).ok(),
+            GgufValue::I16(v) => u64::try_from(v).ok(),
+            GgufValue::I32(v) => u64::try_from(v).ok(),
+            GgufValue::I64(v) => u64::try_from(v).ok(),
+            _ => None,
+        }
+    }
+
+    /// The value as a float, if it is a number.
+    pub fn as_f64(&self) -> Option<f64> {
+        match *self {
+            GgufValue::F32(v) => Some(v.into()),
+            GgufValue::F64(v) => Some(v),
+            GgufValue::I8(v) => Some(v.into()),
+            GgufValue::I16(v) => Some(v.into()),
+            GgufValue::I32(v) => Some(v.into()),
+            GgufValue::I64(v) => Some(v as f64),
+            _ => self.as_u64().map(|v| v as f64),
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            GgufValue::String(v) => Some(v),
+            _ => None,
+        }
+    }
+
+    pub fn as_bool(&self) -> Option<bool> {
+        match *self {
+            GgufValue::Bool(v) => Some(v),
+            _ => None,
+        }
+    }
+
+    pub fn as_array(&self) -> Option<&[GgufValue]> {
+        match self {
+            GgufValue::Array(v) => Some(v),
+            _ => None,
+        }
+    }
+}
+
+/// An entry of the tensor info table.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TensorInfo {
+    pub name: String,
+    pub dimensions: Vec<u64>,
+    /// The `ggml_type` of the tensor data; see [`TensorInfo::type_name`].
+    pub tensor_type: u32,
+    /// Offset of the data from [`GgufFile::data_offset`].
+    pub offset: u64,
+}
+
+impl TensorInfo {
+    /// Number of elements in the tensor, saturating at `u64::MAX` for
+    /// dimensions too large to be real.
+    pub fn element_count(&self) -> u64 {
+        self.dimensions
+            .iter()
+            .try_fold(1u64, |count, &dimension| count.checked_mul(dimension))
+            .unwrap_or(u64::MAX)
+    }
+
+    /// Name of the tensor type, e.g. `Q4_K` or `F16`.
+    pub fn type_name(&self) -> Option<&'static str> {
+        let name = match self.tensor_type {
+            0 => "F32",
+            1 => "F16",
+            2 => "Q4_0",
+            3 => "Q4_1",
+            6 => "Q5_0",
+            7 => "Q5_1",
+            8 => "Q8_0",
+            9 => "Q8_1",
+            10 => "Q2_K",
+            11 => "Q3_K",
+            12 => "Q4_K",
+            13 => "Q5_K",
+            14 => "Q6_K",
+            15 => "Q8_K",
+            16 => "IQ2_XXS",
+            17 => "IQ2_XS",
+            18 => "IQ3_XXS",
+            19 => "IQ1_S",
+            20 => "IQ4_NL",
+            21 => "IQ3_S",
+            22 => "IQ2_S",
+            23 => "IQ4_XS",
+            24 => "I8",
+            25 => "I16",
+            26 => "I32",
+            27 => "I64",
+            28 => "F64",
+            29 => "IQ1_M",
+            30 => "BF16",
+            34 => "TQ1_0",
+            35 => "TQ2_0",
+            _ => return None,
+        };
+        Some(name)
+    }
+}
+
+/// Tokenizer settings from the `tokenizer.ggml.*` metadata.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct TokenizerInfo {
+    /// The tokenizer kind, e.g. `gpt2` or `llama`.
+    pub model: Option<String>,
+    /// Pre-tokenizer, e.g. `llama-bpe`.
+    pub pre: Option<String>,
+    pub vocab_size: Option<usize>,
+    pub merges: Option<usize>,
+    pub bos_token_id: Option<u64>,
+    pub eos_token_id: Option<u64>,
+    pub padding_token_id: Option<u64>,
+}
+
+/// The header, metadata and tensor infos of a GGUF file.
+#[derive(Debug, Clone, Default)]
+pub struct GgufFile {
+    pub version: u32,
+    pub metadata: HashMap<String, GgufValue>,
+    pub tensors: Vec<TensorInfo>,
+    /// Offset of the tensor data from the start of the file.
+    pub data_offset: u64,
+}
+
+impl GgufFile {
+    /// Reads the metadata of the file at `path`.
+    pub fn open(path: impl AsRef<Path>) -> Result<GgufFile, OllamaError> {
+        let path = path.as_ref();
+        let file = File::open(path)
+            .map_err(|e| OllamaError::Gguf(format!("{}: {}", path.display(), e)))?;
+        GgufFile::read(BufReader::new(file))
+    }
+
+    /// Reads the metadata from the start of a GGUF file. Stops before the
+    /// tensor data.
+    pub fn read(reader: impl Read) -> Result<GgufFile, OllamaError> {
+        let mut reader = Reader {
+            inner: reader,
+            position: 0,
+            version: 0,
+        };
+        if reader.u32()? != MAGIC {
+            return Err(OllamaError::Gguf("not a GGUF file".to_string()));
+        }
+        reader.version = reader.u32()?;
+        if !(1..=3).contains(&reader.version) {
+            return Err(OllamaError::Gguf(format!(
+                "unsupported version {}",
+                reader.version
+            )));
+        }
+        let tensor_count = reader.count()?;
+        let metadata_count = reader.count()?;
+
+        let mut metadata = HashMap::new();
+        for _ in 0..metadata_count {
+            let key = reader.string()?;
+            let value_type = reader.u32()?;
+            let value = reader.value(value_type, 0)?;
+            metadata.insert(key, value);
+        }
+
+        let mut tensors = Vec::new();
+        for _ in 0..tensor_count {
+            let name = reader.string()?;
+            let dimension_count = reader.u32()?;
+            if dimension_count > MAX_DIMENSIONS {
+                return Err(OllamaError::Gguf(format!(
+                    "tensor {} has {} dimensions",
+                    name, dimension_count
+                )));
+            }
+            let dimensions = (0..dimension_count)
+                .map(|_| reader.count())
+                .collect::<Result<_, _>>()?;
+            tensors.push(TensorInfo {
+                name,
+                dimensions,
+                tensor_type: reader.u32()?,
+                offset: reader.u64()?,
+            });
+        }
+
+        let alignment = metadata
+            .get("general.alignment")
+            .and_then(GgufValue::as_u64)
+            .filter(|&alignment| alignment > 0)
+            .unwrap_or(DEFAULT_ALIGNMENT);
+        Ok(GgufFile {
+            version: reader.version,
+            metadata,
+            tensors,
+            data_offset: reader.position.div_ceil(alignment) * alignment,
+        })
+    }
+
+    /// Returns the metadata value for `key`.
+    pub fn get(&self, key: &str) -> Option<&GgufValue> {
+        self.metadata.get(key)
+    }
+
+    fn get_str(&self, key: &str) -> Option<&str> {
+        self.get(key).and_then(GgufValue::as_str)
+    }
+
+    fn get_u64(&self, key: &str) -> Option<u64> {
+        self.get(key).and_then(GgufValue::as_u64)
+    }
+
+    /// `general.architecture`, e.g. `llama`.
+    pub fn architecture(&self) -> Option<&str> {
+        self.get_str("general.architecture")
+    }
+
+    /// `general.name`.
+    pub fn name(&self) -> Option<&str> {
+        self.get_str("general.name")
+    }
+
+    /// Returns the architecture-specific value `{architecture}.{key}`, e.g.
+    /// `arch_value("context_length")` for `llama.context_length`.
+    pub fn arch_value(&self, key: &str) -> Option<&GgufValue> {
+        self.get(&format!("{}.{}", self.architecture()?, key))
+    }
+
+    /// Context length the model was trained with.
+    pub fn context_length(&self) -> Option<u64> {
+        self.arch_value("context_length")?.as_u64()
+    }
+
+    /// Size of the embedding vectors.
+    pub fn embedding_length(&self) -> Option<u64> {
+        self.arch_value("embedding_length")?.as_u64()
+    }
+
+    /// `general.parameter_count`, or the number of elements in all tensors,
+    /// saturating at `u64::MAX`.
+    pub fn parameter_count(&self) -> u64 {
+        self.get_u64("general.parameter_count").unwrap_or_else(|| {
+            self.tensors
+                .iter()
+                .map(TensorInfo::element_count)
+                .fold(0, u64::saturating_add)
+        })
+    }
+
+    /// The parameter count as Ollama shows it, e.g. `8.0B` or `135M`.
+    pub fn parameter_size(&self) -> String {
+        human_number(self.parameter_count())
+    }
+
+    /// The quantization in `general.file_type`, e.g. `Q4_K_M`.
+    pub fn quantization_level(&self) -> Option<&'static str> {
+        file_type_name(self.get_u64("general.file_type")?)
+    }
+
+    /// `tokenizer.chat_template`, a Jinja template.
+    pub fn chat_template(&self) -> Option<&str> {
+        self.get_str("tokenizer.chat_template")
+    }
+
+    /// Tokenizer settings.
+    pub fn tokenizer(&self) -> TokenizerInfo {
+        let array_len = |key| {
+            self.get(key)
+                .and_then(GgufValue::as_array)
+                .map(<[GgufValue]>::len)
+        };
+        TokenizerInfo {
+            model: self.get_str("tokenizer.ggml.model").map(str::to_string),
+            pre: self.get_str("tokenizer.ggml.pre").map(str::to_string),
+            vocab_size: array_len("tokenizer.ggml.tokens"),
+            merges: array_len("tokenizer.ggml.merges"),
+            bos_token_id: self.get_u64("tokenizer.ggml.bos_token_id"),
+            eos_token_id: self.get_u64("tokenizer.ggml.eos_token_id"),
+            padding_token_id: self.get_u64("tokenizer.ggml.padding_token_id"),
+        }
+    }
+
+    /// The model details `/api/show` would report for this file.
+    pub fn details(&self) -> ModelDetails {
+        let family = self.architecture().unwrap_or_default().to_string();
+        ModelDetails {
+            format: "gguf".to_string(),
+            families: (!family.is_empty()).then(|| vec![family.clone()]),
+            family,
+            parameter_size: self.parameter_size(),
+            quantization_level: self.quantization_level().unwrap_or_default().to_string(),
+        }
+    }
+}
+
+/// Formats a count like Ollama, e.g. `8.0B`, `135M` or `22.57M`.
+fn human_number(n: u64) -> String {
+    let scaled = |unit: f64| n as f64 / unit;
+    match n {
+        1_000_000_000.. => {
+            let number = scaled(1e9);
+            match number.fract() == 0.0 {
+                true => format!("{:.0}B", number),
+                false => format!("{:.1}B", number),
+            }
+        }
+        1_000_000.. => {
+            let number = scaled(1e6);
+            match number.fract() == 0.0 {
+                true => format!("{:.0}M", number),
+                false => format!("{:.2}M", number),
+            }
+        }
+        1_000.. => format!("{:.0}K", scaled(1e3)),
+        _ => n.to_string(),
+    }
+}
+
+/// Names of the `llama_ftype` values in `general.file_type`.
+fn file_type_name(file_type: u64) -> Option<&'static str> {
+    let name = match file_type {
+        0 => "F32",
+        1 => "F16",
+        2 => "Q4_0",
+        3 => "Q4_1",
+        7 => "Q8_0",
+        8 => "Q5_0",
+        9 => "Q5_1",
+        10 => "Q2_K",
+        11 => "Q3_K_S",
+        12 => "Q3_K_M",
+        13 => "Q3_K_L",
+        14 => "Q4_K_S",
+        15 => "Q4_K_M",
+        16 => "Q5_K_S",
+        17 => "Q5_K_M",
+        18 => "Q6_K",
+        19 => "IQ2_XXS",
+        20 => "IQ2_XS",
+        21 => "Q2_K_S",
+        22 => "IQ3_XS",
+        23 => "IQ3_XXS",
+        24 => "IQ1_S",
+        25 => "IQ4_NL",
+        26 => "IQ3_S",
+        27 => "IQ3_M",
+        28 => "IQ2_S",
+        29 => "IQ2_M",
+        30 => "IQ4_XS",
+        31 => "IQ1_M",
+        32 => "BF16",
+        36 => "TQ1_0",
+        37 => "TQ2_0",
+        _ => return None,
+    };
+    Some(name)
+}
+
+/// Little-endian reader that tracks its position for the data offset.
+struct Reader<R> {
+    inner: R,
+    position: u64,
+    version: u32,
+}
+
+impl<R: Read> Reader<R> {
+    fn bytes<const N: usize>(&mut self) -> Result<[u8; N], OllamaError> {
+        let mut buffer = [0; N];
+        self.inner.read_exact(&mut buffer).map_err(truncated)?;
+        self.position += N as u64;
+        Ok(buffer)
+    }
+
+    fn u32(&mut self) -> Result<u32, OllamaError> {
+        self.bytes().map(u32::from_le_bytes)
+    }
+
+    fn u64(&mut self) -> Result<u64, OllamaError> {
+        self.bytes().map(u64::from_le_bytes)
+    }
+
+    /// A count or length, 32 bits wide in version 1 and 64 bits after.
+    fn count(&mut self) -> Result<u64, OllamaError> {
+        match self.version {
+            1 => self.u32().map(u64::from),
+            _ => self.u64(),
+        }
+    }
+
+    fn string(&mut self) -> Result<String, OllamaError> {
+        let len = self.count()?;
+        if len > MAX_STRING_LEN {
+            return Err(OllamaError::Gguf(format!("string of {} bytes", len)));
+        }
+        let mut buffer = Vec::new();
+        (&mut self.inner)
+            .take(len)
+            .read_to_end(&mut buffer)
+            .map_err(truncated)?;
+        if buffer.len() as u64 != len {
+            return Err(OllamaError::Gguf("unexpected end of file".to_string()));
+        }
+        self.position += len;
+        String::from_utf8(buffer).map_err(|e| OllamaError::Gguf(e.to_string()))
+    }
+
+    /// Reads a value of `value_type`; `depth` counts the arrays it is in.
+    fn value(&mut self, value_type: u32, depth: u32) -> Result<GgufValue, OllamaError> {
+        Ok(match value_type {
+            0 => GgufValue::U8(u8::from_le_bytes(self.bytes()?)),
+            1 => GgufValue::I8(i8::from_le_bytes(self.bytes()?)),
+            2 => GgufValue::U16(u16::from_le_bytes(self.bytes()?)),
+            3 => GgufValue::I16(i16::from_le_bytes(self.bytes()?)),
+            4 => GgufValue::U32(self.u32()?),
+            5 => GgufValue::I32(i32::from_le_bytes(self.bytes()?)),
+            6 => GgufValue::F32(f32::from_le_bytes(self.bytes()?)),
+            7 => GgufValue::Bool(self.bytes::<1>()?[0] != 0),
+            8 => GgufValue::String(self.string()?),
+            9 => {
+                if depth >= MAX_ARRAY_DEPTH {
+                    return Err(OllamaError::Gguf(format!(
+                        "arrays nested more than {} deep",
+                        MAX_ARRAY_DEPTH
+                    )));
+                }
+                let item_type = self.u32()?;
+                let len = self.count()?;
+                // The length is untrusted, so the vector grows as items are read
+                let mut items = Vec::with_capacity(len.min(1 << 16) as usize);
+                for _ in 0..len {
+                    items.push(self.value(item_type, depth + 1)?);
+                }
+                GgufValue::Array(items)
+            }
+            10 => GgufValue::U64(self.u64()?),
+            11 => GgufValue::I64(i64::from_le_bytes(self.bytes()?)),
+            12 => GgufValue::F64(f64::from_le_bytes(self.bytes()?)),
+            _ => {
+                return Err(OllamaError::Gguf(format!(
+                    "unknown value type {}",
+                    value_type
+                )))
+            }
+        })
+    }
+}
+
+fn truncated(error: std::io::Error) -> OllamaError {
+    match error.kind() {
+        std::io::ErrorKind::UnexpectedEof => {
+            OllamaError::Gguf("unexpected end of file".to_string())
+        }
+        _ => OllamaError::Gguf(error.to_string()),
+    }
+}
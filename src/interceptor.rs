@@ -0,0 +1,552 @@
+//! Hooks around client calls.
+//!
+//! An [`Interceptor`] added with
+//! [`OllamaClient::with_interceptor`](crate::OllamaClient::with_interceptor)
+//! sees every call to the native API and the OpenAI-compatible endpoints in
+//! [`crate::openai_compat`]:
+//!
+//! - [`Interceptor::before_request`] may modify the typed request and add
+//!   headers, or reject the call by returning an error such as
+//!   [`OllamaError::Rejected`]. A rejected request is not sent.
+//! - [`Interceptor::on_chunk`] may modify or reject each chunk of a streamed
+//!   response. A rejected chunk ends the stream with the error.
+//! - [`Interceptor::after_response`] sees the final result: the response, the
+//!   chunks of a stream aggregated into one, or the error. For streams it runs
+//!   when the stream ends, not when it is dropped early.
+//!
+//! Interceptors run in the order they were added before the request and in
+//! reverse order afterwards, so the first one added wraps all others.
+//!
+//! Helpers such as [`OllamaClient::embed_batch`](crate::OllamaClient::embed_batch)
+//! are intercepted through the calls they make. The `/v1` endpoints share
+//! operation names with the native ones, e.g. `chat`; tell them apart by the
+//! endpoint or the request variant.
+
+use std::future::Future;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use async_trait::async_trait;
+use futures::{stream, Stream, StreamExt};
+use reqwest::header::HeaderMap;
+
+use crate::error::OllamaError;
+use crate::models::*;
+use crate::openai_compat::{
+    ChatChoice, ChatCompletion, ChatCompletionChunk, ChatCompletionRequest, ChoiceLogprobs,
+    Completion, CompletionChoice, CompletionRequest, EmbeddingsRequest, EmbeddingsResponse,
+    MessageContent, ModelList, OpenAiModel,
+};
+
+/// Hooks run around each client call. All methods default to doing nothing.
+#[async_trait]
+pub trait Interceptor: Send + Sync {
+    /// Called before the request is sent. Returning an error fails the call
+    /// without sending it.
+    async fn before_request(&self, _context: &mut RequestContext<'_>) -> Result<(), OllamaError> {
+        Ok(())
+    }
+
+    /// Called for each chunk of a streamed response of `operation`. Returning
+    /// an error ends the stream with it.
+    fn on_chunk(&self, _operation: &'static str, _chunk: Chunk<'_>) -> Result<(), OllamaError> {
+        Ok(())
+    }
+
+    /// Called with the final result of the call.
+    async fn after_response(&self, _context: &ResponseContext<'_>) {}
+}
+
+/// The typed request of a call.
+pub enum Request<'a> {
+    Generate(&'a mut GenerateRequest),
+    Chat(&'a mut ChatRequest),
+    Embed(&'a mut EmbedRequest),
+    CreateModel(&'a mut CreateModelRequest),
+    CopyModel(&'a mut CopyModelRequest),
+    /// The model name of `show_model`, `pull_model`, `push_model`,
+    /// `delete_model` or `/v1` `retrieve_model`.
+    Model(&'a mut String),
+    /// `list_models`, `list_running_models` and `get_version`, which send no
+    /// body.
+    Empty,
+    /// `/v1/chat/completions`.
+    ChatCompletion(&'a mut ChatCompletionRequest),
+    /// `/v1/completions`.
+    Completion(&'a mut CompletionRequest),
+    /// `/v1/embeddings`.
+    Embeddings(&'a mut EmbeddingsRequest),
+}
+
+impl Request<'_> {
+    /// The model the request names, if any.
+    pub fn model(&self) -> Option<&str> {
+        match self {
+            Request::Generate(request) => Some(&request.model),
+            Request::Chat(request) => Some(&request.model),
+            Request::Embed(request) => Some(&request.model),
+            Request::CreateModel(request) => Some(&request.model),
+            Request::CopyModel(request) => Some(&request.source),
+            Request::Model(name) => Some(name),
+            Request::Empty => None,
+            Request::ChatCompletion(request) => Some(&request.model),
+            Request::Completion(request) => Some(&request.model),
+            Request::Embeddings(request) => Some(&request.model),
+        }
+    }
+}
+
+/// A call about to be sent.
+pub struct RequestContext<'a> {
+    /// The operation, as named on tracing spans: `chat`, `text_completion`,
+    /// `embeddings`, or the method name for model management.
+    pub operation: &'static str,
+    /// The API path, e.g. `/api/chat` or `/v1/chat/completions`.
+    pub endpoint: &'static str,
+    pub request: Request<'a>,
+    /// Headers added to the request after the credentials, replacing any of
+    /// the same name.
+    pub headers: HeaderMap,
+}
+
+/// The typed result of a call. Streamed responses are aggregated into one.
+pub enum Response<'a> {
+    /// The completion text of all chunks, with the statistics of the last.
+    Generate(&'a GenerateResponse),
+    /// The message content and tool calls of all chunks, with the statistics
+    /// of the last.
+    Chat(&'a ChatResponse),
+    Embed(&'a EmbedResponse),
+    ShowModel(&'a ShowModelResponse),
+    Models(&'a [ModelInfo]),
+    RunningModels(&'a [RunningModelInfo]),
+    Version(&'a str),
+    /// The last status of `pull_model`, `push_model` or `create_model`.
+    Status(&'a str),
+    /// `delete_model` and `copy_model`, which return nothing.
+    Empty,
+    /// The choices of all chunks merged by index, with the usage of the last.
+    ChatCompletion(&'a ChatCompletion),
+    /// The text of all chunks merged by choice, with the usage of the last.
+    Completion(&'a Completion),
+    Embeddings(&'a EmbeddingsResponse),
+    ModelList(&'a ModelList),
+    OpenAiModel(&'a OpenAiModel),
+}
+
+/// A finished call.
+pub struct ResponseContext<'a> {
+    pub operation: &'static str,
+    pub endpoint: &'static str,
+    /// The model named by the request, after `before_request`.
+    pub model: Option<&'a str>,
+    pub result: Result<Response<'a>, &'a OllamaError>,
+    /// Time since the request was accepted, including the whole stream.
+    pub elapsed: Duration,
+}
+
+/// A chunk of a streamed response.
+pub enum Chunk<'a> {
+    Generate(&'a mut GenerateResponse),
+    Chat(&'a mut ChatResponse),
+    Pull(&'a mut PullResponse),
+    Push(&'a mut PushResponse),
+    Create(&'a mut CreateResponse),
+    ChatCompletion(&'a mut ChatCompletionChunk),
+    Completion(&'a mut Completion),
+}
+
+tokio::task_local! {
+    static HEADERS: HeaderMap;
+}
+
+/// Headers set by interceptors for the request being sent.
+pub(crate) fn request_headers() -> Option<HeaderMap> {
+    HEADERS.try_with(HeaderMap::clone).ok()
+}
+
+/// The interceptors of a call whose request was accepted.
+pub(crate) struct Hooks {
+    interceptors: Vec<Arc<dyn Interceptor>>,
+    operation: &'static str,
+    endpoint: &'static str,
+    model: Option<String>,
+    headers: HeaderMap,
+    start: Instant,
+}
+
+impl Hooks {
+    /// Runs `before_request` of every interceptor in order.
+    pub(crate) async fn before(
+        interceptors: &[Arc<dyn Interceptor>],
+        operation: &'static str,
+        endpoint: &'static str,
+        request: Request<'_>,
+    ) -> Result<Hooks, OllamaError> {
+        let mut context = RequestContext {
+            operation,
+            endpoint,
+            request,
+            headers: HeaderMap::new(),
+        };
+        for interceptor in interceptors {
+            interceptor.before_request(&mut context).await?;
+        }
+        Ok(Hooks {
+            interceptors: interceptors.to_vec(),
+            operation,
+            endpoint,
+            model: context.request.model().map(str::to_string),
+            headers: context.headers,
+            start: Instant::now(),
+        })
+    }
+
+    /// Runs `future`, which sends the request, with the added headers.
+    pub(crate) async fn send<F: Future>(&self, future: F) -> F::Output {
+        // Boxed so calls made by other calls, such as the version check before
+        // embedding, do not nest their futures inline
+        HEADERS.scope(self.headers.clone(), Box::pin(future)).await
+    }
+
+    /// Runs `after_response` with the result of a complete call.
+    pub(crate) async fn after<T>(
+        &self,
+        result: Result<T, OllamaError>,
+        response: fn(&T) -> Response<'_>,
+    ) -> Result<T, OllamaError> {
+        self.report(result.as_ref().map(response)).await;
+        result
+    }
+
+    async fn report(&self, result: Result<Response<'_>, &OllamaError>) {
+        if self.interceptors.is_empty() {
+            return;
+        }
+        let context = ResponseContext {
+            operation: self.operation,
+            endpoint: self.endpoint,
+            model: self.model.as_deref(),
+            result,
+            elapsed: self.start.elapsed(),
+        };
+        for interceptor in self.interceptors.iter().rev() {
+            interceptor.after_response(&context).await;
+        }
+    }
+
+    fn on_chunk<T: Streamed>(&self, chunk: &mut T) -> Result<(), OllamaError> {
+        for interceptor in self.interceptors.iter().rev() {
+            interceptor.on_chunk(self.operation, chunk.chunk())?;
+        }
+        Ok(())
+    }
+
+    /// Runs `on_chunk` for each chunk of an opened stream, and
+    /// `after_response` with the aggregated chunks once it ends or with the
+    /// first error.
+    pub(crate) async fn stream<T: Streamed>(
+        self,
+        opened: Result<impl Stream<Item = Result<T, OllamaError>>, OllamaError>,
+    ) -> Result<impl Stream<Item = Result<T, OllamaError>>, OllamaError> {
+        let inner = match opened {
+            Ok(inner) => Box::pin(inner),
+            Err(error) => {
+                self.report(Err(&error)).await;
+                return Err(error);
+            }
+        };
+        let state = StreamState {
+            inner,
+            hooks: self,
+            aggregate: T::Aggregate::default(),
+            reported: false,
+            ended: false,
+        };
+        Ok(stream::unfold(state, |mut state| async move {
+            if state.ended {
+                return None;
+            }
+            let item = match state.inner.next().await {
+                Some(Ok(mut chunk)) => match state.hooks.on_chunk(&mut chunk) {
+                    Ok(()) => {
+                        if !state.hooks.interceptors.is_empty() {
+                            T::aggregate(&mut state.aggregate, &chunk);
+                        }
+                        Ok(chunk)
+                    }
+                    Err(error) => {
+                        state.ended = true;
+                        Err(error)
+                    }
+                },
+                Some(Err(error)) => Err(error),
+                None => {
+                    if !state.reported {
+                        let response = T::response(&state.aggregate);
+                        state.hooks.report(Ok(response)).await;
+                    }
+                    return None;
+                }
+            };
+            if let Err(error) = &item {
+                if !state.reported {
+                    state.reported = true;
+                    state.hooks.report(Err(error)).await;
+                }
+            }
+            Some((item, state))
+        }))
+    }
+}
+
+struct StreamState<S, A> {
+    inner: S,
+    hooks: Hooks,
+    aggregate: A,
+    reported: bool,
+    ended: bool,
+}
+
+/// A chunk type of a streamed response.
+pub(crate) trait Streamed: Sized {
+    type Aggregate: Default;
+
+    fn chunk(&mut self) -> Chunk<'_>;
+    fn aggregate(aggregate: &mut Self::Aggregate, chunk: &Self);
+    fn response(aggregate: &Self::Aggregate) -> Response<'_>;
+}
+
+impl Streamed for GenerateResponse {
+    type Aggregate = GenerateResponse;
+
+    fn chunk(&mut self) -> Chunk<'_> {
+        Chunk::Generate(self)
+    }
+
+    fn aggregate(aggregate: &mut GenerateResponse, chunk: &Self) {
+        aggregate.model.clone_from(&chunk.model);
+        aggregate.created_at.clone_from(&chunk.created_at);
+        aggregate.response.push_str(&chunk.response);
+        extend_logprobs(&mut aggregate.logprobs, &chunk.logprobs);
+        if chunk.done {
+            aggregate.done = true;
+            aggregate.context.clone_from(&chunk.context);
+            aggregate.total_duration = chunk.total_duration;
+            aggregate.load_duration = chunk.load_duration;
+            aggregate.prompt_eval_count = chunk.prompt_eval_count;
+            aggregate.prompt_eval_duration = chunk.prompt_eval_duration;
+            aggregate.eval_count = chunk.eval_count;
+            aggregate.eval_duration = chunk.eval_duration;
+            aggregate.done_reason.clone_from(&chunk.done_reason);
+        }
+    }
+
+    fn response(aggregate: &GenerateResponse) -> Response<'_> {
+        Response::Generate(aggregate)
+    }
+}
+
+impl Streamed for ChatResponse {
+    type Aggregate = ChatResponse;
+
+    fn chunk(&mut self) -> Chunk<'_> {
+        Chunk::Chat(self)
+    }
+
+    fn aggregate(aggregate: &mut ChatResponse, chunk: &Self) {
+        aggregate.model.clone_from(&chunk.model);
+        aggregate.created_at.clone_from(&chunk.created_at);
+        if !chunk.message.role.is_empty() {
+            aggregate.message.role.clone_from(&chunk.message.role);
+        }
+        aggregate.message.content.push_str(&chunk.message.content);
+        if let Some(tool_calls) = &chunk.message.tool_calls {
+            aggregate
+                .message
+                .tool_calls
+                .get_or_insert_with(Vec::new)
+                .extend(tool_calls.iter().cloned());
+        }
+        extend_logprobs(&mut aggregate.logprobs, &chunk.logprobs);
+        if chunk.done {
+            aggregate.done = true;
+            aggregate.total_duration = chunk.total_duration;
+            aggregate.load_duration = chunk.load_duration;
+            aggregate.prompt_eval_count = chunk.prompt_eval_count;
+            aggregate.prompt_eval_duration = chunk.prompt_eval_duration;
+            aggregate.eval_count = chunk.eval_count;
+            aggregate.eval_duration = chunk.eval_duration;
+            aggregate.done_reason.clone_from(&chunk.done_reason);
+        }
+    }
+
+    fn response(aggregate: &ChatResponse) -> Response<'_> {
+        Response::Chat(aggregate)
+    }
+}
+
+impl Streamed for PullResponse {
+    type Aggregate = Option<String>;
+
+    fn chunk(&mut self) -> Chunk<'_> {
+        Chunk::Pull(self)
+    }
+
+    fn aggregate(aggregate: &mut Option<String>, chunk: &Self) {
+        last_status(aggregate, &chunk.status);
+    }
+
+    fn response(aggregate: &Option<String>) -> Response<'_> {
+        Response::Status(aggregate.as_deref().unwrap_or_default())
+    }
+}
+
+impl Streamed for PushResponse {
+    type Aggregate = Option<String>;
+
+    fn chunk(&mut self) -> Chunk<'_> {
+        Chunk::Push(self)
+    }
+
+    fn aggregate(aggregate: &mut Option<String>, chunk: &Self) {
+        last_status(aggregate, &chunk.status);
+    }
+
+    fn response(aggregate: &Option<String>) -> Response<'_> {
+        Response::Status(aggregate.as_deref().unwrap_or_default())
+    }
+}
+
+impl Streamed for CreateResponse {
+    type Aggregate = Option<String>;
+
+    fn chunk(&mut self) -> Chunk<'_> {
+        Chunk::Create(self)
+    }
+
+    fn aggregate(aggregate: &mut Option<String>, chunk: &Self) {
+        last_status(aggregate, &chunk.status);
+    }
+
+    fn response(aggregate: &Option<String>) -> Response<'_> {
+        Response::Status(aggregate.as_deref().unwrap_or_default())
+    }
+}
+
+impl Streamed for ChatCompletionChunk {
+    type Aggregate = ChatCompletion;
+
+    fn chunk(&mut self) -> Chunk<'_> {
+        Chunk::ChatCompletion(self)
+    }
+
+    fn aggregate(aggregate: &mut ChatCompletion, chunk: &Self) {
+        aggregate.id.clone_from(&chunk.id);
+        aggregate.object = "chat.completion".to_string();
+        aggregate.created = chunk.created;
+        aggregate.model.clone_from(&chunk.model);
+        aggregate
+            .system_fingerprint
+            .clone_from(&chunk.system_fingerprint);
+        for delta in &chunk.choices {
+            let choices = &mut aggregate.choices;
+            let choice = match choices.iter().position(|c| c.index == delta.index) {
+                Some(position) => &mut choices[position],
+                None => {
+                    choices.push(ChatChoice {
+                        index: delta.index,
+                        ..Default::default()
+                    });
+                    choices.last_mut().unwrap()
+                }
+            };
+            let message = &mut choice.message;
+            if let Some(role) = &delta.delta.role {
+                message.role.clone_from(role);
+            }
+            if let Some(content) = &delta.delta.content {
+                match &mut message.content {
+                    Some(MessageContent::Text(text)) => text.push_str(content),
+                    _ => message.content = Some(MessageContent::Text(content.clone())),
+                }
+            }
+            if let Some(tool_calls) = &delta.delta.tool_calls {
+                message
+                    .tool_calls
+                    .get_or_insert_with(Vec::new)
+                    .extend(tool_calls.iter().cloned());
+            }
+            if let Some(logprobs) = &delta.logprobs {
+                let aggregate = choice.logprobs.get_or_insert_with(ChoiceLogprobs::default);
+                extend_logprobs(&mut aggregate.content, &logprobs.content);
+            }
+            if delta.finish_reason.is_some() {
+                choice.finish_reason.clone_from(&delta.finish_reason);
+            }
+        }
+        if chunk.usage.is_some() {
+            aggregate.usage.clone_from(&chunk.usage);
+        }
+    }
+
+    fn response(aggregate: &ChatCompletion) -> Response<'_> {
+        Response::ChatCompletion(aggregate)
+    }
+}
+
+impl Streamed for Completion {
+    type Aggregate = Completion;
+
+    fn chunk(&mut self) -> Chunk<'_> {
+        Chunk::Completion(self)
+    }
+
+    fn aggregate(aggregate: &mut Completion, chunk: &Self) {
+        aggregate.id.clone_from(&chunk.id);
+        aggregate.object.clone_from(&chunk.object);
+        aggregate.created = chunk.created;
+        aggregate.model.clone_from(&chunk.model);
+        aggregate
+            .system_fingerprint
+            .clone_from(&chunk.system_fingerprint);
+        for part in &chunk.choices {
+            let choices = &mut aggregate.choices;
+            let choice = match choices.iter().position(|c| c.index == part.index) {
+                Some(position) => &mut choices[position],
+                None => {
+                    choices.push(CompletionChoice {
+                        index: part.index,
+                        ..Default::default()
+                    });
+                    choices.last_mut().unwrap()
+                }
+            };
+            choice.text.push_str(&part.text);
+            if part.finish_reason.is_some() {
+                choice.finish_reason.clone_from(&part.finish_reason);
+            }
+        }
+        if chunk.usage.is_some() {
+            aggregate.usage.clone_from(&chunk.usage);
+        }
+    }
+
+    fn response(aggregate: &Completion) -> Response<'_> {
+        Response::Completion(aggregate)
+    }
+}
+
+fn last_status(aggregate: &mut Option<String>, status: &Option<String>) {
+    if status.is_some() {
+        aggregate.clone_from(status);
+    }
+}
+
+fn extend_logprobs(aggregate: &mut Option<Vec<Logprob>>, logprobs: &Option<Vec<Logprob>>) {
+    if let Some(logprobs) = logprobs {
+        aggregate
+            .get_or_insert_with(Vec::new)
+            .extend(logprobs.iter().cloned());
+    }
+}
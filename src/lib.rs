@@ -1,16 +1,75 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+
+use auth::{AuthProvider, BearerToken, Credentials};
+use cache::EmbeddingCache;
+use cassette::Cassette;
+use embeddings::{EmbedBatchOptions, EmbedEndpoint};
 use error::OllamaError;
-use futures::{stream, Stream, TryFutureExt, TryStreamExt};
+use futures::{stream, Stream, StreamExt, TryStreamExt};
+use interceptor::{Hooks, Interceptor};
+use limiter::{ConcurrencyLimiter, LimiterPermit};
 use models::*;
-use reqwest::Client;
-use tracing::debug;
-
+use reqwest::header::HeaderMap;
+use reqwest::{Client, Method, Response, StatusCode};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use telemetry::Call;
+use tracing::{debug, Span};
+
+mod base64;
+pub mod api;
+pub mod auth;
+pub mod batch;
+pub mod cache;
+pub mod cassette;
+pub mod config;
+pub mod context;
+pub mod embeddings;
 pub mod error;
+pub mod gguf;
+pub mod interceptor;
+pub mod limiter;
+pub mod logprobs;
+#[cfg(feature = "metrics")]
+pub mod meter;
+pub mod mock;
+pub mod modelfile;
 pub mod models;
+pub mod openai_compat;
+pub mod rag;
+#[cfg(feature = "server")]
+pub mod server;
+pub mod session;
+pub mod stats;
+pub mod stop;
+pub mod store;
+pub mod telemetry;
+pub mod template;
+#[cfg(feature = "testing")]
+pub mod testing;
 
 /// Client for interacting with the Ollama API.
 pub struct OllamaClient {
     client: Client,
     base_url: String,
+    auth: Option<Arc<dyn AuthProvider>>,
+    capture_content: bool,
+    interceptors: Vec<Arc<dyn Interceptor>>,
+    limiter: Option<Arc<ConcurrencyLimiter>>,
+    cassette: Option<Arc<Cassette>>,
+    embedding_cache: Option<Arc<dyn EmbeddingCache>>,
+    embed_endpoint: Mutex<Option<EmbedEndpoint>>,
+    embedding_dimensions: Mutex<HashMap<String, usize>>,
+    model_digests: Mutex<HashMap<String, (String, Instant)>>,
+}
+
+impl Default for OllamaClient {
+    /// Same as [`OllamaClient::from_env`].
+    fn default() -> Self {
+        OllamaClient::from_env()
+    }
 }
 
 impl OllamaClient {
@@ -19,251 +78,364 @@ impl OllamaClient {
         OllamaClient {
             client: Client::new(),
             base_url: base_url.to_string(),
+            auth: None,
+            capture_content: false,
+            interceptors: Vec::new(),
+            limiter: None,
+            cassette: None,
+            embedding_cache: None,
+            embed_endpoint: Mutex::new(None),
+            embedding_dimensions: Mutex::new(HashMap::new()),
+            model_digests: Mutex::new(HashMap::new()),
         }
     }
-    /// Lists all locally available models.
-    pub async fn list_models(&self) -> Result<Vec<ModelInfo>, OllamaError> {
-        let url = format!("{}/api/tags", self.base_url);
-        let response = self.client.get(&url).send().await?;
 
-        if response.status().is_success() {
-            let response_body: ListModelsResponse = response.json().await?;
-            Ok(response_body.models)
-        } else {
-            let status = response.status();
-            let error_text = response
-                .text()
-                .await
-                .unwrap_or_else(|_| "Unknown error".to_string());
-            Err(OllamaError::ApiError(format!(
-                "Status: {}, Error: {}",
-                status, error_text
-            )))
+    /// Creates a client for the server in `OLLAMA_HOST`, authenticated with
+    /// `OLLAMA_API_KEY` if set. See [`config`] for all variables.
+    pub fn from_env() -> Self {
+        let client = OllamaClient::new(&config::host_from_env())
+            .with_content_capture(config::capture_content_from_env());
+        match config::api_key_from_env() {
+            Some(key) => client.with_api_key(&key),
+            None => client,
         }
     }
 
-    /// Shows information about a specific model.
-    pub async fn show_model(&self, model_name: &str) -> Result<ModelInfo, OllamaError> {
-        let url = format!("{}/api/show", self.base_url);
-        let request = ShowModelRequest {
-            name: model_name.to_string(),
-        };
+    /// Sends `key` as a bearer token with every request.
+    pub fn with_api_key(self, key: &str) -> Self {
+        self.with_auth(Arc::new(BearerToken::new(key)))
+    }
 
-        let response = self.client.post(&url).json(&request).send().await?;
+    /// Attaches credentials from `auth` to every request. See [`auth`].
+    pub fn with_auth(mut self, auth: Arc<dyn AuthProvider>) -> Self {
+        self.auth = Some(auth);
+        self
+    }
 
-        if response.status().is_success() {
-            let response_body: ModelInfo = response.json().await?;
-            Ok(response_body)
-        } else {
-            let status = response.status();
-            let error_text = response
-                .text()
-                .await
-                .unwrap_or_else(|_| "Unknown error".to_string());
-            Err(OllamaError::ApiError(format!(
-                "Status: {}, Error: {}",
-                status, error_text
-            )))
+    /// Records prompts and completions on tracing spans. Off by default, as
+    /// they may contain sensitive data. See [`telemetry`].
+    pub fn with_content_capture(mut self, enabled: bool) -> Self {
+        self.capture_content = enabled;
+        self
+    }
+
+    /// Adds `interceptor` after any already added. See [`interceptor`].
+    pub fn with_interceptor(mut self, interceptor: Arc<dyn Interceptor>) -> Self {
+        self.interceptors.push(interceptor);
+        self
+    }
+
+    /// Returns the base URL requests are sent to.
+    pub fn base_url(&self) -> &str {
+        &self.base_url
+    }
+
+    /// Limits concurrent inference requests (`generate`, `chat` and embeddings).
+    ///
+    /// Streaming responses hold their slot until the stream is dropped.
+    pub fn with_limiter(mut self, limiter: ConcurrencyLimiter) -> Self {
+        self.limiter = Some(Arc::new(limiter));
+        self
+    }
+
+    /// Returns the configured concurrency limiter, if any.
+    pub fn limiter(&self) -> Option<&ConcurrencyLimiter> {
+        self.limiter.as_deref()
+    }
+
+    /// Records or replays every HTTP exchange through `cassette`.
+    pub fn with_cassette(mut self, cassette: Cassette) -> Self {
+        self.cassette = Some(Arc::new(cassette));
+        self
+    }
+
+    /// Returns the configured cassette, if any.
+    pub fn cassette(&self) -> Option<&Cassette> {
+        self.cassette.as_deref()
+    }
+
+    /// Serves embeddings from `cache` where possible. Only cache misses are
+    /// sent to Ollama; see [`cache`] for how entries are keyed.
+    pub fn with_embedding_cache(mut self, cache: Arc<dyn EmbeddingCache>) -> Self {
+        self.embedding_cache = Some(cache);
+        self
+    }
+
+    /// Uses `endpoint` for embeddings instead of detecting it from the server version.
+    pub fn with_embed_endpoint(self, endpoint: EmbedEndpoint) -> Self {
+        *lock(&self.embed_endpoint) = Some(endpoint);
+        self
+    }
+
+    async fn acquire_slot(&self, model: &str) -> Result<Option<LimiterPermit>, OllamaError> {
+        match &self.limiter {
+            Some(limiter) => limiter.acquire(model).await.map(Some),
+            None => Ok(None),
         }
     }
 
-    /// Pulls a model from the registry.
+    async fn before_request(
+        &self,
+        operation: &'static str,
+        endpoint: &'static str,
+        request: interceptor::Request<'_>,
+    ) -> Result<Hooks, OllamaError> {
+        Hooks::before(&self.interceptors, operation, endpoint, request).await
+    }
+
+    fn call(&self, operation: &'static str, endpoint: &'static str, model: Option<&str>) -> Call {
+        Call::new(&self.base_url, operation, endpoint, model)
+    }
+
+    async fn send_json<B: Serialize>(
+        &self,
+        method: Method,
+        path: &str,
+        body: &B,
+    ) -> Result<Response, OllamaError> {
+        self.send(method, path, Some(serde_json::to_value(body)?)).await
+    }
+
+    /// Sends a request with the configured credentials. On a 401 the
+    /// credentials are refreshed and the request retried once.
+    async fn send(
+        &self,
+        method: Method,
+        path: &str,
+        body: Option<serde_json::Value>,
+    ) -> Result<Response, OllamaError> {
+        let credentials = self.credentials().await?;
+        let mut response = self
+            .send_once(method.clone(), path, body.clone(), credentials.headers)
+            .await?;
+        if response.status() != StatusCode::UNAUTHORIZED {
+            return Ok(response);
+        }
+        if let Some(auth) = &self.auth {
+            if auth.refresh(credentials.generation).await? {
+                debug!(path, "retrying with refreshed credentials");
+                let headers = self.credentials().await?.headers;
+                response = self.send_once(method, path, body, headers).await?;
+                if response.status() != StatusCode::UNAUTHORIZED {
+                    return Ok(response);
+                }
+            }
+        }
+        let message = response.text().await.unwrap_or_default();
+        Err(OllamaError::Unauthorized(error_message(&message)))
+    }
+
+    async fn credentials(&self) -> Result<Credentials, OllamaError> {
+        match &self.auth {
+            Some(auth) => auth.headers().await,
+            None => Ok(Credentials::default()),
+        }
+    }
+
+    async fn send_once(
+        &self,
+        method: Method,
+        path: &str,
+        body: Option<serde_json::Value>,
+        mut headers: HeaderMap,
+    ) -> Result<Response, OllamaError> {
+        if let Some(added) = interceptor::request_headers() {
+            headers.extend(added);
+        }
+        let span = Span::current();
+        span.record("http.request.method", method.as_str());
+        let response = if let Some(cassette) = &self.cassette {
+            cassette
+                .send(&self.client, method, &self.base_url, path, headers, body)
+                .await?
+        } else {
+            let url = format!("{}{}", self.base_url, path);
+            let mut builder = self.client.request(method, &url).headers(headers);
+            if let Some(body) = &body {
+                builder = builder.json(body);
+            }
+            builder.send().await?
+        };
+        span.record("http.response.status_code", response.status().as_u16());
+        Ok(response)
+    }
+
+    /// Lists all locally available models.
+    pub async fn list_models(&self) -> Result<Vec<ModelInfo>, OllamaError> {
+        let hooks = self
+            .before_request("list_models", "/api/tags", interceptor::Request::Empty)
+            .await?;
+        let call = self.call("list_models", "/api/tags", None);
+        let result = telemetry::traced(&call, hooks.send(async {
+            let response = self.send(Method::GET, "/api/tags", None).await?;
+
+            if response.status().is_success() {
+                let response_body: ListModelsResponse = response.json().await?;
+                Ok(response_body.models)
+            } else {
+                Err(api_error(response).await)
+            }
+        }))
+        .await;
+        hooks.after(result, |models| interceptor::Response::Models(models)).await
+    }
+
+    /// Shows information about a specific model.
+    pub async fn show_model(&self, model_name: &str) -> Result<ShowModelResponse, OllamaError> {
+        let mut request = ShowModelRequest {
+            name: model_name.to_string(),
+        };
+
+        let hooks = self
+            .before_request(
+                "show_model",
+                "/api/show",
+                interceptor::Request::Model(&mut request.name),
+            )
+            .await?;
+        let call = self.call("show_model", "/api/show", Some(&request.name));
+        let result = telemetry::traced(&call, hooks.send(async {
+            let response = self.send_json(Method::POST, "/api/show", &request).await?;
+
+            if response.status().is_success() {
+                let response_body: ShowModelResponse = response.json().await?;
+                Ok(response_body)
+            } else {
+                Err(api_error(response).await)
+            }
+        }))
+        .await;
+        hooks.after(result, |info| interceptor::Response::ShowModel(info)).await
+    }
 
     /// Pulls a model from the registry.
     pub async fn pull_model(&self, model_name: &str) -> Result<impl Stream<Item = Result<PullResponse, OllamaError>>, OllamaError> {
-        let url = format!("{}/api/pull", self.base_url);
-        let request = PullModelRequest {
+        let mut request = PullModelRequest {
             name: model_name.to_string(),
         };
 
-        let response = self.client.post(&url).json(&request).send().await?;
-
-        if response.status().is_success() {
-            let stream = response
-                .bytes_stream()
-                .map_err(OllamaError::RequestFailed)
-                .try_filter_map(|chunk| async move {
-                    let mut buffer = chunk.to_vec();
-                    let mut lines = Vec::new();
-                    
-                    while let Some(pos) = buffer.iter().position(|&b| b == b'\n') {
-                        let line = buffer.drain(..=pos).collect::<Vec<_>>();
-                        let line_str = String::from_utf8(line)
-                            .map_err(|e| OllamaError::InvalidResponse(e.to_string()))?
-                            .trim_end()
-                            .to_string();
-                        
-                        if !line_str.is_empty() {
-                            lines.push(Ok(line_str));
-                        }
-                    }
-                    
-                    Ok(Some(stream::iter(lines)))
-                })
-                .try_flatten()
-                .and_then(|line| async move {
-                    serde_json::from_str::<PullResponse>(&line)
-                        .map_err(OllamaError::InvalidResponseFormat)
-                });
-            Ok(stream)
-        } else {
-            let status = response.status();
-            let error_text = response.text().await.unwrap_or_else(|_| "Unknown error".to_string());
-            Err(OllamaError::ApiError(format!("Status: {}, Error: {}", status, error_text)))
-        }
+        let hooks = self
+            .before_request(
+                "pull_model",
+                "/api/pull",
+                interceptor::Request::Model(&mut request.name),
+            )
+            .await?;
+        let call = self.call("pull_model", "/api/pull", Some(&request.name));
+        let opened = telemetry::open_stream(&call, hooks.send(async {
+            let response = self.send_json(Method::POST, "/api/pull", &request).await?;
+
+            if response.status().is_success() {
+                let stream = ndjson_stream::<PullResponse>(response);
+                Ok(stream)
+            } else {
+                Err(api_error(response).await)
+            }
+        }))
+        .await;
+        let stream = hooks.stream(opened).await?;
+        Ok(telemetry::traced_stream(stream, call, false))
     }
 
-
     /// Generates a completion using a model.
     pub async fn generate(
         &self,
-        request: GenerateRequest,
+        mut request: GenerateRequest,
     ) -> Result<impl Stream<Item = Result<GenerateResponse, OllamaError>>, OllamaError> {
-        let url = format!("{}/api/generate", self.base_url);
-        let response = self.client.post(&url).json(&request).send().await?;
+        let hooks = self
+            .before_request(
+                "text_completion",
+                "/api/generate",
+                interceptor::Request::Generate(&mut request),
+            )
+            .await?;
+        let call = self.call("text_completion", "/api/generate", Some(&request.model));
+        call.record_options(request.options.as_ref());
+        if self.capture_content {
+            let messages = serde_json::json!([{ "role": "user", "content": request.prompt }]);
+            call.record_messages("gen_ai.input.messages", messages);
+        }
 
-        if response.status().is_success() {
-            let stream = response
-                .bytes_stream()
-                .map_err(OllamaError::RequestFailed)
-                // Process chunks and split into lines
-                .try_filter_map(|chunk| async move {
-                    let mut buffer = chunk.to_vec();
-                    let mut lines = Vec::new();
-
-                    // Split buffer into lines
-                    while let Some(pos) = buffer.iter().position(|&b| b == b'\n') {
-                        let line = buffer.drain(..=pos).collect::<Vec<_>>();
-                        let line_str = String::from_utf8(line)
-                            .map_err(|e| OllamaError::InvalidResponse(e.to_string()))?
-                            .trim_end()
-                            .to_string();
-
-                        if !line_str.is_empty() {
-                            lines.push(Ok(line_str));
-                        }
-                    }
+        let opened = telemetry::open_stream(&call, hooks.send(async {
+            let permit = self.acquire_slot(&request.model).await?;
+            let response = self.send_json(Method::POST, "/api/generate", &request).await?;
 
-                    // Return remaining buffer for next chunk
-                    Ok(Some(stream::iter(lines)))
-                })
-                .try_flatten()
-                // Parse each line as JSON
-                .and_then(|line| async move {
-                    debug!(line);
-                    serde_json::from_str::<GenerateResponse>(&line)
-                        .map_err(OllamaError::InvalidResponseFormat)
+            if response.status().is_success() {
+                // Keep the limiter slot until the stream is dropped
+                let stream = ndjson_stream::<GenerateResponse>(response).map(move |chunk| {
+                    let _held = &permit;
+                    chunk
                 });
-            Ok(stream)
-        } else {
-            let status = response.status();
-            let error_text = response
-                .text()
-                .await
-                .unwrap_or_else(|_| "Unknown error".to_string());
-            Err(OllamaError::ApiError(format!(
-                "Status: {}, Error: {}",
-                status, error_text
-            )))
-        }
+                Ok(stream)
+            } else {
+                Err(api_error(response).await)
+            }
+        }))
+        .await;
+        let stream = hooks.stream(opened).await?;
+        Ok(telemetry::traced_stream(stream, call, self.capture_content))
     }
 
     /// Chats with a model.
     pub async fn chat(
         &self,
-        request: ChatRequest,
+        mut request: ChatRequest,
     ) -> Result<impl Stream<Item = Result<ChatResponse, OllamaError>>, OllamaError> {
-        let url = format!("{}/api/chat", self.base_url);
-        let response = self.client.post(&url).json(&request).send().await?;
+        let hooks = self
+            .before_request("chat", "/api/chat", interceptor::Request::Chat(&mut request))
+            .await?;
+        let call = self.call("chat", "/api/chat", Some(&request.model));
+        call.record_options(request.options.as_ref());
+        if self.capture_content {
+            let messages = serde_json::to_value(&request.messages)?;
+            call.record_messages("gen_ai.input.messages", messages);
+        }
 
-        if response.status().is_success() {
-            let stream = response
-                .bytes_stream()
-                .map_err(OllamaError::RequestFailed)
-                .try_filter_map(|chunk| async move {
-                    let mut buffer = chunk.to_vec();
-                    let mut lines = Vec::new();
-
-                    while let Some(pos) = buffer.iter().position(|&b| b == b'\n') {
-                        let line = buffer.drain(..=pos).collect::<Vec<_>>();
-                        let line_str = String::from_utf8(line)
-                            .map_err(|e| OllamaError::InvalidResponse(e.to_string()))?
-                            .trim_end()
-                            .to_string();
-
-                        if !line_str.is_empty() {
-                            lines.push(Ok(line_str));
-                        }
-                    }
+        let opened = telemetry::open_stream(&call, hooks.send(async {
+            let permit = self.acquire_slot(&request.model).await?;
+            let response = self.send_json(Method::POST, "/api/chat", &request).await?;
 
-                    Ok(Some(stream::iter(lines)))
-                })
-                .try_flatten()
-                .and_then(|line| async move {
-                    serde_json::from_str::<ChatResponse>(&line)
-                        .map_err(OllamaError::InvalidResponseFormat)
+            if response.status().is_success() {
+                let stream = ndjson_stream::<ChatResponse>(response).map(move |chunk| {
+                    let _held = &permit;
+                    chunk
                 });
-            Ok(stream)
-        } else {
-            let status = response.status();
-            let error_text = response
-                .text()
-                .await
-                .unwrap_or_else(|_| "Unknown error".to_string());
-            Err(OllamaError::ApiError(format!(
-                "Status: {}, Error: {}",
-                status, error_text
-            )))
-        }
+                Ok(stream)
+            } else {
+                Err(api_error(response).await)
+            }
+        }))
+        .await;
+        let stream = hooks.stream(opened).await?;
+        Ok(telemetry::traced_stream(stream, call, self.capture_content))
     }
 
     /// Creates a new model.
     pub async fn create_model(
         &self,
-        request: CreateModelRequest,
+        mut request: CreateModelRequest,
     ) -> Result<impl Stream<Item = Result<CreateResponse, OllamaError>>, OllamaError> {
-        let url = format!("{}/api/create", self.base_url);
-        let response = self.client.post(&url).json(&request).send().await?;
-
-        if response.status().is_success() {
-            let stream = response
-                .bytes_stream()
-                .map_err(OllamaError::RequestFailed)
-                .try_filter_map(|chunk| async move {
-                    let mut buffer = chunk.to_vec();
-                    let mut lines = Vec::new();
-
-                    while let Some(pos) = buffer.iter().position(|&b| b == b'\n') {
-                        let line = buffer.drain(..=pos).collect::<Vec<_>>();
-                        let line_str = String::from_utf8(line)
-                            .map_err(|e| OllamaError::InvalidResponse(e.to_string()))?
-                            .trim_end()
-                            .to_string();
-
-                        if !line_str.is_empty() {
-                            lines.push(Ok(line_str));
-                        }
-                    }
-
-                    Ok(Some(stream::iter(lines)))
-                })
-                .try_flatten()
-                .and_then(|line| async move {
-                    serde_json::from_str::<CreateResponse>(&line)
-                        .map_err(OllamaError::InvalidResponseFormat)
-                });
-            Ok(stream)
-        } else {
-            let status = response.status();
-            let error_text = response
-                .text()
-                .await
-                .unwrap_or_else(|_| "Unknown error".to_string());
-            Err(OllamaError::ApiError(format!(
-                "Status: {}, Error: {}",
-                status, error_text
-            )))
-        }
+        let hooks = self
+            .before_request(
+                "create_model",
+                "/api/create",
+                interceptor::Request::CreateModel(&mut request),
+            )
+            .await?;
+        let call = self.call("create_model", "/api/create", Some(&request.model));
+        let opened = telemetry::open_stream(&call, hooks.send(async {
+            let response = self.send_json(Method::POST, "/api/create", &request).await?;
+
+            if response.status().is_success() {
+                let stream = ndjson_stream::<CreateResponse>(response);
+                Ok(stream)
+            } else {
+                Err(api_error(response).await)
+            }
+        }))
+        .await;
+        let stream = hooks.stream(opened).await?;
+        Ok(telemetry::traced_stream(stream, call, false))
     }
 
     /// Pushes a model to the registry.
@@ -271,101 +443,113 @@ impl OllamaClient {
         &self,
         model_name: &str,
     ) -> Result<impl Stream<Item = Result<PushResponse, OllamaError>>, OllamaError> {
-        let url = format!("{}/api/push", self.base_url);
-        let request = PushModelRequest {
+        let mut request = PushModelRequest {
             name: model_name.to_string(),
         };
 
-        let response = self.client.post(&url).json(&request).send().await?;
-
-        if response.status().is_success() {
-            let stream = response
-                .bytes_stream()
-                .map_err(OllamaError::RequestFailed)
-                .try_filter_map(|chunk| async move {
-                    let mut buffer = chunk.to_vec();
-                    let mut lines = Vec::new();
-
-                    while let Some(pos) = buffer.iter().position(|&b| b == b'\n') {
-                        let line = buffer.drain(..=pos).collect::<Vec<_>>();
-                        let line_str = String::from_utf8(line)
-                            .map_err(|e| OllamaError::InvalidResponse(e.to_string()))?
-                            .trim_end()
-                            .to_string();
-
-                        if !line_str.is_empty() {
-                            lines.push(Ok(line_str));
-                        }
-                    }
-
-                    Ok(Some(stream::iter(lines)))
-                })
-                .try_flatten()
-                .and_then(|line| async move {
-                    serde_json::from_str::<PushResponse>(&line)
-                        .map_err(OllamaError::InvalidResponseFormat)
-                });
-            Ok(stream)
-        } else {
-            let status = response.status();
-            let error_text = response
-                .text()
-                .await
-                .unwrap_or_else(|_| "Unknown error".to_string());
-            Err(OllamaError::ApiError(format!(
-                "Status: {}, Error: {}",
-                status, error_text
-            )))
-        }
+        let hooks = self
+            .before_request(
+                "push_model",
+                "/api/push",
+                interceptor::Request::Model(&mut request.name),
+            )
+            .await?;
+        let call = self.call("push_model", "/api/push", Some(&request.name));
+        let opened = telemetry::open_stream(&call, hooks.send(async {
+            let response = self.send_json(Method::POST, "/api/push", &request).await?;
+
+            if response.status().is_success() {
+                let stream = ndjson_stream::<PushResponse>(response);
+                Ok(stream)
+            } else {
+                Err(api_error(response).await)
+            }
+        }))
+        .await;
+        let stream = hooks.stream(opened).await?;
+        Ok(telemetry::traced_stream(stream, call, false))
     }
 
     /// Deletes a model.
     pub async fn delete_model(&self, model_name: &str) -> Result<(), OllamaError> {
-        let url = format!("{}/api/delete", self.base_url);
-        let request = DeleteModelRequest {
+        let mut request = DeleteModelRequest {
             name: model_name.to_string(),
         };
 
-        let response = self.client.delete(&url).json(&request).send().await?;
+        let hooks = self
+            .before_request(
+                "delete_model",
+                "/api/delete",
+                interceptor::Request::Model(&mut request.name),
+            )
+            .await?;
+        let call = self.call("delete_model", "/api/delete", Some(&request.name));
+        let result = telemetry::traced(&call, hooks.send(async {
+            let response = self.send_json(Method::DELETE, "/api/delete", &request).await?;
+
+            if response.status().is_success() {
+                Ok(())
+            } else {
+                Err(api_error(response).await)
+            }
+        }))
+        .await;
+        hooks.after(result, |_| interceptor::Response::Empty).await
+    }
 
-        if response.status().is_success() {
-            Ok(())
-        } else {
-            let status = response.status();
-            let error_text = response
-                .text()
-                .await
-                .unwrap_or_else(|_| "Unknown error".to_string());
-            Err(OllamaError::ApiError(format!(
-                "Status: {}, Error: {}",
-                status, error_text
-            )))
-        }
+    /// Copies a model under a new name.
+    pub async fn copy_model(&self, source: &str, destination: &str) -> Result<(), OllamaError> {
+        let mut request = CopyModelRequest {
+            source: source.to_string(),
+            destination: destination.to_string(),
+        };
+
+        let hooks = self
+            .before_request(
+                "copy_model",
+                "/api/copy",
+                interceptor::Request::CopyModel(&mut request),
+            )
+            .await?;
+        let call = self.call("copy_model", "/api/copy", Some(&request.source));
+        let result = telemetry::traced(&call, hooks.send(async {
+            let response = self.send_json(Method::POST, "/api/copy", &request).await?;
+
+            if response.status().is_success() {
+                Ok(())
+            } else {
+                Err(api_error(response).await)
+            }
+        }))
+        .await;
+        hooks.after(result, |_| interceptor::Response::Empty).await
     }
 
     /// Generates embeddings from a model.
     pub async fn generate_embeddings(
         &self,
-        request: EmbedRequest,
+        mut request: EmbedRequest,
     ) -> Result<EmbedResponse, OllamaError> {
-        let url = format!("{}/api/embed", self.base_url);
-        let response = self.client.post(&url).json(&request).send().await?;
-
-        if response.status().is_success() {
-            let response_body: EmbedResponse = response.json().await?;
-            Ok(response_body)
-        } else {
-            let status = response.status();
-            let error_text = response
-                .text()
-                .await
-                .unwrap_or_else(|_| "Unknown error".to_string());
-            Err(OllamaError::ApiError(format!(
-                "Status: {}, Error: {}",
-                status, error_text
-            )))
-        }
+        let hooks = self
+            .before_request("embeddings", "/api/embed", interceptor::Request::Embed(&mut request))
+            .await?;
+        let call = self.call("embeddings", "/api/embed", Some(&request.model));
+        call.record_options(request.options.as_ref());
+        let result = telemetry::traced(&call, hooks.send(async {
+            match &self.embedding_cache {
+                Some(cache) => cache::embed_with_cache(self, cache.as_ref(), request).await,
+                None => self.embed_uncached(request).await,
+            }
+        }))
+        .await;
+        let response = hooks
+            .after(result, |response| interceptor::Response::Embed(response))
+            .await?;
+        call.record_response_model(&response.model);
+        call.record_usage(response.prompt_eval_count, None);
+        Ok(response)
     }
+
     pub async fn generate_multiple_embeddings(
         &self,
         model: String,
@@ -374,7 +558,6 @@ impl OllamaClient {
         options: Option<GenerateOptions>,
         keep_alive: Option<String>,
     ) -> Result<EmbedResponse, OllamaError> {
-        let url = format!("{}/api/embed", self.base_url);
         let request = EmbedRequest {
             model,
             input: EmbedInput::Multiple(inputs),
@@ -383,62 +566,270 @@ impl OllamaClient {
             keep_alive,
         };
 
-        let response = self.client.post(&url).json(&request).send().await?;
+        self.generate_embeddings(request).await
+    }
+
+    async fn embed_uncached(&self, request: EmbedRequest) -> Result<EmbedResponse, OllamaError> {
+        let _permit = self.acquire_slot(&request.model).await?;
+        if self.embed_endpoint().await == EmbedEndpoint::Legacy {
+            return self.embed_legacy(request).await;
+        }
+
+        let response = self.send_json(Method::POST, "/api/embed", &request).await?;
 
         if response.status().is_success() {
             let response_body: EmbedResponse = response.json().await?;
             Ok(response_body)
-        } else {
-            let status = response.status();
-            let error_text = response
-                .text()
-                .await
-                .unwrap_or_else(|_| "Unknown error".to_string());
+        } else if response.status() == reqwest::StatusCode::NOT_FOUND {
+            // A missing route (as opposed to a missing model) means the server predates /api/embed
+            let error_text = response.text().await.unwrap_or_default();
+            if error_text.trim() == "404 page not found" {
+                *lock(&self.embed_endpoint) = Some(EmbedEndpoint::Legacy);
+                return self.embed_legacy(request).await;
+            }
             Err(OllamaError::ApiError(format!(
                 "Status: {}, Error: {}",
-                status, error_text
+                reqwest::StatusCode::NOT_FOUND,
+                error_text
             )))
+        } else {
+            Err(api_error(response).await)
         }
     }
-    /// Lists running models.
-    pub async fn list_running_models(&self) -> Result<Vec<RunningModelInfo>, OllamaError> {
-        let url = format!("{}/api/ps", self.base_url);
-        let response = self.client.get(&url).send().await?;
 
-        if response.status().is_success() {
-            let response_body: ListRunningModelsResponse = response.json().await?;
-            Ok(response_body.models)
-        } else {
-            let status = response.status();
-            let error_text = response
-                .text()
-                .await
-                .unwrap_or_else(|_| "Unknown error".to_string());
-            Err(OllamaError::ApiError(format!(
-                "Status: {}, Error: {}",
-                status, error_text
-            )))
+    /// Embeds each input with the legacy `/api/embeddings` endpoint.
+    ///
+    /// The legacy endpoint does not normalize its output, so the vectors are
+    /// L2-normalized here to match `/api/embed`. `truncate` is not supported.
+    async fn embed_legacy(&self, request: EmbedRequest) -> Result<EmbedResponse, OllamaError> {
+        let inputs = match request.input {
+            EmbedInput::Single(input) => vec![input],
+            EmbedInput::Multiple(inputs) => inputs,
+        };
+
+        let mut embeddings = Vec::with_capacity(inputs.len());
+        for prompt in inputs {
+            let legacy_request = LegacyEmbeddingRequest {
+                model: request.model.clone(),
+                prompt,
+                options: request.options.clone(),
+                keep_alive: request.keep_alive.clone(),
+            };
+            let response = self
+                .send_json(Method::POST, "/api/embeddings", &legacy_request)
+                .await?;
+
+            if response.status().is_success() {
+                let mut response_body: LegacyEmbeddingResponse = response.json().await?;
+                embeddings::normalize_l2(&mut response_body.embedding);
+                embeddings.push(response_body.embedding);
+            } else {
+                return Err(api_error(response).await);
+            }
+        }
+
+        Ok(EmbedResponse {
+            model: request.model,
+            embeddings,
+            ..Default::default()
+        })
+    }
+
+    /// Returns the embeddings endpoint to use, detecting it from the server
+    /// version on first use.
+    async fn embed_endpoint(&self) -> EmbedEndpoint {
+        if let Some(endpoint) = *lock(&self.embed_endpoint) {
+            return endpoint;
+        }
+        let endpoint = match self.get_version().await {
+            Ok(version) => EmbedEndpoint::for_version(&version),
+            Err(_) => EmbedEndpoint::Embed,
+        };
+        debug!(?endpoint, "detected embeddings endpoint");
+        *lock(&self.embed_endpoint) = Some(endpoint);
+        endpoint
+    }
+
+    /// Returns the size of the vectors produced by an embedding model.
+    ///
+    /// Reads `embedding_length` from the model info and falls back to embedding
+    /// a short probe text. The answer is cached per model.
+    pub async fn embedding_dimensions(&self, model: &str) -> Result<usize, OllamaError> {
+        if let Some(&dimensions) = lock(&self.embedding_dimensions).get(model) {
+            return Ok(dimensions);
         }
+        let call = self.call("embedding_dimensions", "/api/show", Some(model)).unmetered();
+        telemetry::traced(&call, self.detect_embedding_dimensions(model)).await
+    }
+
+    async fn detect_embedding_dimensions(&self, model: &str) -> Result<usize, OllamaError> {
+        let from_info = self
+            .show_model(model)
+            .await
+            .ok()
+            .and_then(|info| info.embedding_length());
+        let dimensions = match from_info {
+            Some(length) => length as usize,
+            None => {
+                let probe = EmbedRequest {
+                    model: model.to_string(),
+                    input: EmbedInput::Single("dimension probe".to_string()),
+                    ..Default::default()
+                };
+                self.generate_embeddings(probe)
+                    .await?
+                    .embeddings
+                    .first()
+                    .map(Vec::len)
+                    .ok_or_else(|| OllamaError::InvalidResponse("no embedding returned".to_string()))?
+            }
+        };
+
+        lock(&self.embedding_dimensions).insert(model.to_string(), dimensions);
+        Ok(dimensions)
+    }
+
+    /// Embeds a large list of inputs in chunks with bounded concurrency,
+    /// preserving input order. See [`embeddings::embed_batch`].
+    pub async fn embed_batch(
+        &self,
+        model: &str,
+        inputs: Vec<String>,
+        options: EmbedBatchOptions,
+    ) -> Result<Vec<Vec<f32>>, OllamaError> {
+        let call = self.call("embed_batch", "/api/embed", Some(model)).unmetered();
+        telemetry::traced(&call, embeddings::embed_batch(self, model, inputs, options)).await
+    }
+
+    /// Returns a handle for the OpenAI-compatible `/v1` endpoints.
+    pub fn openai(&self) -> openai_compat::OpenAiClient<'_> {
+        openai_compat::OpenAiClient::new(self)
+    }
+
+    /// Lists running models.
+    pub async fn list_running_models(&self) -> Result<Vec<RunningModelInfo>, OllamaError> {
+        let hooks = self
+            .before_request("list_running_models", "/api/ps", interceptor::Request::Empty)
+            .await?;
+        let call = self.call("list_running_models", "/api/ps", None);
+        let result = telemetry::traced(&call, hooks.send(async {
+            let response = self.send(Method::GET, "/api/ps", None).await?;
+
+            if response.status().is_success() {
+                let response_body: ListRunningModelsResponse = response.json().await?;
+                Ok(response_body.models)
+            } else {
+                Err(api_error(response).await)
+            }
+        }))
+        .await;
+        hooks.after(result, |models| interceptor::Response::RunningModels(models)).await
     }
 
     /// Retrieves the Ollama version.
     pub async fn get_version(&self) -> Result<String, OllamaError> {
-        let url = format!("{}/api/version", self.base_url);
-        let response = self.client.get(&url).send().await?;
+        let hooks = self
+            .before_request("get_version", "/api/version", interceptor::Request::Empty)
+            .await?;
+        let call = self.call("get_version", "/api/version", None);
+        let result = telemetry::traced(&call, hooks.send(async {
+            let response = self.send(Method::GET, "/api/version", None).await?;
+
+            if response.status().is_success() {
+                let response_body: VersionResponse = response.json().await?;
+                Ok(response_body.version)
+            } else {
+                Err(api_error(response).await)
+            }
+        }))
+        .await;
+        hooks.after(result, |version| interceptor::Response::Version(version)).await
+    }
+}
 
-        if response.status().is_success() {
-            let response_body: VersionResponse = response.json().await?;
-            Ok(response_body.version)
-        } else {
-            let status = response.status();
-            let error_text = response
-                .text()
-                .await
-                .unwrap_or_else(|_| "Unknown error".to_string());
-            Err(OllamaError::ApiError(format!(
-                "Status: {}, Error: {}",
-                status, error_text
-            )))
+/// Locks `mutex`, recovering the guard if another thread panicked while
+/// holding it.
+pub(crate) fn lock<T>(mutex: &Mutex<T>) -> std::sync::MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+/// Builds an [`OllamaError::ApiError`] from a non-success response.
+async fn api_error(response: Response) -> OllamaError {
+    let status = response.status();
+    let error_text = response
+        .text()
+        .await
+        .unwrap_or_else(|_| "Unknown error".to_string());
+    OllamaError::ApiError(format!("Status: {}, Error: {}", status, error_text))
+}
+
+/// Extracts the message from an `{"error": "..."}` or OpenAI-style
+/// `{"error": {"message": "..."}}` body, falling back to the raw text.
+fn error_message(body: &str) -> String {
+    let value: serde_json::Value = serde_json::from_str(body).unwrap_or_default();
+    let error = &value["error"];
+    error
+        .as_str()
+        .or_else(|| error["message"].as_str())
+        .unwrap_or(body)
+        .trim()
+        .to_string()
+}
+
+/// Splits a streamed response body into lines, which may be split across any
+/// number of body chunks. Each line keeps its trailing newline.
+fn body_lines(response: Response) -> impl Stream<Item = Result<Vec<u8>, OllamaError>> {
+    let body = Box::pin(response.bytes_stream());
+    stream::unfold(
+        (body, Vec::new(), false),
+        |(mut body, mut buffer, mut finished)| async move {
+            loop {
+                // Split buffer into lines, keeping any partial line for the next chunk
+                if let Some(pos) = buffer.iter().position(|&b| b == b'\n') {
+                    let line = buffer.drain(..=pos).collect::<Vec<_>>();
+                    return Some((Ok(line), (body, buffer, finished)));
+                }
+                if finished {
+                    if buffer.is_empty() {
+                        return None;
+                    }
+                    let line = std::mem::take(&mut buffer);
+                    return Some((Ok(line), (body, buffer, finished)));
+                }
+                match body.next().await {
+                    Some(Ok(chunk)) => buffer.extend_from_slice(&chunk),
+                    Some(Err(e)) => {
+                        buffer.clear();
+                        return Some((Err(OllamaError::RequestFailed(e)), (body, buffer, true)));
+                    }
+                    None => finished = true,
+                }
+            }
+        },
+    )
+}
+
+/// Parses a newline-delimited JSON response body into a stream of `T`.
+///
+/// Lines may be split across any number of body chunks. An `{"error": ...}`
+/// line sent by the server mid-stream is surfaced as [`OllamaError::ApiError`].
+fn ndjson_stream<T: DeserializeOwned>(
+    response: Response,
+) -> impl Stream<Item = Result<T, OllamaError>> {
+    body_lines(response).try_filter_map(|line| async move {
+        let line =
+            String::from_utf8(line).map_err(|e| OllamaError::InvalidResponse(e.to_string()))?;
+        let line = line.trim();
+        if line.is_empty() {
+            return Ok(None);
         }
-    }
+        debug!(line);
+        match serde_json::from_str::<T>(line) {
+            Ok(value) => Ok(Some(value)),
+            Err(e) => match serde_json::from_str::<ErrorResponse>(line) {
+                Ok(error) => Err(OllamaError::ApiError(error.error)),
+                Err(_) => Err(OllamaError::InvalidResponseFormat(e)),
+            },
+        }
+    })
 }
@@ -0,0 +1,183 @@
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use tokio::sync::{OwnedSemaphorePermit, Semaphore, TryAcquireError};
+use tracing::debug;
+
+use crate::error::OllamaError;
+
+/// Client-side concurrency limiter for inference requests.
+///
+/// Requests first take a slot from the per-model limit (if one is configured
+/// for the model) and then from the global limit. Waiters are served in FIFO
+/// order. When a maximum queue depth is set, requests that would have to wait
+/// while the queue is full are rejected with [`OllamaError::Overloaded`].
+#[derive(Debug)]
+pub struct ConcurrencyLimiter {
+    global: Option<Arc<Semaphore>>,
+    per_model: HashMap<String, Arc<Semaphore>>,
+    max_queue_depth: Option<usize>,
+    queued: AtomicUsize,
+    acquired: AtomicU64,
+    waited: AtomicU64,
+    rejected: AtomicU64,
+    total_wait_micros: AtomicU64,
+    max_wait_micros: AtomicU64,
+}
+
+/// Snapshot of the limiter counters.
+#[derive(Debug, Clone, Default)]
+pub struct LimiterMetrics {
+    /// Requests currently waiting for a slot.
+    pub queued: usize,
+    /// Requests that obtained a slot.
+    pub acquired: u64,
+    /// Requests that had to wait before obtaining a slot.
+    pub waited: u64,
+    /// Requests rejected because the queue was full.
+    pub rejected: u64,
+    /// Total time spent waiting in the queue.
+    pub total_wait: Duration,
+    /// Longest time a single request spent in the queue.
+    pub max_wait: Duration,
+}
+
+impl LimiterMetrics {
+    /// Average queue wait time across all acquired requests.
+    pub fn average_wait(&self) -> Duration {
+        if self.acquired == 0 {
+            Duration::ZERO
+        } else {
+            self.total_wait.div_f64(self.acquired as f64)
+        }
+    }
+}
+
+/// Slots held by a request. Dropping the permit releases them.
+#[derive(Debug)]
+pub struct LimiterPermit {
+    _permits: Vec<OwnedSemaphorePermit>,
+}
+
+impl ConcurrencyLimiter {
+    /// Creates a limiter allowing at most `max_concurrent` requests in flight.
+    pub fn new(max_concurrent: usize) -> Self {
+        let mut limiter = Self::unbounded();
+        limiter.global = Some(Arc::new(Semaphore::new(max_concurrent)));
+        limiter
+    }
+
+    /// Creates a limiter without a global limit. Per-model limits can still be added.
+    pub fn unbounded() -> Self {
+        ConcurrencyLimiter {
+            global: None,
+            per_model: HashMap::new(),
+            max_queue_depth: None,
+            queued: AtomicUsize::new(0),
+            acquired: AtomicU64::new(0),
+            waited: AtomicU64::new(0),
+            rejected: AtomicU64::new(0),
+            total_wait_micros: AtomicU64::new(0),
+            max_wait_micros: AtomicU64::new(0),
+        }
+    }
+
+    /// Limits the number of concurrent requests for a single model.
+    pub fn with_model_limit(mut self, model: &str, max_concurrent: usize) -> Self {
+        self.per_model
+            .insert(model.to_string(), Arc::new(Semaphore::new(max_concurrent)));
+        self
+    }
+
+    /// Rejects requests once `depth` requests are already waiting.
+    pub fn with_max_queue_depth(mut self, depth: usize) -> Self {
+        self.max_queue_depth = Some(depth);
+        self
+    }
+
+    /// Returns a snapshot of the limiter counters.
+    pub fn metrics(&self) -> LimiterMetrics {
+        LimiterMetrics {
+            queued: self.queued.load(Ordering::Relaxed),
+            acquired: self.acquired.load(Ordering::Relaxed),
+            waited: self.waited.load(Ordering::Relaxed),
+            rejected: self.rejected.load(Ordering::Relaxed),
+            total_wait: Duration::from_micros(self.total_wait_micros.load(Ordering::Relaxed)),
+            max_wait: Duration::from_micros(self.max_wait_micros.load(Ordering::Relaxed)),
+        }
+    }
+
+    /// Waits for a slot for `model`.
+    pub async fn acquire(&self, model: &str) -> Result<LimiterPermit, OllamaError> {
+        let start = Instant::now();
+        let semaphores = self
+            .per_model
+            .get(model)
+            .into_iter()
+            .chain(self.global.as_ref());
+
+        let mut permits = Vec::new();
+        let mut slot: Option<QueueSlot<'_>> = None;
+        for semaphore in semaphores {
+            if slot.is_none() {
+                match semaphore.clone().try_acquire_owned() {
+                    Ok(permit) => {
+                        permits.push(permit);
+                        continue;
+                    }
+                    Err(TryAcquireError::NoPermits) => slot = Some(self.enter_queue()?),
+                    Err(TryAcquireError::Closed) => {
+                        unreachable!("limiter semaphores are never closed")
+                    }
+                }
+            }
+            let permit = semaphore
+                .clone()
+                .acquire_owned()
+                .await
+                .expect("limiter semaphores are never closed");
+            permits.push(permit);
+        }
+
+        let wait = start.elapsed();
+        let wait_micros = wait.as_micros() as u64;
+        self.acquired.fetch_add(1, Ordering::Relaxed);
+        self.total_wait_micros
+            .fetch_add(wait_micros, Ordering::Relaxed);
+        self.max_wait_micros
+            .fetch_max(wait_micros, Ordering::Relaxed);
+        if slot.is_some() {
+            self.waited.fetch_add(1, Ordering::Relaxed);
+            debug!(
+                model,
+                wait_ms = wait.as_millis() as u64,
+                "acquired limiter slot"
+            );
+        }
+
+        Ok(LimiterPermit { _permits: permits })
+    }
+
+    fn enter_queue(&self) -> Result<QueueSlot<'_>, OllamaError> {
+        let queued = self.queued.fetch_add(1, Ordering::SeqCst);
+        let slot = QueueSlot(&self.queued);
+        if let Some(max) = self.max_queue_depth {
+            if queued >= max {
+                self.rejected.fetch_add(1, Ordering::Relaxed);
+                return Err(OllamaError::Overloaded(queued));
+            }
+        }
+        Ok(slot)
+    }
+}
+
+/// Position in the wait queue, released on drop so cancelled waiters are not counted.
+struct QueueSlot<'a>(&'a AtomicUsize);
+
+impl Drop for QueueSlot<'_> {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::SeqCst);
+    }
+}
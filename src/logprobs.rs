@@ -0,0 +1,66 @@
+//! Sequence statistics over per-token log probabilities.
+//!
+//! Set `logprobs`, and optionally `top_logprobs`, on a
+//! [`GenerateRequest`](crate::models::GenerateRequest) or
+//! [`ChatRequest`](crate::models::ChatRequest). Each streamed chunk then
+//! carries the [`Logprob`]s of its tokens; collect them across chunks before
+//! using the functions below.
+//!
+//! ```no_run
+//! use futures::TryStreamExt;
+//! use ollama_oxide::logprobs;
+//! use ollama_oxide::models::GenerateRequest;
+//! use ollama_oxide::OllamaClient;
+//!
+//! # async fn example(client: OllamaClient) -> Result<(), ollama_oxide::error::OllamaError> {
+//! let request = GenerateRequest {
+//!     model: "llama3.2".to_string(),
+//!     prompt: "Is the sky blue? Answer yes or no.".to_string(),
+//!     logprobs: Some(true),
+//!     top_logprobs: Some(5),
+//!     ..Default::default()
+//! };
+//! let chunks: Vec<_> = client.generate(request).await?.try_collect().await?;
+//! let tokens: Vec<_> = chunks.into_iter().flat_map(|c| c.logprobs.unwrap_or_default()).collect();
+//! println!("perplexity {:?}", logprobs::perplexity(&tokens));
+//! println!("P(yes) {:?}", tokens.first().and_then(|t| t.probability_of("yes")));
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::models::Logprob;
+
+/// Log-likelihood of the whole sequence: the sum of the token log
+/// probabilities.
+pub fn log_likelihood(tokens: &[Logprob]) -> f64 {
+    tokens.iter().map(|token| token.logprob).sum()
+}
+
+/// Average log probability per token, or `None` for an empty sequence.
+pub fn mean_logprob(tokens: &[Logprob]) -> Option<f64> {
+    match tokens.len() {
+        0 => None,
+        n => Some(log_likelihood(tokens) / n as f64),
+    }
+}
+
+/// Perplexity of the sequence, `exp(-mean_logprob)`. 1.0 means every token
+/// was certain; higher values mean the model was less sure.
+pub fn perplexity(tokens: &[Logprob]) -> Option<f64> {
+    mean_logprob(tokens).map(|mean| (-mean).exp())
+}
+
+/// Probability of each generated token, in order.
+pub fn confidences(tokens: &[Logprob]) -> Vec<f64> {
+    tokens.iter().map(Logprob::probability).collect()
+}
+
+/// Positions of the tokens generated with a probability below `threshold`.
+pub fn uncertain_tokens(tokens: &[Logprob], threshold: f64) -> Vec<usize> {
+    tokens
+        .iter()
+        .enumerate()
+        .filter(|(_, token)| token.probability() < threshold)
+        .map(|(i, _)| i)
+        .collect()
+}
@@ -0,0 +1,112 @@
+//! Usage metrics recorded through the [`metrics`] facade.
+//!
+//! Requires the `metrics` feature. Nothing is exported until the application
+//! installs a recorder, e.g. `metrics-exporter-prometheus`. Call [`describe`]
+//! once after installing it to register units and descriptions.
+//!
+//! | Metric | Kind | Labels |
+//! |---|---|---|
+//! | [`REQUESTS`] | counter | `endpoint`, `model`, `outcome` |
+//! | [`REQUEST_DURATION`] | histogram, seconds | `endpoint`, `model`, `outcome` |
+//! | [`TIME_TO_FIRST_TOKEN`] | histogram, seconds | `endpoint`, `model` |
+//! | [`PROMPT_TOKENS`] | counter | `endpoint`, `model` |
+//! | [`EVAL_TOKENS`] | counter | `endpoint`, `model` |
+//! | [`TRANSFER_BYTES`] | counter, bytes | `endpoint` (`/api/pull` or `/api/push`), `model` |
+//! | [`STREAMS_IN_FLIGHT`] | gauge | `endpoint` |
+//!
+//! `endpoint` is the API path, `model` is empty for calls that do not name a
+//! model, and `outcome` is `success`, `cancelled` (a stream dropped before its
+//! end) or the kind of error, such as `api_error` or `timeout`. A streamed
+//! call is measured until its stream ends.
+//!
+//! Methods that only combine other calls, such as
+//! [`embed_batch`](crate::OllamaClient::embed_batch) and
+//! [`embedding_dimensions`](crate::OllamaClient::embedding_dimensions), are
+//! not counted themselves: each request they send is.
+
+use std::time::Duration;
+
+use metrics::{
+    counter, describe_counter, describe_gauge, describe_histogram, gauge, histogram, Unit,
+};
+
+pub const REQUESTS: &str = "ollama_requests_total";
+pub const REQUEST_DURATION: &str = "ollama_request_duration_seconds";
+pub const TIME_TO_FIRST_TOKEN: &str = "ollama_time_to_first_token_seconds";
+pub const PROMPT_TOKENS: &str = "ollama_prompt_tokens_total";
+pub const EVAL_TOKENS: &str = "ollama_eval_tokens_total";
+pub const TRANSFER_BYTES: &str = "ollama_transfer_bytes_total";
+pub const STREAMS_IN_FLIGHT: &str = "ollama_streams_in_flight";
+
+/// Registers the unit and description of every metric with the installed
+/// recorder.
+pub fn describe() {
+    describe_counter!(REQUESTS, "Client calls by endpoint, model and outcome");
+    describe_histogram!(
+        REQUEST_DURATION,
+        Unit::Seconds,
+        "Duration of client calls, including streamed responses"
+    );
+    describe_histogram!(
+        TIME_TO_FIRST_TOKEN,
+        Unit::Seconds,
+        "Time from sending a generation request to its first token"
+    );
+    describe_counter!(PROMPT_TOKENS, "Prompt tokens evaluated");
+    describe_counter!(EVAL_TOKENS, "Tokens generated");
+    describe_counter!(TRANSFER_BYTES, Unit::Bytes, "Bytes pulled or pushed");
+    describe_gauge!(STREAMS_IN_FLIGHT, "Streamed responses not yet finished");
+}
+
+pub(crate) fn record_request(
+    endpoint: &'static str,
+    model: &str,
+    outcome: &'static str,
+    duration: Duration,
+) {
+    let labels = [
+        ("endpoint", endpoint.to_string()),
+        ("model", model.to_string()),
+        ("outcome", outcome.to_string()),
+    ];
+    counter!(REQUESTS, &labels).increment(1);
+    histogram!(REQUEST_DURATION, &labels).record(duration.as_secs_f64());
+}
+
+pub(crate) fn record_first_token(endpoint: &'static str, model: &str, elapsed: Duration) {
+    histogram!(TIME_TO_FIRST_TOKEN, "endpoint" => endpoint, "model" => model.to_string())
+        .record(elapsed.as_secs_f64());
+}
+
+pub(crate) fn record_tokens(
+    endpoint: &'static str,
+    model: &str,
+    prompt_tokens: Option<u32>,
+    eval_tokens: Option<u32>,
+) {
+    let labels = [
+        ("endpoint", endpoint.to_string()),
+        ("model", model.to_string()),
+    ];
+    if let Some(tokens) = prompt_tokens {
+        counter!(PROMPT_TOKENS, &labels).increment(tokens.into());
+    }
+    if let Some(tokens) = eval_tokens {
+        counter!(EVAL_TOKENS, &labels).increment(tokens.into());
+    }
+}
+
+pub(crate) fn record_transfer(endpoint: &'static str, model: &str, bytes: u64) {
+    if bytes > 0 {
+        counter!(TRANSFER_BYTES, "endpoint" => endpoint, "model" => model.to_string())
+            .increment(bytes);
+    }
+}
+
+pub(crate) fn record_stream_opened(endpoint: &'static str) {
+    gauge!(STREAMS_IN_FLIGHT, "endpoint" => endpoint).increment(1.0);
+}
+
+pub(crate) fn record_stream_closed(endpoint: &'static str) {
+    gauge!(STREAMS_IN_FLIGHT, "endpoint" => endpoint).decrement(1.0);
+}
@@ -0,0 +1,278 @@
+use std::collections::VecDeque;
+use std::sync::Mutex;
+
+use async_trait::async_trait;
+use futures::{stream, StreamExt};
+
+use crate::api::{OllamaApi, ResponseStream};
+use crate::error::OllamaError;
+use crate::models::*;
+
+/// Scripted reply for a streaming endpoint: either the chunks to stream
+/// (each of which may itself be an error) or an error for the initial request.
+pub type MockStream<T> = Result<Vec<Result<T, OllamaError>>, OllamaError>;
+
+/// A request received by [`MockOllama`].
+#[derive(Debug, Clone)]
+pub enum RecordedRequest {
+    ListModels,
+    ShowModel(String),
+    PullModel(String),
+    PushModel(String),
+    CreateModel(CreateModelRequest),
+    DeleteModel(String),
+    CopyModel(CopyModelRequest),
+    Generate(GenerateRequest),
+    Chat(ChatRequest),
+    GenerateEmbeddings(EmbedRequest),
+    ListRunningModels,
+    GetVersion,
+}
+
+/// In-memory [`OllamaApi`] implementation for offline tests.
+///
+/// Replies are scripted per endpoint with the `on_*` methods and consumed in
+/// FIFO order. Calling an endpoint without a scripted reply returns an
+/// [`OllamaError::ApiError`]. Every request is recorded and can be inspected
+/// with [`MockOllama::requests`].
+#[derive(Debug, Default)]
+pub struct MockOllama {
+    state: Mutex<MockState>,
+}
+
+#[derive(Debug, Default)]
+struct MockState {
+    requests: Vec<RecordedRequest>,
+    list_models: VecDeque<Result<Vec<ModelInfo>, OllamaError>>,
+    show_model: VecDeque<Result<ShowModelResponse, OllamaError>>,
+    pull_model: VecDeque<MockStream<PullResponse>>,
+    push_model: VecDeque<MockStream<PushResponse>>,
+    create_model: VecDeque<MockStream<CreateResponse>>,
+    delete_model: VecDeque<Result<(), OllamaError>>,
+    copy_model: VecDeque<Result<(), OllamaError>>,
+    generate: VecDeque<MockStream<GenerateResponse>>,
+    chat: VecDeque<MockStream<ChatResponse>>,
+    generate_embeddings: VecDeque<Result<EmbedResponse, OllamaError>>,
+    list_running_models: VecDeque<Result<Vec<RunningModelInfo>, OllamaError>>,
+    get_version: VecDeque<Result<String, OllamaError>>,
+}
+
+impl MockOllama {
+    /// Creates a mock with no scripted replies.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the requests received so far, in order.
+    pub fn requests(&self) -> Vec<RecordedRequest> {
+        self.state().requests.clone()
+    }
+
+    /// Returns and clears the requests received so far.
+    pub fn take_requests(&self) -> Vec<RecordedRequest> {
+        std::mem::take(&mut self.state().requests)
+    }
+
+    /// Scripts the next `list_models` reply.
+    pub fn on_list_models(&self, reply: Result<Vec<ModelInfo>, OllamaError>) -> &Self {
+        self.state().list_models.push_back(reply);
+        self
+    }
+
+    /// Scripts the next `show_model` reply.
+    pub fn on_show_model(&self, reply: Result<ShowModelResponse, OllamaError>) -> &Self {
+        self.state().show_model.push_back(reply);
+        self
+    }
+
+    /// Scripts the next `pull_model` stream.
+    pub fn on_pull_model(&self, reply: MockStream<PullResponse>) -> &Self {
+        self.state().pull_model.push_back(reply);
+        self
+    }
+
+    /// Scripts the next `push_model` stream.
+    pub fn on_push_model(&self, reply: MockStream<PushResponse>) -> &Self {
+        self.state().push_model.push_back(reply);
+        self
+    }
+
+    /// Scripts the next `create_model` stream.
+    pub fn on_create_model(&self, reply: MockStream<CreateResponse>) -> &Self {
+        self.state().create_model.push_back(reply);
+        self
+    }
+
+    /// Scripts the next `delete_model` reply.
+    pub fn on_delete_model(&self, reply: Result<(), OllamaError>) -> &Self {
+        self.state().delete_model.push_back(reply);
+        self
+    }
+
+    /// Scripts the next `copy_model` reply.
+    pub fn on_copy_model(&self, reply: Result<(), OllamaError>) -> &Self {
+        self.state().copy_model.push_back(reply);
+        self
+    }
+
+    /// Scripts the next `generate` stream.
+    pub fn on_generate(&self, reply: MockStream<GenerateResponse>) -> &Self {
+        self.state().generate.push_back(reply);
+        self
+    }
+
+    /// Scripts the next `chat` stream.
+    pub fn on_chat(&self, reply: MockStream<ChatResponse>) -> &Self {
+        self.state().chat.push_back(reply);
+        self
+    }
+
+    /// Scripts the next `generate_embeddings` reply.
+    pub fn on_generate_embeddings(&self, reply: Result<EmbedResponse, OllamaError>) -> &Self {
+        self.state().generate_embeddings.push_back(reply);
+        self
+    }
+
+    /// Scripts the next `list_running_models` reply.
+    pub fn on_list_running_models(
+        &self,
+        reply: Result<Vec<RunningModelInfo>, OllamaError>,
+    ) -> &Self {
+        self.state().list_running_models.push_back(reply);
+        self
+    }
+
+    /// Scripts the next `get_version` reply.
+    pub fn on_get_version(&self, reply: Result<String, OllamaError>) -> &Self {
+        self.state().get_version.push_back(reply);
+        self
+    }
+
+    fn state(&self) -> std::sync::MutexGuard<'_, MockState> {
+        crate::lock(&self.state)
+    }
+}
+
+fn next_reply<T>(
+    queue: &mut VecDeque<Result<T, OllamaError>>,
+    endpoint: &str,
+) -> Result<T, OllamaError> {
+    queue.pop_front().unwrap_or_else(|| {
+        Err(OllamaError::ApiError(format!(
+            "MockOllama: no response scripted for {}",
+            endpoint
+        )))
+    })
+}
+
+fn into_stream<T: Send + 'static>(chunks: Vec<Result<T, OllamaError>>) -> ResponseStream<T> {
+    stream::iter(chunks).boxed()
+}
+
+#[async_trait]
+impl OllamaApi for MockOllama {
+    async fn list_models(&self) -> Result<Vec<ModelInfo>, OllamaError> {
+        let mut state = self.state();
+        state.requests.push(RecordedRequest::ListModels);
+        next_reply(&mut state.list_models, "list_models")
+    }
+
+    async fn show_model(&self, model_name: &str) -> Result<ShowModelResponse, OllamaError> {
+        let mut state = self.state();
+        state
+            .requests
+            .push(RecordedRequest::ShowModel(model_name.to_string()));
+        next_reply(&mut state.show_model, "show_model")
+    }
+
+    async fn pull_model(
+        &self,
+        model_name: &str,
+    ) -> Result<ResponseStream<PullResponse>, OllamaError> {
+        let mut state = self.state();
+        state
+            .requests
+            .push(RecordedRequest::PullModel(model_name.to_string()));
+        next_reply(&mut state.pull_model, "pull_model").map(into_stream)
+    }
+
+    async fn push_model(
+        &self,
+        model_name: &str,
+    ) -> Result<ResponseStream<PushResponse>, OllamaError> {
+        let mut state = self.state();
+        state
+            .requests
+            .push(RecordedRequest::PushModel(model_name.to_string()));
+        next_reply(&mut state.push_model, "push_model").map(into_stream)
+    }
+
+    async fn create_model(
+        &self,
+        request: CreateModelRequest,
+    ) -> Result<ResponseStream<CreateResponse>, OllamaError> {
+        let mut state = self.state();
+        state.requests.push(RecordedRequest::CreateModel(request));
+        next_reply(&mut state.create_model, "create_model").map(into_stream)
+    }
+
+    async fn delete_model(&self, model_name: &str) -> Result<(), OllamaError> {
+        let mut state = self.state();
+        state
+            .requests
+            .push(RecordedRequest::DeleteModel(model_name.to_string()));
+        next_reply(&mut state.delete_model, "delete_model")
+    }
+
+    async fn copy_model(&self, source: &str, destination: &str) -> Result<(), OllamaError> {
+        let mut state = self.state();
+        state
+            .requests
+            .push(RecordedRequest::CopyModel(CopyModelRequest {
+                source: source.to_string(),
+                destination: destination.to_string(),
+            }));
+        next_reply(&mut state.copy_model, "copy_model")
+    }
+
+    async fn generate(
+        &self,
+        request: GenerateRequest,
+    ) -> Result<ResponseStream<GenerateResponse>, OllamaError> {
+        let mut state = self.state();
+        state.requests.push(RecordedRequest::Generate(request));
+        next_reply(&mut state.generate, "generate").map(into_stream)
+    }
+
+    async fn chat(
+        &self,
+        request: ChatRequest,
+    ) -> Result<ResponseStream<ChatResponse>, OllamaError> {
+        let mut state = self.state();
+        state.requests.push(RecordedRequest::Chat(request));
+        next_reply(&mut state.chat, "chat").map(into_stream)
+    }
+
+    async fn generate_embeddings(
+        &self,
+        request: EmbedRequest,
+    ) -> Result<EmbedResponse, OllamaError> {
+        let mut state = self.state();
+        state
+            .requests
+            .push(RecordedRequest::GenerateEmbeddings(request));
+        next_reply(&mut state.generate_embeddings, "generate_embeddings")
+    }
+
+    async fn list_running_models(&self) -> Result<Vec<RunningModelInfo>, OllamaError> {
+        let mut state = self.state();
+        state.requests.push(RecordedRequest::ListRunningModels);
+        next_reply(&mut state.list_running_models, "list_running_models")
+    }
+
+    async fn get_version(&self) -> Result<String, OllamaError> {
+        let mut state = self.state();
+        state.requests.push(RecordedRequest::GetVersion);
+        next_reply(&mut state.get_version, "get_version")
+    }
+}
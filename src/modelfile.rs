@@ -0,0 +1,178 @@
+//! Parser for the Modelfile format used by `ollama create`.
+//!
+//! [`Modelfile::parse`] reads the instructions and
+//! [`Modelfile::into_create_request`] turns them into a [`CreateModelRequest`].
+//! Instructions that reference local files (`FROM ./model.gguf`, `ADAPTER`)
+//! need blob uploads, which the client does not support yet, and are rejected.
+
+use serde_json::{Map, Value};
+
+use crate::error::OllamaError;
+use crate::models::*;
+
+/// Parameters that may be given several times and are sent as arrays.
+const LIST_PARAMETERS: [&str; 1] = ["stop"];
+
+/// The instructions of a Modelfile.
+#[derive(Debug, Default, Clone)]
+pub struct Modelfile {
+    pub from: String,
+    /// `PARAMETER` instructions in file order.
+    pub parameters: Vec<(String, String)>,
+    pub template: Option<String>,
+    pub system: Option<String>,
+    pub license: Option<String>,
+    pub messages: Vec<ChatMessage>,
+}
+
+impl Modelfile {
+    /// Parses Modelfile text. Instructions are case-insensitive, `#` starts a
+    /// comment line, and arguments may be quoted with `"` or, across several
+    /// lines, with `"""`.
+    pub fn parse(text: &str) -> Result<Self, OllamaError> {
+        let mut modelfile = Modelfile::default();
+        let mut lines = text.lines().enumerate();
+
+        while let Some((index, line)) = lines.next() {
+            let line_number = index + 1;
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let error = |message: &str| {
+                OllamaError::Modelfile(format!("line {}: {}", line_number, message))
+            };
+
+            let (instruction, rest) = line.split_once(char::is_whitespace).unwrap_or((line, ""));
+            let mut argument = rest.trim().to_string();
+            if let Some(start) = argument.strip_prefix("\"\"\"") {
+                argument = match start.find("\"\"\"") {
+                    Some(end) => start[..end].to_string(),
+                    None => {
+                        let mut value = start.to_string();
+                        loop {
+                            let (_, next) = lines
+                                .next()
+                                .ok_or_else(|| error("unterminated \"\"\" string"))?;
+                            value.push('\n');
+                            if let Some(end) = next.find("\"\"\"") {
+                                value.push_str(&next[..end]);
+                                break value;
+                            }
+                            value.push_str(next);
+                        }
+                    }
+                };
+            } else if argument.len() >= 2 && argument.starts_with('"') && argument.ends_with('"') {
+                argument = argument[1..argument.len() - 1].to_string();
+            }
+
+            match instruction.to_ascii_uppercase().as_str() {
+                "FROM" => {
+                    if is_local_path(&argument) {
+                        return Err(error("creating models from local files is not supported"));
+                    }
+                    modelfile.from = argument;
+                }
+                "PARAMETER" => {
+                    let (name, value) = argument
+                        .split_once(char::is_whitespace)
+                        .ok_or_else(|| error("PARAMETER needs a name and a value"))?;
+                    modelfile
+                        .parameters
+                        .push((name.to_string(), unquote(value.trim()).to_string()));
+                }
+                "TEMPLATE" => modelfile.template = Some(argument),
+                "SYSTEM" => modelfile.system = Some(argument),
+                "LICENSE" => modelfile.license = Some(argument),
+                "MESSAGE" => {
+                    let (role, content) = argument
+                        .split_once(char::is_whitespace)
+                        .ok_or_else(|| error("MESSAGE needs a role and a message"))?;
+                    if !matches!(role, "system" | "user" | "assistant") {
+                        return Err(error(&format!("unknown MESSAGE role '{}'", role)));
+                    }
+                    modelfile.messages.push(ChatMessage {
+                        role: role.to_string(),
+                        content: unquote(content.trim()).to_string(),
+                        ..Default::default()
+                    });
+                }
+                "ADAPTER" => return Err(error("ADAPTER is not supported")),
+                other => return Err(error(&format!("unknown instruction '{}'", other))),
+            }
+        }
+
+        if modelfile.from.is_empty() {
+            return Err(OllamaError::Modelfile(
+                "missing FROM instruction".to_string(),
+            ));
+        }
+        Ok(modelfile)
+    }
+
+    /// Builds the request that creates `model` from these instructions.
+    pub fn into_create_request(self, model: &str) -> CreateModelRequest {
+        let mut parameters = Map::new();
+        for (name, value) in self.parameters {
+            if LIST_PARAMETERS.contains(&name.as_str()) {
+                // Stop sequences are text even when they look like numbers
+                let value = Value::String(value);
+                match parameters
+                    .entry(name)
+                    .or_insert_with(|| Value::Array(Vec::new()))
+                {
+                    Value::Array(values) => values.push(value),
+                    _ => unreachable!("list parameters are always arrays"),
+                }
+            } else {
+                parameters.insert(name, parameter_value(&value));
+            }
+        }
+
+        CreateModelRequest {
+            model: model.to_string(),
+            from: Some(self.from),
+            template: self.template,
+            license: self.license,
+            system: self.system,
+            parameters: (!parameters.is_empty()).then_some(Value::Object(parameters)),
+            messages: (!self.messages.is_empty()).then_some(self.messages),
+            ..Default::default()
+        }
+    }
+}
+
+fn is_local_path(from: &str) -> bool {
+    from.starts_with(['.', '/', '~'])
+        || from.ends_with(".gguf")
+        || from.ends_with(".bin")
+        || from.contains('\\')
+}
+
+fn unquote(value: &str) -> &str {
+    if value.len() >= 2 && value.starts_with('"') && value.ends_with('"') {
+        &value[1..value.len() - 1]
+    } else {
+        value
+    }
+}
+
+/// Types a parameter value the way Ollama expects: integers, floats and
+/// booleans as JSON numbers and booleans, anything else as a string.
+fn parameter_value(value: &str) -> Value {
+    if let Ok(integer) = value.parse::<i64>() {
+        return Value::from(integer);
+    }
+    // `inf` and `NaN` parse as floats but have no JSON number form
+    if let Ok(float) = value.parse::<f64>() {
+        if float.is_finite() {
+            return Value::from(float);
+        }
+    }
+    match value {
+        "true" => Value::Bool(true),
+        "false" => Value::Bool(false),
+        _ => Value::String(value.to_string()),
+    }
+}
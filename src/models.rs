@@ -1,3 +1,6 @@
+use std::collections::HashMap;
+use std::time::Duration;
+
 use serde::{Deserialize, Serialize};
 
 
@@ -30,12 +33,62 @@ pub struct ShowModelRequest {
     pub name: String,
 }
 
+#[derive(Serialize, Deserialize, Debug, Default)]
+pub struct ShowModelResponse {
+    pub modelfile: Option<String>,
+    pub parameters: Option<String>,
+    pub template: Option<String>,
+    pub system: Option<String>,
+    pub license: Option<String>,
+    pub details: Option<ModelDetails>,
+    pub model_info: Option<HashMap<String, serde_json::Value>>,
+    pub capabilities: Option<Vec<String>>,
+    pub modified_at: Option<String>,
+}
+
+impl ShowModelResponse {
+    /// The model architecture from `general.architecture`, e.g. `llama`.
+    pub fn architecture(&self) -> Option<&str> {
+        self.model_info
+            .as_ref()?
+            .get("general.architecture")?
+            .as_str()
+    }
+
+    /// Reads an architecture-specific `model_info` number, e.g.
+    /// `architecture_value("context_length")` reads `llama.context_length`.
+    pub fn architecture_value(&self, key: &str) -> Option<u64> {
+        let model_info = self.model_info.as_ref()?;
+        match self.architecture() {
+            Some(architecture) => model_info.get(&format!("{}.{}", architecture, key))?.as_u64(),
+            None => {
+                let suffix = format!(".{}", key);
+                model_info
+                    .iter()
+                    .find(|(name, _)| name.ends_with(&suffix))?
+                    .1
+                    .as_u64()
+            }
+        }
+    }
+
+    /// The model's trained context length.
+    pub fn context_length(&self) -> Option<u64> {
+        self.architecture_value("context_length")
+    }
+
+    /// The size of the model's embedding vectors.
+    pub fn embedding_length(&self) -> Option<u64> {
+        self.architecture_value("embedding_length")
+    }
+}
+
 #[derive(Serialize, Debug, Default)]
 pub struct PullModelRequest {
     pub name: String,
 }
 
-#[derive(Deserialize, Debug, Default)]
+#[derive(Serialize, Deserialize, Debug, Default)]
 pub struct PullResponse {
     pub status: Option<String>,
     pub digest: Option<String>,
@@ -43,24 +96,48 @@ pub struct PullResponse {
     pub completed: Option<u64>,
 }
 
-#[derive(Serialize, Debug, Default)]
+#[derive(Serialize, Deserialize, Debug, Default, Clone)]
 pub struct GenerateRequest {
     pub model: String,
     pub prompt: String,
     pub stream: Option<bool>,
     pub format: Option<String>,
     pub options: Option<GenerateOptions>,
+    /// Sends `prompt` as is, without applying the model's template. See
+    /// [`crate::template`] for building raw prompts locally.
+    pub raw: Option<bool>,
+    /// Returns the log probability of each generated token.
+    pub logprobs: Option<bool>,
+    /// Number of most likely alternatives returned for each token, with
+    /// `logprobs` set.
+    pub top_logprobs: Option<u32>,
 }
 
-#[derive(Serialize, Debug, Default)]
+#[derive(Serialize, Deserialize, Debug, Default, Clone)]
 pub struct GenerateOptions {
     pub temperature: Option<f64>,
     pub top_p: Option<f64>,
     pub max_tokens: Option<u32>,
     pub stop: Option<Vec<String>>,
+    /// Size of the context window, in tokens, used for this request.
+    pub num_ctx: Option<u32>,
+    /// Maximum number of tokens to generate, or -1 for no limit.
+    pub num_predict: Option<i32>,
+    /// Seed for sampling, for reproducible output.
+    pub seed: Option<i64>,
 }
 
-#[derive(Deserialize, Debug, Default)]
+impl GenerateOptions {
+    /// The output token limit, from `num_predict` or else `max_tokens`.
+    pub(crate) fn token_limit(&self) -> Option<u32> {
+        match self.num_predict {
+            Some(num_predict) => u32::try_from(num_predict).ok(),
+            None => self.max_tokens,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Default)]
 pub struct GenerateResponse {
     pub model: String,
     pub created_at: String,
@@ -73,18 +150,30 @@ pub struct GenerateResponse {
     pub prompt_eval_duration: Option<u64>,
     pub eval_count: Option<u32>,
     pub eval_duration: Option<u64>,
+    /// Why the generation ended, on the final chunk.
+    pub done_reason: Option<DoneReason>,
+    /// Log probabilities of the tokens in this chunk, when requested.
+    pub logprobs: Option<Vec<Logprob>>,
 }
 
-#[derive(Serialize, Debug, Default)]
+#[derive(Serialize, Deserialize, Debug, Default, Clone)]
 pub struct ChatRequest {
     pub model: String,
     pub messages: Vec<ChatMessage>,
     pub stream: Option<bool>,
     pub format: Option<String>,
     pub options: Option<GenerateOptions>,
+    /// Functions the model may call, e.g.
+    /// `{"type": "function", "function": {"name": ..., "parameters": ...}}`.
+    pub tools: Option<Vec<serde_json::Value>>,
+    /// Returns the log probability of each generated token.
+    pub logprobs: Option<bool>,
+    /// Number of most likely alternatives returned for each token, with
+    /// `logprobs` set.
+    pub top_logprobs: Option<u32>,
 }
 
-#[derive(Serialize, Deserialize, Debug, Default)]
+#[derive(Serialize, Deserialize, Debug, Default, Clone)]
 pub struct ChatMessage {
     pub role: String,
     pub content: String,
@@ -92,18 +181,18 @@ pub struct ChatMessage {
     pub tool_calls: Option<Vec<ToolCall>>,
 }
 
-#[derive(Serialize, Deserialize, Debug, Default)]
+#[derive(Serialize, Deserialize, Debug, Default, Clone)]
 pub struct ToolCall {
     pub function: FunctionCall,
 }
 
-#[derive(Serialize, Deserialize, Debug, Default)]
+#[derive(Serialize, Deserialize, Debug, Default, Clone)]
 pub struct FunctionCall {
     pub name: String,
     pub arguments: serde_json::Value,
 }
 
-#[derive(Deserialize, Debug, Default)]
+#[derive(Serialize, Deserialize, Debug, Default)]
 pub struct ChatResponse {
     pub model: String,
     pub created_at: String,
@@ -115,9 +204,195 @@ pub struct ChatResponse {
     pub prompt_eval_duration: Option<u64>,
     pub eval_count: Option<u32>,
     pub eval_duration: Option<u64>,
+    /// Why the generation ended, on the final chunk.
+    pub done_reason: Option<DoneReason>,
+    /// Log probabilities of the tokens in this chunk, when requested.
+    pub logprobs: Option<Vec<Logprob>>,
 }
 
-#[derive(Serialize, Debug, Default)]
+/// Why the server ended a generation.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DoneReason {
+    /// The model finished or hit a stop sequence.
+    Stop,
+    /// The `num_predict` token limit was reached.
+    Length,
+    /// The model was loaded by a request with no prompt.
+    Load,
+    /// The model was unloaded by a request with `keep_alive` 0.
+    Unload,
+    /// A reason not known to this version of the client, as sent.
+    Other(String),
+}
+
+impl DoneReason {
+    /// The reason as it appears in `done_reason`.
+    pub fn as_str(&self) -> &str {
+        match self {
+            DoneReason::Stop => "stop",
+            DoneReason::Length => "length",
+            DoneReason::Load => "load",
+            DoneReason::Unload => "unload",
+            DoneReason::Other(reason) => reason,
+        }
+    }
+}
+
+impl From<&str> for DoneReason {
+    fn from(reason: &str) -> Self {
+        match reason {
+            "stop" => DoneReason::Stop,
+            "length" => DoneReason::Length,
+            "load" => DoneReason::Load,
+            "unload" => DoneReason::Unload,
+            other => DoneReason::Other(other.to_string()),
+        }
+    }
+}
+
+impl Serialize for DoneReason {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.as_str())
+    }
+}
+
+impl<'de> Deserialize<'de> for DoneReason {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        Ok(DoneReason::from(
+            String::deserialize(deserializer)?.as_str(),
+        ))
+    }
+}
+
+/// Timings and token counts from the final chunk of a generation. Durations
+/// are in nanoseconds.
+#[derive(Serialize, Deserialize, Debug, Default, Clone, PartialEq)]
+pub struct GenerationStats {
+    pub done_reason: Option<DoneReason>,
+    pub total_duration: Option<u64>,
+    pub load_duration: Option<u64>,
+    pub prompt_eval_count: Option<u32>,
+    pub prompt_eval_duration: Option<u64>,
+    pub eval_count: Option<u32>,
+    pub eval_duration: Option<u64>,
+}
+
+impl GenerationStats {
+    /// Whether the reply was cut off by the `num_predict` token limit.
+    pub fn truncated(&self) -> bool {
+        self.done_reason == Some(DoneReason::Length)
+    }
+
+    /// Generated tokens per second.
+    pub fn tokens_per_second(&self) -> Option<f64> {
+        rate(self.eval_count?, self.eval_duration?)
+    }
+
+    /// Prompt tokens evaluated per second.
+    pub fn prompt_tokens_per_second(&self) -> Option<f64> {
+        rate(self.prompt_eval_count?, self.prompt_eval_duration?)
+    }
+}
+
+fn rate(count: u32, nanos: u64) -> Option<f64> {
+    (nanos > 0).then(|| f64::from(count) / Duration::from_nanos(nanos).as_secs_f64())
+}
+
+/// A streamed `generate` or `chat` chunk. The final chunk carries the
+/// statistics of the generation.
+pub trait FinalChunk {
+    /// Statistics of the generation, on the final chunk only.
+    fn stats(&self) -> Option<GenerationStats>;
+}
+
+impl FinalChunk for GenerateResponse {
+    fn stats(&self) -> Option<GenerationStats> {
+        self.done.then_some(GenerationStats {
+            done_reason: self.done_reason.clone(),
+            total_duration: self.total_duration,
+            load_duration: self.load_duration,
+            prompt_eval_count: self.prompt_eval_count,
+            prompt_eval_duration: self.prompt_eval_duration,
+            eval_count: self.eval_count,
+            eval_duration: self.eval_duration,
+        })
+    }
+}
+
+impl FinalChunk for ChatResponse {
+    fn stats(&self) -> Option<GenerationStats> {
+        self.done.then_some(GenerationStats {
+            done_reason: self.done_reason.clone(),
+            total_duration: self.total_duration,
+            load_duration: self.load_duration,
+            prompt_eval_count: self.prompt_eval_count,
+            prompt_eval_duration: self.prompt_eval_duration,
+            eval_count: self.eval_count,
+            eval_duration: self.eval_duration,
+        })
+    }
+}
+
+/// A generated token with its log probability.
+#[derive(Serialize, Deserialize, Debug, Default, Clone, PartialEq)]
+pub struct TokenLogprob {
+    pub token: String,
+    pub logprob: f64,
+    /// UTF-8 bytes of the token, which may be part of a multi-byte character.
+    pub bytes: Option<Vec<u8>>,
+}
+
+impl TokenLogprob {
+    /// Probability of the token, between 0 and 1.
+    pub fn probability(&self) -> f64 {
+        self.logprob.exp()
+    }
+}
+
+/// Log probability of a generated token and the most likely alternatives
+/// considered in its place.
+#[derive(Serialize, Deserialize, Debug, Default, Clone, PartialEq)]
+pub struct Logprob {
+    pub token: String,
+    pub logprob: f64,
+    pub bytes: Option<Vec<u8>>,
+    pub top_logprobs: Option<Vec<TokenLogprob>>,
+}
+
+impl Logprob {
+    /// Probability of the generated token, between 0 and 1.
+    pub fn probability(&self) -> f64 {
+        self.logprob.exp()
+    }
+
+    /// Probability of `token` at this position: the generated token or one of
+    /// the returned alternatives. `None` if it was not among them.
+    pub fn probability_of(&self, token: &str) -> Option<f64> {
+        if self.token == token {
+            return Some(self.probability());
+        }
+        self.top_logprobs
+            .iter()
+            .flatten()
+            .find(|alternative| alternative.token == token)
+            .map(TokenLogprob::probability)
+    }
+
+    /// Difference in probability between the generated token and the most
+    /// likely other alternative, or `None` without alternatives. A small
+    /// margin means the model hesitated.
+    pub fn margin(&self) -> Option<f64> {
+        self.top_logprobs
+            .iter()
+            .flatten()
+            .filter(|alternative| alternative.token != self.token)
+            .map(TokenLogprob::probability)
+            .reduce(f64::max)
+            .map(|runner_up| self.probability() - runner_up)
+    }
+}
+
+#[derive(Serialize, Debug, Default, Clone)]
 pub struct CreateModelRequest {
     pub model: String,
     pub from: Option<String>,
@@ -132,7 +407,7 @@ pub struct CreateModelRequest {
     pub quantize: Option<String>,
 }
 
-#[derive(Deserialize, Debug, Default)]
+#[derive(Serialize, Deserialize, Debug, Default)]
 pub struct CreateResponse {
     pub status: Option<String>,
     pub digest: Option<String>,
@@ -145,7 +420,7 @@ pub struct PushModelRequest {
     pub name: String,
 }
 
-#[derive(Deserialize, Debug, Default)]
+#[derive(Serialize, Deserialize, Debug, Default)]
 pub struct PushResponse {
     pub status: Option<String>,
     pub digest: Option<String>,
@@ -158,7 +433,13 @@ pub struct DeleteModelRequest {
     pub name: String,
 }
 
-#[derive(Serialize, Debug)]
+#[derive(Serialize, Debug, Default, Clone)]
+pub struct CopyModelRequest {
+    pub source: String,
+    pub destination: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
 #[serde(untagged)]
 pub enum EmbedInput {
     Single(String),
@@ -171,7 +452,7 @@ impl Default for EmbedInput {
     }
 }
 
-#[derive(Serialize, Debug, Default)]
+#[derive(Serialize, Deserialize, Debug, Default, Clone)]
 pub struct EmbedRequest {
     pub model: String,
     pub input: EmbedInput,
@@ -180,13 +461,27 @@ pub struct EmbedRequest {
     pub keep_alive: Option<String>,
 }
 
-#[derive(Deserialize, Debug, Default)]
+#[derive(Serialize, Deserialize, Debug, Default)]
 pub struct EmbedResponse {
     pub model: String,
     pub embeddings: Vec<Vec<f32>>,
-    pub total_duration: u64,
-    pub load_duration: u64,
-    pub prompt_eval_count: u32,
+    pub total_duration: Option<u64>,
+    pub load_duration: Option<u64>,
+    pub prompt_eval_count: Option<u32>,
+}
+
+/// Request for the legacy `/api/embeddings` endpoint, which embeds a single prompt.
+#[derive(Serialize, Debug, Default, Clone)]
+pub struct LegacyEmbeddingRequest {
+    pub model: String,
+    pub prompt: String,
+    pub options: Option<GenerateOptions>,
+    pub keep_alive: Option<String>,
+}
+
+#[derive(Deserialize, Debug, Default)]
+pub struct LegacyEmbeddingResponse {
+    pub embedding: Vec<f32>,
 }
 
 #[derive(Deserialize, Debug, Default)]
@@ -194,7 +489,7 @@ pub struct ListRunningModelsResponse {
     pub models: Vec<RunningModelInfo>,
 }
 
-#[derive(Deserialize, Debug, Default)]
+#[derive(Serialize, Deserialize, Debug, Default)]
 pub struct RunningModelInfo {
     pub name: String,
     pub model: String,
@@ -205,6 +500,11 @@ pub struct RunningModelInfo {
     pub size_vram: u64,
 }
 
+#[derive(Deserialize, Debug, Default)]
+pub struct ErrorResponse {
+    pub error: String,
+}
+
 #[derive(Deserialize, Debug, Default)]
 pub struct VersionResponse {
     pub version: String,
@@ -0,0 +1,971 @@
+//! Client for Ollama's OpenAI-compatible `/v1` endpoints.
+//!
+//! Get a handle with [`crate::OllamaClient::openai`]. It shares the client's
+//! connection pool, limiter, cassette and interceptors. The `From`
+//! conversions at the end of this module translate between these types and
+//! the native chat types.
+
+use futures::{future, Stream, TryStreamExt};
+use reqwest::{Method, Response};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Deserializer, Serialize};
+use serde_json::Value;
+
+use crate::error::OllamaError;
+use crate::models::*;
+use crate::telemetry::{self, Call, Chunk, ChunkStats};
+use crate::{api_error, body_lines, interceptor, OllamaClient};
+
+/// Content of an OpenAI chat message: plain text or a list of parts.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(untagged)]
+pub enum MessageContent {
+    Text(String),
+    Parts(Vec<ContentPart>),
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ContentPart {
+    Text { text: String },
+    ImageUrl { image_url: ImageUrl },
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ImageUrl {
+    /// An `http(s)` URL or a `data:image/...;base64,` URL.
+    pub url: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Default, Clone, PartialEq)]
+pub struct OpenAiMessage {
+    pub role: String,
+    pub content: Option<MessageContent>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tool_calls: Option<Vec<OpenAiToolCall>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tool_call_id: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Default, Clone, PartialEq)]
+pub struct OpenAiToolCall {
+    pub id: Option<String>,
+    #[serde(rename = "type")]
+    pub kind: Option<String>,
+    pub function: OpenAiFunctionCall,
+}
+
+#[derive(Serialize, Deserialize, Debug, Default, Clone, PartialEq)]
+pub struct OpenAiFunctionCall {
+    pub name: String,
+    /// JSON-encoded arguments.
+    pub arguments: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Default, Clone, PartialEq)]
+pub struct StreamOptions {
+    pub include_usage: bool,
+}
+
+#[derive(Serialize, Deserialize, Debug, Default, Clone)]
+pub struct ChatCompletionRequest {
+    pub model: String,
+    pub messages: Vec<OpenAiMessage>,
+    pub stream: Option<bool>,
+    pub stream_options: Option<StreamOptions>,
+    pub temperature: Option<f64>,
+    pub top_p: Option<f64>,
+    pub max_tokens: Option<u32>,
+    /// A single stop sequence or a list of them.
+    #[serde(default, deserialize_with = "string_or_list")]
+    pub stop: Option<Vec<String>>,
+    pub seed: Option<i64>,
+    /// e.g. `{"type": "json_object"}`.
+    pub response_format: Option<Value>,
+    pub tools: Option<Vec<Value>>,
+    pub logprobs: Option<bool>,
+    pub top_logprobs: Option<u32>,
+}
+
+/// Accepts `"stop": "\n"` as well as `"stop": ["\n"]`.
+fn string_or_list<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Option<Vec<String>>, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum StringOrList {
+        String(String),
+        List(Vec<String>),
+    }
+    Ok(
+        Option::<StringOrList>::deserialize(deserializer)?.map(|stop| match stop {
+            StringOrList::String(stop) => vec![stop],
+            StringOrList::List(stop) => stop,
+        }),
+    )
+}
+
+#[derive(Serialize, Deserialize, Debug, Default, Clone, PartialEq)]
+pub struct Usage {
+    pub prompt_tokens: u32,
+    pub completion_tokens: u32,
+    pub total_tokens: u32,
+}
+
+#[derive(Serialize, Deserialize, Debug, Default, Clone)]
+pub struct ChatCompletion {
+    pub id: String,
+    pub object: String,
+    pub created: u64,
+    pub model: String,
+    pub system_fingerprint: Option<String>,
+    pub choices: Vec<ChatChoice>,
+    pub usage: Option<Usage>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Default, Clone)]
+pub struct ChatChoice {
+    pub index: u32,
+    pub message: OpenAiMessage,
+    pub finish_reason: Option<String>,
+    pub logprobs: Option<ChoiceLogprobs>,
+}
+
+/// Per-token log probabilities of a choice, in the same shape as the native
+/// API's.
+#[derive(Serialize, Deserialize, Debug, Default, Clone, PartialEq)]
+pub struct ChoiceLogprobs {
+    pub content: Option<Vec<Logprob>>,
+}
+
+/// One server-sent event of a streamed chat completion.
+#[derive(Serialize, Deserialize, Debug, Default, Clone)]
+pub struct ChatCompletionChunk {
+    pub id: String,
+    pub object: String,
+    pub created: u64,
+    pub model: String,
+    pub system_fingerprint: Option<String>,
+    pub choices: Vec<ChunkChoice>,
+    /// Only sent in the last chunk when `stream_options.include_usage` is set.
+    pub usage: Option<Usage>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Default, Clone)]
+pub struct ChunkChoice {
+    pub index: u32,
+    pub delta: ChatDelta,
+    pub finish_reason: Option<String>,
+    pub logprobs: Option<ChoiceLogprobs>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Default, Clone)]
+pub struct ChatDelta {
+    pub role: Option<String>,
+    pub content: Option<String>,
+    pub tool_calls: Option<Vec<OpenAiToolCall>>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Default, Clone)]
+pub struct CompletionRequest {
+    pub model: String,
+    pub prompt: String,
+    pub suffix: Option<String>,
+    pub stream: Option<bool>,
+    pub stream_options: Option<StreamOptions>,
+    pub temperature: Option<f64>,
+    pub top_p: Option<f64>,
+    pub max_tokens: Option<u32>,
+    /// A single stop sequence or a list of them.
+    #[serde(default, deserialize_with = "string_or_list")]
+    pub stop: Option<Vec<String>>,
+    pub seed: Option<i64>,
+}
+
+/// A text completion, or one event of a streamed completion.
+#[derive(Serialize, Deserialize, Debug, Default, Clone)]
+pub struct Completion {
+    pub id: String,
+    pub object: String,
+    pub created: u64,
+    pub model: String,
+    pub system_fingerprint: Option<String>,
+    pub choices: Vec<CompletionChoice>,
+    pub usage: Option<Usage>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Default, Clone)]
+pub struct CompletionChoice {
+    pub index: u32,
+    pub text: String,
+    pub finish_reason: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Default, Clone)]
+pub struct EmbeddingsRequest {
+    pub model: String,
+    pub input: EmbedInput,
+    /// Only `float` is supported by Ollama.
+    pub encoding_format: Option<String>,
+    pub dimensions: Option<u32>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Default, Clone)]
+pub struct EmbeddingsResponse {
+    pub object: String,
+    pub data: Vec<EmbeddingData>,
+    pub model: String,
+    pub usage: Option<EmbeddingUsage>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Default, Clone)]
+pub struct EmbeddingData {
+    pub object: String,
+    pub embedding: Vec<f32>,
+    pub index: u32,
+}
+
+#[derive(Serialize, Deserialize, Debug, Default, Clone, PartialEq)]
+pub struct EmbeddingUsage {
+    pub prompt_tokens: u32,
+    pub total_tokens: u32,
+}
+
+#[derive(Serialize, Deserialize, Debug, Default, Clone)]
+pub struct ModelList {
+    pub object: String,
+    pub data: Vec<OpenAiModel>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Default, Clone, PartialEq)]
+pub struct OpenAiModel {
+    pub id: String,
+    pub object: String,
+    pub created: u64,
+    pub owned_by: String,
+}
+
+#[derive(Deserialize)]
+struct OpenAiErrorResponse {
+    error: OpenAiErrorDetail,
+}
+
+#[derive(Deserialize)]
+struct OpenAiErrorDetail {
+    message: String,
+}
+
+/// Handle for the OpenAI-compatible endpoints, returned by
+/// [`crate::OllamaClient::openai`].
+#[derive(Clone, Copy)]
+pub struct OpenAiClient<'a> {
+    client: &'a OllamaClient,
+}
+
+impl<'a> OpenAiClient<'a> {
+    pub(crate) fn new(client: &'a OllamaClient) -> Self {
+        OpenAiClient { client }
+    }
+
+    /// Creates a chat completion. `request.stream` is ignored.
+    pub async fn chat_completion(
+        &self,
+        mut request: ChatCompletionRequest,
+    ) -> Result<ChatCompletion, OllamaError> {
+        request.stream = Some(false);
+        let hooks = self
+            .client
+            .before_request(
+                "chat",
+                "/v1/chat/completions",
+                interceptor::Request::ChatCompletion(&mut request),
+            )
+            .await?;
+        let call = self.chat_call(&request);
+        let result = telemetry::traced(
+            &call,
+            hooks.send(async {
+                let _permit = self.client.acquire_slot(&request.model).await?;
+                let response = self
+                    .client
+                    .send_json(Method::POST, "/v1/chat/completions", &request)
+                    .await?;
+                json_response(response).await
+            }),
+        )
+        .await;
+        let completion: ChatCompletion = hooks
+            .after(result, |completion| {
+                interceptor::Response::ChatCompletion(completion)
+            })
+            .await?;
+        record_completion(&call, &completion.model, completion.usage.as_ref());
+        Ok(completion)
+    }
+
+    /// Streams a chat completion. `request.stream` is ignored.
+    pub async fn chat_completion_stream(
+        &self,
+        mut request: ChatCompletionRequest,
+    ) -> Result<impl Stream<Item = Result<ChatCompletionChunk, OllamaError>>, OllamaError> {
+        request.stream = Some(true);
+        let hooks = self
+            .client
+            .before_request(
+                "chat",
+                "/v1/chat/completions",
+                interceptor::Request::ChatCompletion(&mut request),
+            )
+            .await?;
+        let call = self.chat_call(&request);
+        let opened = telemetry::open_stream(
+            &call,
+            hooks.send(async {
+                let permit = self.client.acquire_slot(&request.model).await?;
+                let response = self
+                    .client
+                    .send_json(Method::POST, "/v1/chat/completions", &request)
+                    .await?;
+
+                if response.status().is_success() {
+                    let stream = sse_stream::<ChatCompletionChunk>(response).map_ok(move |chunk| {
+                        let _held = &permit;
+                        chunk
+                    });
+                    Ok(stream)
+                } else {
+                    Err(api_error(response).await)
+                }
+            }),
+        )
+        .await;
+        let stream = hooks.stream(opened).await?;
+        Ok(telemetry::traced_stream(stream, call, false))
+    }
+
+    /// Creates a text completion. `request.stream` is ignored.
+    pub async fn completion(
+        &self,
+        mut request: CompletionRequest,
+    ) -> Result<Completion, OllamaError> {
+        request.stream = Some(false);
+        let hooks = self
+            .client
+            .before_request(
+                "text_completion",
+                "/v1/completions",
+                interceptor::Request::Completion(&mut request),
+            )
+            .await?;
+        let call = self.completion_call(&request);
+        let result = telemetry::traced(
+            &call,
+            hooks.send(async {
+                let _permit = self.client.acquire_slot(&request.model).await?;
+                let response = self
+                    .client
+                    .send_json(Method::POST, "/v1/completions", &request)
+                    .await?;
+                json_response(response).await
+            }),
+        )
+        .await;
+        let completion: Completion = hooks
+            .after(result, |completion| {
+                interceptor::Response::Completion(completion)
+            })
+            .await?;
+        record_completion(&call, &completion.model, completion.usage.as_ref());
+        Ok(completion)
+    }
+
+    /// Streams a text completion. `request.stream` is ignored.
+    pub async fn completion_stream(
+        &self,
+        mut request: CompletionRequest,
+    ) -> Result<impl Stream<Item = Result<Completion, OllamaError>>, OllamaError> {
+        request.stream = Some(true);
+        let hooks = self
+            .client
+            .before_request(
+                "text_completion",
+                "/v1/completions",
+                interceptor::Request::Completion(&mut request),
+            )
+            .await?;
+        let call = self.completion_call(&request);
+        let opened = telemetry::open_stream(
+            &call,
+            hooks.send(async {
+                let permit = self.client.acquire_slot(&request.model).await?;
+                let response = self
+                    .client
+                    .send_json(Method::POST, "/v1/completions", &request)
+                    .await?;
+
+                if response.status().is_success() {
+                    let stream = sse_stream::<Completion>(response).map_ok(move |chunk| {
+                        let _held = &permit;
+                        chunk
+                    });
+                    Ok(stream)
+                } else {
+                    Err(api_error(response).await)
+                }
+            }),
+        )
+        .await;
+        let stream = hooks.stream(opened).await?;
+        Ok(telemetry::traced_stream(stream, call, false))
+    }
+
+    /// Generates embeddings.
+    pub async fn embeddings(
+        &self,
+        mut request: EmbeddingsRequest,
+    ) -> Result<EmbeddingsResponse, OllamaError> {
+        let hooks = self
+            .client
+            .before_request(
+                "embeddings",
+                "/v1/embeddings",
+                interceptor::Request::Embeddings(&mut request),
+            )
+            .await?;
+        let call = self
+            .client
+            .call("embeddings", "/v1/embeddings", Some(&request.model));
+        let result = telemetry::traced(
+            &call,
+            hooks.send(async {
+                let _permit = self.client.acquire_slot(&request.model).await?;
+                let response = self
+                    .client
+                    .send_json(Method::POST, "/v1/embeddings", &request)
+                    .await?;
+                json_response(response).await
+            }),
+        )
+        .await;
+        let response: EmbeddingsResponse = hooks
+            .after(result, |response| {
+                interceptor::Response::Embeddings(response)
+            })
+            .await?;
+        call.record_response_model(&response.model);
+        if let Some(usage) = &response.usage {
+            call.record_usage(Some(usage.prompt_tokens), None);
+        }
+        Ok(response)
+    }
+
+    /// Lists locally available models.
+    pub async fn list_models(&self) -> Result<ModelList, OllamaError> {
+        let hooks = self
+            .client
+            .before_request("list_models", "/v1/models", interceptor::Request::Empty)
+            .await?;
+        let call = self.client.call("list_models", "/v1/models", None);
+        let result = telemetry::traced(
+            &call,
+            hooks.send(async {
+                let response = self.client.send(Method::GET, "/v1/models", None).await?;
+                json_response(response).await
+            }),
+        )
+        .await;
+        hooks
+            .after(result, |models| interceptor::Response::ModelList(models))
+            .await
+    }
+
+    /// Retrieves a single model.
+    pub async fn retrieve_model(&self, model: &str) -> Result<OpenAiModel, OllamaError> {
+        let mut model = model.to_string();
+        let hooks = self
+            .client
+            .before_request(
+                "retrieve_model",
+                "/v1/models/{model}",
+                interceptor::Request::Model(&mut model),
+            )
+            .await?;
+        let path = format!("/v1/models/{}", model);
+        let call = self
+            .client
+            .call("retrieve_model", "/v1/models/{model}", Some(&model));
+        let result = telemetry::traced(
+            &call,
+            hooks.send(async {
+                let response = self.client.send(Method::GET, &path, None).await?;
+                json_response(response).await
+            }),
+        )
+        .await;
+        hooks
+            .after(result, |model| interceptor::Response::OpenAiModel(model))
+            .await
+    }
+
+    fn chat_call(&self, request: &ChatCompletionRequest) -> Call {
+        let call = self
+            .client
+            .call("chat", "/v1/chat/completions", Some(&request.model));
+        let options = GenerateOptions {
+            temperature: request.temperature,
+            top_p: request.top_p,
+            num_predict: request.max_tokens.map(num_predict),
+            stop: request.stop.clone(),
+            seed: request.seed,
+            ..Default::default()
+        };
+        call.record_options(Some(&options));
+        if self.client.capture_content {
+            if let Ok(messages) = serde_json::to_value(&request.messages) {
+                call.record_messages("gen_ai.input.messages", messages);
+            }
+        }
+        call
+    }
+
+    fn completion_call(&self, request: &CompletionRequest) -> Call {
+        let call = self
+            .client
+            .call("text_completion", "/v1/completions", Some(&request.model));
+        let options = GenerateOptions {
+            temperature: request.temperature,
+            top_p: request.top_p,
+            num_predict: request.max_tokens.map(num_predict),
+            stop: request.stop.clone(),
+            seed: request.seed,
+            ..Default::default()
+        };
+        call.record_options(Some(&options));
+        if self.client.capture_content {
+            let messages = serde_json::json!([{ "role": "user", "content": request.prompt }]);
+            call.record_messages("gen_ai.input.messages", messages);
+        }
+        call
+    }
+}
+
+/// Records the response model and, once reported, the token usage.
+fn record_completion(call: &Call, model: &str, usage: Option<&Usage>) {
+    call.record_response_model(model);
+    if let Some(usage) = usage {
+        call.record_usage(Some(usage.prompt_tokens), Some(usage.completion_tokens));
+    }
+}
+
+fn usage_stats<'a>(model: &'a str, usage: Option<&Usage>) -> Option<ChunkStats<'a>> {
+    usage.map(|usage| ChunkStats {
+        model,
+        generation: GenerationStats {
+            prompt_eval_count: Some(usage.prompt_tokens),
+            eval_count: Some(usage.completion_tokens),
+            ..Default::default()
+        },
+    })
+}
+
+impl Chunk for ChatCompletionChunk {
+    fn text(&self) -> &str {
+        self.choices
+            .first()
+            .and_then(|choice| choice.delta.content.as_deref())
+            .unwrap_or_default()
+    }
+
+    fn chunk_stats(&self) -> Option<ChunkStats<'_>> {
+        usage_stats(&self.model, self.usage.as_ref())
+    }
+}
+
+impl Chunk for Completion {
+    fn text(&self) -> &str {
+        self.choices
+            .first()
+            .map(|choice| choice.text.as_str())
+            .unwrap_or_default()
+    }
+
+    fn chunk_stats(&self) -> Option<ChunkStats<'_>> {
+        usage_stats(&self.model, self.usage.as_ref())
+    }
+}
+
+async fn json_response<T: DeserializeOwned>(response: Response) -> Result<T, OllamaError> {
+    if response.status().is_success() {
+        Ok(response.json().await?)
+    } else {
+        Err(api_error(response).await)
+    }
+}
+
+/// Parses a server-sent events body into a stream of `T`, one per `data:`
+/// line, ending at `data: [DONE]`. Other fields and comments are ignored. An
+/// OpenAI-style `{"error": {"message": ...}}` event is surfaced as
+/// [`OllamaError::ApiError`].
+fn sse_stream<T: DeserializeOwned>(
+    response: Response,
+) -> impl Stream<Item = Result<T, OllamaError>> {
+    body_lines(response)
+        .try_filter_map(|line| async move {
+            let line =
+                String::from_utf8(line).map_err(|e| OllamaError::InvalidResponse(e.to_string()))?;
+            Ok(line
+                .trim_end()
+                .strip_prefix("data:")
+                .map(|data| data.trim().to_string()))
+        })
+        .try_take_while(|data| future::ready(Ok(data != "[DONE]")))
+        .and_then(|data| async move {
+            tracing::debug!(data);
+            match serde_json::from_str::<T>(&data) {
+                Ok(value) => Ok(value),
+                Err(e) => match serde_json::from_str::<OpenAiErrorResponse>(&data) {
+                    Ok(error) => Err(OllamaError::ApiError(error.error.message)),
+                    Err(_) => Err(OllamaError::InvalidResponseFormat(e)),
+                },
+            }
+        })
+}
+
+/// Guesses the type of a base64 image from its first bytes, defaulting to
+/// PNG.
+fn image_mime_type(image: &str) -> &'static str {
+    let prefix = image.get(..16).unwrap_or(image);
+    match crate::base64::decode(prefix).as_deref() {
+        Some([0xff, 0xd8, 0xff, ..]) => "image/jpeg",
+        Some([b'G', b'I', b'F', b'8', ..]) => "image/gif",
+        Some([b'R', b'I', b'F', b'F', _, _, _, _, b'W', b'E', b'B', b'P', ..]) => "image/webp",
+        _ => "image/png",
+    }
+}
+
+impl From<ChatMessage> for OpenAiMessage {
+    fn from(message: ChatMessage) -> Self {
+        let content = match message.images {
+            Some(images) if !images.is_empty() => {
+                let mut parts = vec![ContentPart::Text {
+                    text: message.content,
+                }];
+                parts.extend(images.into_iter().map(|image| ContentPart::ImageUrl {
+                    image_url: ImageUrl {
+                        url: format!("data:{};base64,{}", image_mime_type(&image), image),
+                    },
+                }));
+                MessageContent::Parts(parts)
+            }
+            _ => MessageContent::Text(message.content),
+        };
+        let tool_calls = message.tool_calls.map(|calls| {
+            calls
+                .into_iter()
+                .map(|call| OpenAiToolCall {
+                    id: None,
+                    kind: Some("function".to_string()),
+                    function: OpenAiFunctionCall {
+                        name: call.function.name,
+                        arguments: call.function.arguments.to_string(),
+                    },
+                })
+                .collect()
+        });
+        OpenAiMessage {
+            role: message.role,
+            content: Some(content),
+            tool_calls,
+            tool_call_id: None,
+        }
+    }
+}
+
+impl From<OpenAiMessage> for ChatMessage {
+    /// Image URLs other than base64 `data:` URLs cannot be represented and are
+    /// dropped.
+    fn from(message: OpenAiMessage) -> Self {
+        let mut content = String::new();
+        let mut images = Vec::new();
+        match message.content {
+            Some(MessageContent::Text(text)) => content = text,
+            Some(MessageContent::Parts(parts)) => {
+                for part in parts {
+                    match part {
+                        ContentPart::Text { text } => content.push_str(&text),
+                        ContentPart::ImageUrl { image_url } => {
+                            if let Some((_, data)) = image_url.url.split_once(";base64,") {
+                                images.push(data.to_string());
+                            }
+                        }
+                    }
+                }
+            }
+            None => {}
+        }
+        let tool_calls = message.tool_calls.map(|calls| {
+            calls
+                .into_iter()
+                .map(|call| ToolCall {
+                    function: FunctionCall {
+                        arguments: serde_json::from_str(&call.function.arguments)
+                            .unwrap_or(Value::String(call.function.arguments)),
+                        name: call.function.name,
+                    },
+                })
+                .collect()
+        });
+        ChatMessage {
+            role: message.role,
+            content,
+            images: (!images.is_empty()).then_some(images),
+            tool_calls,
+        }
+    }
+}
+
+impl From<ChatRequest> for ChatCompletionRequest {
+    fn from(request: ChatRequest) -> Self {
+        let options = request.options.unwrap_or_default();
+        let response_format = match request.format.as_deref() {
+            Some("json") => Some(serde_json::json!({ "type": "json_object" })),
+            _ => None,
+        };
+        ChatCompletionRequest {
+            model: request.model,
+            messages: request.messages.into_iter().map(Into::into).collect(),
+            stream: request.stream,
+            temperature: options.temperature,
+            top_p: options.top_p,
+            max_tokens: options.token_limit(),
+            stop: options.stop,
+            seed: options.seed,
+            response_format,
+            tools: request.tools,
+            logprobs: request.logprobs,
+            top_logprobs: request.top_logprobs,
+            ..Default::default()
+        }
+    }
+}
+
+impl From<ChatCompletionRequest> for ChatRequest {
+    fn from(request: ChatCompletionRequest) -> Self {
+        let options = GenerateOptions {
+            temperature: request.temperature,
+            top_p: request.top_p,
+            num_predict: request.max_tokens.map(num_predict),
+            stop: request.stop,
+            seed: request.seed,
+            ..Default::default()
+        };
+        let has_options = options.temperature.is_some()
+            || options.top_p.is_some()
+            || options.num_predict.is_some()
+            || options.stop.is_some()
+            || options.seed.is_some();
+        let format = request
+            .response_format
+            .filter(|format| format["type"] == "json_object")
+            .map(|_| "json".to_string());
+        ChatRequest {
+            model: request.model,
+            messages: request.messages.into_iter().map(Into::into).collect(),
+            stream: request.stream,
+            format,
+            options: has_options.then_some(options),
+            tools: request.tools,
+            logprobs: request.logprobs,
+            top_logprobs: request.top_logprobs,
+        }
+    }
+}
+
+/// Ollama reads the output limit from `num_predict`; `max_tokens` is only
+/// honoured by its `/v1` endpoints.
+fn num_predict(max_tokens: u32) -> i32 {
+    i32::try_from(max_tokens).unwrap_or(i32::MAX)
+}
+
+fn usage(response: &ChatResponse) -> Option<Usage> {
+    let prompt_tokens = response.prompt_eval_count?;
+    let completion_tokens = response.eval_count.unwrap_or(0);
+    Some(Usage {
+        prompt_tokens,
+        completion_tokens,
+        total_tokens: prompt_tokens + completion_tokens,
+    })
+}
+
+fn choice_logprobs(logprobs: Option<Vec<Logprob>>) -> Option<ChoiceLogprobs> {
+    logprobs.map(|content| ChoiceLogprobs {
+        content: Some(content),
+    })
+}
+
+fn finish_reason(response: &ChatResponse) -> Option<String> {
+    match (response.done, &response.message.tool_calls) {
+        (false, _) => None,
+        (true, Some(calls)) if !calls.is_empty() => Some("tool_calls".to_string()),
+        (true, _) if response.done_reason == Some(DoneReason::Length) => Some("length".to_string()),
+        (true, _) => Some("stop".to_string()),
+    }
+}
+
+fn done_reason(finish_reason: Option<&str>) -> Option<DoneReason> {
+    finish_reason.map(|reason| match reason {
+        "tool_calls" => DoneReason::Stop,
+        other => DoneReason::from(other),
+    })
+}
+
+impl From<ChatResponse> for ChatCompletion {
+    /// Treats `response` as a complete, non-streamed reply.
+    fn from(response: ChatResponse) -> Self {
+        let created = unix_from_rfc3339(&response.created_at).unwrap_or(0);
+        ChatCompletion {
+            id: format!("chatcmpl-{}", created),
+            object: "chat.completion".to_string(),
+            created,
+            usage: usage(&response),
+            choices: vec![ChatChoice {
+                index: 0,
+                finish_reason: finish_reason(&response),
+                message: response.message.into(),
+                logprobs: choice_logprobs(response.logprobs),
+            }],
+            model: response.model,
+            system_fingerprint: None,
+        }
+    }
+}
+
+impl From<ChatResponse> for ChatCompletionChunk {
+    fn from(response: ChatResponse) -> Self {
+        let created = unix_from_rfc3339(&response.created_at).unwrap_or(0);
+        let finish_reason = finish_reason(&response);
+        let usage = usage(&response);
+        let logprobs = choice_logprobs(response.logprobs);
+        let message = OpenAiMessage::from(response.message);
+        let content = match message.content {
+            Some(MessageContent::Text(text)) => Some(text),
+            _ => None,
+        };
+        ChatCompletionChunk {
+            id: format!("chatcmpl-{}", created),
+            object: "chat.completion.chunk".to_string(),
+            created,
+            model: response.model,
+            system_fingerprint: None,
+            choices: vec![ChunkChoice {
+                index: 0,
+                delta: ChatDelta {
+                    role: Some(message.role),
+                    content,
+                    tool_calls: message.tool_calls,
+                },
+                finish_reason,
+                logprobs,
+            }],
+            usage,
+        }
+    }
+}
+
+impl From<ChatCompletion> for ChatResponse {
+    /// Uses the first choice. Durations are not reported by `/v1` and are left
+    /// unset.
+    fn from(completion: ChatCompletion) -> Self {
+        let choice = completion.choices.into_iter().next().unwrap_or_default();
+        ChatResponse {
+            model: completion.model,
+            created_at: rfc3339_from_unix(completion.created),
+            message: choice.message.into(),
+            done: true,
+            done_reason: done_reason(choice.finish_reason.as_deref()),
+            prompt_eval_count: completion.usage.as_ref().map(|u| u.prompt_tokens),
+            eval_count: completion.usage.as_ref().map(|u| u.completion_tokens),
+            logprobs: choice.logprobs.and_then(|logprobs| logprobs.content),
+            ..Default::default()
+        }
+    }
+}
+
+impl From<ChatCompletionChunk> for ChatResponse {
+    /// Uses the first choice. A chunk with a `finish_reason` is the last one.
+    fn from(chunk: ChatCompletionChunk) -> Self {
+        let choice = chunk.choices.into_iter().next().unwrap_or_default();
+        let message = OpenAiMessage {
+            role: choice.delta.role.unwrap_or_else(|| "assistant".to_string()),
+            content: choice.delta.content.map(MessageContent::Text),
+            tool_calls: choice.delta.tool_calls,
+            tool_call_id: None,
+        };
+        ChatResponse {
+            model: chunk.model,
+            created_at: rfc3339_from_unix(chunk.created),
+            message: message.into(),
+            done: choice.finish_reason.is_some(),
+            done_reason: done_reason(choice.finish_reason.as_deref()),
+            prompt_eval_count: chunk.usage.as_ref().map(|u| u.prompt_tokens),
+            eval_count: chunk.usage.as_ref().map(|u| u.completion_tokens),
+            logprobs: choice.logprobs.and_then(|logprobs| logprobs.content),
+            ..Default::default()
+        }
+    }
+}
+
+/// Days since 1970-01-01 for a proleptic Gregorian date.
+fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let day_of_year = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146097 + day_of_era - 719468
+}
+
+/// Parses the timestamps Ollama sends, e.g. `2025-01-01T12:30:00.123456Z` or
+/// `2025-01-01T12:30:00-07:00`, to Unix seconds.
+pub(crate) fn unix_from_rfc3339(timestamp: &str) -> Option<u64> {
+    let number = |range: std::ops::Range<usize>| timestamp.get(range)?.parse::<i64>().ok();
+    let days = days_from_civil(number(0..4)?, number(5..7)?, number(8..10)?);
+    let seconds = number(11..13)? * 3600 + number(14..16)? * 60 + number(17..19)?;
+
+    let zone = timestamp[19..].trim_start_matches(|c: char| c == '.' || c.is_ascii_digit());
+    let offset = match zone.as_bytes().first() {
+        Some(b'+') | Some(b'-') => {
+            let sign = if zone.starts_with('-') { -1 } else { 1 };
+            let hours = zone.get(1..3)?.parse::<i64>().ok()?;
+            let minutes = zone.get(4..6)?.parse::<i64>().ok()?;
+            sign * (hours * 3600 + minutes * 60)
+        }
+        _ => 0,
+    };
+    u64::try_from(days * 86400 + seconds - offset).ok()
+}
+
+pub(crate) fn rfc3339_from_unix(timestamp: u64) -> String {
+    let days = (timestamp / 86400) as i64;
+    let seconds = timestamp % 86400;
+    // Inverse of days_from_civil
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let day_of_era = z - era * 146097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
+        year,
+        month,
+        day,
+        seconds / 3600,
+        seconds % 3600 / 60,
+        seconds % 60
+    )
+}
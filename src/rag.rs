@@ -0,0 +1,519 @@
+//! Retrieval-augmented generation: text splitters, an ingestion pipeline that
+//! embeds chunks into an [`EmbeddingIndex`], and [`RagChat`], which answers
+//! questions from the retrieved chunks with numbered citations.
+
+use crate::api::{OllamaApi, ResponseStream};
+use crate::embeddings::{embed_batch, EmbedBatchOptions, EmbeddingIndex};
+use crate::error::OllamaError;
+use crate::models::*;
+
+/// A piece of a larger text produced by a [`TextSplitter`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TextChunk {
+    pub text: String,
+    /// Byte offset of the chunk in the original text.
+    pub offset: usize,
+    /// Enclosing Markdown headings, joined with ` > `, if known.
+    pub heading: Option<String>,
+}
+
+/// Splits text into chunks small enough to embed.
+pub trait TextSplitter: Send + Sync {
+    fn split(&self, text: &str) -> Vec<TextChunk>;
+}
+
+/// Splits text into windows of at most `chunk_size` characters, with
+/// `overlap` characters shared between consecutive windows.
+///
+/// Windows end at the last whitespace in their second half when there is one,
+/// and the overlap starts at a word boundary, so words are only cut when they
+/// are longer than half a window. The overlap can therefore be shorter than
+/// requested.
+#[derive(Debug, Clone, Copy)]
+pub struct CharacterSplitter {
+    pub chunk_size: usize,
+    pub overlap: usize,
+}
+
+impl CharacterSplitter {
+    pub fn new(chunk_size: usize, overlap: usize) -> Self {
+        CharacterSplitter {
+            chunk_size: chunk_size.max(1),
+            overlap: overlap.min(chunk_size.saturating_sub(1)),
+        }
+    }
+}
+
+impl TextSplitter for CharacterSplitter {
+    fn split(&self, text: &str) -> Vec<TextChunk> {
+        let chunk_size = self.chunk_size.max(1);
+        let overlap = self.overlap.min(chunk_size - 1);
+        let chars: Vec<(usize, char)> = text.char_indices().collect();
+        let byte_at = |index: usize| chars.get(index).map_or(text.len(), |&(byte, _)| byte);
+
+        let mut chunks = Vec::new();
+        let mut start = 0;
+        while start < chars.len() {
+            let mut end = (start + chunk_size).min(chars.len());
+            if end < chars.len() {
+                let floor = start + chunk_size / 2;
+                if let Some(space) = (floor.max(start + 1)..end)
+                    .rev()
+                    .find(|&index| chars[index].1.is_whitespace())
+                {
+                    end = space + 1;
+                }
+            }
+
+            let chunk = &text[byte_at(start)..byte_at(end)];
+            if !chunk.trim().is_empty() {
+                chunks.push(TextChunk {
+                    text: chunk.trim().to_string(),
+                    offset: byte_at(start) + (chunk.len() - chunk.trim_start().len()),
+                    heading: None,
+                });
+            }
+            if end == chars.len() {
+                break;
+            }
+            let next = (end - overlap.min(end - start - 1)).max(start + 1);
+            // Don't start the overlap mid-word
+            start = match (next..end).find(|&index| chars[index - 1].1.is_whitespace()) {
+                Some(word_start) => word_start,
+                None => next,
+            };
+        }
+        chunks
+    }
+}
+
+/// Splits text into sentences and packs consecutive sentences into chunks of
+/// at most `max_chars` characters. A sentence longer than `max_chars` becomes
+/// a chunk of its own.
+#[derive(Debug, Clone, Copy)]
+pub struct SentenceSplitter {
+    pub max_chars: usize,
+}
+
+impl SentenceSplitter {
+    pub fn new(max_chars: usize) -> Self {
+        SentenceSplitter { max_chars }
+    }
+}
+
+/// Returns the byte ranges of the sentences in `text`. A sentence ends at
+/// `.`, `!` or `?` followed by whitespace, or at a blank line.
+fn sentences(text: &str) -> Vec<(usize, usize)> {
+    let mut sentences = Vec::new();
+    let mut start = 0;
+    let mut chars = text.char_indices().peekable();
+    while let Some((index, c)) = chars.next() {
+        let next = chars.peek().map(|&(_, next)| next);
+        let boundary = match c {
+            '.' | '!' | '?' => next.is_none_or(char::is_whitespace),
+            '\n' => next == Some('\n'),
+            _ => false,
+        };
+        if boundary {
+            let end = index + c.len_utf8();
+            if !text[start..end].trim().is_empty() {
+                sentences.push((start, end));
+            }
+            start = end;
+        }
+    }
+    if !text[start..].trim().is_empty() {
+        sentences.push((start, text.len()));
+    }
+    sentences
+}
+
+impl TextSplitter for SentenceSplitter {
+    fn split(&self, text: &str) -> Vec<TextChunk> {
+        let mut chunks: Vec<TextChunk> = Vec::new();
+        let mut current: Option<(usize, usize)> = None;
+        for (start, end) in sentences(text) {
+            current = match current {
+                Some((chunk_start, _))
+                    if text[chunk_start..end].trim().chars().count() <= self.max_chars =>
+                {
+                    Some((chunk_start, end))
+                }
+                Some(range) => {
+                    chunks.push(trimmed_chunk(text, range));
+                    Some((start, end))
+                }
+                None => Some((start, end)),
+            };
+        }
+        if let Some(range) = current {
+            chunks.push(trimmed_chunk(text, range));
+        }
+        chunks
+    }
+}
+
+fn trimmed_chunk(text: &str, (start, end): (usize, usize)) -> TextChunk {
+    let slice = &text[start..end];
+    TextChunk {
+        text: slice.trim().to_string(),
+        offset: start + (slice.len() - slice.trim_start().len()),
+        heading: None,
+    }
+}
+
+/// Splits Markdown into one chunk per section, starting a new section at each
+/// ATX heading (`#` to `######`) outside fenced code blocks.
+///
+/// Each chunk records the path of headings above it. Sections longer than the
+/// optional `max_chars` are split further with a [`SentenceSplitter`].
+#[derive(Debug, Clone, Copy, Default)]
+pub struct MarkdownHeadingSplitter {
+    pub max_chars: Option<usize>,
+}
+
+impl MarkdownHeadingSplitter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_max_chars(mut self, max_chars: usize) -> Self {
+        self.max_chars = Some(max_chars);
+        self
+    }
+}
+
+/// Parses an ATX heading line into its level and title.
+fn heading(line: &str) -> Option<(usize, &str)> {
+    let level = line.chars().take_while(|&c| c == '#').count();
+    let rest = &line[level..];
+    if (1..=6).contains(&level) && (rest.is_empty() || rest.starts_with([' ', '\t'])) {
+        Some((level, rest.trim().trim_end_matches('#').trim_end()))
+    } else {
+        None
+    }
+}
+
+impl TextSplitter for MarkdownHeadingSplitter {
+    fn split(&self, text: &str) -> Vec<TextChunk> {
+        let mut sections: Vec<(usize, usize, Option<String>)> = Vec::new();
+        let mut path: Vec<(usize, String)> = Vec::new();
+        let mut section_start = 0;
+        let mut section_heading: Option<String> = None;
+        let mut in_fence = false;
+        let mut offset = 0;
+
+        for line in text.split_inclusive('\n') {
+            let trimmed = line.trim_end_matches(['\n', '\r']);
+            if trimmed.trim_start().starts_with("```") || trimmed.trim_start().starts_with("~~~") {
+                in_fence = !in_fence;
+            } else if !in_fence {
+                if let Some((level, title)) = heading(trimmed) {
+                    sections.push((section_start, offset, section_heading.take()));
+                    path.retain(|&(parent, _)| parent < level);
+                    path.push((level, title.to_string()));
+                    section_start = offset;
+                    section_heading = Some(
+                        path.iter()
+                            .map(|(_, title)| title.as_str())
+                            .collect::<Vec<_>>()
+                            .join(" > "),
+                    );
+                }
+            }
+            offset += line.len();
+        }
+        sections.push((section_start, text.len(), section_heading));
+
+        let mut chunks = Vec::new();
+        for (start, end, heading) in sections {
+            let section = &text[start..end];
+            if section.trim().is_empty() {
+                continue;
+            }
+            match self.max_chars {
+                Some(max_chars) if section.trim().chars().count() > max_chars => {
+                    for chunk in SentenceSplitter::new(max_chars).split(section) {
+                        chunks.push(TextChunk {
+                            offset: start + chunk.offset,
+                            heading: heading.clone(),
+                            ..chunk
+                        });
+                    }
+                }
+                _ => chunks.push(TextChunk {
+                    heading,
+                    ..trimmed_chunk(text, (start, end))
+                }),
+            }
+        }
+        chunks
+    }
+}
+
+/// Metadata stored alongside each ingested chunk.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChunkMetadata {
+    /// Caller-supplied name of the document, e.g. a path or URL.
+    pub source: String,
+    /// Position of the chunk within its document.
+    pub index: usize,
+    pub offset: usize,
+    pub heading: Option<String>,
+    pub text: String,
+}
+
+/// Splits documents and embeds the chunks into an [`EmbeddingIndex`].
+///
+/// Chunks are stored under the id `"{source}#{index}"`; ingesting a source
+/// again replaces all of its previous chunks.
+pub struct IngestPipeline {
+    model: String,
+    splitter: Box<dyn TextSplitter>,
+    batch_options: EmbedBatchOptions,
+}
+
+impl IngestPipeline {
+    /// Creates a pipeline that embeds chunks with `model`.
+    pub fn new(model: impl Into<String>, splitter: impl TextSplitter + 'static) -> Self {
+        IngestPipeline {
+            model: model.into(),
+            splitter: Box::new(splitter),
+            batch_options: EmbedBatchOptions::default(),
+        }
+    }
+
+    /// Sets the batching used when embedding chunks.
+    pub fn with_batch_options(mut self, batch_options: EmbedBatchOptions) -> Self {
+        self.batch_options = batch_options;
+        self
+    }
+
+    pub fn model(&self) -> &str {
+        &self.model
+    }
+
+    /// Splits `text`, embeds the chunks and stores them in `index` under
+    /// `source`. Returns the number of chunks stored.
+    pub async fn ingest<A: OllamaApi + ?Sized>(
+        &self,
+        api: &A,
+        index: &mut EmbeddingIndex<ChunkMetadata>,
+        source: &str,
+        text: &str,
+    ) -> Result<usize, OllamaError> {
+        let chunks = self.splitter.split(text);
+        let vectors = embed_batch(
+            api,
+            &self.model,
+            chunks.iter().map(|chunk| chunk.text.clone()).collect(),
+            self.batch_options.clone(),
+        )
+        .await?;
+
+        let stale: Vec<String> = index
+            .iter()
+            .filter(|entry| entry.metadata.source == source)
+            .map(|entry| entry.id.clone())
+            .collect();
+        for id in stale {
+            index.remove(&id);
+        }
+
+        let count = chunks.len();
+        for (position, (chunk, vector)) in chunks.into_iter().zip(vectors).enumerate() {
+            let metadata = ChunkMetadata {
+                source: source.to_string(),
+                index: position,
+                offset: chunk.offset,
+                heading: chunk.heading,
+                text: chunk.text,
+            };
+            index.insert(format!("{}#{}", source, position), vector, metadata)?;
+        }
+        Ok(count)
+    }
+}
+
+/// Default prompt used by [`RagChat`]. `{context}` is replaced with the
+/// numbered passages and `{question}` with the user's question.
+pub const DEFAULT_RAG_TEMPLATE: &str = "Answer the question using only the numbered context passages below. \
+Cite the passages you rely on as [n]. If the context does not contain the answer, say that you don't know.
+
+Context:
+{context}
+
+Question: {question}";
+
+/// A retrieved chunk cited in a [`RagChat`] prompt.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Citation {
+    /// Number used for the passage in the prompt, starting at 1.
+    pub number: usize,
+    pub id: String,
+    pub score: f32,
+    pub metadata: ChunkMetadata,
+}
+
+/// The result of [`RagChat::ask`]: the passages given to the model and the
+/// streamed answer.
+pub struct RagAnswer {
+    pub citations: Vec<Citation>,
+    pub stream: ResponseStream<ChatResponse>,
+}
+
+/// Answers questions from the chunks in an [`EmbeddingIndex`].
+pub struct RagChat<'a, A: OllamaApi + ?Sized> {
+    api: &'a A,
+    index: &'a EmbeddingIndex<ChunkMetadata>,
+    embedding_model: String,
+    chat_model: String,
+    top_k: usize,
+    template: String,
+    system: Option<String>,
+    options: Option<GenerateOptions>,
+}
+
+impl<'a, A: OllamaApi + ?Sized> RagChat<'a, A> {
+    /// Creates a chat over `index`. Queries must be embedded with the same
+    /// model the index was built with.
+    pub fn new(
+        api: &'a A,
+        index: &'a EmbeddingIndex<ChunkMetadata>,
+        embedding_model: impl Into<String>,
+        chat_model: impl Into<String>,
+    ) -> Self {
+        RagChat {
+            api,
+            index,
+            embedding_model: embedding_model.into(),
+            chat_model: chat_model.into(),
+            top_k: 4,
+            template: DEFAULT_RAG_TEMPLATE.to_string(),
+            system: None,
+            options: None,
+        }
+    }
+
+    /// Sets how many chunks are retrieved per question. Defaults to 4.
+    pub fn with_top_k(mut self, top_k: usize) -> Self {
+        self.top_k = top_k;
+        self
+    }
+
+    /// Replaces [`DEFAULT_RAG_TEMPLATE`].
+    pub fn with_template(mut self, template: impl Into<String>) -> Self {
+        self.template = template.into();
+        self
+    }
+
+    pub fn with_system(mut self, system: impl Into<String>) -> Self {
+        self.system = Some(system.into());
+        self
+    }
+
+    pub fn with_options(mut self, options: GenerateOptions) -> Self {
+        self.options = Some(options);
+        self
+    }
+
+    /// Retrieves the chunks most relevant to `question`, best first.
+    pub async fn retrieve(&self, question: &str) -> Result<Vec<Citation>, OllamaError> {
+        let response = self
+            .api
+            .generate_embeddings(EmbedRequest {
+                model: self.embedding_model.clone(),
+                input: EmbedInput::Single(question.to_string()),
+                ..Default::default()
+            })
+            .await?;
+        let query = response.embeddings.into_iter().next().ok_or_else(|| {
+            OllamaError::InvalidResponse("expected 1 embedding, got 0".to_string())
+        })?;
+
+        Ok(self
+            .index
+            .search(&query, self.top_k)?
+            .into_iter()
+            .enumerate()
+            .map(|(position, hit)| Citation {
+                number: position + 1,
+                id: hit.id.to_string(),
+                score: hit.score,
+                metadata: hit.metadata.clone(),
+            })
+            .collect())
+    }
+
+    /// Fills the prompt template with `question` and the numbered passages.
+    pub fn prompt(&self, question: &str, citations: &[Citation]) -> String {
+        let context = citations
+            .iter()
+            .map(|citation| {
+                let label = match &citation.metadata.heading {
+                    Some(heading) => format!("{}, {}", citation.metadata.source, heading),
+                    None => citation.metadata.source.clone(),
+                };
+                format!(
+                    "[{}] ({})\n{}",
+                    citation.number, label, citation.metadata.text
+                )
+            })
+            .collect::<Vec<_>>()
+            .join("\n\n");
+        fill_template(&self.template, &context, question)
+    }
+
+    /// Retrieves context for `question` and streams the model's answer.
+    pub async fn ask(&self, question: &str) -> Result<RagAnswer, OllamaError> {
+        let citations = self.retrieve(question).await?;
+
+        let mut messages = Vec::new();
+        if let Some(system) = &self.system {
+            messages.push(ChatMessage {
+                role: "system".to_string(),
+                content: system.clone(),
+                ..Default::default()
+            });
+        }
+        messages.push(ChatMessage {
+            role: "user".to_string(),
+            content: self.prompt(question, &citations),
+            ..Default::default()
+        });
+
+        let stream = self
+            .api
+            .chat(ChatRequest {
+                model: self.chat_model.clone(),
+                messages,
+                options: self.options.clone(),
+                ..Default::default()
+            })
+            .await?;
+        Ok(RagAnswer { citations, stream })
+    }
+}
+
+/// Replaces `{context}` and `{question}` in one pass, so placeholders inside
+/// the substituted text are left as they are.
+fn fill_template(template: &str, context: &str, question: &str) -> String {
+    let mut prompt = String::with_capacity(template.len() + context.len() + question.len());
+    let mut rest = template;
+    while let Some(start) = rest.find('{') {
+        prompt.push_str(&rest[..start]);
+        rest = &rest[start..];
+        if let Some(after) = rest.strip_prefix("{context}") {
+            prompt.push_str(context);
+            rest = after;
+        } else if let Some(after) = rest.strip_prefix("{question}") {
+            prompt.push_str(question);
+            rest = after;
+        } else {
+            prompt.push('{');
+            rest = &rest[1..];
+        }
+    }
+    prompt.push_str(rest);
+    prompt
+}
@@ -0,0 +1,334 @@
+//! OpenAI-compatible gateway in front of an [`OllamaClient`].
+//!
+//! [`router`] serves `/v1/chat/completions`, `/v1/embeddings` and `/v1/models`,
+//! translating each call into [`OllamaClient::chat`],
+//! [`OllamaClient::generate_embeddings`] and [`OllamaClient::list_models`].
+//! Requests are authenticated with `Authorization: Bearer <key>` against a
+//! [`ProxyConfig`], and each key can be limited to a list of models.
+//!
+//! Requires the `server` feature. The `oxide-proxy` binary wraps this module.
+
+use std::collections::HashMap;
+use std::convert::Infallible;
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use axum::extract::State;
+use axum::http::{header, HeaderMap, StatusCode};
+use axum::response::sse::{Event, KeepAlive, Sse};
+use axum::response::{IntoResponse, Response};
+use axum::routing::{get, post};
+use axum::{Json, Router};
+use futures::{stream, StreamExt, TryStreamExt};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+
+use crate::error::OllamaError;
+use crate::models::*;
+use crate::openai_compat::*;
+use crate::OllamaClient;
+
+/// Permissions of one API key.
+#[derive(Serialize, Deserialize, Debug, Default, Clone)]
+pub struct ApiKey {
+    /// Optional label used in logs.
+    pub name: Option<String>,
+    /// Models the key may use. `None` allows every model.
+    pub models: Option<Vec<String>>,
+}
+
+impl ApiKey {
+    /// Whether the key may use `model`. A name without a tag matches its
+    /// `:latest` tag and vice versa.
+    pub fn allows(&self, model: &str) -> bool {
+        match &self.models {
+            None => true,
+            Some(models) => models
+                .iter()
+                .any(|allowed| with_tag(allowed) == with_tag(model)),
+        }
+    }
+}
+
+fn with_tag(model: &str) -> String {
+    if model.contains(':') {
+        model.to_string()
+    } else {
+        format!("{}:latest", model)
+    }
+}
+
+/// Configuration of the proxy, usually read from a JSON file.
+#[derive(Serialize, Deserialize, Debug, Default, Clone)]
+pub struct ProxyConfig {
+    /// Address to listen on, e.g. `127.0.0.1:8080`.
+    pub listen: Option<String>,
+    /// URL of the Ollama server, e.g. `http://localhost:11434`.
+    pub ollama_url: Option<String>,
+    /// API keys and their permissions. With no keys, authentication is
+    /// disabled and every model is allowed.
+    #[serde(default)]
+    pub keys: HashMap<String, ApiKey>,
+}
+
+impl ProxyConfig {
+    pub fn with_key(mut self, key: impl Into<String>, permissions: ApiKey) -> Self {
+        self.keys.insert(key.into(), permissions);
+        self
+    }
+}
+
+struct ProxyState {
+    client: Arc<OllamaClient>,
+    config: ProxyConfig,
+}
+
+/// Builds the proxy routes.
+pub fn router(client: Arc<OllamaClient>, config: ProxyConfig) -> Router {
+    let state = Arc::new(ProxyState { client, config });
+    Router::new()
+        .route("/v1/chat/completions", post(chat_completions))
+        .route("/v1/embeddings", post(embeddings))
+        .route("/v1/models", get(models))
+        .with_state(state)
+}
+
+/// An error returned to proxy clients with an OpenAI-style body.
+#[derive(Debug)]
+pub struct ProxyError {
+    status: StatusCode,
+    kind: &'static str,
+    message: String,
+}
+
+impl ProxyError {
+    fn new(status: StatusCode, kind: &'static str, message: impl Into<String>) -> Self {
+        ProxyError {
+            status,
+            kind,
+            message: message.into(),
+        }
+    }
+
+    fn body(&self) -> serde_json::Value {
+        json!({ "error": { "message": self.message, "type": self.kind, "code": null } })
+    }
+}
+
+impl From<OllamaError> for ProxyError {
+    /// Client errors reported by Ollama, such as an unknown model, keep their
+    /// status and message. Everything else is a gateway error.
+    fn from(error: OllamaError) -> Self {
+        if let OllamaError::ApiError(message) = &error {
+            if let Some((status, message)) = upstream_client_error(message) {
+                return ProxyError::new(status, "invalid_request_error", message);
+            }
+        }
+        let status = match error {
+            OllamaError::Overloaded(_) => StatusCode::SERVICE_UNAVAILABLE,
+            OllamaError::Timeout => StatusCode::GATEWAY_TIMEOUT,
+            _ => StatusCode::BAD_GATEWAY,
+        };
+        ProxyError::new(status, "api_error", error.to_string())
+    }
+}
+
+/// Splits an API error such as `Status: 404 Not Found, Error: {"error": "..."}`
+/// into its status and message, if the status is a 4xx.
+fn upstream_client_error(message: &str) -> Option<(StatusCode, String)> {
+    let (status, body) = message.strip_prefix("Status: ")?.split_once(", Error: ")?;
+    let status = StatusCode::from_bytes(status.get(..3)?.as_bytes()).ok()?;
+    status
+        .is_client_error()
+        .then(|| (status, crate::error_message(body)))
+}
+
+impl IntoResponse for ProxyError {
+    fn into_response(self) -> Response {
+        (self.status, Json(self.body())).into_response()
+    }
+}
+
+impl ProxyState {
+    /// Returns the permissions of the request's key.
+    fn authorize(&self, headers: &HeaderMap) -> Result<ApiKey, ProxyError> {
+        if self.config.keys.is_empty() {
+            return Ok(ApiKey::default());
+        }
+        let key = headers
+            .get(header::AUTHORIZATION)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.strip_prefix("Bearer "))
+            .ok_or_else(|| {
+                ProxyError::new(
+                    StatusCode::UNAUTHORIZED,
+                    "invalid_request_error",
+                    "missing API key",
+                )
+            })?;
+        self.config.keys.get(key.trim()).cloned().ok_or_else(|| {
+            ProxyError::new(
+                StatusCode::UNAUTHORIZED,
+                "invalid_request_error",
+                "invalid API key",
+            )
+        })
+    }
+
+    fn authorize_model(&self, headers: &HeaderMap, model: &str) -> Result<ApiKey, ProxyError> {
+        let key = self.authorize(headers)?;
+        if !key.allows(model) {
+            tracing::info!(key = key.name, model, "model not allowed for key");
+            return Err(ProxyError::new(
+                StatusCode::FORBIDDEN,
+                "permission_error",
+                format!("model '{}' is not allowed for this API key", model),
+            ));
+        }
+        Ok(key)
+    }
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_secs())
+}
+
+async fn chat_completions(
+    State(state): State<Arc<ProxyState>>,
+    headers: HeaderMap,
+    Json(request): Json<ChatCompletionRequest>,
+) -> Result<Response, ProxyError> {
+    state.authorize_model(&headers, &request.model)?;
+    let streaming = request.stream == Some(true);
+    let include_usage = request
+        .stream_options
+        .as_ref()
+        .is_some_and(|options| options.include_usage);
+    let mut chat_request = ChatRequest::from(request);
+    chat_request.stream = Some(true);
+
+    let created = now();
+    let id = format!(
+        "chatcmpl-{}",
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |elapsed| elapsed.as_nanos())
+    );
+    let responses = state.client.chat(chat_request).await?;
+
+    if !streaming {
+        let chunks: Vec<ChatResponse> = responses.try_collect().await?;
+        let mut completion = ChatCompletion::from(merge_chunks(chunks));
+        completion.id = id;
+        completion.created = created;
+        return Ok(Json(completion).into_response());
+    }
+
+    let events = responses
+        .flat_map(move |result| {
+            let events: Vec<Event> = match result {
+                Ok(response) => {
+                    let mut chunk = ChatCompletionChunk::from(response);
+                    chunk.id = id.clone();
+                    chunk.created = created;
+                    let usage = chunk.usage.take();
+                    let mut events = vec![json_event(&chunk)];
+                    if let (true, Some(usage)) = (include_usage, usage) {
+                        chunk.choices.clear();
+                        chunk.usage = Some(usage);
+                        events.push(json_event(&chunk));
+                    }
+                    events
+                }
+                Err(error) => vec![json_event(&ProxyError::from(error).body())],
+            };
+            stream::iter(events)
+        })
+        .chain(stream::once(async { Event::default().data("[DONE]") }))
+        .map(Ok::<_, Infallible>);
+    Ok(Sse::new(events)
+        .keep_alive(KeepAlive::default())
+        .into_response())
+}
+
+fn json_event<T: Serialize>(value: &T) -> Event {
+    Event::default().data(serde_json::to_string(value).unwrap_or_default())
+}
+
+/// Folds a streamed chat reply into a single response carrying the final
+/// chunk's statistics.
+fn merge_chunks(chunks: Vec<ChatResponse>) -> ChatResponse {
+    let mut content = String::new();
+    let mut tool_calls: Vec<ToolCall> = Vec::new();
+    let mut logprobs: Vec<Logprob> = Vec::new();
+    let mut last = ChatResponse::default();
+    for chunk in chunks {
+        content.push_str(&chunk.message.content);
+        tool_calls.extend(chunk.message.tool_calls.iter().flatten().cloned());
+        logprobs.extend(chunk.logprobs.iter().flatten().cloned());
+        last = chunk;
+    }
+    last.message.content = content;
+    last.message.tool_calls = (!tool_calls.is_empty()).then_some(tool_calls);
+    last.logprobs = (!logprobs.is_empty()).then_some(logprobs);
+    last
+}
+
+async fn embeddings(
+    State(state): State<Arc<ProxyState>>,
+    headers: HeaderMap,
+    Json(request): Json<EmbeddingsRequest>,
+) -> Result<Json<EmbeddingsResponse>, ProxyError> {
+    state.authorize_model(&headers, &request.model)?;
+    let response = state
+        .client
+        .generate_embeddings(EmbedRequest {
+            model: request.model,
+            input: request.input,
+            ..Default::default()
+        })
+        .await?;
+
+    let prompt_tokens = response.prompt_eval_count.unwrap_or(0);
+    Ok(Json(EmbeddingsResponse {
+        object: "list".to_string(),
+        data: response
+            .embeddings
+            .into_iter()
+            .enumerate()
+            .map(|(index, embedding)| EmbeddingData {
+                object: "embedding".to_string(),
+                embedding,
+                index: index as u32,
+            })
+            .collect(),
+        model: response.model,
+        usage: Some(EmbeddingUsage {
+            prompt_tokens,
+            total_tokens: prompt_tokens,
+        }),
+    }))
+}
+
+async fn models(
+    State(state): State<Arc<ProxyState>>,
+    headers: HeaderMap,
+) -> Result<Json<ModelList>, ProxyError> {
+    let key = state.authorize(&headers)?;
+    let models = state.client.list_models().await?;
+    Ok(Json(ModelList {
+        object: "list".to_string(),
+        data: models
+            .into_iter()
+            .filter(|model| key.allows(&model.name))
+            .map(|model| OpenAiModel {
+                created: unix_from_rfc3339(&model.modified_at).unwrap_or(0),
+                id: model.name,
+                object: "model".to_string(),
+                owned_by: "library".to_string(),
+            })
+            .collect(),
+    }))
+}
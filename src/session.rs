@@ -0,0 +1,311 @@
+//! Stateful multi-turn chat.
+
+use futures::TryStreamExt;
+
+use crate::api::OllamaApi;
+use crate::context::{ContextBudget, TrimStrategy};
+use crate::error::OllamaError;
+use crate::models::*;
+
+/// Default instructions given to the summarization model.
+pub const DEFAULT_SUMMARY_PROMPT: &str = "Summarize the conversation below in a few sentences. \
+Keep names, facts, decisions and open questions; leave out small talk.";
+
+/// Prefix of the message that replaces summarized history.
+pub const SUMMARY_PREFIX: &str = "Summary of the earlier conversation: ";
+
+/// Configures [`summarize_history`] and automatic summarization in
+/// [`ChatSession`].
+#[derive(Debug, Clone)]
+pub struct SummaryOptions {
+    /// Model that writes the summary.
+    pub model: String,
+    pub prompt: String,
+    /// Role of the summary message, `system` or `assistant`.
+    pub role: String,
+    /// Fraction of the available context that triggers summarization.
+    pub threshold: f64,
+    pub options: Option<GenerateOptions>,
+}
+
+impl SummaryOptions {
+    pub fn new(model: impl Into<String>) -> Self {
+        SummaryOptions {
+            model: model.into(),
+            prompt: DEFAULT_SUMMARY_PROMPT.to_string(),
+            role: "system".to_string(),
+            threshold: 0.8,
+            options: None,
+        }
+    }
+
+    pub fn with_prompt(mut self, prompt: impl Into<String>) -> Self {
+        self.prompt = prompt.into();
+        self
+    }
+
+    pub fn with_role(mut self, role: impl Into<String>) -> Self {
+        self.role = role.into();
+        self
+    }
+
+    pub fn with_threshold(mut self, threshold: f64) -> Self {
+        self.threshold = threshold;
+        self
+    }
+
+    pub fn with_options(mut self, options: GenerateOptions) -> Self {
+        self.options = Some(options);
+        self
+    }
+}
+
+/// A conversation with a model that keeps its own message history.
+///
+/// With a [`ContextBudget`] attached, the history is trimmed or summarized
+/// before each request so it fits in the model's context window.
+#[derive(Clone)]
+pub struct ChatSession {
+    model: String,
+    messages: Vec<ChatMessage>,
+    options: Option<GenerateOptions>,
+    budget: Option<ContextBudget>,
+    summary: Option<SummaryOptions>,
+}
+
+impl ChatSession {
+    pub fn new(model: impl Into<String>) -> Self {
+        ChatSession {
+            model: model.into(),
+            messages: Vec::new(),
+            options: None,
+            budget: None,
+            summary: None,
+        }
+    }
+
+    /// Starts the history with a system prompt.
+    pub fn with_system(mut self, prompt: impl Into<String>) -> Self {
+        self.messages.insert(0, message("system", prompt.into()));
+        self
+    }
+
+    /// Sets the options sent with each request. A `num_ctx` also caps the
+    /// attached budget.
+    pub fn with_options(mut self, options: GenerateOptions) -> Self {
+        if let Some(budget) = self.budget.take() {
+            self.budget = Some(budget.with_num_ctx(options.num_ctx));
+        }
+        self.options = Some(options);
+        self
+    }
+
+    pub fn with_budget(mut self, budget: ContextBudget) -> Self {
+        let num_ctx = self.options.as_ref().and_then(|options| options.num_ctx);
+        self.budget = Some(match num_ctx {
+            Some(_) => budget.with_num_ctx(num_ctx),
+            None => budget,
+        });
+        self
+    }
+
+    /// Summarizes old turns once the estimated context usage passes
+    /// `summary.threshold`, regardless of the budget's strategy. Requires a
+    /// budget. [`TrimStrategy::Summarize`] uses [`SummaryOptions::new`] with
+    /// the session's model unless this is set.
+    pub fn with_summarization(mut self, summary: SummaryOptions) -> Self {
+        self.summary = Some(summary);
+        self
+    }
+
+    pub fn model(&self) -> &str {
+        &self.model
+    }
+
+    pub fn messages(&self) -> &[ChatMessage] {
+        &self.messages
+    }
+
+    pub fn budget(&self) -> Option<&ContextBudget> {
+        self.budget.as_ref()
+    }
+
+    /// Appends a message to the history without sending it.
+    pub fn push(&mut self, message: ChatMessage) {
+        self.messages.push(message);
+    }
+
+    /// Removes all messages except leading system prompts.
+    pub fn clear(&mut self) {
+        let system = self
+            .messages
+            .iter()
+            .take_while(|message| message.role == "system")
+            .count();
+        self.messages.truncate(system);
+    }
+
+    /// Sends a user message and returns the model's reply, which is also
+    /// added to the history.
+    pub async fn send<A: OllamaApi + ?Sized>(
+        &mut self,
+        api: &A,
+        content: impl Into<String>,
+    ) -> Result<ChatMessage, OllamaError> {
+        self.messages.push(message("user", content.into()));
+        self.fit(api).await?;
+
+        let request = ChatRequest {
+            model: self.model.clone(),
+            messages: self.messages.clone(),
+            options: self.options.clone(),
+            ..Default::default()
+        };
+        let (reply, prompt_eval_count) = collect_reply(api, request).await?;
+
+        if let (Some(budget), Some(count)) = (self.budget.as_mut(), prompt_eval_count) {
+            budget.calibrate(&self.messages, count);
+        }
+        self.messages.push(reply.clone());
+        Ok(reply)
+    }
+
+    /// Trims or summarizes the history until it fits the budget. Does nothing
+    /// without a budget.
+    pub async fn fit<A: OllamaApi + ?Sized>(&mut self, api: &A) -> Result<(), OllamaError> {
+        let Some(budget) = &self.budget else {
+            return Ok(());
+        };
+        let summary = match (&self.summary, budget.strategy()) {
+            (Some(summary), _) => Some(summary.clone()),
+            (None, TrimStrategy::Summarize) => Some(SummaryOptions::new(&self.model)),
+            (None, _) => None,
+        };
+        if let Some(summary) = summary {
+            let available = budget.available();
+            if budget.estimate(&self.messages) as f64 > available as f64 * summary.threshold {
+                // Keep the most recent messages that fit in half the window
+                let mut kept = 0;
+                let mut keep_recent = 0;
+                for message in self.messages.iter().rev() {
+                    kept += budget.estimate_message(message);
+                    if keep_recent > 0 && kept > available / 2 {
+                        break;
+                    }
+                    keep_recent += 1;
+                }
+                summarize_history(api, &mut self.messages, keep_recent, &summary).await?;
+            }
+        }
+
+        // Also catches a summary that still doesn't fit
+        let budget = self.budget.as_ref().expect("budget checked above");
+        let dropped = budget.trim(&mut self.messages);
+        if !dropped.is_empty() {
+            tracing::debug!(
+                dropped = dropped.len(),
+                "dropped chat history to fit the context window"
+            );
+        }
+        Ok(())
+    }
+}
+
+/// Replaces the messages between the leading system prompt and the last
+/// `keep_recent` messages with a single summary written by `options.model`.
+/// Returns the number of messages replaced.
+///
+/// A previous summary is folded into the new one. The boundary never falls
+/// between an assistant message with tool calls and the tool results that
+/// follow it; such a pair is kept whole rather than summarized in half.
+pub async fn summarize_history<A: OllamaApi + ?Sized>(
+    api: &A,
+    messages: &mut Vec<ChatMessage>,
+    keep_recent: usize,
+    options: &SummaryOptions,
+) -> Result<usize, OllamaError> {
+    let mut start = messages
+        .iter()
+        .take_while(|message| message.role == "system")
+        .count();
+    if start > 0 && messages[start - 1].content.starts_with(SUMMARY_PREFIX) {
+        start -= 1;
+    }
+    let mut end = messages.len().saturating_sub(keep_recent).max(start);
+    while end > start
+        && messages
+            .get(end)
+            .is_some_and(|message| message.role == "tool")
+    {
+        end -= 1;
+    }
+    if end <= start || (end - start == 1 && messages[start].content.starts_with(SUMMARY_PREFIX)) {
+        return Ok(0);
+    }
+
+    let transcript = messages[start..end]
+        .iter()
+        .map(transcript_line)
+        .collect::<Vec<_>>()
+        .join("\n");
+    let request = ChatRequest {
+        model: options.model.clone(),
+        messages: vec![
+            message("system", options.prompt.clone()),
+            message("user", transcript),
+        ],
+        options: options.options.clone(),
+        ..Default::default()
+    };
+    let (reply, _) = collect_reply(api, request).await?;
+
+    let summary = message(
+        &options.role,
+        format!("{}{}", SUMMARY_PREFIX, reply.content),
+    );
+    messages.splice(start..end, [summary]);
+    Ok(end - start)
+}
+
+fn transcript_line(message: &ChatMessage) -> String {
+    let mut line = format!("{}: {}", message.role, message.content);
+    for call in message.tool_calls.iter().flatten() {
+        line.push_str(&format!(
+            "\n{} called {}({})",
+            message.role, call.function.name, call.function.arguments
+        ));
+    }
+    line
+}
+
+fn message(role: &str, content: String) -> ChatMessage {
+    ChatMessage {
+        role: role.to_string(),
+        content,
+        ..Default::default()
+    }
+}
+
+/// Runs a chat request to completion, returning the assembled reply and the
+/// reported prompt token count.
+async fn collect_reply<A: OllamaApi + ?Sized>(
+    api: &A,
+    request: ChatRequest,
+) -> Result<(ChatMessage, Option<u32>), OllamaError> {
+    let mut stream = api.chat(request).await?;
+    let mut reply = message("assistant", String::new());
+    let mut prompt_eval_count = None;
+    while let Some(chunk) = stream.try_next().await? {
+        reply.content.push_str(&chunk.message.content);
+        if let Some(tool_calls) = chunk.message.tool_calls {
+            reply
+                .tool_calls
+                .get_or_insert_with(Vec::new)
+                .extend(tool_calls);
+        }
+        if chunk.done {
+            prompt_eval_count = chunk.prompt_eval_count;
+        }
+    }
+    Ok((reply, prompt_eval_count))
+}
@@ -0,0 +1,72 @@
+//! Awaiting the statistics of a streamed reply separately from its content.
+//!
+//! [`split_stats`] passes the chunks of [`generate`](crate::OllamaClient::generate)
+//! or [`chat`](crate::OllamaClient::chat) through unchanged and returns a
+//! [`StatsFuture`] that resolves with the [`GenerationStats`] of the final
+//! chunk. The content can then be forwarded, e.g. to a UI task, while the
+//! caller waits for the token counts and timings.
+//!
+//! The future only resolves as the stream is consumed, so await it
+//! concurrently with the stream or after it, not before.
+//!
+//! ```no_run
+//! use futures::TryStreamExt;
+//! use ollama_oxide::models::GenerateRequest;
+//! use ollama_oxide::stats::split_stats;
+//! use ollama_oxide::OllamaClient;
+//!
+//! # async fn example(client: OllamaClient, request: GenerateRequest) -> Result<(), ollama_oxide::error::OllamaError> {
+//! let (chunks, stats) = split_stats(client.generate(request).await?);
+//! tokio::spawn(chunks.try_for_each(|chunk| async move {
+//!     print!("{}", chunk.response);
+//!     Ok(())
+//! }));
+//! if let Some(stats) = stats.await {
+//!     println!("{:?} tokens/s, truncated: {}", stats.tokens_per_second(), stats.truncated());
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+use std::future::Future;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use futures::{Stream, StreamExt};
+use tokio::sync::oneshot;
+
+use crate::error::OllamaError;
+use crate::models::{FinalChunk, GenerationStats};
+
+/// Resolves with the statistics of the final chunk, or `None` if the stream
+/// ended or was dropped before it.
+pub struct StatsFuture {
+    receiver: oneshot::Receiver<GenerationStats>,
+}
+
+impl Future for StatsFuture {
+    type Output = Option<GenerationStats>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        Pin::new(&mut self.receiver).poll(cx).map(Result::ok)
+    }
+}
+
+/// Splits a stream of chunks into the chunks and a future for the final
+/// statistics.
+pub fn split_stats<S, T>(stream: S) -> (impl Stream<Item = Result<T, OllamaError>>, StatsFuture)
+where
+    S: Stream<Item = Result<T, OllamaError>>,
+    T: FinalChunk,
+{
+    let (sender, receiver) = oneshot::channel();
+    let mut sender = Some(sender);
+    let stream = stream.inspect(move |item| {
+        if let Some(stats) = item.as_ref().ok().and_then(FinalChunk::stats) {
+            if let Some(sender) = sender.take() {
+                let _ = sender.send(stats);
+            }
+        }
+    });
+    (stream, StatsFuture { receiver })
+}
@@ -0,0 +1,262 @@
+//! Client-side stop sequences and output budgets for streamed replies.
+//!
+//! [`StopStream`] wraps the chunks of [`generate`](crate::OllamaClient::generate)
+//! or [`chat`](crate::OllamaClient::chat) and watches the text as it arrives.
+//! A stop sequence split over several chunks is still found: only the end of
+//! the text that could be the start of a stop sequence is held back until
+//! the next chunk decides it. When a stop sequence or the character budget is
+//! reached, the text is cut there and the upstream stream is dropped, which
+//! ends the request. The last chunk is marked done and carries a
+//! [`FinishReason`].
+//!
+//! ```no_run
+//! use futures::TryStreamExt;
+//! use ollama_oxide::models::GenerateRequest;
+//! use ollama_oxide::stop::{StopOptions, StopStream};
+//! use ollama_oxide::OllamaClient;
+//!
+//! # async fn example(client: OllamaClient, request: GenerateRequest) -> Result<(), ollama_oxide::error::OllamaError> {
+//! let options = StopOptions::new(["\n\nUser:"]).with_max_chars(2000);
+//! let mut stream = StopStream::new(client.generate(request).await?, options);
+//! while let Some(chunk) = stream.try_next().await? {
+//!     print!("{}", chunk.chunk.response);
+//!     if let Some(reason) = chunk.finish_reason {
+//!         println!("\n[{:?}]", reason);
+//!     }
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use futures::Stream;
+use serde::{Deserialize, Serialize};
+
+use crate::error::OllamaError;
+use crate::models::{ChatResponse, DoneReason, FinalChunk, GenerateResponse};
+
+/// Why a stream wrapped in [`StopStream`] ended.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum FinishReason {
+    /// The server finished the reply.
+    Stop,
+    /// The character budget, or the server's `num_predict` token limit, was
+    /// reached.
+    Length,
+    /// One of the client-side stop sequences was found.
+    ClientStop,
+    /// The stream was cancelled, or ended without a final chunk.
+    Cancelled,
+}
+
+/// Stop sequences and budget enforced by [`StopStream`].
+#[derive(Debug, Clone, Default)]
+pub struct StopOptions {
+    pub sequences: Vec<String>,
+    /// Maximum number of characters of output.
+    pub max_chars: Option<usize>,
+}
+
+impl StopOptions {
+    pub fn new<I, S>(sequences: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        Self {
+            sequences: sequences
+                .into_iter()
+                .map(Into::into)
+                .filter(|s: &String| !s.is_empty())
+                .collect(),
+            max_chars: None,
+        }
+    }
+
+    pub fn with_max_chars(mut self, max_chars: usize) -> Self {
+        self.max_chars = Some(max_chars);
+        self
+    }
+}
+
+/// A streamed chunk whose text [`StopStream`] can inspect and trim.
+pub trait TextChunk: FinalChunk + Default {
+    fn text_mut(&mut self) -> &mut String;
+    fn is_done(&self) -> bool;
+    fn set_done(&mut self);
+}
+
+impl TextChunk for GenerateResponse {
+    fn text_mut(&mut self) -> &mut String {
+        &mut self.response
+    }
+
+    fn is_done(&self) -> bool {
+        self.done
+    }
+
+    fn set_done(&mut self) {
+        self.done = true;
+    }
+}
+
+impl TextChunk for ChatResponse {
+    fn text_mut(&mut self) -> &mut String {
+        &mut self.message.content
+    }
+
+    fn is_done(&self) -> bool {
+        self.done
+    }
+
+    fn set_done(&mut self) {
+        self.done = true;
+    }
+}
+
+/// A chunk passed through [`StopStream`]. Only the last one has a
+/// `finish_reason`.
+#[derive(Debug)]
+pub struct StopChunk<T> {
+    pub chunk: T,
+    pub finish_reason: Option<FinishReason>,
+}
+
+/// Enforces [`StopOptions`] on a stream of chunks.
+pub struct StopStream<S> {
+    upstream: Option<Pin<Box<S>>>,
+    options: StopOptions,
+    /// Text that may be the start of a stop sequence.
+    held: String,
+    emitted: usize,
+    cancelled: bool,
+}
+
+impl<S, T> StopStream<S>
+where
+    S: Stream<Item = Result<T, OllamaError>>,
+    T: TextChunk,
+{
+    pub fn new(upstream: S, options: StopOptions) -> Self {
+        Self {
+            upstream: Some(Box::pin(upstream)),
+            options,
+            held: String::new(),
+            emitted: 0,
+            cancelled: false,
+        }
+    }
+
+    /// Ends the stream: the next item is a final chunk with any held back
+    /// text and [`FinishReason::Cancelled`], and the request is dropped.
+    pub fn cancel(&mut self) {
+        self.cancelled = true;
+    }
+
+    /// Splits the new text into what can be emitted now and what is held
+    /// back, and finds out whether the stream ends with this chunk.
+    fn process(&mut self, chunk: &mut T) -> Option<FinishReason> {
+        let mut text = std::mem::take(&mut self.held);
+        text.push_str(chunk.text_mut());
+
+        let stop = self
+            .options
+            .sequences
+            .iter()
+            .filter_map(|sequence| text.find(sequence.as_str()))
+            .min();
+        let (end, mut reason) = match stop {
+            Some(position) => (position, Some(FinishReason::ClientStop)),
+            None if chunk.is_done() => (text.len(), Some(server_reason(chunk))),
+            None => (text.len() - self.ambiguous_suffix(&text), None),
+        };
+        self.held = text.split_off(end);
+        if reason.is_some() {
+            self.held.clear();
+        }
+
+        if let Some(max_chars) = self.options.max_chars {
+            let remaining = max_chars.saturating_sub(self.emitted);
+            if let Some((cut, _)) = text.char_indices().nth(remaining) {
+                text.truncate(cut);
+                self.held.clear();
+                reason = Some(FinishReason::Length);
+            }
+        }
+        self.emitted += text.chars().count();
+        *chunk.text_mut() = text;
+        reason
+    }
+
+    /// Length in bytes of the longest end of `text` that is the start of a
+    /// stop sequence.
+    fn ambiguous_suffix(&self, text: &str) -> usize {
+        text.char_indices()
+            .map(|(i, _)| &text[i..])
+            .find(|suffix| {
+                self.options
+                    .sequences
+                    .iter()
+                    .any(|sequence| sequence.starts_with(suffix))
+            })
+            .map_or(0, str::len)
+    }
+
+    fn finish(&mut self, mut chunk: T, reason: FinishReason) -> StopChunk<T> {
+        self.upstream = None;
+        chunk.set_done();
+        StopChunk {
+            chunk,
+            finish_reason: Some(reason),
+        }
+    }
+}
+
+fn server_reason(chunk: &impl FinalChunk) -> FinishReason {
+    match chunk.stats().and_then(|stats| stats.done_reason) {
+        Some(DoneReason::Length) => FinishReason::Length,
+        _ => FinishReason::Stop,
+    }
+}
+
+impl<S, T> Stream for StopStream<S>
+where
+    S: Stream<Item = Result<T, OllamaError>>,
+    T: TextChunk,
+{
+    type Item = Result<StopChunk<T>, OllamaError>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+        let Some(upstream) = this.upstream.as_mut() else {
+            return Poll::Ready(None);
+        };
+        if this.cancelled {
+            let mut chunk = T::default();
+            *chunk.text_mut() = std::mem::take(&mut this.held);
+            let chunk = this.finish(chunk, FinishReason::Cancelled);
+            return Poll::Ready(Some(Ok(chunk)));
+        }
+        match upstream.as_mut().poll_next(cx) {
+            Poll::Pending => Poll::Pending,
+            Poll::Ready(Some(Err(error))) => Poll::Ready(Some(Err(error))),
+            Poll::Ready(Some(Ok(mut chunk))) => {
+                Poll::Ready(Some(Ok(match this.process(&mut chunk) {
+                    Some(reason) => this.finish(chunk, reason),
+                    None => StopChunk {
+                        chunk,
+                        finish_reason: None,
+                    },
+                })))
+            }
+            // Ended without a final chunk
+            Poll::Ready(None) => {
+                this.cancel();
+                Pin::new(this).poll_next(cx)
+            }
+        }
+    }
+}
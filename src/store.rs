@@ -0,0 +1,480 @@
+//! Offline inspection of the model directory of a local Ollama server.
+//!
+//! A [`ModelStore`] reads the files the server keeps in `OLLAMA_MODELS`
+//! without talking to it, so it also works while the server is down:
+//!
+//! ```text
+//! manifests/registry.ollama.ai/library/llama3.2/latest
+//! blobs/sha256-<hex>
+//! ```
+//!
+//! Each manifest lists the blobs (layers) a model is made of, and a model's
+//! config blob holds the details reported by `/api/tags`. Blobs are shared
+//! between models that have layers in common, such as tags of the same model
+//! or models created `FROM` another.
+
+use std::collections::{BTreeMap, HashSet};
+use std::fs::{self, File};
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::config;
+use crate::error::OllamaError;
+use crate::models::{ModelDetails, ModelInfo};
+use crate::openai_compat::rfc3339_from_unix;
+
+/// Registry of models pulled without a host, e.g. `llama3.2`.
+pub const DEFAULT_REGISTRY: &str = "registry.ollama.ai";
+
+/// Namespace of models pulled without one.
+pub const DEFAULT_NAMESPACE: &str = "library";
+
+/// A model manifest.
+#[derive(Serialize, Deserialize, Debug, Default, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct Manifest {
+    pub schema_version: u32,
+    #[serde(default)]
+    pub media_type: String,
+    pub config: Layer,
+    #[serde(default)]
+    pub layers: Vec<Layer>,
+}
+
+/// A blob referenced by a manifest.
+#[derive(Serialize, Deserialize, Debug, Default, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct Layer {
+    pub media_type: String,
+    /// `sha256:<hex>`.
+    pub digest: String,
+    pub size: u64,
+}
+
+impl Manifest {
+    /// The config blob followed by the layers.
+    pub fn blobs(&self) -> impl Iterator<Item = &Layer> {
+        std::iter::once(&self.config).chain(&self.layers)
+    }
+
+    /// Total size of the blobs, as reported by `/api/tags`.
+    pub fn size(&self) -> u64 {
+        self.blobs().map(|layer| layer.size).sum()
+    }
+}
+
+/// A model found in the store.
+#[derive(Debug, Clone)]
+pub struct StoredModel {
+    /// The name as shown by `ollama list`, e.g. `llama3.2:latest` or
+    /// `example.com/team/model:v1`.
+    pub name: String,
+    pub manifest: Manifest,
+    pub manifest_path: PathBuf,
+    /// SHA-256 of the manifest file, as reported by `/api/tags`.
+    pub digest: String,
+    /// Modification time of the manifest, in Unix seconds.
+    pub modified: u64,
+}
+
+/// A blob file in the store.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Blob {
+    /// `sha256:<hex>`.
+    pub digest: String,
+    pub path: PathBuf,
+    pub size: u64,
+}
+
+/// A manifest that could not be read or parsed. It is left out of the
+/// models.
+#[derive(Debug, Clone)]
+pub struct InvalidManifest {
+    pub path: PathBuf,
+    pub error: String,
+}
+
+/// Disk used by one model.
+#[derive(Debug, Clone, Default)]
+pub struct ModelUsage {
+    pub name: String,
+    /// Bytes of all blobs of the model.
+    pub size: u64,
+    /// Bytes of blobs no other model uses, i.e. freed by removing the model.
+    pub unique: u64,
+    /// Bytes of blobs shared with other models.
+    pub shared: u64,
+}
+
+/// A blob used by more than one model.
+#[derive(Debug, Clone)]
+pub struct SharedBlob {
+    pub digest: String,
+    pub size: u64,
+    pub models: Vec<String>,
+}
+
+/// Disk usage of a store.
+#[derive(Debug, Clone, Default)]
+pub struct DiskUsage {
+    pub models: Vec<ModelUsage>,
+    pub shared: Vec<SharedBlob>,
+    /// Blobs no manifest references.
+    pub orphaned: Vec<Blob>,
+    /// Bytes of all blob files, counting shared blobs once.
+    pub total: u64,
+    /// Manifests skipped because they could not be read or parsed. Blobs
+    /// only they reference show up as orphaned.
+    pub invalid_manifests: Vec<InvalidManifest>,
+}
+
+/// Result of checking a blob against its digest.
+#[derive(Debug, Clone, PartialEq)]
+pub enum BlobStatus {
+    Ok,
+    Missing,
+    SizeMismatch { expected: u64, actual: u64 },
+    DigestMismatch { actual: String },
+}
+
+/// A blob checked by [`ModelStore::verify`].
+#[derive(Debug, Clone)]
+pub struct BlobCheck {
+    pub digest: String,
+    /// Models that reference the blob.
+    pub models: Vec<String>,
+    pub status: BlobStatus,
+}
+
+/// The subset of a config blob used for [`ModelDetails`].
+#[derive(Deserialize, Default)]
+#[serde(default)]
+struct ModelConfig {
+    model_format: String,
+    model_family: String,
+    model_families: Option<Vec<String>>,
+    model_type: String,
+    file_type: String,
+}
+
+/// A model directory of a local Ollama server.
+#[derive(Debug, Clone)]
+pub struct ModelStore {
+    root: PathBuf,
+}
+
+impl ModelStore {
+    /// Opens the model directory at `root`. Nothing is read until a method is
+    /// called.
+    pub fn open(root: impl Into<PathBuf>) -> Self {
+        ModelStore { root: root.into() }
+    }
+
+    /// Opens the directory in `OLLAMA_MODELS`, or `~/.ollama/models`.
+    pub fn from_env() -> Self {
+        ModelStore::open(config::models_dir_from_env())
+    }
+
+    /// Returns the model directory.
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// Returns the path of the blob with `digest` (`sha256:<hex>`).
+    pub fn blob_path(&self, digest: &str) -> PathBuf {
+        self.root.join("blobs").join(digest.replacen(':', "-", 1))
+    }
+
+    /// Reads every manifest, sorted by name. Manifests that cannot be read
+    /// or parsed are skipped; [`invalid_manifests`](Self::invalid_manifests)
+    /// lists them.
+    pub fn models(&self) -> Result<Vec<StoredModel>, OllamaError> {
+        Ok(self.scan()?.0)
+    }
+
+    /// Lists the manifests skipped by [`models`](Self::models), sorted by
+    /// path.
+    pub fn invalid_manifests(&self) -> Result<Vec<InvalidManifest>, OllamaError> {
+        Ok(self.scan()?.1)
+    }
+
+    fn scan(&self) -> Result<(Vec<StoredModel>, Vec<InvalidManifest>), OllamaError> {
+        let dir = self.root.join("manifests");
+        let mut paths = Vec::new();
+        if dir.is_dir() {
+            collect_files(&dir, &mut paths)?;
+        }
+        paths.sort();
+
+        let mut models = Vec::new();
+        let mut invalid = Vec::new();
+        for path in paths {
+            let Some(name) = model_name(&dir, &path) else {
+                continue;
+            };
+            let parsed = fs::read(&path)
+                .map_err(|e| e.to_string())
+                .and_then(|contents| {
+                    let manifest: Manifest =
+                        serde_json::from_slice(&contents).map_err(|e| e.to_string())?;
+                    Ok((manifest, contents))
+                });
+            let (manifest, contents) = match parsed {
+                Ok(parsed) => parsed,
+                Err(error) => {
+                    invalid.push(InvalidManifest { path, error });
+                    continue;
+                }
+            };
+            let modified = fs::metadata(&path)
+                .and_then(|metadata| metadata.modified())
+                .ok()
+                .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+                .map_or(0, |duration| duration.as_secs());
+            models.push(StoredModel {
+                name,
+                manifest,
+                manifest_path: path,
+                digest: format!("{:x}", Sha256::digest(&contents)),
+                modified,
+            });
+        }
+        models.sort_by(|a, b| a.name.cmp(&b.name));
+        Ok((models, invalid))
+    }
+
+    /// Lists the models as `/api/tags` would. Details are empty for models
+    /// whose config blob is missing or unreadable.
+    pub fn list_models(&self) -> Result<Vec<ModelInfo>, OllamaError> {
+        Ok(self
+            .models()?
+            .into_iter()
+            .map(|model| ModelInfo {
+                details: self.details(&model.manifest),
+                size: model.manifest.size(),
+                modified_at: rfc3339_from_unix(model.modified),
+                digest: model.digest,
+                name: model.name,
+            })
+            .collect())
+    }
+
+    fn details(&self, manifest: &Manifest) -> ModelDetails {
+        let config: ModelConfig = fs::read(self.blob_path(&manifest.config.digest))
+            .ok()
+            .and_then(|contents| serde_json::from_slice(&contents).ok())
+            .unwrap_or_default();
+        ModelDetails {
+            format: config.model_format,
+            family: config.model_family,
+            families: config.model_families,
+            parameter_size: config.model_type,
+            quantization_level: config.file_type,
+        }
+    }
+
+    /// Lists the blob files, sorted by digest. Partial downloads are skipped.
+    pub fn blobs(&self) -> Result<Vec<Blob>, OllamaError> {
+        let dir = self.root.join("blobs");
+        if !dir.is_dir() {
+            return Ok(Vec::new());
+        }
+        let mut blobs = Vec::new();
+        for entry in fs::read_dir(&dir).map_err(|e| io_error(&dir, e))? {
+            let entry = entry.map_err(|e| io_error(&dir, e))?;
+            let file_name = entry.file_name();
+            let Some(hex) = file_name
+                .to_str()
+                .and_then(|name| name.strip_prefix("sha256-"))
+                .filter(|hex| hex.len() == 64 && hex.bytes().all(|b| b.is_ascii_hexdigit()))
+            else {
+                continue;
+            };
+            let metadata = entry.metadata().map_err(|e| io_error(&entry.path(), e))?;
+            blobs.push(Blob {
+                digest: format!("sha256:{}", hex),
+                path: entry.path(),
+                size: metadata.len(),
+            });
+        }
+        blobs.sort_by(|a, b| a.digest.cmp(&b.digest));
+        Ok(blobs)
+    }
+
+    /// Computes per-model and shared disk usage. Blobs missing from disk
+    /// count with the size in their manifest.
+    pub fn disk_usage(&self) -> Result<DiskUsage, OllamaError> {
+        let (models, invalid_manifests) = self.scan()?;
+        let blobs = self.blobs()?;
+        let sizes: BTreeMap<&str, u64> = blobs
+            .iter()
+            .map(|blob| (blob.digest.as_str(), blob.size))
+            .collect();
+        let users = blob_users(&models);
+
+        let usage = models
+            .iter()
+            .map(|model| {
+                let mut usage = ModelUsage {
+                    name: model.name.clone(),
+                    ..Default::default()
+                };
+                let mut seen = HashSet::new();
+                for layer in model.manifest.blobs() {
+                    if !seen.insert(layer.digest.as_str()) {
+                        continue;
+                    }
+                    let size = sizes
+                        .get(layer.digest.as_str())
+                        .copied()
+                        .unwrap_or(layer.size);
+                    usage.size += size;
+                    match users[layer.digest.as_str()].len() {
+                        1 => usage.unique += size,
+                        _ => usage.shared += size,
+                    }
+                }
+                usage
+            })
+            .collect();
+        let shared = users
+            .iter()
+            .filter(|(_, models)| models.len() > 1)
+            .map(|(digest, models)| SharedBlob {
+                digest: digest.to_string(),
+                size: sizes.get(digest).copied().unwrap_or_default(),
+                models: models.clone(),
+            })
+            .collect();
+
+        Ok(DiskUsage {
+            models: usage,
+            shared,
+            total: blobs.iter().map(|blob| blob.size).sum(),
+            orphaned: blobs
+                .into_iter()
+                .filter(|blob| !users.contains_key(blob.digest.as_str()))
+                .collect(),
+            invalid_manifests,
+        })
+    }
+
+    /// Lists the blobs no manifest references, e.g. left behind by removed
+    /// models.
+    pub fn orphaned_blobs(&self) -> Result<Vec<Blob>, OllamaError> {
+        Ok(self.disk_usage()?.orphaned)
+    }
+
+    /// Checks every blob referenced by a manifest against its size and
+    /// SHA-256 digest. Reads every blob in full.
+    pub fn verify(&self) -> Result<Vec<BlobCheck>, OllamaError> {
+        let models = self.models()?;
+        let mut sizes = BTreeMap::new();
+        for layer in models.iter().flat_map(|model| model.manifest.blobs()) {
+            sizes.insert(layer.digest.as_str(), layer.size);
+        }
+        blob_users(&models)
+            .into_iter()
+            .map(|(digest, models)| {
+                Ok(BlobCheck {
+                    status: self.verify_blob(digest, Some(sizes[digest]))?,
+                    digest: digest.to_string(),
+                    models,
+                })
+            })
+            .collect()
+    }
+
+    /// Checks the blob with `digest` (`sha256:<hex>`), and its size if
+    /// `expected_size` is given.
+    pub fn verify_blob(
+        &self,
+        digest: &str,
+        expected_size: Option<u64>,
+    ) -> Result<BlobStatus, OllamaError> {
+        let path = self.blob_path(digest);
+        let mut file = match File::open(&path) {
+            Ok(file) => file,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(BlobStatus::Missing),
+            Err(e) => return Err(io_error(&path, e)),
+        };
+        let actual_size = file.metadata().map_err(|e| io_error(&path, e))?.len();
+        if let Some(expected) = expected_size.filter(|&expected| expected != actual_size) {
+            return Ok(BlobStatus::SizeMismatch {
+                expected,
+                actual: actual_size,
+            });
+        }
+
+        let mut hasher = Sha256::new();
+        let mut buffer = vec![0; 1 << 20];
+        loop {
+            match file.read(&mut buffer).map_err(|e| io_error(&path, e))? {
+                0 => break,
+                read => hasher.update(&buffer[..read]),
+            }
+        }
+        let actual = format!("sha256:{:x}", hasher.finalize());
+        if actual == digest {
+            Ok(BlobStatus::Ok)
+        } else {
+            Ok(BlobStatus::DigestMismatch { actual })
+        }
+    }
+}
+
+/// Maps each referenced digest to the models that use it.
+fn blob_users(models: &[StoredModel]) -> BTreeMap<&str, Vec<String>> {
+    let mut users: BTreeMap<&str, Vec<String>> = BTreeMap::new();
+    for model in models {
+        for layer in model.manifest.blobs() {
+            let names = users.entry(layer.digest.as_str()).or_default();
+            if names.last() != Some(&model.name) {
+                names.push(model.name.clone());
+            }
+        }
+    }
+    users
+}
+
+fn collect_files(dir: &Path, files: &mut Vec<PathBuf>) -> Result<(), OllamaError> {
+    for entry in fs::read_dir(dir).map_err(|e| io_error(dir, e))? {
+        let path = entry.map_err(|e| io_error(dir, e))?.path();
+        if path.is_dir() {
+            collect_files(&path, files)?;
+        } else {
+            files.push(path);
+        }
+    }
+    Ok(())
+}
+
+/// Builds the name of the manifest at `manifests/<host>/<namespace>/<model>/<tag>`,
+/// leaving out the default registry and namespace.
+fn model_name(manifests: &Path, path: &Path) -> Option<String> {
+    let parts: Vec<&str> = path
+        .strip_prefix(manifests)
+        .ok()?
+        .iter()
+        .map(|part| part.to_str())
+        .collect::<Option<_>>()?;
+    let [host, namespace, model, tag] = parts[..] else {
+        return None;
+    };
+    if parts.iter().any(|part| part.starts_with('.')) {
+        return None;
+    }
+    Some(match (host, namespace) {
+        (DEFAULT_REGISTRY, DEFAULT_NAMESPACE) => format!("{}:{}", model, tag),
+        (DEFAULT_REGISTRY, _) => format!("{}/{}:{}", namespace, model, tag),
+        _ => format!("{}/{}/{}:{}", host, namespace, model, tag),
+    })
+}
+
+fn io_error(path: &Path, error: std::io::Error) -> OllamaError {
+    OllamaError::Store(format!("{}: {}", path.display(), error))
+}
@@ -0,0 +1,420 @@
+//! `tracing` instrumentation for client calls.
+//!
+//! Every [`OllamaClient`](crate::OllamaClient) method runs in an `INFO` span
+//! named `ollama` whose fields follow the OpenTelemetry GenAI semantic
+//! conventions, so a `tracing-opentelemetry` layer exports them as-is:
+//!
+//! | Field | Value |
+//! |---|---|
+//! | `otel.name` | `{operation} {model}`, e.g. `chat llama3.2` |
+//! | `gen_ai.operation.name` | `chat`, `text_completion`, `embeddings`, or the method name for model management |
+//! | `gen_ai.request.model`, `gen_ai.response.model` | Requested and reported model |
+//! | `gen_ai.request.temperature`, `top_p`, `max_tokens`, `stop_sequences` | Request options |
+//! | `gen_ai.usage.input_tokens`, `gen_ai.usage.output_tokens` | `prompt_eval_count` and `eval_count` |
+//! | `gen_ai.response.finish_reasons` | `done_reason` of the final chunk, e.g. `stop` or `length` |
+//! | `server.address`, `server.port`, `url.path`, `http.request.method`, `http.response.status_code` | HTTP details |
+//! | `ollama.response.*_duration`, `ollama.response.time_to_first_token` | Durations in seconds |
+//! | `error.type`, `otel.status_code` | Set when the call fails |
+//!
+//! Streaming calls keep their span open until the stream is dropped.
+//!
+//! Prompts and completions are recorded in `gen_ai.input.messages` and
+//! `gen_ai.output.messages` only after opting in with
+//! [`OllamaClient::with_content_capture`](crate::OllamaClient::with_content_capture),
+//! since they may contain sensitive data.
+//!
+//! With the `metrics` feature the same calls are also counted; see
+//! [`crate::meter`].
+
+use std::collections::HashMap;
+use std::future::Future;
+use std::task::Poll;
+use std::time::{Duration, Instant};
+
+use futures::{stream, Stream};
+use serde_json::json;
+use tracing::field::Empty;
+use tracing::{Instrument, Span};
+
+use crate::error::OllamaError;
+use crate::models::*;
+
+/// The span and metric labels of one client call.
+#[derive(Clone)]
+pub(crate) struct Call {
+    pub(crate) span: Span,
+    // Metric labels
+    #[cfg_attr(not(feature = "metrics"), allow(dead_code))]
+    endpoint: &'static str,
+    #[cfg_attr(not(feature = "metrics"), allow(dead_code))]
+    model: Option<String>,
+    /// Whether the call counts as a request in the metrics.
+    #[cfg_attr(not(feature = "metrics"), allow(dead_code))]
+    metered: bool,
+    start: Instant,
+}
+
+impl Call {
+    /// Opens the span for a call to `endpoint`. Endpoints with path
+    /// parameters are given as templates, e.g. `/v1/models/{model}`.
+    pub(crate) fn new(
+        base_url: &str,
+        operation: &'static str,
+        endpoint: &'static str,
+        model: Option<&str>,
+    ) -> Self {
+        let name = match model {
+            Some(model) => format!("{} {}", operation, model),
+            None => operation.to_string(),
+        };
+        let url = reqwest::Url::parse(base_url).ok();
+        let span = tracing::info_span!(
+            "ollama",
+            otel.name = name,
+            otel.kind = "client",
+            otel.status_code = Empty,
+            gen_ai.system = "ollama",
+            gen_ai.operation.name = operation,
+            gen_ai.request.model = model,
+            gen_ai.request.temperature = Empty,
+            gen_ai.request.top_p = Empty,
+            gen_ai.request.max_tokens = Empty,
+            gen_ai.request.stop_sequences = Empty,
+            gen_ai.response.model = Empty,
+            gen_ai.response.finish_reasons = Empty,
+            gen_ai.usage.input_tokens = Empty,
+            gen_ai.usage.output_tokens = Empty,
+            gen_ai.input.messages = Empty,
+            gen_ai.output.messages = Empty,
+            server.address = url.as_ref().and_then(|url| url.host_str()),
+            server.port = url.as_ref().and_then(|url| url.port_or_known_default()),
+            url.path = endpoint,
+            http.request.method = Empty,
+            http.response.status_code = Empty,
+            ollama.request.num_ctx = Empty,
+            ollama.response.total_duration = Empty,
+            ollama.response.load_duration = Empty,
+            ollama.response.prompt_eval_duration = Empty,
+            ollama.response.eval_duration = Empty,
+            ollama.response.time_to_first_token = Empty,
+            error.type = Empty,
+        );
+        Call {
+            span,
+            endpoint,
+            model: model.map(str::to_string),
+            metered: true,
+            start: Instant::now(),
+        }
+    }
+
+    /// Keeps the span but records no request metrics, for methods that only
+    /// group other calls which are measured themselves.
+    pub(crate) fn unmetered(mut self) -> Self {
+        self.metered = false;
+        self
+    }
+
+    pub(crate) fn record_options(&self, options: Option<&GenerateOptions>) {
+        let Some(options) = options else {
+            return;
+        };
+        let span = &self.span;
+        span.record("gen_ai.request.temperature", options.temperature);
+        span.record("gen_ai.request.top_p", options.top_p);
+        span.record("gen_ai.request.max_tokens", options.token_limit());
+        if let Some(stop) = &options.stop {
+            span.record("gen_ai.request.stop_sequences", json!(stop).to_string());
+        }
+        span.record("ollama.request.num_ctx", options.num_ctx);
+    }
+
+    /// Records a message list as JSON, e.g. `[{"role": "user", "content": "..."}]`.
+    pub(crate) fn record_messages(&self, field: &str, messages: serde_json::Value) {
+        self.span.record(field, messages.to_string());
+    }
+
+    pub(crate) fn record_response_model(&self, model: &str) {
+        self.span.record("gen_ai.response.model", model);
+    }
+
+    pub(crate) fn record_usage(&self, input_tokens: Option<u32>, output_tokens: Option<u32>) {
+        self.span.record("gen_ai.usage.input_tokens", input_tokens);
+        self.span
+            .record("gen_ai.usage.output_tokens", output_tokens);
+        #[cfg(feature = "metrics")]
+        crate::meter::record_tokens(self.endpoint, self.model(), input_tokens, output_tokens);
+    }
+
+    fn record_stats(&self, stats: ChunkStats<'_>) {
+        let seconds = |nanos: Option<u64>| nanos.map(|n| Duration::from_nanos(n).as_secs_f64());
+        let span = &self.span;
+        self.record_response_model(stats.model);
+        self.record_usage(
+            stats.generation.prompt_eval_count,
+            stats.generation.eval_count,
+        );
+        if let Some(reason) = &stats.generation.done_reason {
+            span.record("gen_ai.response.finish_reasons", reason.as_str());
+        }
+        span.record(
+            "ollama.response.total_duration",
+            seconds(stats.generation.total_duration),
+        );
+        span.record(
+            "ollama.response.load_duration",
+            seconds(stats.generation.load_duration),
+        );
+        span.record(
+            "ollama.response.prompt_eval_duration",
+            seconds(stats.generation.prompt_eval_duration),
+        );
+        span.record(
+            "ollama.response.eval_duration",
+            seconds(stats.generation.eval_duration),
+        );
+    }
+
+    fn record_first_token(&self) {
+        let elapsed = self.start.elapsed();
+        self.span
+            .record("ollama.response.time_to_first_token", elapsed.as_secs_f64());
+        #[cfg(feature = "metrics")]
+        crate::meter::record_first_token(self.endpoint, self.model(), elapsed);
+    }
+
+    fn record_error(&self, error: &OllamaError) {
+        self.span.record("otel.status_code", "ERROR");
+        self.span.record("error.type", error_kind(error));
+    }
+
+    /// Records the end of the call. `outcome` is `success`, `cancelled` or an
+    /// [`error_kind`].
+    fn finish(&self, outcome: &'static str) {
+        #[cfg(feature = "metrics")]
+        if self.metered {
+            crate::meter::record_request(
+                self.endpoint,
+                self.model(),
+                outcome,
+                self.start.elapsed(),
+            );
+        }
+        #[cfg(not(feature = "metrics"))]
+        let _ = outcome;
+    }
+
+    #[cfg(feature = "metrics")]
+    fn model(&self) -> &str {
+        self.model.as_deref().unwrap_or_default()
+    }
+}
+
+/// A short, stable name for the kind of `error`, used for `error.type` and
+/// metric labels.
+pub(crate) fn error_kind(error: &OllamaError) -> &'static str {
+    match error {
+        OllamaError::RequestFailed(_) => "request_failed",
+        OllamaError::ApiError(_) => "api_error",
+        OllamaError::InvalidResponseFormat(_) | OllamaError::InvalidResponse(_) => {
+            "invalid_response"
+        }
+        OllamaError::Timeout => "timeout",
+        OllamaError::Overloaded(_) => "overloaded",
+        OllamaError::Cassette(_) => "cassette",
+        OllamaError::DimensionMismatch(..) => "dimension_mismatch",
+        OllamaError::Cache(_) => "cache",
+        OllamaError::Modelfile(_) => "modelfile",
+        OllamaError::Unauthorized(_) => "unauthorized",
+        OllamaError::Auth(_) => "auth",
+        OllamaError::Rejected(_) => "rejected",
+        OllamaError::Batch(_) => "batch",
+        OllamaError::Store(_) => "store",
+        OllamaError::Gguf(_) => "gguf",
+        OllamaError::Template(_) => "template",
+    }
+}
+
+/// Runs a complete call inside its span and records the outcome.
+pub(crate) async fn traced<T>(
+    call: &Call,
+    future: impl Future<Output = Result<T, OllamaError>>,
+) -> Result<T, OllamaError> {
+    let result = open_stream(call, future).await;
+    if result.is_ok() {
+        call.finish("success");
+    }
+    result
+}
+
+/// Runs the request that opens a stream inside the call's span. Only a
+/// failure ends the call here; otherwise [`traced_stream`] records the
+/// outcome when the stream ends.
+pub(crate) async fn open_stream<T>(
+    call: &Call,
+    future: impl Future<Output = Result<T, OllamaError>>,
+) -> Result<T, OllamaError> {
+    let result = future.instrument(call.span.clone()).await;
+    if let Err(error) = &result {
+        call.record_error(error);
+        call.finish(error_kind(error));
+    }
+    result
+}
+
+/// Statistics reported on the final chunk of a generation.
+pub(crate) struct ChunkStats<'a> {
+    pub model: &'a str,
+    pub generation: GenerationStats,
+}
+
+/// A chunk of a streamed response. The defaults suit chunks that carry
+/// neither text nor statistics.
+pub(crate) trait Chunk {
+    /// Generated text in this chunk.
+    fn text(&self) -> &str {
+        ""
+    }
+
+    fn role(&self) -> &str {
+        "assistant"
+    }
+
+    /// Statistics, present on the final chunk of a generation.
+    fn chunk_stats(&self) -> Option<ChunkStats<'_>> {
+        None
+    }
+
+    /// Layer digest and bytes transferred so far, for pull and push progress.
+    fn transfer(&self) -> Option<(&str, u64)> {
+        None
+    }
+}
+
+impl Chunk for GenerateResponse {
+    fn text(&self) -> &str {
+        &self.response
+    }
+
+    fn chunk_stats(&self) -> Option<ChunkStats<'_>> {
+        Some(ChunkStats {
+            model: &self.model,
+            generation: self.stats()?,
+        })
+    }
+}
+
+impl Chunk for ChatResponse {
+    fn text(&self) -> &str {
+        &self.message.content
+    }
+
+    fn role(&self) -> &str {
+        &self.message.role
+    }
+
+    fn chunk_stats(&self) -> Option<ChunkStats<'_>> {
+        Some(ChunkStats {
+            model: &self.model,
+            generation: self.stats()?,
+        })
+    }
+}
+
+impl Chunk for PullResponse {
+    fn transfer(&self) -> Option<(&str, u64)> {
+        Some((self.digest.as_deref()?, self.completed?))
+    }
+}
+
+impl Chunk for PushResponse {
+    fn transfer(&self) -> Option<(&str, u64)> {
+        Some((self.digest.as_deref()?, self.completed?))
+    }
+}
+
+impl Chunk for CreateResponse {}
+
+/// State of a traced stream. Dropping it ends the call.
+struct StreamState {
+    call: Call,
+    capture: bool,
+    first_token: bool,
+    output: String,
+    transferred: HashMap<String, u64>,
+    outcome: Option<&'static str>,
+}
+
+impl StreamState {
+    fn observe<T: Chunk>(&mut self, chunk: &T) {
+        if self.first_token && !chunk.text().is_empty() {
+            self.first_token = false;
+            self.call.record_first_token();
+        }
+        if self.capture {
+            self.output.push_str(chunk.text());
+        }
+        if let Some(stats) = chunk.chunk_stats() {
+            self.call.record_stats(stats);
+            if self.capture {
+                let message = json!([{ "role": chunk.role(), "content": self.output }]);
+                self.call.record_messages("gen_ai.output.messages", message);
+            }
+        }
+        if let Some((digest, completed)) = chunk.transfer() {
+            let previous = self.transferred.entry(digest.to_string()).or_default();
+            let delta = completed.saturating_sub(*previous);
+            *previous = (*previous).max(completed);
+            #[cfg(feature = "metrics")]
+            crate::meter::record_transfer(self.call.endpoint, self.call.model(), delta);
+            #[cfg(not(feature = "metrics"))]
+            let _ = delta;
+        }
+    }
+}
+
+impl Drop for StreamState {
+    fn drop(&mut self) {
+        self.call.finish(self.outcome.unwrap_or("cancelled"));
+        #[cfg(feature = "metrics")]
+        crate::meter::record_stream_closed(self.call.endpoint);
+    }
+}
+
+/// Polls `stream` inside the call's span, recording the time to first token,
+/// the final chunk's statistics, transfer progress and, with `capture`, the
+/// completion text. The call ends when the stream does or is dropped.
+pub(crate) fn traced_stream<T: Chunk>(
+    stream: impl Stream<Item = Result<T, OllamaError>>,
+    call: Call,
+    capture: bool,
+) -> impl Stream<Item = Result<T, OllamaError>> {
+    #[cfg(feature = "metrics")]
+    crate::meter::record_stream_opened(call.endpoint);
+    let mut stream = Box::pin(stream);
+    let mut state = StreamState {
+        call,
+        capture,
+        first_token: true,
+        output: String::new(),
+        transferred: HashMap::new(),
+        outcome: None,
+    };
+    stream::poll_fn(move |cx| {
+        let span = state.call.span.clone();
+        let _entered = span.enter();
+        let item = stream.as_mut().poll_next(cx);
+        match &item {
+            Poll::Ready(Some(Ok(chunk))) => state.observe(chunk),
+            Poll::Ready(Some(Err(error))) => {
+                state.call.record_error(error);
+                state.outcome.get_or_insert(error_kind(error));
+            }
+            Poll::Ready(None) => {
+                state.outcome.get_or_insert("success");
+            }
+            Poll::Pending => {}
+        }
+        item
+    })
+}
@@ -0,0 +1,1067 @@
+//! Local rendering of Ollama prompt templates.
+//!
+//! Modelfile `TEMPLATE`s are written in a subset of Go's `text/template`.
+//! [`Template`] parses that subset and renders a conversation the same way the
+//! server does, so the exact prompt can be inspected, or sent with
+//! [`GenerateRequest::raw`](crate::models::GenerateRequest::raw) set to skip
+//! the server-side template.
+//!
+//! Supported are `{{- -}}` trim markers, comments, fields such as `.System`,
+//! `.Prompt`, `.Response`, `.Messages` and `.Tools`, variables, `if`/`else`,
+//! `range` with `break` and `continue`, `with`, pipelines and the functions
+//! `and`, `or`, `not`, `eq`, `ne`, `lt`, `le`, `gt`, `ge`, `len`, `index`,
+//! `slice`, `print`, `json` and `currentDate`. Field names are also looked up
+//! in snake case, so `.ToolCalls` reads the `tool_calls` of a message.
+//!
+//! ```no_run
+//! use ollama_oxide::models::{ChatMessage, GenerateRequest};
+//! use ollama_oxide::template::Template;
+//! use ollama_oxide::OllamaClient;
+//!
+//! # async fn example(client: OllamaClient, messages: Vec<ChatMessage>) -> Result<(), ollama_oxide::error::OllamaError> {
+//! let show = client.show_model("llama3.2").await?;
+//! let template = Template::parse(show.template.as_deref().unwrap_or("{{ .Prompt }}"))?;
+//! let request = GenerateRequest {
+//!     model: "llama3.2".to_string(),
+//!     prompt: template.render(&messages, &[])?,
+//!     raw: Some(true),
+//!     ..Default::default()
+//! };
+//! # Ok(())
+//! # }
+//! ```
+
+use serde_json::{json, Value};
+
+use crate::error::OllamaError;
+use crate::models::ChatMessage;
+
+const FUNCTIONS: &[&str] = &[
+    "and",
+    "or",
+    "not",
+    "eq",
+    "ne",
+    "lt",
+    "le",
+    "gt",
+    "ge",
+    "len",
+    "index",
+    "slice",
+    "print",
+    "json",
+    "currentDate",
+];
+
+static NULL: Value = Value::Null;
+
+/// A parsed prompt template.
+#[derive(Debug, Clone)]
+pub struct Template {
+    nodes: Vec<Node>,
+}
+
+#[derive(Debug, Clone)]
+enum Node {
+    Text(String),
+    Action(Pipeline),
+    If(Branch),
+    With(Branch),
+    Range(Branch),
+    Break,
+    Continue,
+}
+
+#[derive(Debug, Clone)]
+struct Branch {
+    pipeline: Pipeline,
+    list: Vec<Node>,
+    otherwise: Vec<Node>,
+}
+
+#[derive(Debug, Clone)]
+struct Pipeline {
+    /// Variables declared (`:=`) or assigned (`=`) by the pipeline.
+    variables: Vec<String>,
+    assign: bool,
+    commands: Vec<Vec<Arg>>,
+}
+
+#[derive(Debug, Clone)]
+enum Arg {
+    Field(Vec<String>),
+    Variable(String, Vec<String>),
+    Function(String),
+    Literal(Value),
+    Nested(Pipeline, Vec<String>),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Field(Vec<String>),
+    Variable(String, Vec<String>),
+    /// A field chain directly after a closing parenthesis.
+    Chain(Vec<String>),
+    Identifier(String),
+    Literal(Value),
+    Declare,
+    Assign,
+    Pipe,
+    Comma,
+    Open,
+    Close,
+}
+
+enum Segment {
+    Text(String),
+    Action(Vec<Token>),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Flow {
+    Normal,
+    Break,
+    Continue,
+}
+
+impl Template {
+    /// Parses a template, e.g. the `template` of a
+    /// [`ShowModelResponse`](crate::models::ShowModelResponse).
+    pub fn parse(source: &str) -> Result<Self, OllamaError> {
+        let mut parser = Parser {
+            segments: segments(source)?.into_iter(),
+            ranges: 0,
+        };
+        match parser.list()? {
+            (nodes, Stop::Eof) => Ok(Self { nodes }),
+            (_, Stop::End) => Err(error("unexpected {{end}}")),
+            (_, Stop::Else(_)) => Err(error("unexpected {{else}}")),
+        }
+    }
+
+    /// Whether the template iterates `.Messages` itself. Templates that do
+    /// not are rendered once per exchange with `.Prompt` and `.Response`.
+    pub fn uses_messages(&self) -> bool {
+        let mut found = false;
+        visit_fields(&self.nodes, &mut |path| {
+            found |= path
+                .iter()
+                .any(|name| name.eq_ignore_ascii_case("messages"));
+        });
+        found
+    }
+
+    /// Renders the template with arbitrary data as `.`.
+    pub fn execute(&self, data: &Value) -> Result<String, OllamaError> {
+        let mut exec = Exec {
+            root: data,
+            variables: Vec::new(),
+            out: String::new(),
+        };
+        exec.walk(&self.nodes, data)?;
+        Ok(exec.out)
+    }
+
+    /// Renders a conversation into the raw prompt the server would build,
+    /// ending where the model's response begins.
+    ///
+    /// System messages are joined into `.System` and consecutive messages
+    /// from the same role are merged, except for tool results.
+    pub fn render(&self, messages: &[ChatMessage], tools: &[Value]) -> Result<String, OllamaError> {
+        let (system, messages) = collate(messages);
+        if self.uses_messages() {
+            return self.execute(&json!({
+                "System": system,
+                "Messages": serde_json::to_value(&messages)?,
+                "Tools": tools,
+                "Response": "",
+            }));
+        }
+
+        let mut out = String::new();
+        let (mut system, mut prompt, mut response) = (String::new(), String::new(), String::new());
+        for message in &messages {
+            let flush = match message.role.as_str() {
+                "system" => !prompt.is_empty() || !response.is_empty(),
+                "user" => !response.is_empty(),
+                _ => false,
+            };
+            if flush {
+                out += &self.execute(&legacy(&system, &prompt, &response))?;
+                system.clear();
+                prompt.clear();
+                response.clear();
+            }
+            match message.role.as_str() {
+                "system" => system = message.content.clone(),
+                "user" => prompt = message.content.clone(),
+                "assistant" => response = message.content.clone(),
+                _ => {}
+            }
+        }
+
+        // The last exchange stops at `.Response`, leaving the turn open.
+        let last = Self {
+            nodes: cut_list(&self.nodes, &mut false),
+        };
+        out += &last.execute(&legacy(&system, &prompt, &response))?;
+        Ok(out)
+    }
+}
+
+fn error(message: impl Into<String>) -> OllamaError {
+    OllamaError::Template(message.into())
+}
+
+fn legacy(system: &str, prompt: &str, response: &str) -> Value {
+    json!({ "System": system, "Prompt": prompt, "Response": response })
+}
+
+fn collate(messages: &[ChatMessage]) -> (String, Vec<ChatMessage>) {
+    let mut system = Vec::new();
+    let mut collated: Vec<ChatMessage> = Vec::new();
+    for message in messages {
+        if message.role == "system" {
+            system.push(message.content.as_str());
+        }
+        match collated.last_mut() {
+            Some(last) if last.role == message.role && message.role != "tool" => {
+                last.content.push_str("\n\n");
+                last.content.push_str(&message.content);
+            }
+            _ => collated.push(message.clone()),
+        }
+    }
+    (system.join("\n\n"), collated)
+}
+
+/// Drops everything after the first printed `.Response`, like the server
+/// does for the final exchange of a legacy template.
+fn cut_list(nodes: &[Node], cut: &mut bool) -> Vec<Node> {
+    let mut kept = Vec::new();
+    for node in nodes {
+        if *cut {
+            break;
+        }
+        let node = match node {
+            Node::Action(pipeline) => match cut_pipeline(pipeline, cut) {
+                Some(pipeline) => Node::Action(pipeline),
+                None => continue,
+            },
+            Node::If(branch) => Node::If(cut_branch(branch, cut)),
+            Node::With(branch) => Node::With(cut_branch(branch, cut)),
+            Node::Range(branch) => Node::Range(cut_branch(branch, cut)),
+            node => node.clone(),
+        };
+        kept.push(node);
+    }
+    kept
+}
+
+fn cut_branch(branch: &Branch, cut: &mut bool) -> Branch {
+    Branch {
+        pipeline: branch.pipeline.clone(),
+        list: cut_list(&branch.list, cut),
+        otherwise: cut_list(&branch.otherwise, cut),
+    }
+}
+
+fn cut_pipeline(pipeline: &Pipeline, cut: &mut bool) -> Option<Pipeline> {
+    let mut commands = Vec::new();
+    for command in &pipeline.commands {
+        let mut args = Vec::new();
+        for arg in command {
+            match arg {
+                Arg::Field(path) if path.iter().any(|name| name == "Response") => {
+                    *cut = true;
+                    args.push(arg.clone());
+                }
+                _ if *cut => {}
+                Arg::Nested(nested, path) => {
+                    if let Some(nested) = cut_pipeline(nested, cut) {
+                        args.push(Arg::Nested(nested, path.clone()));
+                    }
+                }
+                arg => args.push(arg.clone()),
+            }
+        }
+        if args.is_empty() {
+            return None;
+        }
+        commands.push(args);
+    }
+    Some(Pipeline {
+        commands,
+        ..pipeline.clone()
+    })
+}
+
+fn visit_fields(nodes: &[Node], visit: &mut impl FnMut(&[String])) {
+    fn fields(pipeline: &Pipeline, visit: &mut impl FnMut(&[String])) {
+        for arg in pipeline.commands.iter().flatten() {
+            match arg {
+                Arg::Field(path) => visit(path),
+                Arg::Nested(nested, _) => fields(nested, visit),
+                _ => {}
+            }
+        }
+    }
+    for node in nodes {
+        match node {
+            Node::Action(pipeline) => fields(pipeline, visit),
+            Node::If(branch) | Node::With(branch) | Node::Range(branch) => {
+                fields(&branch.pipeline, visit);
+                visit_fields(&branch.list, visit);
+                visit_fields(&branch.otherwise, visit);
+            }
+            _ => {}
+        }
+    }
+}
+
+fn is_space(c: char) -> bool {
+    matches!(c, ' ' | '\t' | '\r' | '\n')
+}
+
+/// Splits the source into text and lexed actions, applying trim markers and
+/// dropping comments.
+fn segments(source: &str) -> Result<Vec<Segment>, OllamaError> {
+    let mut segments = Vec::new();
+    let mut rest = source;
+    let mut trim_next = false;
+    while let Some(start) = rest.find("{{") {
+        let mut text = &rest[..start];
+        if trim_next {
+            text = text.trim_start_matches(is_space);
+        }
+        let mut action = &rest[start + 2..];
+        if action.starts_with('-') && action[1..].starts_with(is_space) {
+            text = text.trim_end_matches(is_space);
+            action = &action[1..];
+        }
+        if !text.is_empty() {
+            segments.push(Segment::Text(text.to_string()));
+        }
+
+        let end = action_end(action)?;
+        let mut body = &action[..end];
+        rest = &action[end + 2..];
+        trim_next = body.ends_with('-') && body[..body.len() - 1].ends_with(is_space);
+        if trim_next {
+            body = &body[..body.len() - 1];
+        }
+        let body = body.trim_matches(is_space);
+        if body.starts_with("/*") {
+            if !body.ends_with("*/") || body.len() < 4 {
+                return Err(error("unclosed comment"));
+            }
+            continue;
+        }
+        segments.push(Segment::Action(lex(body)?));
+    }
+    let text = match trim_next {
+        true => rest.trim_start_matches(is_space),
+        false => rest,
+    };
+    if !text.is_empty() {
+        segments.push(Segment::Text(text.to_string()));
+    }
+    Ok(segments)
+}
+
+/// Finds the closing `}}` of an action, skipping over quoted strings.
+fn action_end(action: &str) -> Result<usize, OllamaError> {
+    let mut quote = None;
+    let mut escaped = false;
+    for (i, c) in action.char_indices() {
+        match quote {
+            Some(_) if escaped => escaped = false,
+            Some('"') if c == '\\' => escaped = true,
+            Some(q) if c == q => quote = None,
+            Some(_) => {}
+            None if c == '"' || c == '`' => quote = Some(c),
+            None if action[i..].starts_with("}}") => return Ok(i),
+            None => {}
+        }
+    }
+    Err(error("unclosed action"))
+}
+
+fn path(text: &str) -> Result<Vec<String>, OllamaError> {
+    match text {
+        "" => Ok(Vec::new()),
+        _ => text[1..]
+            .split('.')
+            .map(|name| match name.is_empty() {
+                true => Err(error(format!("bad field name in {:?}", text))),
+                false => Ok(name.to_string()),
+            })
+            .collect(),
+    }
+}
+
+fn lex(action: &str) -> Result<Vec<Token>, OllamaError> {
+    let mut tokens = Vec::new();
+    let mut chars = action.char_indices().peekable();
+    let word_end = |start: usize| {
+        action[start + 1..]
+            .find(|c: char| !(c.is_alphanumeric() || c == '_' || c == '.'))
+            .map_or(action.len(), |end| start + 1 + end)
+    };
+    while let Some(&(i, c)) = chars.peek() {
+        let end = match c {
+            c if is_space(c) => i + 1,
+            '(' => {
+                tokens.push(Token::Open);
+                i + 1
+            }
+            ')' => {
+                tokens.push(Token::Close);
+                i + 1
+            }
+            '|' => {
+                tokens.push(Token::Pipe);
+                i + 1
+            }
+            ',' => {
+                tokens.push(Token::Comma);
+                i + 1
+            }
+            '=' => {
+                tokens.push(Token::Assign);
+                i + 1
+            }
+            ':' if action[i..].starts_with(":=") => {
+                tokens.push(Token::Declare);
+                i + 2
+            }
+            '.' if action[..i].ends_with(')') => {
+                let end = word_end(i);
+                tokens.push(Token::Chain(path(&action[i..end])?));
+                end
+            }
+            '.' => {
+                let end = word_end(i);
+                let text = &action[i..end];
+                tokens.push(Token::Field(match text {
+                    "." => Vec::new(),
+                    _ => path(text)?,
+                }));
+                end
+            }
+            '$' => {
+                let end = word_end(i);
+                let text = &action[i..end];
+                let (name, fields) = text.split_at(text.find('.').unwrap_or(text.len()));
+                tokens.push(Token::Variable(name.to_string(), path(fields)?));
+                end
+            }
+            '"' => {
+                let (value, end) = quoted(action, i)?;
+                tokens.push(Token::Literal(Value::String(value)));
+                end
+            }
+            '`' => {
+                let close = action[i + 1..]
+                    .find('`')
+                    .ok_or_else(|| error("unterminated raw string"))?;
+                let value = &action[i + 1..i + 1 + close];
+                tokens.push(Token::Literal(Value::String(value.to_string())));
+                i + close + 2
+            }
+            c if c.is_ascii_digit() || c == '-' => {
+                let end = action[i + 1..]
+                    .find(|c: char| !(c.is_ascii_digit() || c == '.'))
+                    .map_or(action.len(), |end| i + 1 + end);
+                let text = &action[i..end];
+                let number = match text.parse::<i64>() {
+                    Ok(number) => Value::from(number),
+                    Err(_) => text
+                        .parse::<f64>()
+                        .ok()
+                        .and_then(serde_json::Number::from_f64)
+                        .map(Value::Number)
+                        .ok_or_else(|| error(format!("bad number syntax: {:?}", text)))?,
+                };
+                tokens.push(Token::Literal(number));
+                end
+            }
+            c if c.is_alphabetic() || c == '_' => {
+                let end = action[i..]
+                    .find(|c: char| !(c.is_alphanumeric() || c == '_'))
+                    .map_or(action.len(), |end| i + end);
+                tokens.push(match &action[i..end] {
+                    "true" => Token::Literal(Value::Bool(true)),
+                    "false" => Token::Literal(Value::Bool(false)),
+                    "nil" => Token::Literal(Value::Null),
+                    word => Token::Identifier(word.to_string()),
+                });
+                end
+            }
+            c => return Err(error(format!("unexpected {:?} in action", c))),
+        };
+        while chars.next_if(|&(i, _)| i < end).is_some() {}
+    }
+    Ok(tokens)
+}
+
+/// Reads a double-quoted string starting at `start`, returning its value and
+/// the index after the closing quote.
+fn quoted(action: &str, start: usize) -> Result<(String, usize), OllamaError> {
+    let mut value = String::new();
+    let mut chars = action[start + 1..].char_indices();
+    while let Some((i, c)) = chars.next() {
+        match c {
+            '"' => return Ok((value, start + 2 + i)),
+            '\\' => value.push(match chars.next().map(|(_, c)| c) {
+                Some('n') => '\n',
+                Some('t') => '\t',
+                Some('r') => '\r',
+                Some(c @ ('\\' | '"' | '\'')) => c,
+                c => return Err(error(format!("unknown escape sequence: {:?}", c))),
+            }),
+            c => value.push(c),
+        }
+    }
+    Err(error("unterminated quoted string"))
+}
+
+enum Stop {
+    End,
+    Else(Vec<Token>),
+    Eof,
+}
+
+struct Parser {
+    segments: std::vec::IntoIter<Segment>,
+    ranges: usize,
+}
+
+impl Parser {
+    fn list(&mut self) -> Result<(Vec<Node>, Stop), OllamaError> {
+        let mut nodes = Vec::new();
+        while let Some(segment) = self.segments.next() {
+            let tokens = match segment {
+                Segment::Text(text) => {
+                    nodes.push(Node::Text(text));
+                    continue;
+                }
+                Segment::Action(tokens) => tokens,
+            };
+            let keyword = match tokens.first() {
+                Some(Token::Identifier(word)) => word.as_str(),
+                _ => "",
+            };
+            let node = match keyword {
+                "end" if tokens.len() == 1 => return Ok((nodes, Stop::End)),
+                "else" => return Ok((nodes, Stop::Else(tokens[1..].to_vec()))),
+                "if" => Node::If(self.branch(&tokens[1..], "if")?),
+                "with" => Node::With(self.branch(&tokens[1..], "with")?),
+                "range" => {
+                    self.ranges += 1;
+                    let branch = self.branch(&tokens[1..], "range");
+                    self.ranges -= 1;
+                    Node::Range(branch?)
+                }
+                "break" | "continue" if self.ranges == 0 => {
+                    return Err(error(format!("{{{{{}}}}} outside {{{{range}}}}", keyword)))
+                }
+                "break" if tokens.len() == 1 => Node::Break,
+                "continue" if tokens.len() == 1 => Node::Continue,
+                "end" | "break" | "continue" => {
+                    return Err(error(format!(
+                        "unexpected arguments to {{{{{}}}}}",
+                        keyword
+                    )))
+                }
+                "define" | "template" | "block" => {
+                    return Err(error(format!("{{{{{}}}}} is not supported", keyword)))
+                }
+                _ => Node::Action(parse_pipeline(&tokens, 1)?),
+            };
+            nodes.push(node);
+        }
+        Ok((nodes, Stop::Eof))
+    }
+
+    fn branch(&mut self, tokens: &[Token], keyword: &str) -> Result<Branch, OllamaError> {
+        let declarations = if keyword == "range" { 2 } else { 1 };
+        let pipeline = parse_pipeline(tokens, declarations)?;
+        let (list, stop) = self.list()?;
+        let otherwise = match stop {
+            Stop::End => Vec::new(),
+            Stop::Eof => {
+                return Err(error(format!(
+                    "missing {{{{end}}}} for {{{{{}}}}}",
+                    keyword
+                )))
+            }
+            Stop::Else(rest) if rest.is_empty() => match self.list()? {
+                (otherwise, Stop::End) => otherwise,
+                (_, Stop::Else(_)) => return Err(error("expected {{end}}; found {{else}}")),
+                (_, Stop::Eof) => {
+                    return Err(error(format!(
+                        "missing {{{{end}}}} for {{{{{}}}}}",
+                        keyword
+                    )))
+                }
+            },
+            Stop::Else(rest) => match rest.first() {
+                Some(Token::Identifier(word)) if word == keyword && keyword != "range" => {
+                    let branch = self.branch(&rest[1..], keyword)?;
+                    vec![match keyword {
+                        "if" => Node::If(branch),
+                        _ => Node::With(branch),
+                    }]
+                }
+                _ => {
+                    return Err(error(format!(
+                        "unexpected {{{{else}}}} in {{{{{}}}}}",
+                        keyword
+                    )))
+                }
+            },
+        };
+        Ok(Branch {
+            pipeline,
+            list,
+            otherwise,
+        })
+    }
+}
+
+/// Parses a whole action, allowing up to `declarations` variables before
+/// `:=` or `=`.
+fn parse_pipeline(tokens: &[Token], declarations: usize) -> Result<Pipeline, OllamaError> {
+    let mut variables = Vec::new();
+    let mut assign = false;
+    let mut pos = 0;
+    while let Some(Token::Variable(name, fields)) = tokens.get(pos) {
+        match tokens.get(pos + 1) {
+            Some(Token::Declare | Token::Assign | Token::Comma) if fields.is_empty() => {}
+            _ => break,
+        }
+        variables.push(name.clone());
+        pos += 2;
+        match tokens[pos - 1] {
+            Token::Comma => continue,
+            Token::Assign => assign = true,
+            _ => {}
+        }
+        break;
+    }
+    if variables.len() > declarations {
+        return Err(error("too many declarations"));
+    }
+    if !variables.is_empty() && !matches!(tokens[pos - 1], Token::Declare | Token::Assign) {
+        return Err(error("expected := or = after variables"));
+    }
+    let (commands, end) = parse_commands(tokens, pos)?;
+    match tokens.get(end) {
+        None => Ok(Pipeline {
+            variables,
+            assign,
+            commands,
+        }),
+        Some(_) => Err(error("unexpected )")),
+    }
+}
+
+/// Parses commands until the end of the action or an unmatched `)`.
+fn parse_commands(tokens: &[Token], mut pos: usize) -> Result<(Vec<Vec<Arg>>, usize), OllamaError> {
+    let mut commands = Vec::new();
+    let mut command = Vec::new();
+    loop {
+        let arg = match tokens.get(pos) {
+            None | Some(Token::Close) => break,
+            Some(Token::Pipe) => {
+                if command.is_empty() {
+                    return Err(error("missing command before |"));
+                }
+                commands.push(std::mem::take(&mut command));
+                pos += 1;
+                continue;
+            }
+            Some(Token::Field(path)) => Arg::Field(path.clone()),
+            Some(Token::Variable(name, path)) => Arg::Variable(name.clone(), path.clone()),
+            Some(Token::Literal(value)) => Arg::Literal(value.clone()),
+            Some(Token::Identifier(name)) if FUNCTIONS.contains(&name.as_str()) => {
+                Arg::Function(name.clone())
+            }
+            Some(Token::Identifier(name)) => {
+                return Err(error(format!("function {:?} not defined", name)))
+            }
+            Some(Token::Open) => {
+                let (commands, end) = parse_commands(tokens, pos + 1)?;
+                if tokens.get(end) != Some(&Token::Close) {
+                    return Err(error("unclosed left paren"));
+                }
+                pos = end;
+                let path = match tokens.get(end + 1) {
+                    Some(Token::Chain(path)) => {
+                        pos += 1;
+                        path.clone()
+                    }
+                    _ => Vec::new(),
+                };
+                let nested = Pipeline {
+                    variables: Vec::new(),
+                    assign: false,
+                    commands,
+                };
+                Arg::Nested(nested, path)
+            }
+            Some(token) => return Err(error(format!("unexpected {:?} in command", token))),
+        };
+        command.push(arg);
+        pos += 1;
+    }
+    if command.is_empty() {
+        return Err(error("missing value for command"));
+    }
+    commands.push(command);
+    Ok((commands, pos))
+}
+
+struct Exec<'a> {
+    root: &'a Value,
+    variables: Vec<(String, Value)>,
+    out: String,
+}
+
+impl Exec<'_> {
+    fn walk(&mut self, nodes: &[Node], dot: &Value) -> Result<Flow, OllamaError> {
+        for node in nodes {
+            let flow = match node {
+                Node::Text(text) => {
+                    self.out.push_str(text);
+                    Flow::Normal
+                }
+                Node::Action(pipeline) => {
+                    let value = self.pipeline(pipeline, dot)?;
+                    if pipeline.variables.is_empty() {
+                        self.out.push_str(&display(&value));
+                    }
+                    Flow::Normal
+                }
+                Node::If(branch) | Node::With(branch) => {
+                    let mark = self.variables.len();
+                    let value = self.pipeline(&branch.pipeline, dot)?;
+                    let flow = match (truth(&value), node) {
+                        (true, Node::With(_)) => self.walk(&branch.list, &value)?,
+                        (true, _) => self.walk(&branch.list, dot)?,
+                        (false, _) => self.walk(&branch.otherwise, dot)?,
+                    };
+                    self.variables.truncate(mark);
+                    flow
+                }
+                Node::Range(branch) => self.range(branch, dot)?,
+                Node::Break => Flow::Break,
+                Node::Continue => Flow::Continue,
+            };
+            if flow != Flow::Normal {
+                return Ok(flow);
+            }
+        }
+        Ok(Flow::Normal)
+    }
+
+    fn range(&mut self, branch: &Branch, dot: &Value) -> Result<Flow, OllamaError> {
+        let mark = self.variables.len();
+        let value = self.commands(&branch.pipeline.commands, dot)?;
+        let items: Vec<(Value, Value)> = match value {
+            Value::Null => Vec::new(),
+            Value::Array(items) => items
+                .into_iter()
+                .enumerate()
+                .map(|(i, item)| (Value::from(i), item))
+                .collect(),
+            Value::Object(map) => {
+                let mut items: Vec<_> = map
+                    .into_iter()
+                    .map(|(k, v)| (Value::String(k), v))
+                    .collect();
+                items.sort_by(|a, b| a.0.as_str().cmp(&b.0.as_str()));
+                items
+            }
+            Value::Number(n) if n.as_u64().is_some() => (0..n.as_u64().unwrap_or_default())
+                .map(|i| (Value::from(i), Value::from(i)))
+                .collect(),
+            value => {
+                return Err(error(format!(
+                    "range can't iterate over {}",
+                    display(&value)
+                )))
+            }
+        };
+        if items.is_empty() {
+            let flow = self.walk(&branch.otherwise, dot)?;
+            self.variables.truncate(mark);
+            return Ok(flow);
+        }
+        for (key, item) in items {
+            match branch.pipeline.variables.as_slice() {
+                [value] => self.variables.push((value.clone(), item.clone())),
+                [key_name, value] => {
+                    self.variables.push((key_name.clone(), key));
+                    self.variables.push((value.clone(), item.clone()));
+                }
+                _ => {}
+            }
+            let flow = self.walk(&branch.list, &item)?;
+            self.variables.truncate(mark);
+            if flow == Flow::Break {
+                break;
+            }
+        }
+        Ok(Flow::Normal)
+    }
+
+    fn pipeline(&mut self, pipeline: &Pipeline, dot: &Value) -> Result<Value, OllamaError> {
+        let value = self.commands(&pipeline.commands, dot)?;
+        if let [name] = pipeline.variables.as_slice() {
+            if pipeline.assign {
+                let variable = self
+                    .variables
+                    .iter_mut()
+                    .rev()
+                    .find(|(n, _)| n == name)
+                    .ok_or_else(|| error(format!("undefined variable: {}", name)))?;
+                variable.1 = value.clone();
+            } else {
+                self.variables.push((name.clone(), value.clone()));
+            }
+        }
+        Ok(value)
+    }
+
+    fn commands(&mut self, commands: &[Vec<Arg>], dot: &Value) -> Result<Value, OllamaError> {
+        let mut piped = None;
+        for command in commands {
+            piped = Some(self.command(command, dot, piped)?);
+        }
+        Ok(piped.unwrap_or(Value::Null))
+    }
+
+    fn command(
+        &mut self,
+        command: &[Arg],
+        dot: &Value,
+        piped: Option<Value>,
+    ) -> Result<Value, OllamaError> {
+        match command {
+            [Arg::Function(name), args @ ..] => {
+                let mut values = args
+                    .iter()
+                    .map(|arg| self.arg(arg, dot))
+                    .collect::<Result<Vec<_>, _>>()?;
+                values.extend(piped);
+                call(name, values)
+            }
+            [arg] if piped.is_none() => self.arg(arg, dot),
+            _ => Err(error("can't give argument to non-function")),
+        }
+    }
+
+    fn arg(&mut self, arg: &Arg, dot: &Value) -> Result<Value, OllamaError> {
+        Ok(match arg {
+            Arg::Field(path) => lookup(dot, path),
+            Arg::Variable(name, path) if name == "$" => lookup(self.root, path),
+            Arg::Variable(name, path) => {
+                let (_, value) = self
+                    .variables
+                    .iter()
+                    .rev()
+                    .find(|(n, _)| n == name)
+                    .ok_or_else(|| error(format!("undefined variable: {}", name)))?;
+                lookup(value, path)
+            }
+            Arg::Function(name) => call(name, Vec::new())?,
+            Arg::Literal(value) => value.clone(),
+            Arg::Nested(pipeline, path) => lookup(&self.pipeline(pipeline, dot)?, path),
+        })
+    }
+}
+
+/// Looks up a field chain, trying each name as is and in snake case.
+fn lookup(value: &Value, path: &[String]) -> Value {
+    let mut current = value;
+    for name in path {
+        current = match current {
+            Value::Object(map) => map
+                .get(name)
+                .or_else(|| map.get(&snake_case(name)))
+                .unwrap_or(&NULL),
+            _ => &NULL,
+        };
+    }
+    current.clone()
+}
+
+fn snake_case(name: &str) -> String {
+    let mut out = String::new();
+    for (i, c) in name.chars().enumerate() {
+        if c.is_uppercase() && i > 0 {
+            out.push('_');
+        }
+        out.extend(c.to_lowercase());
+    }
+    out
+}
+
+fn truth(value: &Value) -> bool {
+    match value {
+        Value::Null => false,
+        Value::Bool(b) => *b,
+        Value::Number(n) => n.as_f64() != Some(0.0),
+        Value::String(s) => !s.is_empty(),
+        Value::Array(items) => !items.is_empty(),
+        Value::Object(map) => !map.is_empty(),
+    }
+}
+
+/// Prints a value the way the server does; objects and arrays are printed as
+/// JSON, like tools and tool call arguments.
+fn display(value: &Value) -> String {
+    match value {
+        Value::Null => "<no value>".to_string(),
+        Value::String(s) => s.clone(),
+        value => value.to_string(),
+    }
+}
+
+fn equal(a: &Value, b: &Value) -> bool {
+    match (a, b) {
+        (Value::Number(a), Value::Number(b)) => a.as_f64() == b.as_f64(),
+        (a, b) => a == b,
+    }
+}
+
+fn compare(a: &Value, b: &Value) -> Result<std::cmp::Ordering, OllamaError> {
+    match (a, b) {
+        (Value::Number(a), Value::Number(b)) => a.as_f64().partial_cmp(&b.as_f64()),
+        (Value::String(a), Value::String(b)) => Some(a.cmp(b)),
+        _ => None,
+    }
+    .ok_or_else(|| error("incompatible types for comparison"))
+}
+
+fn index(value: &Value) -> Result<usize, OllamaError> {
+    value
+        .as_u64()
+        .map(|i| i as usize)
+        .ok_or_else(|| error(format!("cannot index with {}", display(value))))
+}
+
+fn call(name: &str, mut args: Vec<Value>) -> Result<Value, OllamaError> {
+    let arity = |min: usize, max: usize| match args.len() {
+        n if n < min || n > max => Err(error(format!(
+            "wrong number of args for {}: got {}",
+            name,
+            args.len()
+        ))),
+        _ => Ok(()),
+    };
+    match name {
+        "and" | "or" => {
+            arity(1, usize::MAX)?;
+            let last = args.len() - 1;
+            let position = args.iter().position(|v| truth(v) == (name == "or"));
+            Ok(args.swap_remove(position.unwrap_or(last)))
+        }
+        "not" => {
+            arity(1, 1)?;
+            Ok(Value::Bool(!truth(&args[0])))
+        }
+        "eq" => {
+            arity(2, usize::MAX)?;
+            Ok(Value::Bool(args[1..].iter().any(|v| equal(&args[0], v))))
+        }
+        "ne" => {
+            arity(2, 2)?;
+            Ok(Value::Bool(!equal(&args[0], &args[1])))
+        }
+        "lt" | "le" | "gt" | "ge" => {
+            arity(2, 2)?;
+            let ordering = compare(&args[0], &args[1])?;
+            Ok(Value::Bool(match name {
+                "lt" => ordering.is_lt(),
+                "le" => ordering.is_le(),
+                "gt" => ordering.is_gt(),
+                _ => ordering.is_ge(),
+            }))
+        }
+        "len" => {
+            arity(1, 1)?;
+            match &args[0] {
+                Value::String(s) => Ok(Value::from(s.len())),
+                Value::Array(items) => Ok(Value::from(items.len())),
+                Value::Object(map) => Ok(Value::from(map.len())),
+                value => Err(error(format!("len of {}", display(value)))),
+            }
+        }
+        "index" => {
+            arity(1, usize::MAX)?;
+            let mut current = args.remove(0);
+            for key in &args {
+                current = match (&current, key) {
+                    (Value::Array(items), key) => items
+                        .get(index(key)?)
+                        .cloned()
+                        .ok_or_else(|| error(format!("index out of range: {}", key)))?,
+                    (Value::Object(map), Value::String(key)) => {
+                        map.get(key).cloned().unwrap_or_default()
+                    }
+                    (value, _) => {
+                        return Err(error(format!("can't index item of {}", display(value))))
+                    }
+                };
+            }
+            Ok(current)
+        }
+        "slice" => {
+            arity(1, 3)?;
+            let bounds = args[1..].iter().map(index).collect::<Result<Vec<_>, _>>()?;
+            let (start, end) = (bounds.first().copied().unwrap_or(0), bounds.get(1).copied());
+            let out_of_range = || error(format!("slice index out of range: {:?}", bounds));
+            match &args[0] {
+                Value::Array(items) => {
+                    let end = end.unwrap_or(items.len());
+                    let items = items.get(start..end).ok_or_else(out_of_range)?;
+                    Ok(Value::Array(items.to_vec()))
+                }
+                Value::String(s) => {
+                    let end = end.unwrap_or(s.len());
+                    let s = s.get(start..end).ok_or_else(out_of_range)?;
+                    Ok(Value::String(s.to_string()))
+                }
+                value => Err(error(format!("can't slice item of {}", display(value)))),
+            }
+        }
+        "print" => {
+            let mut out = String::new();
+            for (i, value) in args.iter().enumerate() {
+                if i > 0 && !value.is_string() && !args[i - 1].is_string() {
+                    out.push(' ');
+                }
+                out.push_str(&display(value));
+            }
+            Ok(Value::String(out))
+        }
+        "json" => {
+            arity(1, 1)?;
+            Ok(Value::String(args[0].to_string()))
+        }
+        "currentDate" => {
+            arity(0, 0)?;
+            let now = std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_secs();
+            let date = crate::openai_compat::rfc3339_from_unix(now);
+            Ok(Value::String(date[..10].to_string()))
+        }
+        _ => Err(error(format!("function {:?} not defined", name))),
+    }
+}
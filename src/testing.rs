@@ -0,0 +1,757 @@
+//! In-process stub Ollama server for integration tests.
+//!
+//! [`StubServer`] listens on a random local port and answers the Ollama
+//! endpoints with canned responses. Each endpoint can be overridden with a
+//! [`StubResponse`], which controls the status, JSON, streamed NDJSON or
+//! server-sent events body, injected latency, chunk fragmentation and
+//! mid-stream failures.
+
+use std::collections::{HashMap, VecDeque};
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::Duration;
+
+use reqwest::StatusCode;
+use serde_json::{json, Value};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::task::JoinHandle;
+
+use crate::{lock, OllamaClient};
+
+/// A request received by the [`StubServer`].
+#[derive(Debug, Clone)]
+pub struct ReceivedRequest {
+    pub method: String,
+    pub path: String,
+    pub headers: Vec<(String, String)>,
+    pub body: Vec<u8>,
+}
+
+impl ReceivedRequest {
+    /// Parses the request body as JSON, returning `Value::Null` for empty bodies.
+    pub fn json(&self) -> Value {
+        serde_json::from_slice(&self.body).unwrap_or(Value::Null)
+    }
+
+    /// Returns the first header with the given name (case-insensitive).
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(key, _)| key.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+}
+
+#[derive(Debug, Clone)]
+enum StubBody {
+    Empty,
+    Text(String),
+    Json(Value),
+    NdJson(Vec<Value>),
+    Sse(Vec<Value>),
+}
+
+/// A scripted response served by the [`StubServer`].
+#[derive(Debug, Clone)]
+pub struct StubResponse {
+    status: u16,
+    body: StubBody,
+    headers: Vec<(String, String)>,
+    latency: Duration,
+    fragment_size: Option<usize>,
+    chunk_delay: Duration,
+    disconnect_after: Option<usize>,
+}
+
+impl StubResponse {
+    fn new(body: StubBody) -> Self {
+        StubResponse {
+            status: 200,
+            body,
+            headers: Vec::new(),
+            latency: Duration::ZERO,
+            fragment_size: None,
+            chunk_delay: Duration::ZERO,
+            disconnect_after: None,
+        }
+    }
+
+    /// A `200 OK` response with an empty body.
+    pub fn empty() -> Self {
+        Self::new(StubBody::Empty)
+    }
+
+    /// A `200 OK` response with a plain text body.
+    pub fn text(body: &str) -> Self {
+        Self::new(StubBody::Text(body.to_string()))
+    }
+
+    /// A `200 OK` response with a single JSON body.
+    pub fn json(body: Value) -> Self {
+        Self::new(StubBody::Json(body))
+    }
+
+    /// A `200 OK` streamed response with one JSON object per line.
+    pub fn ndjson(lines: Vec<Value>) -> Self {
+        Self::new(StubBody::NdJson(lines))
+    }
+
+    /// A `200 OK` server-sent events response with one `data:` event per
+    /// value, terminated by `data: [DONE]` as the OpenAI-compatible endpoints do.
+    pub fn sse(events: Vec<Value>) -> Self {
+        Self::new(StubBody::Sse(events))
+    }
+
+    /// An error response with an Ollama-style `{"error": ...}` body.
+    pub fn error(status: u16, message: &str) -> Self {
+        Self::json(json!({ "error": message })).with_status(status)
+    }
+
+    /// Sets the HTTP status code.
+    pub fn with_status(mut self, status: u16) -> Self {
+        self.status = status;
+        self
+    }
+
+    /// Adds a response header.
+    pub fn with_header(mut self, name: &str, value: &str) -> Self {
+        self.headers.push((name.to_string(), value.to_string()));
+        self
+    }
+
+    /// Waits before sending the response headers.
+    pub fn with_latency(mut self, latency: Duration) -> Self {
+        self.latency = latency;
+        self
+    }
+
+    /// Splits the streamed body into chunks of `size` bytes, ignoring line boundaries.
+    pub fn fragmented(mut self, size: usize) -> Self {
+        self.fragment_size = Some(size.max(1));
+        self
+    }
+
+    /// Waits between streamed chunks.
+    pub fn with_chunk_delay(mut self, delay: Duration) -> Self {
+        self.chunk_delay = delay;
+        self
+    }
+
+    /// Inserts an `{"error": ...}` line after `lines` lines of a streamed body.
+    /// Server-sent events get an OpenAI-style `{"error": {"message": ...}}`.
+    pub fn with_stream_error_after(mut self, lines: usize, message: &str) -> Self {
+        match &mut self.body {
+            StubBody::NdJson(body) => {
+                body.truncate(lines.min(body.len()));
+                body.push(json!({ "error": message }));
+            }
+            StubBody::Sse(body) => {
+                body.truncate(lines.min(body.len()));
+                body.push(json!({ "error": { "message": message, "type": "api_error" } }));
+            }
+            _ => {}
+        }
+        self
+    }
+
+    /// Closes the connection after `lines` lines of a streamed body, without
+    /// terminating the response.
+    pub fn disconnect_after(mut self, lines: usize) -> Self {
+        self.disconnect_after = Some(lines);
+        self
+    }
+}
+
+#[derive(Debug, Default)]
+struct StubState {
+    routes: HashMap<String, StubResponse>,
+    queued: HashMap<String, VecDeque<StubResponse>>,
+    requests: Vec<ReceivedRequest>,
+}
+
+/// In-process HTTP server implementing the Ollama API with canned responses.
+///
+/// The server runs on the current Tokio runtime and stops when dropped.
+pub struct StubServer {
+    addr: SocketAddr,
+    state: Arc<Mutex<StubState>>,
+    handle: JoinHandle<()>,
+}
+
+impl StubServer {
+    /// Starts a server on a random local port.
+    pub async fn start() -> std::io::Result<Self> {
+        let listener = TcpListener::bind("127.0.0.1:0").await?;
+        let addr = listener.local_addr()?;
+        let state = Arc::new(Mutex::new(StubState::default()));
+
+        let accept_state = state.clone();
+        let handle = tokio::spawn(async move {
+            while let Ok((socket, _)) = listener.accept().await {
+                let state = accept_state.clone();
+                tokio::spawn(async move {
+                    let _ = handle_connection(socket, state).await;
+                });
+            }
+        });
+
+        Ok(StubServer {
+            addr,
+            state,
+            handle,
+        })
+    }
+
+    /// Base URL of the server, e.g. `http://127.0.0.1:41234`.
+    pub fn url(&self) -> String {
+        format!("http://{}", self.addr)
+    }
+
+    /// Creates an [`OllamaClient`] pointed at this server.
+    pub fn client(&self) -> OllamaClient {
+        OllamaClient::new(&self.url())
+    }
+
+    /// Serves `response` for every request to `path` (e.g. `/api/generate`).
+    pub fn respond(&self, path: &str, response: StubResponse) -> &Self {
+        self.state().routes.insert(path.to_string(), response);
+        self
+    }
+
+    /// Serves `response` for the next request to `path`. One-shot responses
+    /// are used in FIFO order before falling back to [`StubServer::respond`]
+    /// overrides and the canned defaults.
+    pub fn respond_once(&self, path: &str, response: StubResponse) -> &Self {
+        self.state()
+            .queued
+            .entry(path.to_string())
+            .or_default()
+            .push_back(response);
+        self
+    }
+
+    /// Returns the requests received so far, in order.
+    pub fn requests(&self) -> Vec<ReceivedRequest> {
+        self.state().requests.clone()
+    }
+
+    fn state(&self) -> MutexGuard<'_, StubState> {
+        lock(&self.state)
+    }
+}
+
+impl Drop for StubServer {
+    fn drop(&mut self) {
+        self.handle.abort();
+    }
+}
+
+async fn handle_connection(
+    mut socket: TcpStream,
+    state: Arc<Mutex<StubState>>,
+) -> std::io::Result<()> {
+    socket.set_nodelay(true)?;
+    let Some(request) = read_request(&mut socket).await? else {
+        return Ok(());
+    };
+
+    let response = {
+        let mut state = lock(&state);
+        state.requests.push(request.clone());
+        let queued = state
+            .queued
+            .get_mut(&request.path)
+            .and_then(|queue| queue.pop_front());
+        queued
+            .or_else(|| state.routes.get(&request.path).cloned())
+            .unwrap_or_else(|| default_response(&request))
+    };
+
+    write_response(&mut socket, response).await
+}
+
+async fn read_request(socket: &mut TcpStream) -> std::io::Result<Option<ReceivedRequest>> {
+    let mut buffer = Vec::new();
+    let mut chunk = [0u8; 4096];
+    let header_end = loop {
+        if let Some(pos) = buffer.windows(4).position(|w| w == b"\r\n\r\n") {
+            break pos + 4;
+        }
+        let n = socket.read(&mut chunk).await?;
+        if n == 0 {
+            return Ok(None);
+        }
+        buffer.extend_from_slice(&chunk[..n]);
+    };
+
+    let head = String::from_utf8_lossy(&buffer[..header_end]).to_string();
+    let mut lines = head.split("\r\n");
+    let mut request_line = lines.next().unwrap_or_default().split_whitespace();
+    let method = request_line.next().unwrap_or_default().to_string();
+    let path = request_line.next().unwrap_or_default().to_string();
+    let headers: Vec<(String, String)> = lines
+        .filter_map(|line| line.split_once(':'))
+        .map(|(key, value)| (key.trim().to_string(), value.trim().to_string()))
+        .collect();
+
+    let content_length = headers
+        .iter()
+        .find(|(key, _)| key.eq_ignore_ascii_case("content-length"))
+        .and_then(|(_, value)| value.parse::<usize>().ok())
+        .unwrap_or(0);
+    let mut body = buffer[header_end..].to_vec();
+    while body.len() < content_length {
+        let n = socket.read(&mut chunk).await?;
+        if n == 0 {
+            break;
+        }
+        body.extend_from_slice(&chunk[..n]);
+    }
+    body.truncate(content_length);
+
+    Ok(Some(ReceivedRequest {
+        method,
+        path,
+        headers,
+        body,
+    }))
+}
+
+async fn write_response(socket: &mut TcpStream, response: StubResponse) -> std::io::Result<()> {
+    if !response.latency.is_zero() {
+        tokio::time::sleep(response.latency).await;
+    }
+
+    let reason = StatusCode::from_u16(response.status)
+        .ok()
+        .and_then(|status| status.canonical_reason())
+        .unwrap_or("Unknown");
+    let mut head = format!(
+        "HTTP/1.1 {} {}\r\nConnection: close\r\n",
+        response.status, reason
+    );
+    for (name, value) in &response.headers {
+        head.push_str(&format!("{}: {}\r\n", name, value));
+    }
+
+    let sse = matches!(response.body, StubBody::Sse(_));
+    match response.body {
+        StubBody::Empty => {
+            head.push_str("Content-Length: 0\r\n\r\n");
+            socket.write_all(head.as_bytes()).await?;
+        }
+        StubBody::Text(body) => {
+            head.push_str(&format!(
+                "Content-Type: text/plain; charset=utf-8\r\nContent-Length: {}\r\n\r\n",
+                body.len()
+            ));
+            socket.write_all(head.as_bytes()).await?;
+            socket.write_all(body.as_bytes()).await?;
+        }
+        StubBody::Json(value) => {
+            let body = value.to_string();
+            head.push_str(&format!(
+                "Content-Type: application/json; charset=utf-8\r\nContent-Length: {}\r\n\r\n",
+                body.len()
+            ));
+            socket.write_all(head.as_bytes()).await?;
+            socket.write_all(body.as_bytes()).await?;
+        }
+        StubBody::NdJson(lines) | StubBody::Sse(lines) => {
+            let content_type = if sse {
+                "text/event-stream"
+            } else {
+                "application/x-ndjson"
+            };
+            head.push_str(&format!(
+                "Content-Type: {}\r\nTransfer-Encoding: chunked\r\n\r\n",
+                content_type
+            ));
+            socket.write_all(head.as_bytes()).await?;
+            socket.flush().await?;
+
+            let disconnect = response.disconnect_after.is_some();
+            let mut lines: Vec<String> = lines
+                .iter()
+                .take(response.disconnect_after.unwrap_or(usize::MAX))
+                .map(|line| match sse {
+                    true => format!("data: {}\n\n", line),
+                    false => format!("{}\n", line),
+                })
+                .collect();
+            if sse && !disconnect {
+                lines.push("data: [DONE]\n\n".to_string());
+            }
+            let chunks: Vec<Vec<u8>> = match response.fragment_size {
+                Some(size) => lines
+                    .concat()
+                    .into_bytes()
+                    .chunks(size)
+                    .map(<[u8]>::to_vec)
+                    .collect(),
+                None => lines.into_iter().map(String::into_bytes).collect(),
+            };
+
+            for chunk in chunks {
+                socket
+                    .write_all(format!("{:x}\r\n", chunk.len()).as_bytes())
+                    .await?;
+                socket.write_all(&chunk).await?;
+                socket.write_all(b"\r\n").await?;
+                socket.flush().await?;
+                if !response.chunk_delay.is_zero() {
+                    tokio::time::sleep(response.chunk_delay).await;
+                }
+            }
+
+            if disconnect {
+                return Ok(());
+            }
+            socket.write_all(b"0\r\n\r\n").await?;
+        }
+    }
+
+    socket.flush().await?;
+    socket.shutdown().await
+}
+
+/// Canned responses for each Ollama endpoint.
+fn default_response(request: &ReceivedRequest) -> StubResponse {
+    let body = request.json();
+    let model = body
+        .get("model")
+        .or_else(|| body.get("name"))
+        .and_then(Value::as_str)
+        .unwrap_or("llama3.2:latest")
+        .to_string();
+
+    match request.path.as_str() {
+        "/api/tags" => StubResponse::json(json!({ "models": [sample_model(&model)] })),
+        "/api/show" => StubResponse::json(json!({
+            "modelfile": format!("FROM {}\n", model),
+            "parameters": "stop \"<|eot_id|>\"",
+            "template": SAMPLE_TEMPLATE,
+            "details": sample_details(),
+            "model_info": {
+                "general.architecture": "llama",
+                "general.parameter_count": 3212749888u64,
+                "llama.context_length": STUB_CONTEXT_LENGTH,
+                "llama.embedding_length": STUB_EMBEDDING_DIMENSIONS,
+            },
+            "capabilities": ["completion"],
+            "modified_at": SAMPLE_CREATED_AT,
+        })),
+        "/api/ps" => StubResponse::json(json!({
+            "models": [{
+                "name": model,
+                "model": model,
+                "size": 5137025024u64,
+                "digest": SAMPLE_DIGEST,
+                "details": sample_details(),
+                "expires_at": "2025-01-01T00:00:00Z",
+                "size_vram": 5137025024u64,
+            }]
+        })),
+        "/api/version" => StubResponse::json(json!({ "version": "0.5.7" })),
+        "/api/delete" | "/api/copy" => StubResponse::empty(),
+        "/api/pull" | "/api/push" => StubResponse::ndjson(vec![
+            json!({ "status": "pulling manifest" }),
+            json!({ "status": "downloading", "digest": SAMPLE_DIGEST, "total": 2048, "completed": 1024 }),
+            json!({ "status": "downloading", "digest": SAMPLE_DIGEST, "total": 2048, "completed": 2048 }),
+            json!({ "status": "success" }),
+        ]),
+        "/api/create" => StubResponse::ndjson(vec![
+            json!({ "status": "reading model metadata" }),
+            json!({ "status": "writing manifest" }),
+            json!({ "status": "success" }),
+        ]),
+        "/api/generate" => {
+            let mut lines: Vec<Value> = SAMPLE_COMPLETION
+                .iter()
+                .enumerate()
+                .map(|(i, token)| {
+                    let mut chunk = json!({
+                        "model": model,
+                        "created_at": SAMPLE_CREATED_AT,
+                        "response": token,
+                        "done": false,
+                    });
+                    if body["logprobs"] == true {
+                        chunk["logprobs"] = sample_logprobs(&body, i);
+                    }
+                    chunk
+                })
+                .collect();
+            let mut last = json!({
+                "model": model,
+                "created_at": SAMPLE_CREATED_AT,
+                "response": "",
+                "done": true,
+                "context": [1, 2, 3],
+            });
+            merge_stats(&mut last);
+            lines.push(last);
+            StubResponse::ndjson(lines)
+        }
+        "/api/chat" => {
+            let mut lines: Vec<Value> = SAMPLE_COMPLETION
+                .iter()
+                .enumerate()
+                .map(|(i, token)| {
+                    let mut chunk = json!({
+                        "model": model,
+                        "created_at": SAMPLE_CREATED_AT,
+                        "message": { "role": "assistant", "content": token },
+                        "done": false,
+                    });
+                    if body["logprobs"] == true {
+                        chunk["logprobs"] = sample_logprobs(&body, i);
+                    }
+                    chunk
+                })
+                .collect();
+            let mut last = json!({
+                "model": model,
+                "created_at": SAMPLE_CREATED_AT,
+                "message": { "role": "assistant", "content": "" },
+                "done": true,
+            });
+            merge_stats(&mut last);
+            lines.push(last);
+            StubResponse::ndjson(lines)
+        }
+        "/api/embed" => {
+            let inputs: Vec<String> = match body.get("input") {
+                Some(Value::Array(items)) => items
+                    .iter()
+                    .map(|item| item.as_str().unwrap_or_default().to_string())
+                    .collect(),
+                Some(Value::String(text)) => vec![text.clone()],
+                _ => Vec::new(),
+            };
+            StubResponse::json(json!({
+                "model": model,
+                "embeddings": inputs.iter().map(|text| stub_embedding(text)).collect::<Vec<_>>(),
+                "total_duration": 14143917,
+                "load_duration": 1019500,
+                "prompt_eval_count": inputs.len() * 8,
+            }))
+        }
+        "/api/embeddings" => {
+            let prompt = body
+                .get("prompt")
+                .and_then(Value::as_str)
+                .unwrap_or_default();
+            StubResponse::json(json!({ "embedding": stub_embedding(prompt) }))
+        }
+        "/v1/chat/completions" | "/v1/completions" => {
+            let chat = request.path == "/v1/chat/completions";
+            let (id, object) = match chat {
+                true => ("chatcmpl-stub", "chat.completion"),
+                false => ("cmpl-stub", "text_completion"),
+            };
+            let usage = json!({
+                "prompt_tokens": 26,
+                "completion_tokens": SAMPLE_COMPLETION.len(),
+                "total_tokens": 26 + SAMPLE_COMPLETION.len(),
+            });
+            let choice =
+                |content: &str, finish_reason: Option<&str>, stream: bool| match (chat, stream) {
+                    (true, false) => json!({
+                        "index": 0,
+                        "message": { "role": "assistant", "content": content },
+                        "finish_reason": finish_reason,
+                    }),
+                    (true, true) => json!({
+                        "index": 0,
+                        "delta": { "role": "assistant", "content": content },
+                        "finish_reason": finish_reason,
+                    }),
+                    (false, _) => json!({
+                        "index": 0,
+                        "text": content,
+                        "finish_reason": finish_reason,
+                    }),
+                };
+
+            if body.get("stream").and_then(Value::as_bool) == Some(true) {
+                let object = match chat {
+                    true => "chat.completion.chunk",
+                    false => "text_completion",
+                };
+                let mut events: Vec<Value> = SAMPLE_COMPLETION
+                    .iter()
+                    .map(|token| {
+                        json!({
+                            "id": id,
+                            "object": object,
+                            "created": SAMPLE_CREATED_UNIX,
+                            "model": model,
+                            "choices": [choice(token, None, true)],
+                        })
+                    })
+                    .collect();
+                events.push(json!({
+                    "id": id,
+                    "object": object,
+                    "created": SAMPLE_CREATED_UNIX,
+                    "model": model,
+                    "choices": [choice("", Some("stop"), true)],
+                }));
+                if body.pointer("/stream_options/include_usage") == Some(&Value::Bool(true)) {
+                    events.push(json!({
+                        "id": id,
+                        "object": object,
+                        "created": SAMPLE_CREATED_UNIX,
+                        "model": model,
+                        "choices": [],
+                        "usage": usage,
+                    }));
+                }
+                StubResponse::sse(events)
+            } else {
+                StubResponse::json(json!({
+                    "id": id,
+                    "object": object,
+                    "created": SAMPLE_CREATED_UNIX,
+                    "model": model,
+                    "system_fingerprint": "fp_ollama",
+                    "choices": [choice(&SAMPLE_COMPLETION.concat(), Some("stop"), false)],
+                    "usage": usage,
+                }))
+            }
+        }
+        "/v1/embeddings" => {
+            let inputs: Vec<String> = match body.get("input") {
+                Some(Value::Array(items)) => items
+                    .iter()
+                    .map(|item| item.as_str().unwrap_or_default().to_string())
+                    .collect(),
+                Some(Value::String(text)) => vec![text.clone()],
+                _ => Vec::new(),
+            };
+            StubResponse::json(json!({
+                "object": "list",
+                "data": inputs.iter().enumerate().map(|(index, text)| json!({
+                    "object": "embedding",
+                    "embedding": stub_embedding(text),
+                    "index": index,
+                })).collect::<Vec<_>>(),
+                "model": model,
+                "usage": { "prompt_tokens": inputs.len() * 8, "total_tokens": inputs.len() * 8 },
+            }))
+        }
+        "/v1/models" => StubResponse::json(json!({
+            "object": "list",
+            "data": [sample_openai_model("llama3.2:latest")],
+        })),
+        path if path.starts_with("/v1/models/") => {
+            StubResponse::json(sample_openai_model(&path["/v1/models/".len()..]))
+        }
+        // Unknown routes get Go's default plain text 404, like a real server
+        _ => StubResponse::text("404 page not found").with_status(404),
+    }
+}
+
+/// Tokens streamed by the canned `generate` and `chat` responses.
+pub const SAMPLE_COMPLETION: [&str; 4] = ["The", " sky", " is", " blue."];
+
+/// Log probabilities of [`SAMPLE_COMPLETION`], returned with `logprobs` set.
+/// The alternatives requested with `top_logprobs` are the token itself
+/// followed by `alt1`, `alt2`, ... at log probabilities -3, -4, ...
+pub const SAMPLE_LOGPROBS: [f64; 4] = [-0.5, -0.25, -0.125, -1.0];
+
+const SAMPLE_DIGEST: &str =
+    "sha256:a80c4f17acd55265feec403c7aef86be0c25983ab279d83f3bcd3abbcb5b8b72";
+const SAMPLE_CREATED_AT: &str = "2025-01-01T00:00:00Z";
+const SAMPLE_CREATED_UNIX: u64 = 1735689600;
+
+const SAMPLE_TEMPLATE: &str = "{{ if .System }}<|start_header_id|>system<|end_header_id|>\n\n{{ .System }}<|eot_id|>{{ end }}<|start_header_id|>user<|end_header_id|>\n\n{{ .Prompt }}<|eot_id|><|start_header_id|>assistant<|end_header_id|>\n\n{{ .Response }}<|eot_id|>";
+
+/// Context length reported by the canned `/api/show` response.
+pub const STUB_CONTEXT_LENGTH: usize = 4096;
+
+/// Dimension of the vectors returned by the canned embedding responses.
+pub const STUB_EMBEDDING_DIMENSIONS: usize = 8;
+
+/// Deterministic embedding used by the canned `/api/embed` response: a
+/// normalized histogram of the input bytes.
+pub fn stub_embedding(text: &str) -> Vec<f32> {
+    let mut vector = vec![0.0f32; STUB_EMBEDDING_DIMENSIONS];
+    for byte in text.bytes() {
+        vector[byte as usize % STUB_EMBEDDING_DIMENSIONS] += 1.0;
+    }
+    let norm = vector.iter().map(|x| x * x).sum::<f32>().sqrt();
+    if norm > 0.0 {
+        vector.iter_mut().for_each(|x| *x /= norm);
+    }
+    vector
+}
+
+fn sample_details() -> Value {
+    json!({
+        "format": "gguf",
+        "family": "llama",
+        "families": ["llama"],
+        "parameter_size": "3.2B",
+        "quantization_level": "Q4_K_M",
+    })
+}
+
+fn sample_model(name: &str) -> Value {
+    json!({
+        "name": name,
+        "size": 2019393189u64,
+        "modified_at": SAMPLE_CREATED_AT,
+        "digest": SAMPLE_DIGEST,
+        "details": sample_details(),
+    })
+}
+
+fn sample_openai_model(id: &str) -> Value {
+    json!({
+        "id": id,
+        "object": "model",
+        "created": SAMPLE_CREATED_UNIX,
+        "owned_by": "library",
+    })
+}
+
+fn sample_logprobs(body: &Value, index: usize) -> Value {
+    let token = SAMPLE_COMPLETION[index];
+    let logprob = SAMPLE_LOGPROBS[index];
+    let top: Vec<Value> = (0..body["top_logprobs"].as_u64().unwrap_or(0))
+        .map(|rank| match rank {
+            0 => json!({ "token": token, "logprob": logprob, "bytes": token.as_bytes() }),
+            _ => {
+                let alternative = format!("alt{}", rank);
+                json!({
+                    "token": alternative,
+                    "logprob": -2.0 - rank as f64,
+                    "bytes": alternative.as_bytes(),
+                })
+            }
+        })
+        .collect();
+    let mut logprobs = json!({ "token": token, "logprob": logprob, "bytes": token.as_bytes() });
+    if !top.is_empty() {
+        logprobs["top_logprobs"] = Value::Array(top);
+    }
+    json!([logprobs])
+}
+
+fn merge_stats(chunk: &mut Value) {
+    let stats = json!({
+        "done_reason": "stop",
+        "total_duration": 5589157167u64,
+        "load_duration": 3013701500u64,
+        "prompt_eval_count": 26,
+        "prompt_eval_duration": 342546000u64,
+        "eval_count": SAMPLE_COMPLETION.len(),
+        "eval_duration": 2232000000u64,
+    });
+    if let (Value::Object(chunk), Value::Object(stats)) = (chunk, stats) {
+        chunk.extend(stats);
+    }
+}
@@ -0,0 +1,127 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+use ollama_oxide::auth::*;
+use ollama_oxide::cassette::Cassette;
+use ollama_oxide::error::OllamaError;
+use ollama_oxide::testing::{StubResponse, StubServer};
+
+#[tokio::test]
+async fn built_in_providers_set_headers() {
+    let server = StubServer::start().await.unwrap();
+    let providers: [(Arc<dyn AuthProvider>, &str, &str); 3] = [
+        (
+            Arc::new(BearerToken::new("secret")),
+            "authorization",
+            "Bearer secret",
+        ),
+        (
+            Arc::new(BasicAuth::new("aladdin", Some("opensesame".to_string()))),
+            "authorization",
+            "Basic YWxhZGRpbjpvcGVuc2VzYW1l",
+        ),
+        (
+            Arc::new(HeaderAuth::new("X-Api-Key", "k1")),
+            "x-api-key",
+            "k1",
+        ),
+    ];
+
+    for (i, (provider, header, expected)) in providers.into_iter().enumerate() {
+        server
+            .client()
+            .with_auth(provider)
+            .get_version()
+            .await
+            .unwrap();
+        assert_eq!(server.requests()[i].header(header), Some(expected));
+    }
+}
+
+#[tokio::test]
+async fn refreshes_token_once_on_401() {
+    let server = StubServer::start().await.unwrap();
+    server.respond_once("/api/version", StubResponse::error(401, "token expired"));
+    let fetches = Arc::new(AtomicUsize::new(0));
+    let counter = fetches.clone();
+    let provider = RefreshingToken::new(move || {
+        let n = counter.fetch_add(1, Ordering::SeqCst);
+        async move { Ok(format!("token-{}", n)) }
+    });
+
+    let client = server.client().with_auth(Arc::new(provider));
+    client.get_version().await.unwrap();
+    client.get_version().await.unwrap();
+
+    let tokens: Vec<_> = server
+        .requests()
+        .iter()
+        .map(|request| request.header("authorization").unwrap().to_string())
+        .collect();
+    assert_eq!(
+        tokens,
+        ["Bearer token-0", "Bearer token-1", "Bearer token-1"]
+    );
+    assert_eq!(fetches.load(Ordering::SeqCst), 2);
+}
+
+#[tokio::test]
+async fn stale_rejections_do_not_refresh_again() {
+    let fetches = Arc::new(AtomicUsize::new(0));
+    let counter = fetches.clone();
+    let provider = RefreshingToken::new(move || {
+        let n = counter.fetch_add(1, Ordering::SeqCst);
+        async move { Ok(format!("token-{}", n)) }
+    });
+
+    // Two requests sent with the first token are both rejected
+    let used = provider.headers().await.unwrap().generation;
+    assert!(provider.refresh(used).await.unwrap());
+    assert!(provider.refresh(used).await.unwrap());
+
+    let credentials = provider.headers().await.unwrap();
+    assert_eq!(credentials.generation, used + 1);
+    assert_eq!(credentials.headers["authorization"], "Bearer token-1");
+    assert_eq!(fetches.load(Ordering::SeqCst), 2);
+}
+
+#[tokio::test]
+async fn repeated_401_is_unauthorized() {
+    let server = StubServer::start().await.unwrap();
+    server.respond("/api/tags", StubResponse::error(401, "invalid token"));
+
+    let err = server.client().list_models().await.unwrap_err();
+    assert!(
+        matches!(&err, OllamaError::Unauthorized(m) if m == "invalid token"),
+        "{err:?}"
+    );
+    assert_eq!(server.requests().len(), 1);
+
+    let refreshing = RefreshingToken::new(|| async { Ok("still-bad".to_string()) });
+    let err = server
+        .client()
+        .with_auth(Arc::new(refreshing))
+        .list_models()
+        .await
+        .unwrap_err();
+    assert!(matches!(err, OllamaError::Unauthorized(_)), "{err:?}");
+    assert_eq!(server.requests().len(), 3);
+}
+
+#[tokio::test]
+async fn cassettes_redact_credentials() {
+    let server = StubServer::start().await.unwrap();
+    let path = std::env::temp_dir().join(format!("ollama-oxide-auth-{}.json", std::process::id()));
+    server
+        .client()
+        .with_api_key("do-not-record")
+        .with_cassette(Cassette::record(&path))
+        .get_version()
+        .await
+        .unwrap();
+
+    let recorded = std::fs::read_to_string(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+    assert!(server.requests()[0].header("authorization").is_some());
+    assert!(!recorded.contains("do-not-record"));
+}
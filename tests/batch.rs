@@ -0,0 +1,115 @@
+use std::path::PathBuf;
+
+use ollama_oxide::batch::*;
+use ollama_oxide::testing::{StubResponse, StubServer, SAMPLE_COMPLETION};
+
+const INPUT: &str = r#"{"id": "gen", "model": "llama3.2", "prompt": "Why is the sky blue?"}
+{"id": "chat", "model": "llama3.2", "messages": [{"role": "user", "content": "Hi"}]}
+{"id": "embed", "model": "all-minilm", "input": ["first", "second"]}
+{"model": "llama3.2"}
+"#;
+
+fn temp_path(name: &str) -> PathBuf {
+    std::env::temp_dir().join(format!(
+        "ollama-oxide-batch-{}-{}",
+        std::process::id(),
+        name
+    ))
+}
+
+/// Parses the output, skipping a partial line left by an interrupted run.
+fn results(output: &PathBuf) -> Vec<BatchResult> {
+    std::fs::read_to_string(output)
+        .unwrap()
+        .lines()
+        .filter_map(|line| serde_json::from_str(line).ok())
+        .collect()
+}
+
+fn result<'a>(results: &'a [BatchResult], id: &str) -> &'a BatchResult {
+    results.iter().rev().find(|result| result.id == id).unwrap()
+}
+
+#[test]
+fn parses_request_kinds() {
+    let item = BatchItem::parse(r#"{"model": "m", "messages": []}"#, 7).unwrap();
+    assert_eq!(item.id, "7");
+    assert!(matches!(item.request, BatchRequest::Chat(_)));
+
+    let item = BatchItem::parse(r#"{"id": 3, "model": "m", "input": "x"}"#, 1).unwrap();
+    assert_eq!(item.id, "3");
+    assert!(matches!(item.request, BatchRequest::Embed(_)));
+
+    assert!(BatchItem::parse(r#"{"id": "a", "model": "m"}"#, 1).is_err());
+}
+
+#[tokio::test]
+async fn writes_results_and_token_totals() {
+    let server = StubServer::start().await.unwrap();
+    let (input, output) = (temp_path("run.in"), temp_path("run.out"));
+    std::fs::write(&input, INPUT).unwrap();
+    let _ = std::fs::remove_file(&output);
+
+    let report = run_batch(&server.client(), &input, &output, BatchOptions::default())
+        .await
+        .unwrap();
+    let results = results(&output);
+    std::fs::remove_file(&input).unwrap();
+    std::fs::remove_file(&output).unwrap();
+
+    assert_eq!(
+        (
+            report.total,
+            report.succeeded,
+            report.failed,
+            report.skipped
+        ),
+        (4, 3, 1, 0)
+    );
+    assert_eq!(report.prompt_tokens, 26 + 26 + 16);
+    assert_eq!(report.eval_tokens, 2 * SAMPLE_COMPLETION.len() as u64);
+    let completion = SAMPLE_COMPLETION.concat();
+    assert_eq!(
+        result(&results, "gen").response.as_ref().unwrap()["response"],
+        completion.as_str()
+    );
+    assert_eq!(
+        result(&results, "chat").response.as_ref().unwrap()["message"]["content"],
+        completion.as_str()
+    );
+    let embeddings = &result(&results, "embed").response.as_ref().unwrap()["embeddings"];
+    assert_eq!(embeddings.as_array().unwrap().len(), 2);
+    assert!(result(&results, "4").error.is_some());
+}
+
+#[tokio::test]
+async fn resumes_from_output() {
+    let server = StubServer::start().await.unwrap();
+    server.respond_once("/api/chat", StubResponse::error(500, "overloaded"));
+    let (input, output) = (temp_path("resume.in"), temp_path("resume.out"));
+    std::fs::write(&input, INPUT).unwrap();
+    // A line cut short by an interrupted run
+    std::fs::write(
+        &output,
+        r#"{"id": "gen", "response": {}}"#.to_string() + "\n{\"id\": \"emb",
+    )
+    .unwrap();
+
+    let first = run_batch(&server.client(), &input, &output, BatchOptions::default())
+        .await
+        .unwrap();
+    let second = run_batch(&server.client(), &input, &output, BatchOptions::default())
+        .await
+        .unwrap();
+    let results = results(&output);
+    std::fs::remove_file(&input).unwrap();
+    std::fs::remove_file(&output).unwrap();
+
+    assert_eq!((first.skipped, first.succeeded, first.failed), (1, 1, 2));
+    assert_eq!((second.skipped, second.succeeded, second.failed), (2, 1, 1));
+    assert!(server
+        .requests()
+        .iter()
+        .all(|request| request.path != "/api/generate"));
+    assert!(result(&results, "chat").response.is_some());
+}
@@ -0,0 +1,194 @@
+use std::path::PathBuf;
+use std::time::Duration;
+
+use futures::TryStreamExt;
+use ollama_oxide::cassette::{Cassette, ChunkEncoding};
+use ollama_oxide::error::OllamaError;
+use ollama_oxide::models::*;
+use ollama_oxide::testing::{StubResponse, StubServer, SAMPLE_COMPLETION};
+use ollama_oxide::OllamaClient;
+use serde_json::json;
+
+fn cassette_path(name: &str) -> PathBuf {
+    std::env::temp_dir().join(format!("ollama-oxide-{}-{}.json", name, std::process::id()))
+}
+
+fn chat_request(temperature: Option<f64>) -> ChatRequest {
+    ChatRequest {
+        model: "llama3.2".to_string(),
+        messages: vec![ChatMessage {
+            role: "user".to_string(),
+            content: "Why is the sky blue?".to_string(),
+            ..Default::default()
+        }],
+        options: temperature.map(|temperature| GenerateOptions {
+            temperature: Some(temperature),
+            ..Default::default()
+        }),
+        ..Default::default()
+    }
+}
+
+async fn collect_chat(client: &OllamaClient, request: ChatRequest) -> Vec<ChatResponse> {
+    client
+        .chat(request)
+        .await
+        .unwrap()
+        .try_collect()
+        .await
+        .unwrap()
+}
+
+#[tokio::test]
+async fn recorded_stream_replays_offline() {
+    let path = cassette_path("replay");
+    let server = StubServer::start().await.unwrap();
+    server.respond(
+        "/api/version",
+        StubResponse::json(json!({ "version": "0.5.7" })).with_header("x-request-id", "secret-id"),
+    );
+
+    let recorder = server
+        .client()
+        .with_cassette(Cassette::record(&path).redact_header("x-request-id"));
+    let recorded = collect_chat(&recorder, chat_request(None)).await;
+    assert_eq!(recorder.get_version().await.unwrap(), "0.5.7");
+
+    let interactions = recorder.cassette().unwrap().interactions();
+    assert_eq!(interactions.len(), 2);
+    assert!(interactions[0].response.chunks.len() > 1);
+    assert_eq!(
+        interactions[1].response.headers["x-request-id"],
+        "[REDACTED]"
+    );
+    drop(server);
+
+    let replayer =
+        OllamaClient::new("http://127.0.0.1:9").with_cassette(Cassette::replay(&path).unwrap());
+    let replayed = collect_chat(&replayer, chat_request(None)).await;
+    let text: String = replayed
+        .iter()
+        .map(|chunk| chunk.message.content.as_str())
+        .collect();
+    assert_eq!(text, SAMPLE_COMPLETION.concat());
+    assert_eq!(replayed.len(), recorded.len());
+    assert_eq!(replayer.get_version().await.unwrap(), "0.5.7");
+
+    std::fs::remove_file(path).unwrap();
+}
+
+#[tokio::test]
+async fn replay_matches_with_ignored_fields() {
+    let path = cassette_path("ignored");
+    let server = StubServer::start().await.unwrap();
+    let recorder = server.client().with_cassette(Cassette::record(&path));
+    collect_chat(&recorder, chat_request(Some(0.0))).await;
+
+    // Differs only in `options.temperature`, which is ignored when matching
+    let request = chat_request(Some(0.7));
+
+    let strict =
+        OllamaClient::new("http://127.0.0.1:9").with_cassette(Cassette::replay(&path).unwrap());
+    assert!(matches!(
+        strict.chat(request.clone()).await.err(),
+        Some(OllamaError::Cassette(_))
+    ));
+
+    let lenient = OllamaClient::new("http://127.0.0.1:9").with_cassette(
+        Cassette::replay(&path)
+            .unwrap()
+            .ignore_field("options.temperature"),
+    );
+    assert!(!collect_chat(&lenient, request).await.is_empty());
+
+    std::fs::remove_file(path).unwrap();
+}
+
+#[tokio::test]
+async fn replay_timing_preserves_chunk_delays() {
+    let path = cassette_path("timing");
+    let server = StubServer::start().await.unwrap();
+    let lines: Vec<_> = (0..3)
+        .map(|i| json!({ "model": "m", "created_at": "", "response": i.to_string(), "done": i == 2 }))
+        .collect();
+    server.respond(
+        "/api/generate",
+        StubResponse::ndjson(lines).with_chunk_delay(Duration::from_millis(50)),
+    );
+    let request = GenerateRequest {
+        model: "m".to_string(),
+        prompt: "count".to_string(),
+        ..Default::default()
+    };
+
+    let recorder = server.client().with_cassette(Cassette::record(&path));
+    let _: Vec<GenerateResponse> = recorder
+        .generate(request.clone())
+        .await
+        .unwrap()
+        .try_collect()
+        .await
+        .unwrap();
+
+    let replayer = OllamaClient::new("http://127.0.0.1:9")
+        .with_cassette(Cassette::replay(&path).unwrap().with_replay_timing(true));
+    let start = std::time::Instant::now();
+    let chunks: Vec<GenerateResponse> = replayer
+        .generate(request)
+        .await
+        .unwrap()
+        .try_collect()
+        .await
+        .unwrap();
+    assert_eq!(chunks.len(), 3);
+    assert!(start.elapsed() >= Duration::from_millis(80));
+
+    std::fs::remove_file(path).unwrap();
+}
+
+#[tokio::test]
+async fn chunks_split_inside_a_character_replay_intact() {
+    let path = cassette_path("utf8");
+    let server = StubServer::start().await.unwrap();
+    let line = json!({ "model": "m", "created_at": "", "response": "héllo wörld", "done": true });
+    server.respond(
+        "/api/generate",
+        StubResponse::ndjson(vec![line])
+            .fragmented(1)
+            .with_chunk_delay(Duration::from_millis(1)),
+    );
+    let request = GenerateRequest {
+        model: "m".to_string(),
+        prompt: "greet".to_string(),
+        ..Default::default()
+    };
+
+    let recorder = server.client().with_cassette(Cassette::record(&path));
+    let _: Vec<GenerateResponse> = recorder
+        .generate(request.clone())
+        .await
+        .unwrap()
+        .try_collect()
+        .await
+        .unwrap();
+    let chunks = &recorder.cassette().unwrap().interactions()[0]
+        .response
+        .chunks;
+    assert!(chunks
+        .iter()
+        .any(|chunk| chunk.encoding == Some(ChunkEncoding::Base64)));
+    drop(server);
+
+    let replayer =
+        OllamaClient::new("http://127.0.0.1:9").with_cassette(Cassette::replay(&path).unwrap());
+    let replayed: Vec<GenerateResponse> = replayer
+        .generate(request)
+        .await
+        .unwrap()
+        .try_collect()
+        .await
+        .unwrap();
+    assert_eq!(replayed[0].response, "héllo wörld");
+
+    std::fs::remove_file(path).unwrap();
+}
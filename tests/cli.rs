@@ -0,0 +1,100 @@
+#![cfg(feature = "cli")]
+
+use ollama_oxide::testing::{StubServer, SAMPLE_COMPLETION};
+use serde_json::Value;
+use tokio::process::Command;
+
+async fn oxide(server: &StubServer, args: &[&str]) -> (bool, String) {
+    let output = Command::new(env!("CARGO_BIN_EXE_oxide"))
+        .args(args)
+        .env("OLLAMA_HOST", server.url())
+        .output()
+        .await
+        .unwrap();
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    (output.status.success(), stdout)
+}
+
+#[tokio::test]
+async fn list_prints_json() {
+    let server = StubServer::start().await.unwrap();
+    let (ok, stdout) = oxide(&server, &["list", "--json"]).await;
+
+    assert!(ok);
+    let models: Value = serde_json::from_str(&stdout).unwrap();
+    assert_eq!(models[0]["name"], "llama3.2:latest");
+}
+
+#[tokio::test]
+async fn generate_streams_text() {
+    let server = StubServer::start().await.unwrap();
+    let (ok, stdout) = oxide(
+        &server,
+        &["generate", "llama3.2", "why", "is", "the", "sky", "blue"],
+    )
+    .await;
+
+    assert!(ok);
+    assert_eq!(stdout.trim_end(), SAMPLE_COMPLETION.concat());
+    assert_eq!(server.requests()[0].json()["prompt"], "why is the sky blue");
+}
+
+#[tokio::test]
+async fn embed_prints_csv() {
+    let server = StubServer::start().await.unwrap();
+    let (ok, stdout) = oxide(
+        &server,
+        &["embed", "all-minilm", "a,b", "c", "--format", "csv"],
+    )
+    .await;
+
+    assert!(ok);
+    let lines: Vec<&str> = stdout.lines().collect();
+    assert_eq!(lines.len(), 2);
+    assert!(lines[0].starts_with("\"a,b\","));
+    assert!(lines[1].starts_with("c,"));
+}
+
+#[tokio::test]
+async fn api_errors_exit_with_failure() {
+    let server = StubServer::start().await.unwrap();
+    server.respond(
+        "/api/delete",
+        ollama_oxide::testing::StubResponse::error(404, "model 'nope' not found"),
+    );
+    let (ok, _) = oxide(&server, &["rm", "nope"]).await;
+
+    assert!(!ok);
+}
+
+#[tokio::test]
+async fn batch_reports_totals() {
+    let server = StubServer::start().await.unwrap();
+    let dir = std::env::temp_dir();
+    let input = dir.join(format!("ollama-oxide-cli-batch-{}.in", std::process::id()));
+    let output = dir.join(format!("ollama-oxide-cli-batch-{}.out", std::process::id()));
+    std::fs::write(
+        &input,
+        "{\"id\": \"q1\", \"model\": \"llama3.2\", \"prompt\": \"Hi\"}\n",
+    )
+    .unwrap();
+    let _ = std::fs::remove_file(&output);
+
+    let args = [
+        "batch",
+        input.to_str().unwrap(),
+        "--output",
+        output.to_str().unwrap(),
+        "--json",
+    ];
+    let (ok, stdout) = oxide(&server, &args).await;
+    let written = std::fs::read_to_string(&output).unwrap();
+    std::fs::remove_file(&input).unwrap();
+    std::fs::remove_file(&output).unwrap();
+
+    assert!(ok);
+    let report: Value = serde_json::from_str(&stdout).unwrap();
+    assert_eq!(report["succeeded"], 1);
+    assert_eq!(report["eval_tokens"], SAMPLE_COMPLETION.len());
+    assert!(written.starts_with("{\"id\":\"q1\",\"response\":"));
+}
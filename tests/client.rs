@@ -0,0 +1,387 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use futures::{StreamExt, TryStreamExt};
+use ollama_oxide::error::OllamaError;
+use ollama_oxide::limiter::ConcurrencyLimiter;
+use ollama_oxide::models::*;
+use ollama_oxide::testing::{
+    stub_embedding, StubResponse, StubServer, SAMPLE_COMPLETION, STUB_CONTEXT_LENGTH,
+};
+use serde_json::json;
+
+fn generate_request() -> GenerateRequest {
+    GenerateRequest {
+        model: "llama3.2".to_string(),
+        prompt: "Why is the sky blue?".to_string(),
+        ..Default::default()
+    }
+}
+
+fn chat_request() -> ChatRequest {
+    ChatRequest {
+        model: "llama3.2".to_string(),
+        messages: vec![ChatMessage {
+            role: "user".to_string(),
+            content: "Why is the sky blue?".to_string(),
+            ..Default::default()
+        }],
+        ..Default::default()
+    }
+}
+
+#[tokio::test]
+async fn list_models_returns_models() {
+    let server = StubServer::start().await.unwrap();
+    let models = server.client().list_models().await.unwrap();
+
+    assert_eq!(models.len(), 1);
+    assert_eq!(models[0].details.family, "llama");
+    assert_eq!(server.requests()[0].method, "GET");
+    assert_eq!(server.requests()[0].path, "/api/tags");
+}
+
+#[tokio::test]
+async fn show_model_sends_name() {
+    let server = StubServer::start().await.unwrap();
+    let model = server.client().show_model("llama3.2").await.unwrap();
+
+    assert_eq!(model.architecture(), Some("llama"));
+    assert_eq!(model.context_length(), Some(STUB_CONTEXT_LENGTH as u64));
+    assert_eq!(model.details.unwrap().family, "llama");
+    assert_eq!(server.requests()[0].json(), json!({ "name": "llama3.2" }));
+}
+
+#[tokio::test]
+async fn pull_model_streams_progress() {
+    let server = StubServer::start().await.unwrap();
+    let progress: Vec<PullResponse> = server
+        .client()
+        .pull_model("llama3.2")
+        .await
+        .unwrap()
+        .try_collect()
+        .await
+        .unwrap();
+
+    assert_eq!(progress.len(), 4);
+    assert_eq!(progress[2].completed, Some(2048));
+    assert_eq!(progress.last().unwrap().status.as_deref(), Some("success"));
+}
+
+#[tokio::test]
+async fn push_model_streams_progress() {
+    let server = StubServer::start().await.unwrap();
+    let progress: Vec<PushResponse> = server
+        .client()
+        .push_model("me/llama3.2")
+        .await
+        .unwrap()
+        .try_collect()
+        .await
+        .unwrap();
+
+    assert_eq!(progress.last().unwrap().status.as_deref(), Some("success"));
+    assert_eq!(server.requests()[0].path, "/api/push");
+    assert_eq!(server.requests()[0].json()["name"], "me/llama3.2");
+}
+
+#[tokio::test]
+async fn create_model_streams_status() {
+    let server = StubServer::start().await.unwrap();
+    let request = CreateModelRequest {
+        model: "mario".to_string(),
+        from: Some("llama3.2".to_string()),
+        system: Some("You are Mario.".to_string()),
+        ..Default::default()
+    };
+    let status: Vec<CreateResponse> = server
+        .client()
+        .create_model(request)
+        .await
+        .unwrap()
+        .try_collect()
+        .await
+        .unwrap();
+
+    assert_eq!(status.len(), 3);
+    let body = server.requests()[0].json();
+    assert_eq!(body["model"], "mario");
+    assert_eq!(body["from"], "llama3.2");
+}
+
+#[tokio::test]
+async fn delete_model_uses_delete_method() {
+    let server = StubServer::start().await.unwrap();
+    server.client().delete_model("llama3.2").await.unwrap();
+
+    let request = &server.requests()[0];
+    assert_eq!(request.method, "DELETE");
+    assert_eq!(request.json()["name"], "llama3.2");
+}
+
+#[tokio::test]
+async fn copy_model_posts_source_and_destination() {
+    let server = StubServer::start().await.unwrap();
+    server
+        .client()
+        .copy_model("llama3.2", "llama3.2-backup")
+        .await
+        .unwrap();
+
+    let request = &server.requests()[0];
+    assert_eq!(request.path, "/api/copy");
+    assert_eq!(request.json()["source"], "llama3.2");
+    assert_eq!(request.json()["destination"], "llama3.2-backup");
+}
+
+#[tokio::test]
+async fn delete_missing_model_is_api_error() {
+    let server = StubServer::start().await.unwrap();
+    server.respond(
+        "/api/delete",
+        StubResponse::error(404, "model 'nope' not found"),
+    );
+
+    let err = server.client().delete_model("nope").await.unwrap_err();
+    match err {
+        OllamaError::ApiError(message) => assert!(message.contains("not found")),
+        other => panic!("unexpected error: {other:?}"),
+    }
+}
+
+#[tokio::test]
+async fn generate_streams_tokens_and_stats() {
+    let server = StubServer::start().await.unwrap();
+    let chunks: Vec<GenerateResponse> = server
+        .client()
+        .generate(generate_request())
+        .await
+        .unwrap()
+        .try_collect()
+        .await
+        .unwrap();
+
+    let text: String = chunks.iter().map(|chunk| chunk.response.as_str()).collect();
+    assert_eq!(text, SAMPLE_COMPLETION.concat());
+    let last = chunks.last().unwrap();
+    assert!(last.done);
+    assert_eq!(last.eval_count, Some(SAMPLE_COMPLETION.len() as u32));
+    assert_eq!(
+        server.requests()[0].json()["prompt"],
+        "Why is the sky blue?"
+    );
+}
+
+#[tokio::test]
+async fn generate_reassembles_fragmented_lines() {
+    let server = StubServer::start().await.unwrap();
+    let lines: Vec<_> = (0..20)
+        .map(|i| json!({ "model": "m", "created_at": "", "response": format!("{i} "), "done": i == 19 }))
+        .collect();
+    server.respond(
+        "/api/generate",
+        StubResponse::ndjson(lines)
+            .fragmented(7)
+            .with_chunk_delay(Duration::from_millis(1)),
+    );
+
+    let chunks: Vec<GenerateResponse> = server
+        .client()
+        .generate(generate_request())
+        .await
+        .unwrap()
+        .try_collect()
+        .await
+        .unwrap();
+
+    assert_eq!(chunks.len(), 20);
+    assert_eq!(chunks[13].response, "13 ");
+    assert!(chunks[19].done);
+}
+
+#[tokio::test]
+async fn generate_surfaces_mid_stream_error() {
+    let server = StubServer::start().await.unwrap();
+    let lines: Vec<_> = SAMPLE_COMPLETION
+        .iter()
+        .map(|token| json!({ "model": "m", "created_at": "", "response": token, "done": false }))
+        .collect();
+    server.respond(
+        "/api/generate",
+        StubResponse::ndjson(lines).with_stream_error_after(2, "out of memory"),
+    );
+
+    let mut stream = Box::pin(server.client().generate(generate_request()).await.unwrap());
+    assert!(stream.next().await.unwrap().is_ok());
+    assert!(stream.next().await.unwrap().is_ok());
+    match stream.next().await.unwrap() {
+        Err(OllamaError::ApiError(message)) => assert_eq!(message, "out of memory"),
+        other => panic!("unexpected item: {other:?}"),
+    }
+}
+
+#[tokio::test]
+async fn generate_reports_dropped_connection() {
+    let server = StubServer::start().await.unwrap();
+    server.respond(
+        "/api/generate",
+        StubResponse::ndjson(vec![
+            json!({ "model": "m", "created_at": "", "response": "a", "done": false });
+            3
+        ])
+        .disconnect_after(1),
+    );
+
+    let results: Vec<_> = server
+        .client()
+        .generate(generate_request())
+        .await
+        .unwrap()
+        .collect()
+        .await;
+
+    assert!(results[0].is_ok());
+    assert!(matches!(
+        results.last(),
+        Some(Err(OllamaError::RequestFailed(_)))
+    ));
+}
+
+#[tokio::test]
+async fn generate_error_status_is_api_error() {
+    let server = StubServer::start().await.unwrap();
+    server.respond_once(
+        "/api/generate",
+        StubResponse::error(500, "model failed to load"),
+    );
+
+    let err = server
+        .client()
+        .generate(generate_request())
+        .await
+        .err()
+        .unwrap();
+    assert!(
+        matches!(err, OllamaError::ApiError(message) if message.contains("model failed to load"))
+    );
+
+    // The one-shot failure is consumed and the canned response is served again
+    assert!(server.client().generate(generate_request()).await.is_ok());
+}
+
+#[tokio::test]
+async fn chat_streams_assistant_message() {
+    let server = StubServer::start().await.unwrap();
+    let chunks: Vec<ChatResponse> = server
+        .client()
+        .chat(chat_request())
+        .await
+        .unwrap()
+        .try_collect()
+        .await
+        .unwrap();
+
+    let text: String = chunks
+        .iter()
+        .map(|chunk| chunk.message.content.as_str())
+        .collect();
+    assert_eq!(text, SAMPLE_COMPLETION.concat());
+    assert_eq!(chunks[0].message.role, "assistant");
+    assert_eq!(server.requests()[0].json()["messages"][0]["role"], "user");
+}
+
+#[tokio::test]
+async fn generate_embeddings_single_input() {
+    let server = StubServer::start().await.unwrap();
+    let request = EmbedRequest {
+        model: "all-minilm".to_string(),
+        input: EmbedInput::Single("hello".to_string()),
+        ..Default::default()
+    };
+    let response = server.client().generate_embeddings(request).await.unwrap();
+
+    assert_eq!(response.embeddings, vec![stub_embedding("hello")]);
+    assert_eq!(response.prompt_eval_count, Some(8));
+    let request = server.requests().pop().unwrap();
+    assert_eq!(request.path, "/api/embed");
+    assert_eq!(request.json()["input"], "hello");
+}
+
+#[tokio::test]
+async fn generate_multiple_embeddings_keeps_order() {
+    let server = StubServer::start().await.unwrap();
+    let inputs = vec!["first".to_string(), "second".to_string()];
+    let response = server
+        .client()
+        .generate_multiple_embeddings("all-minilm".to_string(), inputs, Some(true), None, None)
+        .await
+        .unwrap();
+
+    assert_eq!(
+        response.embeddings,
+        vec![stub_embedding("first"), stub_embedding("second")]
+    );
+    assert_eq!(server.requests().pop().unwrap().json()["truncate"], true);
+}
+
+#[tokio::test]
+async fn list_running_models_returns_models() {
+    let server = StubServer::start().await.unwrap();
+    let models = server.client().list_running_models().await.unwrap();
+
+    assert_eq!(models.len(), 1);
+    assert_eq!(server.requests()[0].path, "/api/ps");
+}
+
+#[tokio::test]
+async fn get_version_returns_version() {
+    let server = StubServer::start().await.unwrap();
+    assert_eq!(server.client().get_version().await.unwrap(), "0.5.7");
+}
+
+#[tokio::test]
+async fn limiter_rejects_when_queue_is_full() {
+    let server = StubServer::start().await.unwrap();
+    server.respond(
+        "/api/embed",
+        StubResponse::json(json!({
+            "model": "m",
+            "embeddings": [],
+            "total_duration": 0,
+            "load_duration": 0,
+            "prompt_eval_count": 0,
+        }))
+        .with_latency(Duration::from_millis(200)),
+    );
+    let client = Arc::new(
+        server
+            .client()
+            .with_limiter(ConcurrencyLimiter::new(1).with_max_queue_depth(1)),
+    );
+
+    let embed = |client: Arc<ollama_oxide::OllamaClient>| async move {
+        client
+            .generate_embeddings(EmbedRequest {
+                model: "m".to_string(),
+                ..Default::default()
+            })
+            .await
+    };
+    let first = tokio::spawn(embed(client.clone()));
+    tokio::time::sleep(Duration::from_millis(20)).await;
+    let second = tokio::spawn(embed(client.clone()));
+    tokio::time::sleep(Duration::from_millis(20)).await;
+
+    let third = embed(client.clone()).await;
+    assert!(matches!(third, Err(OllamaError::Overloaded(1))));
+
+    first.await.unwrap().unwrap();
+    second.await.unwrap().unwrap();
+    let metrics = client.limiter().unwrap().metrics();
+    assert_eq!(metrics.acquired, 2);
+    assert_eq!(metrics.waited, 1);
+    assert_eq!(metrics.rejected, 1);
+    assert_eq!(metrics.queued, 0);
+    assert!(metrics.max_wait >= Duration::from_millis(100));
+}
@@ -0,0 +1,61 @@
+use ollama_oxide::config::{parse_host, DEFAULT_HOST};
+use ollama_oxide::testing::StubServer;
+use ollama_oxide::OllamaClient;
+
+#[test]
+fn parses_host_like_the_ollama_cli() {
+    let cases = [
+        ("", DEFAULT_HOST),
+        ("gpu-box", "http://gpu-box:11434"),
+        ("gpu-box:8080", "http://gpu-box:8080"),
+        (":8080", "http://127.0.0.1:8080"),
+        ("0.0.0.0", "http://127.0.0.1:11434"),
+        ("http://0.0.0.0:11434", "http://127.0.0.1:11434"),
+        ("http://gpu-box", "http://gpu-box:80"),
+        (
+            "https://ollama.example.com/",
+            "https://ollama.example.com:443",
+        ),
+        (
+            "https://example.com/ollama/",
+            "https://example.com:443/ollama",
+        ),
+        ("[::]:11434", "http://[::1]:11434"),
+        ("[fe80::1]", "http://[fe80::1]:11434"),
+        ("  localhost  ", "http://localhost:11434"),
+    ];
+    for (value, expected) in cases {
+        assert_eq!(parse_host(value), expected, "OLLAMA_HOST={value:?}");
+    }
+}
+
+#[tokio::test]
+async fn api_key_is_sent_as_bearer_token() {
+    let server = StubServer::start().await.unwrap();
+    let client = OllamaClient::new(&server.url()).with_api_key("secret");
+    client.list_models().await.unwrap();
+
+    let request = &server.requests()[0];
+    assert_eq!(request.header("authorization"), Some("Bearer secret"));
+}
+
+// The only test that touches the process environment, so it cannot race
+// with another test in this binary.
+#[tokio::test]
+async fn from_env_reads_host_and_api_key() {
+    let server = StubServer::start().await.unwrap();
+    std::env::set_var("OLLAMA_HOST", server.url().trim_start_matches("http://"));
+    std::env::set_var("OLLAMA_API_KEY", "from-env");
+
+    let client = OllamaClient::default();
+    assert_eq!(client.base_url(), server.url());
+    client.get_version().await.unwrap();
+
+    std::env::remove_var("OLLAMA_HOST");
+    std::env::remove_var("OLLAMA_API_KEY");
+    assert_eq!(
+        server.requests()[0].header("authorization"),
+        Some("Bearer from-env")
+    );
+    assert_eq!(OllamaClient::from_env().base_url(), DEFAULT_HOST);
+}
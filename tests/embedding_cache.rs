@@ -0,0 +1,133 @@
+use std::sync::Arc;
+
+use ollama_oxide::cache::{CacheKey, EmbeddingCache, MemoryCache};
+use ollama_oxide::testing::{stub_embedding, StubServer};
+
+fn strings(items: &[&str]) -> Vec<String> {
+    items.iter().map(|item| item.to_string()).collect()
+}
+
+#[tokio::test]
+async fn only_cache_misses_are_sent() {
+    let server = StubServer::start().await.unwrap();
+    let cache = Arc::new(MemoryCache::new(100));
+    let client = server.client().with_embedding_cache(cache.clone());
+
+    client
+        .generate_multiple_embeddings(
+            "llama3.2".to_string(),
+            strings(&["a", "b"]),
+            None,
+            None,
+            None,
+        )
+        .await
+        .unwrap();
+    let response = client
+        .generate_multiple_embeddings(
+            "llama3.2".to_string(),
+            strings(&["b", "c", "a"]),
+            None,
+            None,
+            None,
+        )
+        .await
+        .unwrap();
+
+    assert_eq!(
+        response.embeddings,
+        vec![
+            stub_embedding("b"),
+            stub_embedding("c"),
+            stub_embedding("a")
+        ]
+    );
+    assert_eq!(cache.len(), 3);
+    let embed_inputs: Vec<_> = server
+        .requests()
+        .iter()
+        .filter(|request| request.path == "/api/embed")
+        .map(|request| request.json()["input"].clone())
+        .collect();
+    assert_eq!(embed_inputs.len(), 2);
+    assert_eq!(embed_inputs[1], serde_json::json!(["c"]));
+}
+
+#[tokio::test]
+async fn fully_cached_batch_skips_embed_request() {
+    let server = StubServer::start().await.unwrap();
+    let client = server
+        .client()
+        .with_embedding_cache(Arc::new(MemoryCache::new(100)));
+
+    for _ in 0..2 {
+        client
+            .generate_multiple_embeddings("llama3.2".to_string(), strings(&["x"]), None, None, None)
+            .await
+            .unwrap();
+    }
+
+    let count = |path: &str| {
+        server
+            .requests()
+            .iter()
+            .filter(|request| request.path == path)
+            .count()
+    };
+    assert_eq!(count("/api/embed"), 1);
+    // The model digest is looked up once and reused
+    assert_eq!(count("/api/tags"), 1);
+}
+
+#[test]
+fn keys_depend_on_digest_and_truncate() {
+    let key = CacheKey::new("m", "sha256:1", None, "text");
+    assert_ne!(key, CacheKey::new("m", "sha256:2", None, "text"));
+    assert_ne!(key, CacheKey::new("m", "sha256:1", Some(true), "text"));
+    assert_eq!(key, CacheKey::new("m", "sha256:1", None, "text"));
+}
+
+#[test]
+fn memory_cache_evicts_least_recently_used() {
+    let cache = MemoryCache::new(2);
+    let key = |input: &str| CacheKey::new("m", "d", None, input);
+    cache.put(key("a"), vec![1.0]);
+    cache.put(key("b"), vec![2.0]);
+    assert!(cache.get(&key("a")).is_some());
+    cache.put(key("c"), vec![3.0]);
+
+    assert_eq!(cache.len(), 2);
+    assert!(cache.get(&key("b")).is_none());
+    assert_eq!(cache.get(&key("a")), Some(vec![1.0]));
+    assert_eq!(cache.get(&key("c")), Some(vec![3.0]));
+}
+
+#[cfg(feature = "sled")]
+#[test]
+fn sled_cache_persists_across_reopen() {
+    use ollama_oxide::cache::SledCache;
+
+    const WRITE_TO: &str = "OLLAMA_OXIDE_SLED_WRITE_TO";
+    let key = CacheKey::new("m", "d", Some(false), "persisted");
+    if let Ok(path) = std::env::var(WRITE_TO) {
+        SledCache::open(path).unwrap().put(key, vec![0.25, -1.5]);
+        return;
+    }
+
+    // sled releases its file lock from background threads some time after
+    // the last handle is dropped, so write from a child process, whose exit
+    // releases it
+    let path = std::env::temp_dir().join(format!("ollama-oxide-sled-{}", std::process::id()));
+    let status = std::process::Command::new(std::env::current_exe().unwrap())
+        .args(["--exact", "sled_cache_persists_across_reopen"])
+        .env(WRITE_TO, &path)
+        .status()
+        .unwrap();
+    assert!(status.success());
+
+    assert_eq!(
+        SledCache::open(&path).unwrap().get(&key),
+        Some(vec![0.25, -1.5])
+    );
+    std::fs::remove_dir_all(path).unwrap();
+}
@@ -0,0 +1,220 @@
+use ollama_oxide::embeddings::*;
+use ollama_oxide::error::OllamaError;
+use ollama_oxide::models::*;
+use ollama_oxide::testing::{stub_embedding, StubResponse, StubServer, STUB_EMBEDDING_DIMENSIONS};
+use serde_json::json;
+
+/// Legacy vectors are re-normalized client side, so allow for rounding.
+fn assert_close(actual: &[Vec<f32>], expected: &[Vec<f32>]) {
+    assert_eq!(actual.len(), expected.len());
+    for (a, e) in actual.iter().zip(expected) {
+        assert!(
+            a.iter().zip(e).all(|(x, y)| (x - y).abs() < 1e-6),
+            "{a:?} != {e:?}"
+        );
+    }
+}
+
+#[tokio::test]
+async fn embed_batch_chunks_and_preserves_order() {
+    let server = StubServer::start().await.unwrap();
+    let inputs: Vec<String> = (0..7).map(|i| format!("document number {i}")).collect();
+
+    let vectors = server
+        .client()
+        .embed_batch(
+            "all-minilm",
+            inputs.clone(),
+            EmbedBatchOptions {
+                chunk_size: 3,
+                concurrency: 2,
+                ..Default::default()
+            },
+        )
+        .await
+        .unwrap();
+
+    let expected: Vec<_> = inputs.iter().map(|input| stub_embedding(input)).collect();
+    assert_eq!(vectors, expected);
+    let sizes: Vec<usize> = server
+        .requests()
+        .iter()
+        .filter(|request| request.path == "/api/embed")
+        .map(|request| request.json()["input"].as_array().unwrap().len())
+        .collect();
+    assert_eq!(sizes.iter().sum::<usize>(), 7);
+    assert_eq!(sizes.len(), 3);
+}
+
+#[test]
+fn similarity_helpers() {
+    let mut v = vec![3.0, 4.0];
+    normalize_l2(&mut v);
+    assert_eq!(v, vec![0.6, 0.8]);
+    assert_eq!(dot_product(&[1.0, 2.0], &[3.0, 4.0]), 11.0);
+    assert!((cosine_similarity(&[1.0, 0.0], &[2.0, 0.0]) - 1.0).abs() < 1e-6);
+    assert_eq!(cosine_similarity(&[1.0, 0.0], &[0.0, 1.0]), 0.0);
+    assert_eq!(cosine_similarity(&[0.0, 0.0], &[1.0, 1.0]), 0.0);
+}
+
+#[test]
+fn index_returns_top_k_by_similarity() {
+    let mut index = EmbeddingIndex::new(Similarity::Cosine);
+    index.insert("east", vec![1.0, 0.0], "e").unwrap();
+    index.insert("north", vec![0.0, 1.0], "n").unwrap();
+    index.insert("north-east", vec![1.0, 1.0], "ne").unwrap();
+
+    let hits = index.search(&[1.0, 0.2], 2).unwrap();
+    assert_eq!(hits.len(), 2);
+    assert_eq!(hits[0].id, "east");
+    assert_eq!(*hits[1].metadata, "ne");
+    assert!(hits[0].score > hits[1].score);
+
+    index.remove("east");
+    assert_eq!(index.search(&[1.0, 0.2], 1).unwrap()[0].id, "north-east");
+    assert!(matches!(
+        index.insert("bad", vec![1.0], "x"),
+        Err(OllamaError::DimensionMismatch(2, 1))
+    ));
+}
+
+#[test]
+fn dot_product_index_uses_magnitude() {
+    let mut index: EmbeddingIndex = EmbeddingIndex::new(Similarity::DotProduct);
+    index.insert("small", vec![1.0, 0.0], ()).unwrap();
+    index.insert("large", vec![5.0, 0.0], ()).unwrap();
+    index.insert("small", vec![10.0, 0.0], ()).unwrap();
+
+    assert_eq!(index.len(), 2);
+    assert_eq!(index.search(&[1.0, 0.0], 1).unwrap()[0].id, "small");
+}
+
+#[tokio::test]
+async fn old_servers_use_legacy_endpoint() {
+    let server = StubServer::start().await.unwrap();
+    server.respond(
+        "/api/version",
+        StubResponse::json(json!({ "version": "0.2.8" })),
+    );
+    let client = server.client();
+
+    for _ in 0..2 {
+        let response = client
+            .generate_multiple_embeddings(
+                "m".to_string(),
+                vec!["a".to_string(), "bc".to_string()],
+                None,
+                None,
+                None,
+            )
+            .await
+            .unwrap();
+        assert_close(
+            &response.embeddings,
+            &[stub_embedding("a"), stub_embedding("bc")],
+        );
+        assert_eq!(response.total_duration, None);
+    }
+
+    let paths: Vec<String> = server
+        .requests()
+        .into_iter()
+        .map(|request| request.path)
+        .collect();
+    assert_eq!(
+        paths,
+        [
+            "/api/version",
+            "/api/embeddings",
+            "/api/embeddings",
+            "/api/embeddings",
+            "/api/embeddings"
+        ]
+    );
+    assert_eq!(server.requests()[1].json()["prompt"], "a");
+}
+
+#[tokio::test]
+async fn missing_embed_route_falls_back_to_legacy() {
+    let server = StubServer::start().await.unwrap();
+    server.respond(
+        "/api/embed",
+        StubResponse::text("404 page not found").with_status(404),
+    );
+
+    let response = server
+        .client()
+        .generate_embeddings(EmbedRequest {
+            model: "m".to_string(),
+            input: EmbedInput::Single("hello".to_string()),
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+    assert_close(&response.embeddings, &[stub_embedding("hello")]);
+}
+
+#[tokio::test]
+async fn missing_model_is_not_a_missing_route() {
+    let server = StubServer::start().await.unwrap();
+    server.respond(
+        "/api/embed",
+        StubResponse::error(404, "model \"m\" not found"),
+    );
+
+    let err = server
+        .client()
+        .generate_embeddings(EmbedRequest {
+            model: "m".to_string(),
+            ..Default::default()
+        })
+        .await
+        .unwrap_err();
+    assert!(matches!(err, OllamaError::ApiError(message) if message.contains("not found")));
+}
+
+#[tokio::test]
+async fn embedding_dimensions_reads_model_info_then_caches() {
+    let server = StubServer::start().await.unwrap();
+    let client = server.client();
+
+    assert_eq!(
+        client.embedding_dimensions("m").await.unwrap(),
+        STUB_EMBEDDING_DIMENSIONS
+    );
+    assert_eq!(
+        client.embedding_dimensions("m").await.unwrap(),
+        STUB_EMBEDDING_DIMENSIONS
+    );
+    assert_eq!(server.requests().len(), 1);
+    assert_eq!(server.requests()[0].path, "/api/show");
+}
+
+#[tokio::test]
+async fn embedding_dimensions_probes_without_model_info() {
+    let server = StubServer::start().await.unwrap();
+    server.respond("/api/show", StubResponse::json(json!({ "modelfile": "" })));
+
+    let client = server.client();
+    assert_eq!(
+        client.embedding_dimensions("m").await.unwrap(),
+        STUB_EMBEDDING_DIMENSIONS
+    );
+    assert!(server
+        .requests()
+        .iter()
+        .any(|request| request.path == "/api/embed"));
+}
+
+#[test]
+fn endpoint_detection_by_version() {
+    assert_eq!(EmbedEndpoint::for_version("0.1.48"), EmbedEndpoint::Legacy);
+    assert_eq!(
+        EmbedEndpoint::for_version("0.2.8-rc1"),
+        EmbedEndpoint::Legacy
+    );
+    assert_eq!(EmbedEndpoint::for_version("0.3.0"), EmbedEndpoint::Embed);
+    assert_eq!(EmbedEndpoint::for_version("0.5.7"), EmbedEndpoint::Embed);
+    assert_eq!(EmbedEndpoint::for_version("0.0.0"), EmbedEndpoint::Embed);
+    assert_eq!(EmbedEndpoint::for_version("dev"), EmbedEndpoint::Embed);
+}
@@ -0,0 +1,204 @@
+use std::io::Cursor;
+
+use ollama_oxide::error::OllamaError;
+use ollama_oxide::gguf::*;
+
+/// Writes a version 3 GGUF header.
+#[derive(Default)]
+struct Writer {
+    bytes: Vec<u8>,
+    metadata: Vec<u8>,
+    metadata_count: u64,
+    tensors: Vec<u8>,
+    tensor_count: u64,
+}
+
+impl Writer {
+    fn string(out: &mut Vec<u8>, value: &str) {
+        out.extend((value.len() as u64).to_le_bytes());
+        out.extend(value.as_bytes());
+    }
+
+    fn kv(mut self, key: &str, value_type: u32, value: &[u8]) -> Self {
+        Self::string(&mut self.metadata, key);
+        self.metadata.extend(value_type.to_le_bytes());
+        self.metadata.extend(value);
+        self.metadata_count += 1;
+        self
+    }
+
+    fn kv_str(self, key: &str, value: &str) -> Self {
+        let mut bytes = Vec::new();
+        Self::string(&mut bytes, value);
+        self.kv(key, 8, &bytes)
+    }
+
+    fn kv_strings(self, key: &str, values: &[&str]) -> Self {
+        let mut bytes = Vec::new();
+        bytes.extend(8u32.to_le_bytes());
+        bytes.extend((values.len() as u64).to_le_bytes());
+        for value in values {
+            Self::string(&mut bytes, value);
+        }
+        self.kv(key, 9, &bytes)
+    }
+
+    fn tensor(mut self, name: &str, dimensions: &[u64], tensor_type: u32) -> Self {
+        Self::string(&mut self.tensors, name);
+        self.tensors.extend((dimensions.len() as u32).to_le_bytes());
+        for dimension in dimensions {
+            self.tensors.extend(dimension.to_le_bytes());
+        }
+        self.tensors.extend(tensor_type.to_le_bytes());
+        self.tensors.extend(0u64.to_le_bytes());
+        self.tensor_count += 1;
+        self
+    }
+
+    fn finish(mut self) -> Vec<u8> {
+        self.bytes.extend(b"GGUF");
+        self.bytes.extend(3u32.to_le_bytes());
+        self.bytes.extend(self.tensor_count.to_le_bytes());
+        self.bytes.extend(self.metadata_count.to_le_bytes());
+        self.bytes.extend(self.metadata);
+        self.bytes.extend(self.tensors);
+        self.bytes
+    }
+}
+
+fn llama() -> Writer {
+    Writer::default()
+        .kv_str("general.architecture", "llama")
+        .kv_str("general.name", "Tiny Llama")
+        .kv("general.file_type", 4, &15u32.to_le_bytes())
+        .kv("llama.context_length", 4, &131072u32.to_le_bytes())
+        .kv("llama.embedding_length", 10, &2048u64.to_le_bytes())
+        .kv_str("tokenizer.ggml.model", "gpt2")
+        .kv_strings("tokenizer.ggml.tokens", &["<s>", "</s>", "hello"])
+        .kv("tokenizer.ggml.bos_token_id", 4, &0u32.to_le_bytes())
+        .kv("tokenizer.ggml.eos_token_id", 4, &1u32.to_le_bytes())
+        .kv_str(
+            "tokenizer.chat_template",
+            "{% for m in messages %}{{ m.content }}{% endfor %}",
+        )
+        .tensor("token_embd.weight", &[2048, 3], 12)
+        .tensor("output.weight", &[2048, 1000], 14)
+}
+
+#[test]
+fn reads_model_metadata() {
+    let bytes = llama().finish();
+    let file = GgufFile::read(Cursor::new(&bytes)).unwrap();
+
+    assert_eq!(file.version, 3);
+    assert_eq!(file.architecture(), Some("llama"));
+    assert_eq!(file.name(), Some("Tiny Llama"));
+    assert_eq!(file.context_length(), Some(131072));
+    assert_eq!(file.embedding_length(), Some(2048));
+    assert_eq!(file.parameter_count(), 2048 * 1003);
+    assert!(file.chat_template().unwrap().starts_with("{% for"));
+    assert_eq!(
+        file.tokenizer(),
+        TokenizerInfo {
+            model: Some("gpt2".to_string()),
+            vocab_size: Some(3),
+            bos_token_id: Some(0),
+            eos_token_id: Some(1),
+            ..Default::default()
+        }
+    );
+
+    let details = file.details();
+    assert_eq!(details.format, "gguf");
+    assert_eq!(details.family, "llama");
+    assert_eq!(details.parameter_size, "2.05M");
+    assert_eq!(details.quantization_level, "Q4_K_M");
+
+    assert_eq!(file.tensors.len(), 2);
+    assert_eq!(file.tensors[1].type_name(), Some("Q6_K"));
+    assert_eq!(file.data_offset % DEFAULT_ALIGNMENT, 0);
+    assert!(file.data_offset >= bytes.len() as u64);
+}
+
+#[test]
+fn reads_every_value_type() {
+    let mut nested = Vec::new();
+    nested.extend(9u32.to_le_bytes());
+    nested.extend(2u64.to_le_bytes());
+    for items in [[1i16, -1], [2, -2]] {
+        nested.extend(3u32.to_le_bytes());
+        nested.extend(2u64.to_le_bytes());
+        for item in items {
+            nested.extend(item.to_le_bytes());
+        }
+    }
+    let bytes = Writer::default()
+        .kv("u8", 0, &[200])
+        .kv("i8", 1, &(-5i8).to_le_bytes())
+        .kv("u16", 2, &60000u16.to_le_bytes())
+        .kv("i16", 3, &(-300i16).to_le_bytes())
+        .kv("u32", 4, &7u32.to_le_bytes())
+        .kv("i32", 5, &(-7i32).to_le_bytes())
+        .kv("f32", 6, &0.5f32.to_le_bytes())
+        .kv("bool", 7, &[1])
+        .kv_str("string", "héllo")
+        .kv("nested", 9, &nested)
+        .kv("u64", 10, &u64::MAX.to_le_bytes())
+        .kv("i64", 11, &(-9i64).to_le_bytes())
+        .kv("f64", 12, &1e-6f64.to_le_bytes())
+        .finish();
+
+    let file = GgufFile::read(Cursor::new(bytes)).unwrap();
+    let get = |key| file.get(key).unwrap().clone();
+    assert_eq!(get("u8"), GgufValue::U8(200));
+    assert_eq!(get("i8"), GgufValue::I8(-5));
+    assert_eq!(get("u16"), GgufValue::U16(60000));
+    assert_eq!(get("i16"), GgufValue::I16(-300));
+    assert_eq!(get("u32").as_u64(), Some(7));
+    assert_eq!(get("i32").as_u64(), None);
+    assert_eq!(get("f32").as_f64(), Some(0.5));
+    assert_eq!(get("bool").as_bool(), Some(true));
+    assert_eq!(get("string").as_str(), Some("héllo"));
+    assert_eq!(get("u64"), GgufValue::U64(u64::MAX));
+    assert_eq!(get("i64"), GgufValue::I64(-9));
+    assert_eq!(get("f64"), GgufValue::F64(1e-6));
+    assert_eq!(
+        get("nested").as_array().unwrap()[1],
+        GgufValue::Array(vec![GgufValue::I16(2), GgufValue::I16(-2)])
+    );
+}
+
+#[test]
+fn rejects_invalid_files() {
+    let err = GgufFile::read(Cursor::new(b"GGML\x03\x00\x00\x00")).unwrap_err();
+    assert!(matches!(err, OllamaError::Gguf(m) if m == "not a GGUF file"));
+
+    let mut bytes = llama().finish();
+    bytes.truncate(bytes.len() - 10);
+    let err = GgufFile::read(Cursor::new(bytes)).unwrap_err();
+    assert!(matches!(err, OllamaError::Gguf(m) if m == "unexpected end of file"));
+
+    // Arrays of arrays of ... are rejected before they exhaust the stack
+    let mut nested = Vec::new();
+    for _ in 0..100_000 {
+        nested.extend(9u32.to_le_bytes());
+        nested.extend(1u64.to_le_bytes());
+    }
+    let bytes = Writer::default().kv("deep", 9, &nested).finish();
+    let err = GgufFile::read(Cursor::new(bytes)).unwrap_err();
+    assert!(matches!(err, OllamaError::Gguf(m) if m == "arrays nested more than 4 deep"));
+}
+
+#[test]
+fn huge_dimensions_saturate() {
+    let bytes = Writer::default()
+        .tensor("a", &[u64::MAX / 2, 4], 0)
+        .tensor("b", &[u64::MAX / 2], 0)
+        .tensor("c", &[u64::MAX / 2], 0)
+        .finish();
+    let file = GgufFile::read(Cursor::new(bytes)).unwrap();
+
+    assert_eq!(file.tensors[0].element_count(), u64::MAX);
+    assert_eq!(file.tensors[1].element_count(), u64::MAX / 2);
+    assert_eq!(file.parameter_count(), u64::MAX);
+}
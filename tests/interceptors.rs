@@ -0,0 +1,255 @@
+use std::sync::{Arc, Mutex};
+
+use async_trait::async_trait;
+use futures::TryStreamExt;
+use ollama_oxide::error::OllamaError;
+use ollama_oxide::interceptor::*;
+use ollama_oxide::models::*;
+use ollama_oxide::openai_compat::{
+    ChatCompletionChunk, ChatCompletionRequest, MessageContent, OpenAiMessage,
+};
+use ollama_oxide::testing::{StubResponse, StubServer, SAMPLE_COMPLETION};
+use reqwest::header::HeaderValue;
+
+/// Adds a tenant header and maps model aliases.
+struct Tenant;
+
+#[async_trait]
+impl Interceptor for Tenant {
+    async fn before_request(&self, context: &mut RequestContext<'_>) -> Result<(), OllamaError> {
+        context
+            .headers
+            .insert("x-tenant", HeaderValue::from_static("acme"));
+        let model = match &mut context.request {
+            Request::Chat(request) => &mut request.model,
+            Request::ChatCompletion(request) => &mut request.model,
+            _ => return Ok(()),
+        };
+        if model == "default" {
+            *model = "llama3.2".to_string();
+        }
+        Ok(())
+    }
+}
+
+/// Rejects models outside an allowlist.
+struct Allowlist(&'static [&'static str]);
+
+#[async_trait]
+impl Interceptor for Allowlist {
+    async fn before_request(&self, context: &mut RequestContext<'_>) -> Result<(), OllamaError> {
+        match context.request.model() {
+            Some(model) if !self.0.contains(&model) => Err(OllamaError::Rejected(format!(
+                "model '{}' is not allowed",
+                model
+            ))),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Records the hooks it sees.
+struct Audit {
+    name: &'static str,
+    log: Arc<Mutex<Vec<String>>>,
+}
+
+#[async_trait]
+impl Interceptor for Audit {
+    async fn before_request(&self, context: &mut RequestContext<'_>) -> Result<(), OllamaError> {
+        self.log
+            .lock()
+            .unwrap()
+            .push(format!("{} before {}", self.name, context.operation));
+        Ok(())
+    }
+
+    fn on_chunk(&self, _operation: &'static str, chunk: Chunk<'_>) -> Result<(), OllamaError> {
+        match chunk {
+            Chunk::Chat(chunk) => {
+                chunk.message.content = chunk.message.content.replace("blue", "[color]");
+            }
+            Chunk::ChatCompletion(chunk) => {
+                for choice in &mut chunk.choices {
+                    if let Some(content) = &mut choice.delta.content {
+                        *content = content.replace("blue", "[color]");
+                    }
+                }
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    async fn after_response(&self, context: &ResponseContext<'_>) {
+        let entry = match &context.result {
+            Ok(Response::Chat(response)) => format!("chat {}", response.message.content),
+            Ok(Response::Version(version)) => format!("version {}", version),
+            Ok(Response::ChatCompletion(completion)) => format!(
+                "{} {:?}",
+                context.endpoint, completion.choices[0].message.content
+            ),
+            Ok(_) => "ok".to_string(),
+            Err(error) => format!("error {}", error),
+        };
+        self.log
+            .lock()
+            .unwrap()
+            .push(format!("{} after {}", self.name, entry));
+    }
+}
+
+fn chat_request(model: &str) -> ChatRequest {
+    ChatRequest {
+        model: model.to_string(),
+        messages: vec![ChatMessage {
+            role: "user".to_string(),
+            content: "Why is the sky blue?".to_string(),
+            ..Default::default()
+        }],
+        ..Default::default()
+    }
+}
+
+#[tokio::test]
+async fn before_request_modifies_request_and_headers() {
+    let server = StubServer::start().await.unwrap();
+    let client = server.client().with_interceptor(Arc::new(Tenant));
+
+    let chunks: Vec<ChatResponse> = client
+        .chat(chat_request("default"))
+        .await
+        .unwrap()
+        .try_collect()
+        .await
+        .unwrap();
+    client.get_version().await.unwrap();
+
+    let requests = server.requests();
+    assert_eq!(requests[0].json()["model"], "llama3.2");
+    assert_eq!(chunks[0].model, "llama3.2");
+    assert!(requests
+        .iter()
+        .all(|request| request.header("x-tenant") == Some("acme")));
+}
+
+#[tokio::test]
+async fn rejected_request_is_not_sent() {
+    let server = StubServer::start().await.unwrap();
+    let client = server
+        .client()
+        .with_interceptor(Arc::new(Allowlist(&["llama3.2"])));
+
+    let err = client.show_model("mistral").await.unwrap_err();
+    assert!(matches!(&err, OllamaError::Rejected(m) if m.contains("mistral")));
+    assert!(client.chat(chat_request("mistral")).await.is_err());
+    assert!(server.requests().is_empty());
+
+    client.show_model("llama3.2").await.unwrap();
+    assert_eq!(server.requests().len(), 1);
+}
+
+#[tokio::test]
+async fn hooks_run_in_order_and_see_aggregated_stream() {
+    let server = StubServer::start().await.unwrap();
+    let log = Arc::new(Mutex::new(Vec::new()));
+    let client = server
+        .client()
+        .with_interceptor(Arc::new(Audit {
+            name: "outer",
+            log: log.clone(),
+        }))
+        .with_interceptor(Arc::new(Audit {
+            name: "inner",
+            log: log.clone(),
+        }));
+
+    let chunks: Vec<ChatResponse> = client
+        .chat(chat_request("llama3.2"))
+        .await
+        .unwrap()
+        .try_collect()
+        .await
+        .unwrap();
+
+    let text: String = chunks.iter().map(|c| c.message.content.as_str()).collect();
+    let expected = SAMPLE_COMPLETION.concat().replace("blue", "[color]");
+    assert_eq!(text, expected);
+    assert_eq!(
+        *log.lock().unwrap(),
+        [
+            "outer before chat".to_string(),
+            "inner before chat".to_string(),
+            format!("inner after chat {}", expected),
+            format!("outer after chat {}", expected),
+        ]
+    );
+}
+
+#[tokio::test]
+async fn after_response_sees_errors() {
+    let server = StubServer::start().await.unwrap();
+    server.respond("/api/version", StubResponse::error(500, "boom"));
+    let log = Arc::new(Mutex::new(Vec::new()));
+    let client = server.client().with_interceptor(Arc::new(Audit {
+        name: "audit",
+        log: log.clone(),
+    }));
+
+    client.get_version().await.unwrap_err();
+
+    let log = log.lock().unwrap();
+    assert_eq!(log[0], "audit before get_version");
+    assert!(log[1].starts_with("audit after error") && log[1].contains("boom"));
+}
+
+#[tokio::test]
+async fn openai_endpoints_are_intercepted() {
+    let server = StubServer::start().await.unwrap();
+    let log = Arc::new(Mutex::new(Vec::new()));
+    let client = server
+        .client()
+        .with_interceptor(Arc::new(Tenant))
+        .with_interceptor(Arc::new(Audit {
+            name: "audit",
+            log: log.clone(),
+        }));
+    let request = ChatCompletionRequest {
+        model: "default".to_string(),
+        messages: vec![OpenAiMessage {
+            role: "user".to_string(),
+            content: Some(MessageContent::Text("Why is the sky blue?".to_string())),
+            ..Default::default()
+        }],
+        ..Default::default()
+    };
+
+    let chunks: Vec<ChatCompletionChunk> = client
+        .openai()
+        .chat_completion_stream(request)
+        .await
+        .unwrap()
+        .try_collect()
+        .await
+        .unwrap();
+
+    let text: String = chunks
+        .iter()
+        .filter_map(|chunk| chunk.choices.first()?.delta.content.as_deref())
+        .collect();
+    let expected = SAMPLE_COMPLETION.concat().replace("blue", "[color]");
+    assert_eq!(text, expected);
+    let request = &server.requests()[0];
+    assert_eq!(request.json()["model"], "llama3.2");
+    assert_eq!(request.header("x-tenant"), Some("acme"));
+    assert_eq!(
+        *log.lock().unwrap(),
+        [
+            "audit before chat".to_string(),
+            format!(
+                "audit after /v1/chat/completions {:?}",
+                Some(MessageContent::Text(expected))
+            ),
+        ]
+    );
+}
@@ -0,0 +1,69 @@
+use futures::FutureExt;
+use ollama_oxide::error::OllamaError;
+use ollama_oxide::limiter::ConcurrencyLimiter;
+
+#[tokio::test]
+async fn model_limit_below_global_limit() {
+    let limiter = ConcurrencyLimiter::new(3).with_model_limit("big", 1);
+
+    let big = limiter.acquire("big").await.unwrap();
+    let mut waiting = Box::pin(limiter.acquire("big"));
+    assert!((&mut waiting).now_or_never().is_none());
+    assert_eq!(limiter.metrics().queued, 1);
+
+    // Other models only count against the global limit
+    let small = limiter.acquire("small").await.unwrap();
+    assert!(limiter.acquire("small").now_or_never().is_some());
+
+    drop(big);
+    let second = waiting.await.unwrap();
+    let metrics = limiter.metrics();
+    assert_eq!(metrics.acquired, 4);
+    assert_eq!(metrics.waited, 1);
+    assert_eq!(metrics.queued, 0);
+    drop((small, second));
+}
+
+#[tokio::test]
+async fn model_limit_holds_a_global_slot() {
+    let limiter = ConcurrencyLimiter::new(2).with_model_limit("big", 1);
+    let _big = limiter.acquire("big").await.unwrap();
+    let _small = limiter.acquire("small").await.unwrap();
+
+    assert!(limiter.acquire("other").now_or_never().is_none());
+    assert!(limiter.acquire("big").now_or_never().is_none());
+}
+
+#[tokio::test]
+async fn rejects_when_queue_is_full() {
+    let limiter = ConcurrencyLimiter::new(1).with_max_queue_depth(1);
+    let first = limiter.acquire("m").await.unwrap();
+    let mut waiting = Box::pin(limiter.acquire("m"));
+    assert!((&mut waiting).now_or_never().is_none());
+
+    let rejected = limiter.acquire("m").await;
+    assert!(matches!(rejected, Err(OllamaError::Overloaded(1))));
+    assert_eq!(limiter.metrics().rejected, 1);
+
+    drop(first);
+    drop(waiting.await.unwrap());
+    let metrics = limiter.metrics();
+    assert_eq!(metrics.acquired, 2);
+    assert_eq!(metrics.queued, 0);
+}
+
+#[tokio::test]
+async fn cancelled_waiters_leave_the_queue() {
+    let limiter = ConcurrencyLimiter::unbounded()
+        .with_model_limit("m", 1)
+        .with_max_queue_depth(1);
+    let _first = limiter.acquire("m").await.unwrap();
+
+    assert!(limiter.acquire("m").now_or_never().is_none());
+    assert_eq!(limiter.metrics().queued, 0);
+
+    let mut waiting = Box::pin(limiter.acquire("m"));
+    assert!((&mut waiting).now_or_never().is_none());
+    assert_eq!(limiter.metrics().queued, 1);
+    assert_eq!(limiter.metrics().rejected, 0);
+}
@@ -0,0 +1,136 @@
+use futures::TryStreamExt;
+use ollama_oxide::logprobs::*;
+use ollama_oxide::models::{ChatMessage, ChatRequest, ChatResponse, GenerateRequest, Logprob};
+use ollama_oxide::openai_compat::ChatCompletion;
+use ollama_oxide::testing::{StubServer, SAMPLE_COMPLETION, SAMPLE_LOGPROBS};
+
+fn close(a: f64, b: f64) -> bool {
+    (a - b).abs() < 1e-9
+}
+
+#[tokio::test]
+async fn generate_returns_logprobs_per_chunk() {
+    let server = StubServer::start().await.unwrap();
+    let request = GenerateRequest {
+        model: "llama3.2".to_string(),
+        prompt: "Why is the sky blue?".to_string(),
+        logprobs: Some(true),
+        top_logprobs: Some(3),
+        ..Default::default()
+    };
+    let chunks: Vec<_> = server
+        .client()
+        .generate(request)
+        .await
+        .unwrap()
+        .try_collect()
+        .await
+        .unwrap();
+
+    let body = server.requests()[0].json();
+    assert_eq!(
+        (body["logprobs"].clone(), body["top_logprobs"].clone()),
+        (true.into(), 3.into())
+    );
+    let tokens: Vec<Logprob> = chunks
+        .into_iter()
+        .flat_map(|chunk| chunk.logprobs.unwrap_or_default())
+        .collect();
+    let text: Vec<&str> = tokens.iter().map(|token| token.token.as_str()).collect();
+    assert_eq!(text, SAMPLE_COMPLETION);
+    assert_eq!(tokens[1].bytes.as_deref(), Some(" sky".as_bytes()));
+
+    let first = &tokens[0];
+    assert_eq!(first.top_logprobs.as_ref().unwrap().len(), 3);
+    assert!(close(first.probability_of("The").unwrap(), (-0.5f64).exp()));
+    assert!(close(
+        first.probability_of("alt1").unwrap(),
+        (-3.0f64).exp()
+    ));
+    assert_eq!(first.probability_of("A"), None);
+    assert!(close(
+        first.margin().unwrap(),
+        (-0.5f64).exp() - (-3.0f64).exp()
+    ));
+}
+
+#[tokio::test]
+async fn chat_logprobs_are_optional() {
+    let server = StubServer::start().await.unwrap();
+    let mut request = ChatRequest {
+        model: "llama3.2".to_string(),
+        messages: vec![ChatMessage {
+            role: "user".to_string(),
+            content: "Hi".to_string(),
+            ..Default::default()
+        }],
+        ..Default::default()
+    };
+    let chunks: Vec<_> = server
+        .client()
+        .chat(request.clone())
+        .await
+        .unwrap()
+        .try_collect()
+        .await
+        .unwrap();
+    assert!(chunks.iter().all(|chunk| chunk.logprobs.is_none()));
+
+    request.logprobs = Some(true);
+    let chunks: Vec<_> = server
+        .client()
+        .chat(request)
+        .await
+        .unwrap()
+        .try_collect()
+        .await
+        .unwrap();
+    let tokens: Vec<Logprob> = chunks
+        .iter()
+        .flat_map(|chunk| chunk.logprobs.clone().unwrap_or_default())
+        .collect();
+    assert_eq!(confidences(&tokens).len(), SAMPLE_COMPLETION.len());
+    assert_eq!(tokens[0].top_logprobs, None);
+    assert_eq!(tokens[0].margin(), None);
+
+    // The logprobs survive the conversion to and from `/v1` completions
+    let response = ChatResponse {
+        done: true,
+        logprobs: Some(tokens.clone()),
+        ..Default::default()
+    };
+    let completion = ChatCompletion::from(response);
+    assert_eq!(
+        completion.choices[0]
+            .logprobs
+            .as_ref()
+            .unwrap()
+            .content
+            .as_ref(),
+        Some(&tokens)
+    );
+    let native = ChatResponse::from(completion);
+    assert_eq!(native.logprobs, Some(tokens));
+}
+
+#[test]
+fn sequence_statistics() {
+    let tokens: Vec<Logprob> = SAMPLE_LOGPROBS
+        .iter()
+        .map(|&logprob| Logprob {
+            token: "t".to_string(),
+            logprob,
+            ..Default::default()
+        })
+        .collect();
+
+    assert!(close(log_likelihood(&tokens), -1.875));
+    assert!(close(mean_logprob(&tokens).unwrap(), -1.875 / 4.0));
+    assert!(close(perplexity(&tokens).unwrap(), (1.875f64 / 4.0).exp()));
+    assert!(close(confidences(&tokens)[3], (-1.0f64).exp()));
+    assert_eq!(uncertain_tokens(&tokens, 0.5), [3]);
+
+    assert_eq!(log_likelihood(&[]), 0.0);
+    assert_eq!(mean_logprob(&[]), None);
+    assert_eq!(perplexity(&[]), None);
+}
@@ -0,0 +1,208 @@
+#![cfg(feature = "metrics")]
+
+use std::future::Future;
+
+use futures::{StreamExt, TryStreamExt};
+use metrics_util::debugging::{DebugValue, DebuggingRecorder};
+use metrics_util::CompositeKey;
+use ollama_oxide::embeddings::EmbedBatchOptions;
+use ollama_oxide::meter::*;
+use ollama_oxide::models::*;
+use ollama_oxide::testing::{StubResponse, StubServer, SAMPLE_COMPLETION};
+
+type Snapshot = Vec<(CompositeKey, DebugValue)>;
+
+/// Runs `test` on a current-thread runtime with a recorder local to this
+/// thread, so tests running in parallel do not see each other's metrics.
+/// Histograms are drained by a snapshot, so it is taken once at the end.
+fn with_recorder<F: Future<Output = ()>>(test: impl FnOnce() -> F) -> Snapshot {
+    let recorder = DebuggingRecorder::new();
+    let snapshotter = recorder.snapshotter();
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .unwrap();
+    metrics::with_local_recorder(&recorder, || runtime.block_on(test()));
+    snapshotter
+        .snapshot()
+        .into_vec()
+        .into_iter()
+        .map(|(key, _, _, value)| (key, value))
+        .collect()
+}
+
+fn metric<'a>(
+    snapshot: &'a Snapshot,
+    name: &str,
+    labels: &[(&str, &str)],
+) -> Option<&'a DebugValue> {
+    snapshot
+        .iter()
+        .find(|(key, _)| {
+            let key = key.key();
+            key.name() == name
+                && key.labels().count() == labels.len()
+                && labels.iter().all(|(name, value)| {
+                    key.labels()
+                        .any(|label| label.key() == *name && label.value() == *value)
+                })
+        })
+        .map(|(_, value)| value)
+}
+
+fn chat_request() -> ChatRequest {
+    ChatRequest {
+        model: "llama3.2".to_string(),
+        messages: vec![ChatMessage {
+            role: "user".to_string(),
+            content: "Why is the sky blue?".to_string(),
+            ..Default::default()
+        }],
+        ..Default::default()
+    }
+}
+
+#[test]
+fn streamed_chat_records_requests_tokens_and_latency() {
+    let snapshot = with_recorder(|| async {
+        let server = StubServer::start().await.unwrap();
+        let _: Vec<ChatResponse> = server
+            .client()
+            .chat(chat_request())
+            .await
+            .unwrap()
+            .try_collect()
+            .await
+            .unwrap();
+    });
+
+    let call = [
+        ("endpoint", "/api/chat"),
+        ("model", "llama3.2"),
+        ("outcome", "success"),
+    ];
+    let tokens = [("endpoint", "/api/chat"), ("model", "llama3.2")];
+    assert_eq!(
+        metric(&snapshot, REQUESTS, &call),
+        Some(&DebugValue::Counter(1))
+    );
+    assert!(matches!(
+        metric(&snapshot, REQUEST_DURATION, &call),
+        Some(DebugValue::Histogram(values)) if values.len() == 1
+    ));
+    assert!(matches!(
+        metric(&snapshot, TIME_TO_FIRST_TOKEN, &tokens),
+        Some(DebugValue::Histogram(values)) if values.len() == 1
+    ));
+    assert_eq!(
+        metric(&snapshot, PROMPT_TOKENS, &tokens),
+        Some(&DebugValue::Counter(26))
+    );
+    assert_eq!(
+        metric(&snapshot, EVAL_TOKENS, &tokens),
+        Some(&DebugValue::Counter(SAMPLE_COMPLETION.len() as u64))
+    );
+    assert!(matches!(
+        metric(&snapshot, STREAMS_IN_FLIGHT, &[("endpoint", "/api/chat")]),
+        Some(DebugValue::Gauge(value)) if value.0 == 0.0
+    ));
+}
+
+#[test]
+fn pull_records_transferred_bytes() {
+    let snapshot = with_recorder(|| async {
+        let server = StubServer::start().await.unwrap();
+        let _: Vec<PullResponse> = server
+            .client()
+            .pull_model("llama3.2")
+            .await
+            .unwrap()
+            .try_collect()
+            .await
+            .unwrap();
+    });
+
+    assert_eq!(
+        metric(
+            &snapshot,
+            TRANSFER_BYTES,
+            &[("endpoint", "/api/pull"), ("model", "llama3.2")]
+        ),
+        Some(&DebugValue::Counter(2048))
+    );
+}
+
+#[test]
+fn failures_and_dropped_streams_are_labelled() {
+    let snapshot = with_recorder(|| async {
+        let server = StubServer::start().await.unwrap();
+        server.respond(
+            "/api/show",
+            StubResponse::error(404, "model 'nope' not found"),
+        );
+        server.client().show_model("nope").await.unwrap_err();
+
+        let mut stream = server.client().chat(chat_request()).await.unwrap();
+        stream.next().await.unwrap().unwrap();
+    });
+
+    assert_eq!(
+        metric(
+            &snapshot,
+            REQUESTS,
+            &[
+                ("endpoint", "/api/show"),
+                ("model", "nope"),
+                ("outcome", "api_error")
+            ]
+        ),
+        Some(&DebugValue::Counter(1))
+    );
+    assert_eq!(
+        metric(
+            &snapshot,
+            REQUESTS,
+            &[
+                ("endpoint", "/api/chat"),
+                ("model", "llama3.2"),
+                ("outcome", "cancelled")
+            ]
+        ),
+        Some(&DebugValue::Counter(1))
+    );
+    assert!(matches!(
+        metric(&snapshot, STREAMS_IN_FLIGHT, &[("endpoint", "/api/chat")]),
+        Some(DebugValue::Gauge(value)) if value.0 == 0.0
+    ));
+}
+
+#[test]
+fn batches_count_each_request_once() {
+    let snapshot = with_recorder(|| async {
+        let server = StubServer::start().await.unwrap();
+        let inputs = ["a", "b", "c"].map(String::from).to_vec();
+        let options = EmbedBatchOptions {
+            chunk_size: 2,
+            ..Default::default()
+        };
+        server
+            .client()
+            .embed_batch("llama3.2", inputs, options)
+            .await
+            .unwrap();
+    });
+
+    let call = [
+        ("endpoint", "/api/embed"),
+        ("model", "llama3.2"),
+        ("outcome", "success"),
+    ];
+    assert_eq!(
+        metric(&snapshot, REQUESTS, &call),
+        Some(&DebugValue::Counter(2))
+    );
+    assert!(matches!(
+        metric(&snapshot, REQUEST_DURATION, &call),
+        Some(DebugValue::Histogram(values)) if values.len() == 2
+    ));
+}
@@ -0,0 +1,123 @@
+use futures::TryStreamExt;
+use ollama_oxide::api::OllamaApi;
+use ollama_oxide::error::OllamaError;
+use ollama_oxide::mock::{MockOllama, RecordedRequest};
+use ollama_oxide::models::*;
+
+fn generate_request() -> GenerateRequest {
+    GenerateRequest {
+        model: "llama3.2".to_string(),
+        prompt: "Why is the sky blue?".to_string(),
+        ..Default::default()
+    }
+}
+
+fn chunk(text: &str, done: bool) -> GenerateResponse {
+    GenerateResponse {
+        model: "llama3.2".to_string(),
+        response: text.to_string(),
+        done,
+        ..Default::default()
+    }
+}
+
+#[tokio::test]
+async fn replies_in_scripted_order() {
+    let mock = MockOllama::new();
+    mock.on_get_version(Ok("0.5.7".to_string()))
+        .on_get_version(Ok("0.6.0".to_string()))
+        .on_list_models(Ok(vec![ModelInfo {
+            name: "llama3.2:latest".to_string(),
+            ..Default::default()
+        }]));
+
+    assert_eq!(mock.get_version().await.unwrap(), "0.5.7");
+    assert_eq!(mock.get_version().await.unwrap(), "0.6.0");
+    assert_eq!(mock.list_models().await.unwrap()[0].name, "llama3.2:latest");
+}
+
+#[tokio::test]
+async fn streams_scripted_chunks_and_errors() {
+    let mock = MockOllama::new();
+    mock.on_generate(Ok(vec![
+        Ok(chunk("The sky", false)),
+        Ok(chunk(" is blue.", true)),
+    ]))
+    .on_generate(Ok(vec![
+        Ok(chunk("The", false)),
+        Err(OllamaError::ApiError("model unloaded".to_string())),
+    ]))
+    .on_chat(Err(OllamaError::ApiError("model not found".to_string())));
+
+    let chunks: Vec<_> = mock
+        .generate(generate_request())
+        .await
+        .unwrap()
+        .try_collect()
+        .await
+        .unwrap();
+    let text: String = chunks.iter().map(|chunk| chunk.response.as_str()).collect();
+    assert_eq!(text, "The sky is blue.");
+    assert!(chunks[1].done);
+
+    let failed: Result<Vec<_>, _> = mock
+        .generate(generate_request())
+        .await
+        .unwrap()
+        .try_collect()
+        .await;
+    assert!(matches!(failed, Err(OllamaError::ApiError(message)) if message == "model unloaded"));
+
+    let chat = mock.chat(ChatRequest::default()).await;
+    assert!(matches!(chat, Err(OllamaError::ApiError(message)) if message == "model not found"));
+}
+
+#[tokio::test]
+async fn records_requests() {
+    let mock = MockOllama::new();
+    mock.on_show_model(Ok(ShowModelResponse::default()))
+        .on_copy_model(Ok(()))
+        .on_generate(Ok(Vec::new()));
+
+    mock.show_model("llama3.2").await.unwrap();
+    mock.copy_model("llama3.2", "backup").await.unwrap();
+    let _ = mock.generate(generate_request()).await.unwrap();
+
+    let requests = mock.requests();
+    assert_eq!(requests.len(), 3);
+    assert!(matches!(&requests[0], RecordedRequest::ShowModel(name) if name == "llama3.2"));
+    assert!(matches!(
+        &requests[1],
+        RecordedRequest::CopyModel(request)
+            if request.source == "llama3.2" && request.destination == "backup"
+    ));
+    assert!(matches!(
+        &requests[2],
+        RecordedRequest::Generate(request) if request.prompt == "Why is the sky blue?"
+    ));
+
+    assert_eq!(mock.take_requests().len(), 3);
+    assert!(mock.requests().is_empty());
+}
+
+#[tokio::test]
+async fn unscripted_endpoints_fail() {
+    let mock = MockOllama::new();
+    mock.on_delete_model(Ok(()));
+    mock.delete_model("llama3.2").await.unwrap();
+
+    let error = mock.delete_model("llama3.2").await.unwrap_err();
+    assert!(
+        matches!(&error, OllamaError::ApiError(message) if message.contains("delete_model")),
+        "{error:?}"
+    );
+    let error = mock
+        .generate_embeddings(EmbedRequest::default())
+        .await
+        .unwrap_err();
+    assert!(
+        matches!(error, OllamaError::ApiError(message) if message.contains("generate_embeddings"))
+    );
+    // Failed calls are recorded too
+    assert_eq!(mock.requests().len(), 3);
+}
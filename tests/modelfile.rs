@@ -0,0 +1,79 @@
+use ollama_oxide::error::OllamaError;
+use ollama_oxide::modelfile::Modelfile;
+use serde_json::json;
+
+const MODELFILE: &str = r#"
+# A pirate assistant
+FROM llama3.2
+PARAMETER temperature 0.7
+PARAMETER num_ctx 4096
+PARAMETER stop "<|eot_id|>"
+PARAMETER stop "User:"
+SYSTEM """You are a pirate.
+Answer every question in character."""
+MESSAGE user Who are you?
+MESSAGE assistant "Captain Oxide, at yer service."
+"#;
+
+#[test]
+fn parses_instructions() {
+    let modelfile = Modelfile::parse(MODELFILE).unwrap();
+
+    assert_eq!(modelfile.from, "llama3.2");
+    assert_eq!(modelfile.parameters.len(), 4);
+    assert_eq!(
+        modelfile.system.as_deref(),
+        Some("You are a pirate.\nAnswer every question in character.")
+    );
+    assert_eq!(modelfile.messages.len(), 2);
+    assert_eq!(modelfile.messages[1].role, "assistant");
+    assert_eq!(
+        modelfile.messages[1].content,
+        "Captain Oxide, at yer service."
+    );
+}
+
+#[test]
+fn create_request_types_parameters_and_groups_stop() {
+    let request = Modelfile::parse(MODELFILE)
+        .unwrap()
+        .into_create_request("pirate");
+
+    assert_eq!(request.model, "pirate");
+    assert_eq!(request.from.as_deref(), Some("llama3.2"));
+    assert_eq!(
+        request.parameters,
+        Some(json!({
+            "temperature": 0.7,
+            "num_ctx": 4096,
+            "stop": ["<|eot_id|>", "User:"],
+        }))
+    );
+    assert_eq!(request.messages.map(|messages| messages.len()), Some(2));
+
+    let text = "FROM llama3.2\nPARAMETER stop 42\nPARAMETER stop true\nPARAMETER mirostat_tau inf";
+    let request = Modelfile::parse(text)
+        .unwrap()
+        .into_create_request("strings");
+    assert_eq!(
+        request.parameters,
+        Some(json!({ "stop": ["42", "true"], "mirostat_tau": "inf" }))
+    );
+}
+
+#[test]
+fn rejects_local_files_and_adapters() {
+    for text in ["FROM ./model.gguf", "FROM llama3.2\nADAPTER ./lora.gguf"] {
+        let err = Modelfile::parse(text).unwrap_err();
+        assert!(matches!(err, OllamaError::Modelfile(_)), "{err:?}");
+    }
+}
+
+#[test]
+fn reports_line_of_error() {
+    let err = Modelfile::parse("FROM llama3.2\n\nSYSTEM \"\"\"never closed").unwrap_err();
+    assert!(err.to_string().contains("line 3"), "{err}");
+
+    let err = Modelfile::parse("PARAMETER temperature 0.1").unwrap_err();
+    assert!(err.to_string().contains("missing FROM"), "{err}");
+}
@@ -0,0 +1,303 @@
+use futures::TryStreamExt;
+use ollama_oxide::error::OllamaError;
+use ollama_oxide::models::*;
+use ollama_oxide::openai_compat::*;
+use ollama_oxide::testing::{stub_embedding, StubResponse, StubServer, SAMPLE_COMPLETION};
+use serde_json::json;
+
+fn chat_request() -> ChatCompletionRequest {
+    ChatCompletionRequest {
+        model: "llama3.2".to_string(),
+        messages: vec![OpenAiMessage {
+            role: "user".to_string(),
+            content: Some(MessageContent::Text("Why is the sky blue?".to_string())),
+            ..Default::default()
+        }],
+        ..Default::default()
+    }
+}
+
+#[tokio::test]
+async fn chat_completion_returns_message_and_usage() {
+    let server = StubServer::start().await.unwrap();
+    let completion = server
+        .client()
+        .openai()
+        .chat_completion(chat_request())
+        .await
+        .unwrap();
+
+    assert_eq!(
+        completion.choices[0].message.content,
+        Some(MessageContent::Text(SAMPLE_COMPLETION.concat()))
+    );
+    assert_eq!(completion.choices[0].finish_reason.as_deref(), Some("stop"));
+    assert_eq!(completion.usage.unwrap().completion_tokens, 4);
+    let request = &server.requests()[0];
+    assert_eq!(request.path, "/v1/chat/completions");
+    assert_eq!(request.json()["stream"], false);
+    assert_eq!(
+        request.json()["messages"][0]["content"],
+        "Why is the sky blue?"
+    );
+}
+
+#[tokio::test]
+async fn chat_completion_stream_parses_sse_until_done() {
+    let server = StubServer::start().await.unwrap();
+    let request = ChatCompletionRequest {
+        stream_options: Some(StreamOptions {
+            include_usage: true,
+        }),
+        ..chat_request()
+    };
+    let chunks: Vec<ChatCompletionChunk> = server
+        .client()
+        .openai()
+        .chat_completion_stream(request)
+        .await
+        .unwrap()
+        .try_collect()
+        .await
+        .unwrap();
+
+    let text: String = chunks
+        .iter()
+        .flat_map(|chunk| &chunk.choices)
+        .filter_map(|choice| choice.delta.content.as_deref())
+        .collect();
+    assert_eq!(text, SAMPLE_COMPLETION.concat());
+    assert_eq!(chunks.len(), SAMPLE_COMPLETION.len() + 2);
+    assert_eq!(
+        chunks.last().unwrap().usage.as_ref().unwrap().prompt_tokens,
+        26
+    );
+    assert_eq!(server.requests()[0].json()["stream"], true);
+}
+
+#[tokio::test]
+async fn sse_stream_handles_fragments_and_errors() {
+    let server = StubServer::start().await.unwrap();
+    let events: Vec<_> = (0..5)
+        .map(|i| {
+            json!({
+                "id": "c", "object": "chat.completion.chunk", "created": 0, "model": "m",
+                "choices": [{ "index": 0, "delta": { "content": format!("{i}") }, "finish_reason": null }],
+            })
+        })
+        .collect();
+    server.respond_once(
+        "/v1/chat/completions",
+        StubResponse::sse(events.clone()).fragmented(5),
+    );
+    server.respond_once(
+        "/v1/chat/completions",
+        StubResponse::sse(events).with_stream_error_after(2, "model crashed"),
+    );
+    let openai = server.client();
+    let openai = openai.openai();
+
+    let chunks: Vec<ChatCompletionChunk> = openai
+        .chat_completion_stream(chat_request())
+        .await
+        .unwrap()
+        .try_collect()
+        .await
+        .unwrap();
+    assert_eq!(chunks.len(), 5);
+    assert_eq!(chunks[3].choices[0].delta.content.as_deref(), Some("3"));
+
+    let results: Vec<_> =
+        futures::StreamExt::collect(openai.chat_completion_stream(chat_request()).await.unwrap())
+            .await;
+    assert_eq!(results.len(), 3);
+    assert!(
+        matches!(&results[2], Err(OllamaError::ApiError(message)) if message == "model crashed")
+    );
+}
+
+#[tokio::test]
+async fn completion_streams_text() {
+    let server = StubServer::start().await.unwrap();
+    let request = CompletionRequest {
+        model: "llama3.2".to_string(),
+        prompt: "The sky".to_string(),
+        ..Default::default()
+    };
+    let openai = server.client();
+    let openai = openai.openai();
+
+    let completion = openai.completion(request.clone()).await.unwrap();
+    assert_eq!(completion.choices[0].text, SAMPLE_COMPLETION.concat());
+
+    let chunks: Vec<Completion> = openai
+        .completion_stream(request)
+        .await
+        .unwrap()
+        .try_collect()
+        .await
+        .unwrap();
+    let text: String = chunks
+        .iter()
+        .map(|chunk| chunk.choices[0].text.as_str())
+        .collect();
+    assert_eq!(text, SAMPLE_COMPLETION.concat());
+}
+
+#[tokio::test]
+async fn embeddings_and_models() {
+    let server = StubServer::start().await.unwrap();
+    let client = server.client();
+    let openai = client.openai();
+
+    let response = openai
+        .embeddings(EmbeddingsRequest {
+            model: "all-minilm".to_string(),
+            input: EmbedInput::Multiple(vec!["a".to_string(), "b".to_string()]),
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+    assert_eq!(response.data[1].index, 1);
+    assert_eq!(response.data[1].embedding, stub_embedding("b"));
+
+    let models = openai.list_models().await.unwrap();
+    assert_eq!(models.data[0].id, "llama3.2:latest");
+    let model = openai.retrieve_model("llama3.2:latest").await.unwrap();
+    assert_eq!(model.owned_by, "library");
+    assert_eq!(server.requests()[2].path, "/v1/models/llama3.2:latest");
+}
+
+#[tokio::test]
+async fn error_status_is_api_error() {
+    let server = StubServer::start().await.unwrap();
+    server.respond(
+        "/v1/chat/completions",
+        StubResponse::json(
+            json!({ "error": { "message": "model \"nope\" not found", "type": "api_error" } }),
+        )
+        .with_status(404),
+    );
+
+    let err = server
+        .client()
+        .openai()
+        .chat_completion(chat_request())
+        .await
+        .unwrap_err();
+    assert!(matches!(err, OllamaError::ApiError(message) if message.contains("not found")));
+}
+
+#[test]
+fn chat_request_round_trips() {
+    let native = ChatRequest {
+        model: "llama3.2".to_string(),
+        messages: vec![ChatMessage {
+            role: "assistant".to_string(),
+            content: "Looking".to_string(),
+            images: Some(vec![
+                "iVBORw0KGgo=".to_string(),
+                "/9j/4AAQSkZJRg==".to_string(),
+                "R0lGODlhAQABAA==".to_string(),
+                "UklGRiQAAABXRUJQVlA4IA==".to_string(),
+            ]),
+            tool_calls: Some(vec![ToolCall {
+                function: FunctionCall {
+                    name: "get_weather".to_string(),
+                    arguments: json!({ "city": "Paris" }),
+                },
+            }]),
+        }],
+        format: Some("json".to_string()),
+        options: Some(GenerateOptions {
+            temperature: Some(0.2),
+            num_predict: Some(64),
+            seed: Some(42),
+            ..Default::default()
+        }),
+        ..Default::default()
+    };
+
+    let openai = ChatCompletionRequest::from(native.clone());
+    assert_eq!(openai.max_tokens, Some(64));
+    assert_eq!(openai.seed, Some(42));
+    assert_eq!(
+        openai.response_format,
+        Some(json!({ "type": "json_object" }))
+    );
+    let call = &openai.messages[0].tool_calls.as_ref().unwrap()[0];
+    assert_eq!(call.function.arguments, r#"{"city":"Paris"}"#);
+    let urls = serde_json::to_value(&openai.messages[0].content).unwrap();
+    let types: Vec<_> = urls.as_array().unwrap()[1..]
+        .iter()
+        .map(|part| {
+            let url = part["image_url"]["url"].as_str().unwrap();
+            url.split(';').next().unwrap().to_string()
+        })
+        .collect();
+    assert_eq!(
+        types,
+        [
+            "data:image/png",
+            "data:image/jpeg",
+            "data:image/gif",
+            "data:image/webp"
+        ]
+    );
+
+    let back = ChatRequest::from(openai);
+    assert_eq!(
+        serde_json::to_value(&back).unwrap(),
+        serde_json::to_value(&native).unwrap()
+    );
+
+    // `stop` may be a single string
+    let request: ChatCompletionRequest =
+        serde_json::from_value(json!({ "model": "m", "messages": [], "stop": "\n" })).unwrap();
+    assert_eq!(request.stop, Some(vec!["\n".to_string()]));
+    let request: CompletionRequest =
+        serde_json::from_value(json!({ "model": "m", "prompt": "", "stop": ["a", "b"] })).unwrap();
+    assert_eq!(request.stop, Some(vec!["a".to_string(), "b".to_string()]));
+}
+
+#[test]
+fn chat_response_conversions() {
+    let response = ChatResponse {
+        model: "llama3.2".to_string(),
+        created_at: "2025-01-01T01:00:00.5+01:00".to_string(),
+        message: ChatMessage {
+            role: "assistant".to_string(),
+            content: "Hi".to_string(),
+            ..Default::default()
+        },
+        done: true,
+        prompt_eval_count: Some(10),
+        eval_count: Some(2),
+        ..Default::default()
+    };
+
+    let completion = ChatCompletion::from(response);
+    assert_eq!(completion.created, 1735689600);
+    assert_eq!(completion.usage.as_ref().unwrap().total_tokens, 12);
+    assert_eq!(completion.choices[0].finish_reason.as_deref(), Some("stop"));
+
+    let native = ChatResponse::from(completion);
+    assert_eq!(native.created_at, "2025-01-01T00:00:00Z");
+    assert_eq!(native.message.content, "Hi");
+    assert!(native.done);
+
+    let chunk = ChatCompletionChunk {
+        model: "m".to_string(),
+        choices: vec![ChunkChoice {
+            delta: ChatDelta {
+                content: Some("x".to_string()),
+                ..Default::default()
+            },
+            ..Default::default()
+        }],
+        ..Default::default()
+    };
+    let native = ChatResponse::from(chunk);
+    assert_eq!(native.message.role, "assistant");
+    assert!(!native.done);
+}
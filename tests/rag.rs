@@ -0,0 +1,178 @@
+use futures::TryStreamExt;
+use ollama_oxide::embeddings::EmbeddingIndex;
+use ollama_oxide::mock::{MockOllama, RecordedRequest};
+use ollama_oxide::models::*;
+use ollama_oxide::rag::*;
+use ollama_oxide::testing::{stub_embedding, StubServer, SAMPLE_COMPLETION};
+
+const GUIDE: &str = "# Install\n\
+Download the binary.\n\
+\n\
+## Linux\n\
+Run the install script. It sets up a service.\n\
+\n\
+```sh\n\
+# not a heading\n\
+curl ...\n\
+```\n\
+\n\
+# Usage\n\
+Run `ollama serve`.\n";
+
+#[test]
+fn character_splitter_overlaps_on_word_boundaries() {
+    let chunks = CharacterSplitter::new(12, 6).split("alpha beta gamma delta echo");
+    let texts: Vec<&str> = chunks.iter().map(|chunk| chunk.text.as_str()).collect();
+
+    assert_eq!(
+        texts,
+        ["alpha beta", "beta gamma", "gamma delta", "delta echo"]
+    );
+    assert_eq!(chunks[1].offset, 6);
+    assert!(CharacterSplitter::new(5, 0).split("").is_empty());
+}
+
+#[test]
+fn sentence_splitter_packs_sentences() {
+    let text = "One is short. Two is short too! Three? A much longer fourth sentence follows.";
+    let texts: Vec<String> = SentenceSplitter::new(32)
+        .split(text)
+        .into_iter()
+        .map(|chunk| chunk.text)
+        .collect();
+
+    assert_eq!(
+        texts,
+        [
+            "One is short. Two is short too!",
+            "Three?",
+            "A much longer fourth sentence follows."
+        ]
+    );
+}
+
+#[test]
+fn markdown_splitter_tracks_heading_path() {
+    let chunks = MarkdownHeadingSplitter::new().split(GUIDE);
+    let headings: Vec<Option<&str>> = chunks
+        .iter()
+        .map(|chunk| chunk.heading.as_deref())
+        .collect();
+
+    assert_eq!(
+        headings,
+        [Some("Install"), Some("Install > Linux"), Some("Usage")]
+    );
+    assert!(chunks[1].text.contains("# not a heading"));
+    assert_eq!(&GUIDE[chunks[2].offset..chunks[2].offset + 7], "# Usage");
+
+    let small = MarkdownHeadingSplitter::new()
+        .with_max_chars(40)
+        .split(GUIDE);
+    assert!(
+        small
+            .iter()
+            .filter(|chunk| chunk.heading.as_deref() == Some("Install > Linux"))
+            .count()
+            > 1
+    );
+}
+
+#[tokio::test]
+async fn ingest_replaces_previous_chunks_of_a_source() {
+    let server = StubServer::start().await.unwrap();
+    let client = server.client();
+    let pipeline = IngestPipeline::new("all-minilm", MarkdownHeadingSplitter::new());
+    let mut index = EmbeddingIndex::default();
+
+    assert_eq!(
+        pipeline
+            .ingest(&client, &mut index, "guide.md", GUIDE)
+            .await
+            .unwrap(),
+        3
+    );
+    pipeline
+        .ingest(&client, &mut index, "other.md", "# Other\nText.")
+        .await
+        .unwrap();
+    assert_eq!(
+        pipeline
+            .ingest(&client, &mut index, "guide.md", "# Only\nOne section.")
+            .await
+            .unwrap(),
+        1
+    );
+
+    assert_eq!(index.len(), 2);
+    let entry = index.get("guide.md#0").unwrap();
+    assert_eq!(entry.metadata.heading.as_deref(), Some("Only"));
+    assert_eq!(entry.vector, stub_embedding("# Only\nOne section."));
+}
+
+#[tokio::test]
+async fn rag_chat_cites_retrieved_chunks() {
+    let server = StubServer::start().await.unwrap();
+    let client = server.client();
+    let mut index = EmbeddingIndex::default();
+    IngestPipeline::new("all-minilm", MarkdownHeadingSplitter::new())
+        .ingest(&client, &mut index, "guide.md", GUIDE)
+        .await
+        .unwrap();
+
+    let rag = RagChat::new(&client, &index, "all-minilm", "llama3.2")
+        .with_top_k(2)
+        .with_system("Be brief.");
+    let answer = rag.ask("Run `ollama serve`.").await.unwrap();
+
+    assert_eq!(answer.citations.len(), 2);
+    assert_eq!(answer.citations[0].number, 1);
+    assert_eq!(answer.citations[0].id, "guide.md#2");
+    let chunks: Vec<ChatResponse> = answer.stream.try_collect().await.unwrap();
+    let text: String = chunks
+        .iter()
+        .map(|chunk| chunk.message.content.as_str())
+        .collect();
+    assert_eq!(text, SAMPLE_COMPLETION.concat());
+
+    let body = server.requests().pop().unwrap().json();
+    assert_eq!(body["model"], "llama3.2");
+    assert_eq!(body["messages"][0]["content"], "Be brief.");
+    let prompt = body["messages"][1]["content"].as_str().unwrap();
+    assert!(prompt.contains("[1] (guide.md, Usage)\n# Usage\nRun `ollama serve`."));
+    assert!(prompt.ends_with("Question: Run `ollama serve`."));
+}
+
+#[tokio::test]
+async fn rag_chat_uses_custom_template() {
+    let mock = MockOllama::new();
+    mock.on_generate_embeddings(Ok(EmbedResponse {
+        embeddings: vec![vec![1.0, 0.0]],
+        ..Default::default()
+    }))
+    .on_chat(Ok(vec![]));
+    let mut index = EmbeddingIndex::default();
+    let metadata = ChunkMetadata {
+        source: "notes".to_string(),
+        index: 0,
+        offset: 0,
+        heading: None,
+        // Placeholders in passages are not substituted
+        text: "The sky is blue in {question}.".to_string(),
+    };
+    index.insert("notes#0", vec![1.0, 0.0], metadata).unwrap();
+
+    RagChat::new(&mock, &index, "e", "c")
+        .with_template("{question}\n---\n{context}")
+        .ask("Colour?")
+        .await
+        .unwrap();
+
+    match mock.requests().pop() {
+        Some(RecordedRequest::Chat(request)) => assert_eq!(
+            request.messages[0].content,
+            "Colour?\n---\n[1] (notes)\nThe sky is blue in {question}."
+        ),
+        other => panic!("unexpected request: {other:?}"),
+    }
+}
@@ -0,0 +1,251 @@
+#![cfg(feature = "server")]
+
+use std::sync::Arc;
+
+use futures::TryStreamExt;
+use ollama_oxide::openai_compat::*;
+use ollama_oxide::server::{router, ApiKey, ProxyConfig};
+use ollama_oxide::testing::{stub_embedding, StubResponse, StubServer, SAMPLE_COMPLETION};
+use ollama_oxide::OllamaClient;
+use serde_json::{json, Value};
+
+async fn start_proxy(upstream: &StubServer, config: ProxyConfig) -> String {
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("http://{}", listener.local_addr().unwrap());
+    let app = router(Arc::new(upstream.client()), config);
+    tokio::spawn(async move { axum::serve(listener, app).await });
+    url
+}
+
+fn keyed_config() -> ProxyConfig {
+    ProxyConfig::default()
+        .with_key("admin-key", ApiKey::default())
+        .with_key(
+            "team-key",
+            ApiKey {
+                name: Some("team".to_string()),
+                models: Some(vec!["llama3.2".to_string()]),
+            },
+        )
+}
+
+fn chat_body(model: &str, stream: bool) -> Value {
+    json!({
+        "model": model,
+        "messages": [{ "role": "user", "content": "Why is the sky blue?" }],
+        "stream": stream,
+        "stream_options": { "include_usage": true },
+    })
+}
+
+#[tokio::test]
+async fn chat_completion_is_translated_to_native_chat() {
+    let upstream = StubServer::start().await.unwrap();
+    let proxy = start_proxy(&upstream, ProxyConfig::default()).await;
+
+    let completion = OllamaClient::new(&proxy)
+        .openai()
+        .chat_completion(ChatCompletionRequest {
+            model: "llama3.2".to_string(),
+            messages: vec![OpenAiMessage {
+                role: "user".to_string(),
+                content: Some(MessageContent::Text("Hi".to_string())),
+                ..Default::default()
+            }],
+            temperature: Some(0.1),
+            tools: Some(vec![json!({
+                "type": "function",
+                "function": { "name": "get_weather", "parameters": { "type": "object" } },
+            })]),
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+
+    assert_eq!(
+        completion.choices[0].message.content,
+        Some(MessageContent::Text(SAMPLE_COMPLETION.concat()))
+    );
+    assert_eq!(completion.usage.unwrap().completion_tokens, 4);
+    let request = &upstream.requests()[0];
+    assert_eq!(request.path, "/api/chat");
+    assert_eq!(request.json()["options"]["temperature"], 0.1);
+    assert_eq!(
+        request.json()["tools"][0]["function"]["name"],
+        "get_weather"
+    );
+}
+
+#[tokio::test]
+async fn generation_limits_reach_upstream() {
+    let upstream = StubServer::start().await.unwrap();
+    let proxy = start_proxy(&upstream, ProxyConfig::default()).await;
+
+    let mut body = chat_body("llama3.2", false);
+    body["max_tokens"] = json!(32);
+    body["seed"] = json!(7);
+    body["stop"] = json!("\n");
+    let response = reqwest::Client::new()
+        .post(format!("{proxy}/v1/chat/completions"))
+        .json(&body)
+        .send()
+        .await
+        .unwrap();
+
+    assert_eq!(response.status(), 200);
+    let options = &upstream.requests()[0].json()["options"];
+    assert_eq!(options["num_predict"], 32);
+    assert_eq!(options["seed"], 7);
+    assert_eq!(options["stop"], json!(["\n"]));
+}
+
+#[tokio::test]
+async fn streamed_chat_is_sent_as_sse() {
+    let upstream = StubServer::start().await.unwrap();
+    let proxy = start_proxy(&upstream, ProxyConfig::default()).await;
+
+    let request: ChatCompletionRequest =
+        serde_json::from_value(chat_body("llama3.2", true)).unwrap();
+    let chunks: Vec<ChatCompletionChunk> = OllamaClient::new(&proxy)
+        .openai()
+        .chat_completion_stream(request)
+        .await
+        .unwrap()
+        .try_collect()
+        .await
+        .unwrap();
+
+    let text: String = chunks
+        .iter()
+        .flat_map(|chunk| &chunk.choices)
+        .filter_map(|choice| choice.delta.content.as_deref())
+        .collect();
+    assert_eq!(text, SAMPLE_COMPLETION.concat());
+    assert!(chunks.iter().all(|chunk| chunk.id == chunks[0].id));
+    let last = chunks.last().unwrap();
+    assert!(last.choices.is_empty());
+    assert_eq!(last.usage.as_ref().unwrap().prompt_tokens, 26);
+}
+
+#[tokio::test]
+async fn api_keys_and_allowlists_are_enforced() {
+    let upstream = StubServer::start().await.unwrap();
+    let proxy = start_proxy(&upstream, keyed_config()).await;
+    let http = reqwest::Client::new();
+    let post = |key: Option<&str>, model: &str| {
+        let mut request = http
+            .post(format!("{proxy}/v1/chat/completions"))
+            .json(&chat_body(model, false));
+        if let Some(key) = key {
+            request = request.bearer_auth(key);
+        }
+        request.send()
+    };
+
+    assert_eq!(post(None, "llama3.2").await.unwrap().status(), 401);
+    assert_eq!(post(Some("wrong"), "llama3.2").await.unwrap().status(), 401);
+    let forbidden = post(Some("team-key"), "mistral").await.unwrap();
+    assert_eq!(forbidden.status(), 403);
+    let body: Value = forbidden.json().await.unwrap();
+    assert_eq!(body["error"]["type"], "permission_error");
+
+    assert_eq!(
+        post(Some("team-key"), "llama3.2:latest")
+            .await
+            .unwrap()
+            .status(),
+        200
+    );
+    assert_eq!(
+        post(Some("admin-key"), "mistral").await.unwrap().status(),
+        200
+    );
+    assert_eq!(upstream.requests().len(), 2);
+}
+
+#[tokio::test]
+async fn upstream_client_errors_keep_their_status() {
+    let upstream = StubServer::start().await.unwrap();
+    upstream.respond(
+        "/api/chat",
+        StubResponse::error(404, "model 'nope' not found"),
+    );
+    let proxy = start_proxy(&upstream, ProxyConfig::default()).await;
+
+    let response = reqwest::Client::new()
+        .post(format!("{proxy}/v1/chat/completions"))
+        .json(&chat_body("nope", false))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 404);
+    let body: Value = response.json().await.unwrap();
+    assert_eq!(body["error"]["message"], "model 'nope' not found");
+    assert_eq!(body["error"]["type"], "invalid_request_error");
+
+    upstream.respond("/api/chat", StubResponse::error(500, "out of memory"));
+    let response = reqwest::Client::new()
+        .post(format!("{proxy}/v1/chat/completions"))
+        .json(&chat_body("llama3.2", false))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 502);
+}
+
+#[tokio::test]
+async fn models_are_filtered_by_key() {
+    let upstream = StubServer::start().await.unwrap();
+    let proxy = start_proxy(&upstream, keyed_config()).await;
+    let http = reqwest::Client::new();
+    let list = |key: &'static str| {
+        let request = http.get(format!("{proxy}/v1/models")).bearer_auth(key);
+        async move {
+            request
+                .send()
+                .await
+                .unwrap()
+                .json::<ModelList>()
+                .await
+                .unwrap()
+        }
+    };
+
+    let models = list("team-key").await;
+    assert_eq!(models.data.len(), 1);
+    assert_eq!(models.data[0].id, "llama3.2:latest");
+    assert_eq!(models.data[0].created, 1735689600);
+
+    upstream.respond(
+        "/api/tags",
+        ollama_oxide::testing::StubResponse::json(json!({ "models": [{
+            "name": "mistral:latest", "size": 1, "modified_at": "2025-01-01T00:00:00Z",
+            "digest": "sha256:0", "details": {
+                "format": "gguf", "family": "llama", "families": null,
+                "parameter_size": "7B", "quantization_level": "Q4_0"
+            }
+        }] })),
+    );
+    assert!(list("team-key").await.data.is_empty());
+    assert_eq!(list("admin-key").await.data.len(), 1);
+}
+
+#[tokio::test]
+async fn embeddings_are_translated() {
+    let upstream = StubServer::start().await.unwrap();
+    let proxy = start_proxy(&upstream, ProxyConfig::default()).await;
+
+    let response = OllamaClient::new(&proxy)
+        .openai()
+        .embeddings(EmbeddingsRequest {
+            model: "all-minilm".to_string(),
+            input: ollama_oxide::models::EmbedInput::Multiple(vec!["a".into(), "b".into()]),
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+
+    assert_eq!(response.data[1].embedding, stub_embedding("b"));
+    assert_eq!(response.usage.unwrap().prompt_tokens, 16);
+    assert_eq!(upstream.requests().pop().unwrap().path, "/api/embed");
+}
@@ -0,0 +1,292 @@
+use ollama_oxide::context::*;
+use ollama_oxide::mock::{MockOllama, RecordedRequest};
+use ollama_oxide::models::*;
+use ollama_oxide::session::*;
+use ollama_oxide::testing::{StubServer, SAMPLE_COMPLETION, STUB_CONTEXT_LENGTH};
+
+fn message(role: &str, content: &str) -> ChatMessage {
+    ChatMessage {
+        role: role.to_string(),
+        content: content.to_string(),
+        ..Default::default()
+    }
+}
+
+fn reply(content: &str, prompt_eval_count: Option<u32>) -> ChatResponse {
+    ChatResponse {
+        message: message("assistant", content),
+        done: true,
+        prompt_eval_count,
+        ..Default::default()
+    }
+}
+
+fn sent_messages(mock: &MockOllama) -> Vec<String> {
+    match mock.requests().pop() {
+        Some(RecordedRequest::Chat(request)) => {
+            request.messages.into_iter().map(|m| m.content).collect()
+        }
+        other => panic!("unexpected request: {other:?}"),
+    }
+}
+
+#[tokio::test]
+async fn budget_reads_context_length_from_model_info() {
+    let server = StubServer::start().await.unwrap();
+    let budget = ContextBudget::for_model(&server.client(), "llama3.2")
+        .await
+        .unwrap()
+        .with_reserve(96);
+
+    assert_eq!(budget.context_length(), STUB_CONTEXT_LENGTH);
+    assert_eq!(budget.available(), STUB_CONTEXT_LENGTH - 96);
+    assert_eq!(
+        budget.clone().with_num_ctx(Some(1024)).context_length(),
+        1024
+    );
+}
+
+#[test]
+fn estimates_use_pluggable_estimator_and_calibrate() {
+    let history = vec![message("user", "12345678")];
+    let heuristic = ContextBudget::new(100);
+    assert_eq!(heuristic.estimate(&history), MESSAGE_OVERHEAD + 2);
+
+    let mut budget = ContextBudget::new(100).with_estimator(|text: &str| text.len());
+    assert_eq!(budget.estimate(&history), MESSAGE_OVERHEAD + 8);
+
+    budget.calibrate(&history, 24);
+    assert_eq!(budget.scale(), Some(2.0));
+    assert_eq!(budget.estimate(&history), 24);
+
+    // A mostly cached prompt reports few tokens and is ignored
+    budget.calibrate(&history, 1);
+    assert_eq!(budget.scale(), Some(2.0));
+}
+
+#[test]
+fn trim_strategies() {
+    let history = vec![
+        message("system", "s"),
+        message("user", "one"),
+        message("assistant", "two"),
+        message("user", "three"),
+    ];
+    let budget = ContextBudget::new(10)
+        .with_reserve(0)
+        .with_estimator(|_: &str| 1);
+
+    let mut keep_system = history.clone();
+    let dropped = budget.trim(&mut keep_system);
+    assert_eq!(dropped.len(), 2);
+    let kept: Vec<&str> = keep_system.iter().map(|m| m.content.as_str()).collect();
+    assert_eq!(kept, ["s", "three"]);
+
+    let mut drop_oldest = history.clone();
+    budget
+        .clone()
+        .with_strategy(TrimStrategy::DropOldest)
+        .trim(&mut drop_oldest);
+    let kept: Vec<&str> = drop_oldest.iter().map(|m| m.content.as_str()).collect();
+    assert_eq!(kept, ["two", "three"]);
+
+    // The newest message is kept even when it doesn't fit on its own
+    let mut tiny = history;
+    ContextBudget::new(1).with_reserve(0).trim(&mut tiny);
+    assert_eq!(tiny.last().unwrap().content, "three");
+}
+
+#[tokio::test]
+async fn session_keeps_history_and_calibrates() {
+    let server = StubServer::start().await.unwrap();
+    let client = server.client();
+    let mut session = ChatSession::new("llama3.2")
+        .with_system("Be brief.")
+        .with_budget(ContextBudget::new(4096));
+
+    let answer = session.send(&client, "Why is the sky blue?").await.unwrap();
+    assert_eq!(answer.content, SAMPLE_COMPLETION.concat());
+    session.send(&client, "And at sunset?").await.unwrap();
+
+    assert_eq!(session.messages().len(), 5);
+    assert!(session.budget().unwrap().scale().is_some());
+    let body = server.requests().pop().unwrap().json();
+    assert_eq!(body["messages"].as_array().unwrap().len(), 4);
+}
+
+#[tokio::test]
+async fn session_summarizes_old_turns() {
+    let mock = MockOllama::new();
+    mock.on_chat(Ok(vec![Ok(reply("first answer", None))]))
+        .on_chat(Ok(vec![Ok(reply("they talked about the sky", None))]))
+        .on_chat(Ok(vec![Ok(reply("second answer", None))]));
+
+    let budget = ContextBudget::new(3 * (MESSAGE_OVERHEAD + 1))
+        .with_reserve(0)
+        .with_estimator(|_: &str| 1)
+        .with_strategy(TrimStrategy::Summarize);
+    let mut session = ChatSession::new("llama3.2")
+        .with_system("Be brief.")
+        .with_budget(budget);
+
+    session.send(&mock, "first question").await.unwrap();
+    session.send(&mock, "second question").await.unwrap();
+
+    assert_eq!(
+        sent_messages(&mock),
+        [
+            "Be brief.",
+            "Summary of the earlier conversation: they talked about the sky",
+            "second question"
+        ]
+    );
+    match &mock.requests()[1] {
+        RecordedRequest::Chat(request) => assert_eq!(
+            request.messages[1].content,
+            "user: first question\nassistant: first answer"
+        ),
+        other => panic!("unexpected request: {other:?}"),
+    }
+}
+
+fn tool_call(name: &str) -> ChatMessage {
+    ChatMessage {
+        tool_calls: Some(vec![ToolCall {
+            function: FunctionCall {
+                name: name.to_string(),
+                arguments: serde_json::json!({ "city": "Paris" }),
+            },
+        }]),
+        ..message("assistant", "")
+    }
+}
+
+#[test]
+fn trim_drops_tool_calls_with_their_results() {
+    let budget = ContextBudget::new(4 * (MESSAGE_OVERHEAD + 1))
+        .with_reserve(0)
+        .with_estimator(|_: &str| 1);
+    let mut history = vec![
+        message("system", "s"),
+        message("user", "q"),
+        tool_call("get_weather"),
+        message("tool", "sunny"),
+        message("tool", "22C"),
+        message("user", "next"),
+    ];
+    let dropped = budget.trim(&mut history);
+    assert_eq!(dropped.len(), 4);
+    let roles: Vec<&str> = history.iter().map(|m| m.role.as_str()).collect();
+    assert_eq!(roles, ["system", "user"]);
+
+    // Results up to the newest message keep their tool call
+    let mut history = vec![
+        message("system", "s"),
+        message("user", "q"),
+        tool_call("get_weather"),
+        message("tool", "sunny"),
+        message("tool", "22C"),
+    ];
+    let dropped = budget.trim(&mut history);
+    assert_eq!(dropped.len(), 1);
+    let roles: Vec<&str> = history.iter().map(|m| m.role.as_str()).collect();
+    assert_eq!(roles, ["system", "assistant", "tool", "tool"]);
+}
+
+#[tokio::test]
+async fn summarize_history_keeps_tool_pairs_together() {
+    let mock = MockOllama::new();
+    mock.on_chat(Ok(vec![Ok(reply("asked about weather", None))]));
+    let mut history = vec![
+        message("system", "Be brief."),
+        message("user", "Weather in Paris?"),
+        tool_call("get_weather"),
+        message("tool", "sunny"),
+        message("tool", "22C"),
+    ];
+
+    let options = SummaryOptions::new("summarizer")
+        .with_role("assistant")
+        .with_prompt("Summarize.");
+    let replaced = summarize_history(&mock, &mut history, 1, &options)
+        .await
+        .unwrap();
+
+    assert_eq!(replaced, 1);
+    let roles: Vec<&str> = history.iter().map(|m| m.role.as_str()).collect();
+    assert_eq!(roles, ["system", "assistant", "assistant", "tool", "tool"]);
+    assert_eq!(
+        history[1].content,
+        format!("{SUMMARY_PREFIX}asked about weather")
+    );
+    match &mock.requests()[0] {
+        RecordedRequest::Chat(request) => {
+            assert_eq!(request.model, "summarizer");
+            assert_eq!(request.messages[0].content, "Summarize.");
+            assert_eq!(request.messages[1].content, "user: Weather in Paris?");
+        }
+        other => panic!("unexpected request: {other:?}"),
+    }
+}
+
+#[tokio::test]
+async fn summarize_history_folds_previous_summary() {
+    let mock = MockOllama::new();
+    mock.on_chat(Ok(vec![Ok(reply("everything so far", None))]));
+    let mut history = vec![
+        message("system", "Be brief."),
+        message("system", &format!("{SUMMARY_PREFIX}earlier things")),
+        message("user", "more"),
+        message("assistant", "ok"),
+        message("user", "latest"),
+    ];
+
+    summarize_history(&mock, &mut history, 1, &SummaryOptions::new("m"))
+        .await
+        .unwrap();
+
+    assert_eq!(history.len(), 3);
+    assert_eq!(
+        history[1].content,
+        format!("{SUMMARY_PREFIX}everything so far")
+    );
+    assert!(sent_messages(&mock)[1].starts_with(&format!("system: {SUMMARY_PREFIX}earlier things")));
+
+    // Nothing left to summarize but the summary itself
+    assert_eq!(
+        summarize_history(&mock, &mut history, 1, &SummaryOptions::new("m"))
+            .await
+            .unwrap(),
+        0
+    );
+}
+
+#[tokio::test]
+async fn session_summarizes_past_threshold() {
+    let mock = MockOllama::new();
+    for content in ["a1", "a2", "summary", "a3"] {
+        mock.on_chat(Ok(vec![Ok(reply(content, None))]));
+    }
+    let budget = ContextBudget::new(40)
+        .with_reserve(0)
+        .with_estimator(|_: &str| 6);
+    let mut session = ChatSession::new("llama3.2")
+        .with_budget(budget)
+        .with_summarization(SummaryOptions::new("small").with_threshold(0.9));
+
+    session.send(&mock, "q1").await.unwrap();
+    session.send(&mock, "q2").await.unwrap();
+    assert_eq!(session.messages().len(), 4);
+    session.send(&mock, "q3").await.unwrap();
+
+    // 50 estimated tokens passed 90% of the window; the newest half is kept
+    let contents: Vec<&str> = session
+        .messages()
+        .iter()
+        .map(|m| m.content.as_str())
+        .collect();
+    assert_eq!(
+        contents,
+        [&format!("{SUMMARY_PREFIX}summary"), "a2", "q3", "a3"]
+    );
+}
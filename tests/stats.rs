@@ -0,0 +1,114 @@
+use futures::{StreamExt, TryStreamExt};
+use ollama_oxide::models::*;
+use ollama_oxide::openai_compat::ChatCompletion;
+use ollama_oxide::stats::split_stats;
+use ollama_oxide::stop::{FinishReason, StopOptions, StopStream};
+use ollama_oxide::testing::{StubResponse, StubServer, SAMPLE_COMPLETION};
+use serde_json::json;
+
+fn generate_request() -> GenerateRequest {
+    GenerateRequest {
+        model: "llama3.2".to_string(),
+        prompt: "Why is the sky blue?".to_string(),
+        ..Default::default()
+    }
+}
+
+fn chat_request() -> ChatRequest {
+    ChatRequest {
+        model: "llama3.2".to_string(),
+        messages: vec![ChatMessage {
+            role: "user".to_string(),
+            content: "Hi".to_string(),
+            ..Default::default()
+        }],
+        ..Default::default()
+    }
+}
+
+/// A chat reply cut off by `num_predict`.
+fn truncated_chat() -> StubResponse {
+    let chunk = |content: &str, done: bool| {
+        json!({
+            "model": "m",
+            "created_at": "",
+            "message": { "role": "assistant", "content": content },
+            "done": done,
+        })
+    };
+    let mut last = chunk("", true);
+    last["done_reason"] = json!("length");
+    last["eval_count"] = json!(2);
+    StubResponse::ndjson(vec![chunk("The sky", false), last])
+}
+
+#[tokio::test]
+async fn stats_resolve_separately_from_content() {
+    let server = StubServer::start().await.unwrap();
+    let (chunks, stats) = split_stats(server.client().generate(generate_request()).await.unwrap());
+    let content = tokio::spawn(async move {
+        chunks
+            .map_ok(|chunk| chunk.response)
+            .try_collect::<String>()
+            .await
+            .unwrap()
+    });
+
+    let stats = stats.await.unwrap();
+    assert_eq!(content.await.unwrap(), SAMPLE_COMPLETION.concat());
+    assert_eq!(stats.done_reason, Some(DoneReason::Stop));
+    assert!(!stats.truncated());
+    assert_eq!(stats.eval_count, Some(SAMPLE_COMPLETION.len() as u32));
+    assert_eq!(stats.prompt_eval_count, Some(26));
+    let rate = stats.tokens_per_second().unwrap();
+    assert!((rate - 4.0 / 2.232).abs() < 1e-9);
+    assert!(stats.prompt_tokens_per_second().unwrap() > 0.0);
+}
+
+#[tokio::test]
+async fn length_done_reason_marks_truncation() {
+    let server = StubServer::start().await.unwrap();
+    server.respond("/api/chat", truncated_chat());
+    let chunks: Vec<ChatResponse> = server
+        .client()
+        .chat(chat_request())
+        .await
+        .unwrap()
+        .try_collect()
+        .await
+        .unwrap();
+    let last = chunks.last().unwrap();
+    assert!(last.stats().unwrap().truncated());
+    assert_eq!(chunks[0].stats(), None);
+    assert_eq!(
+        ChatCompletion::from(chunks.into_iter().last().unwrap()).choices[0]
+            .finish_reason
+            .as_deref(),
+        Some("length")
+    );
+
+    server.respond("/api/chat", truncated_chat());
+    let upstream = server.client().chat(chat_request()).await.unwrap();
+    let chunks: Vec<_> = StopStream::new(upstream, StopOptions::default())
+        .try_collect()
+        .await
+        .unwrap();
+    assert_eq!(
+        chunks.last().unwrap().finish_reason,
+        Some(FinishReason::Length)
+    );
+}
+
+#[tokio::test]
+async fn stats_are_none_without_a_final_chunk() {
+    let server = StubServer::start().await.unwrap();
+    let (chunks, stats) = split_stats(server.client().generate(generate_request()).await.unwrap());
+    let mut chunks = Box::pin(chunks);
+    chunks.next().await.unwrap().unwrap();
+    drop(chunks);
+    assert_eq!(stats.await, None);
+
+    let reason: DoneReason = serde_json::from_value(json!("evicted")).unwrap();
+    assert_eq!(reason, DoneReason::Other("evicted".to_string()));
+    assert_eq!(serde_json::to_value(&reason).unwrap(), json!("evicted"));
+}
@@ -0,0 +1,117 @@
+use futures::{StreamExt, TryStreamExt};
+use ollama_oxide::models::{ChatMessage, ChatRequest, GenerateRequest, GenerateResponse};
+use ollama_oxide::stop::*;
+use ollama_oxide::testing::{StubResponse, StubServer, SAMPLE_COMPLETION};
+use serde_json::json;
+
+fn generate_request() -> GenerateRequest {
+    GenerateRequest {
+        model: "llama3.2".to_string(),
+        prompt: "Why is the sky blue?".to_string(),
+        ..Default::default()
+    }
+}
+
+async fn collect<S>(stream: S) -> Vec<StopChunk<GenerateResponse>>
+where
+    S: futures::Stream<
+        Item = Result<StopChunk<GenerateResponse>, ollama_oxide::error::OllamaError>,
+    >,
+{
+    stream.try_collect().await.unwrap()
+}
+
+fn texts(chunks: &[StopChunk<GenerateResponse>]) -> Vec<&str> {
+    chunks
+        .iter()
+        .map(|chunk| chunk.chunk.response.as_str())
+        .collect()
+}
+
+#[tokio::test]
+async fn stops_on_sequences_spanning_chunks() {
+    let server = StubServer::start().await.unwrap();
+    let lines = ["Héllo", " wor", "ld\n\nUs", "er: hi", " there", ""]
+        .iter()
+        .enumerate()
+        .map(|(i, text)| {
+            json!({
+                "model": "m",
+                "created_at": "",
+                "response": text,
+                "done": i == 5,
+            })
+        })
+        .collect();
+    server.respond("/api/generate", StubResponse::ndjson(lines));
+
+    let upstream = server.client().generate(generate_request()).await.unwrap();
+    let chunks = collect(StopStream::new(upstream, StopOptions::new(["\n\nUser:"]))).await;
+
+    assert_eq!(texts(&chunks), ["Héllo", " wor", "ld", ""]);
+    let last = chunks.last().unwrap();
+    assert!(last.chunk.done);
+    assert_eq!(last.finish_reason, Some(FinishReason::ClientStop));
+    assert!(chunks[..3]
+        .iter()
+        .all(|chunk| chunk.finish_reason.is_none()));
+}
+
+#[tokio::test]
+async fn flushes_held_text_when_the_server_finishes() {
+    let server = StubServer::start().await.unwrap();
+    let upstream = server.client().generate(generate_request()).await.unwrap();
+    let chunks = collect(StopStream::new(upstream, StopOptions::new(["blue.!"]))).await;
+
+    assert_eq!(texts(&chunks), ["The", " sky", " is", " ", "blue."]);
+    let last = chunks.last().unwrap();
+    assert_eq!(last.finish_reason, Some(FinishReason::Stop));
+    assert_eq!(last.chunk.eval_count, Some(SAMPLE_COMPLETION.len() as u32));
+}
+
+#[tokio::test]
+async fn enforces_character_budget_on_chat() {
+    let server = StubServer::start().await.unwrap();
+    let request = ChatRequest {
+        model: "llama3.2".to_string(),
+        messages: vec![ChatMessage {
+            role: "user".to_string(),
+            content: "Hi".to_string(),
+            ..Default::default()
+        }],
+        ..Default::default()
+    };
+    let upstream = server.client().chat(request).await.unwrap();
+    let chunks: Vec<_> = StopStream::new(upstream, StopOptions::default().with_max_chars(8))
+        .try_collect()
+        .await
+        .unwrap();
+
+    let text: String = chunks
+        .iter()
+        .map(|chunk| chunk.chunk.message.content.as_str())
+        .collect();
+    assert_eq!(text, "The sky ");
+    assert_eq!(chunks.len(), 3);
+    assert_eq!(chunks[2].finish_reason, Some(FinishReason::Length));
+}
+
+#[tokio::test]
+async fn cancel_ends_with_held_text() {
+    let server = StubServer::start().await.unwrap();
+    let upstream = server.client().generate(generate_request()).await.unwrap();
+    let mut stream = StopStream::new(upstream, StopOptions::new([" sky!"]));
+
+    let first = stream.next().await.unwrap().unwrap();
+    assert_eq!(first.chunk.response, "The");
+    // " sky" could start the stop sequence, so it is held back
+    let second = stream.next().await.unwrap().unwrap();
+    assert_eq!(second.chunk.response, "");
+
+    stream.cancel();
+    let last = stream.next().await.unwrap().unwrap();
+    assert_eq!(last.chunk.response, " sky");
+    assert!(last.chunk.done);
+    assert_eq!(last.finish_reason, Some(FinishReason::Cancelled));
+    assert!(stream.next().await.is_none());
+}
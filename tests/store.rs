@@ -0,0 +1,191 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use ollama_oxide::store::*;
+use serde_json::json;
+use sha2::{Digest, Sha256};
+
+const CONFIG: &str = r#"{"model_format":"gguf","model_family":"llama","model_families":["llama"],"model_type":"3.2B","file_type":"Q4_K_M"}"#;
+
+fn write_blob(root: &Path, media_type: &str, contents: &[u8]) -> serde_json::Value {
+    let digest = format!("sha256:{:x}", Sha256::digest(contents));
+    fs::write(root.join("blobs").join(digest.replace(':', "-")), contents).unwrap();
+    json!({ "mediaType": media_type, "digest": digest, "size": contents.len() })
+}
+
+fn write_manifest(
+    root: &Path,
+    path: &str,
+    config: &serde_json::Value,
+    layers: &[&serde_json::Value],
+) {
+    let path = root.join("manifests").join(path);
+    fs::create_dir_all(path.parent().unwrap()).unwrap();
+    let manifest = json!({
+        "schemaVersion": 2,
+        "mediaType": "application/vnd.docker.distribution.manifest.v2+json",
+        "config": config,
+        "layers": layers,
+    });
+    fs::write(path, manifest.to_string()).unwrap();
+}
+
+/// Two models sharing their weights, one orphaned blob and a partial
+/// download.
+fn sample_store(name: &str) -> (PathBuf, ModelStore) {
+    let root = std::env::temp_dir().join(format!(
+        "ollama-oxide-store-{}-{}",
+        std::process::id(),
+        name
+    ));
+    let _ = fs::remove_dir_all(&root);
+    fs::create_dir_all(root.join("blobs")).unwrap();
+
+    let config = write_blob(
+        &root,
+        "application/vnd.docker.container.image.v1+json",
+        CONFIG.as_bytes(),
+    );
+    let weights = write_blob(&root, "application/vnd.ollama.image.model", &[7; 1000]);
+    let template = write_blob(
+        &root,
+        "application/vnd.ollama.image.template",
+        b"{{ .Prompt }}",
+    );
+    let system = write_blob(
+        &root,
+        "application/vnd.ollama.image.system",
+        b"You are terse.",
+    );
+    write_manifest(
+        &root,
+        "registry.ollama.ai/library/llama3.2/latest",
+        &config,
+        &[&weights, &template],
+    );
+    write_manifest(
+        &root,
+        "registry.ollama.ai/team/assistant/v1",
+        &config,
+        &[&weights, &template, &system],
+    );
+    write_blob(&root, "", b"left behind");
+    fs::write(
+        root.join("blobs")
+            .join(format!("sha256-{}-partial", "0".repeat(64))),
+        b"...",
+    )
+    .unwrap();
+
+    let store = ModelStore::open(&root);
+    (root, store)
+}
+
+#[test]
+fn lists_models_like_the_server() {
+    let (root, store) = sample_store("list");
+    let models = store.list_models().unwrap();
+    fs::remove_dir_all(root).unwrap();
+
+    let names: Vec<&str> = models.iter().map(|model| model.name.as_str()).collect();
+    assert_eq!(names, ["llama3.2:latest", "team/assistant:v1"]);
+    let llama = &models[0];
+    assert_eq!(llama.size, CONFIG.len() as u64 + 1000 + 13);
+    assert_eq!(llama.digest.len(), 64);
+    assert_eq!(llama.details.family, "llama");
+    assert_eq!(llama.details.parameter_size, "3.2B");
+    assert_eq!(llama.details.quantization_level, "Q4_K_M");
+    assert!(llama.modified_at.ends_with('Z'));
+}
+
+#[test]
+fn computes_shared_usage_and_orphans() {
+    let (root, store) = sample_store("usage");
+    let usage = store.disk_usage().unwrap();
+    fs::remove_dir_all(root).unwrap();
+
+    let shared = CONFIG.len() as u64 + 1000 + 13;
+    let llama = &usage.models[0];
+    assert_eq!(
+        (llama.size, llama.unique, llama.shared),
+        (shared, 0, shared)
+    );
+    let assistant = &usage.models[1];
+    assert_eq!(assistant.unique, 14);
+    assert_eq!(usage.shared.len(), 3);
+    assert_eq!(
+        usage.shared[0].models,
+        ["llama3.2:latest", "team/assistant:v1"]
+    );
+    assert_eq!(usage.orphaned.len(), 1);
+    assert_eq!(usage.orphaned[0].size, 11);
+    assert_eq!(usage.total, shared + 14 + 11);
+}
+
+#[test]
+fn verify_detects_corrupt_and_missing_blobs() {
+    let (root, store) = sample_store("verify");
+    let models = store.models().unwrap();
+    let weights = models[0].manifest.layers[0].digest.clone();
+    let template = models[0].manifest.layers[1].digest.clone();
+    fs::write(store.blob_path(&weights), [8; 1000]).unwrap();
+    fs::remove_file(store.blob_path(&template)).unwrap();
+
+    let checks = store.verify().unwrap();
+    let truncated = store.verify_blob(&weights, Some(999)).unwrap();
+    fs::remove_dir_all(root).unwrap();
+
+    let status = |digest: &str| {
+        &checks
+            .iter()
+            .find(|check| check.digest == digest)
+            .unwrap()
+            .status
+    };
+    assert!(matches!(
+        status(&weights),
+        BlobStatus::DigestMismatch { .. }
+    ));
+    assert_eq!(*status(&template), BlobStatus::Missing);
+    assert_eq!(
+        checks
+            .iter()
+            .filter(|check| check.status == BlobStatus::Ok)
+            .count(),
+        2
+    );
+    assert_eq!(
+        truncated,
+        BlobStatus::SizeMismatch {
+            expected: 999,
+            actual: 1000
+        }
+    );
+}
+
+#[test]
+fn skips_and_reports_invalid_manifests() {
+    let (root, store) = sample_store("invalid");
+    let broken = root.join("manifests/registry.ollama.ai/library/broken/latest");
+    fs::create_dir_all(broken.parent().unwrap()).unwrap();
+    fs::write(&broken, "{\"schemaVersion\":").unwrap();
+
+    let names: Vec<_> = store
+        .list_models()
+        .unwrap()
+        .into_iter()
+        .map(|model| model.name)
+        .collect();
+    let invalid = store.invalid_manifests().unwrap();
+    let usage = store.disk_usage().unwrap();
+    let checks = store.verify().unwrap();
+    fs::remove_dir_all(root).unwrap();
+
+    assert_eq!(names, ["llama3.2:latest", "team/assistant:v1"]);
+    assert_eq!(invalid.len(), 1);
+    assert_eq!(invalid[0].path, broken);
+    assert!(invalid[0].error.contains("EOF"));
+    assert_eq!(usage.models.len(), 2);
+    assert_eq!(usage.invalid_manifests.len(), 1);
+    assert_eq!(checks.len(), 4);
+}
@@ -0,0 +1,178 @@
+use std::collections::HashMap;
+use std::fmt::Debug;
+use std::sync::{Arc, Mutex};
+
+use futures::TryStreamExt;
+use ollama_oxide::models::*;
+use ollama_oxide::testing::{StubResponse, StubServer, SAMPLE_COMPLETION};
+use ollama_oxide::OllamaClient;
+use tracing::field::{Field, Visit};
+use tracing::span::{Attributes, Id, Record};
+use tracing::{Event, Metadata, Subscriber};
+use tracing_core::span::Current;
+
+type Fields = HashMap<String, String>;
+
+/// Collects the fields of every `ollama` span and tracks the entered spans,
+/// so `Span::current()` works.
+#[derive(Clone, Default)]
+struct Collector {
+    spans: Arc<Mutex<Vec<(&'static Metadata<'static>, Fields)>>>,
+    stack: Arc<Mutex<Vec<Id>>>,
+}
+
+struct Visitor<'a>(&'a mut Fields);
+
+impl Visit for Visitor<'_> {
+    fn record_str(&mut self, field: &Field, value: &str) {
+        self.0.insert(field.name().to_string(), value.to_string());
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn Debug) {
+        self.0
+            .insert(field.name().to_string(), format!("{:?}", value));
+    }
+}
+
+impl Subscriber for Collector {
+    fn enabled(&self, _: &Metadata<'_>) -> bool {
+        true
+    }
+
+    fn new_span(&self, attributes: &Attributes<'_>) -> Id {
+        let mut spans = self.spans.lock().unwrap();
+        let mut fields = Fields::new();
+        if attributes.metadata().name() == "ollama" {
+            attributes.record(&mut Visitor(&mut fields));
+        }
+        spans.push((attributes.metadata(), fields));
+        Id::from_u64(spans.len() as u64)
+    }
+
+    fn record(&self, span: &Id, values: &Record<'_>) {
+        let mut spans = self.spans.lock().unwrap();
+        let (_, fields) = &mut spans[span.into_u64() as usize - 1];
+        if !fields.is_empty() {
+            values.record(&mut Visitor(fields));
+        }
+    }
+
+    fn record_follows_from(&self, _: &Id, _: &Id) {}
+    fn event(&self, _: &Event<'_>) {}
+
+    fn enter(&self, span: &Id) {
+        self.stack.lock().unwrap().push(span.clone());
+    }
+
+    fn exit(&self, span: &Id) {
+        let mut stack = self.stack.lock().unwrap();
+        if let Some(position) = stack.iter().rposition(|entered| entered == span) {
+            stack.remove(position);
+        }
+    }
+
+    fn current_span(&self) -> Current {
+        match self.stack.lock().unwrap().last() {
+            Some(id) => {
+                let metadata = self.spans.lock().unwrap()[id.into_u64() as usize - 1].0;
+                Current::new(id.clone(), metadata)
+            }
+            None => Current::none(),
+        }
+    }
+}
+
+impl Collector {
+    fn span(&self, otel_name: &str) -> Fields {
+        self.spans
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(_, fields)| fields)
+            .find(|fields| fields.get("otel.name").map(String::as_str) == Some(otel_name))
+            .cloned()
+            .unwrap_or_else(|| panic!("no span named {otel_name}"))
+    }
+}
+
+async fn chat(client: &OllamaClient) {
+    let request = ChatRequest {
+        model: "llama3.2".to_string(),
+        messages: vec![ChatMessage {
+            role: "user".to_string(),
+            content: "Why is the sky blue?".to_string(),
+            ..Default::default()
+        }],
+        options: Some(GenerateOptions {
+            temperature: Some(0.5),
+            ..Default::default()
+        }),
+        ..Default::default()
+    };
+    let _: Vec<ChatResponse> = client
+        .chat(request)
+        .await
+        .unwrap()
+        .try_collect()
+        .await
+        .unwrap();
+}
+
+#[tokio::test]
+async fn chat_span_carries_genai_attributes() {
+    let collector = Collector::default();
+    let _guard = tracing::subscriber::set_default(collector.clone());
+    let server = StubServer::start().await.unwrap();
+
+    chat(&server.client()).await;
+
+    let span = collector.span("chat llama3.2");
+    assert_eq!(span["gen_ai.operation.name"], "chat");
+    assert_eq!(span["gen_ai.system"], "ollama");
+    assert_eq!(span["gen_ai.request.model"], "llama3.2");
+    assert_eq!(span["gen_ai.request.temperature"], "0.5");
+    assert_eq!(span["gen_ai.response.model"], "llama3.2");
+    assert_eq!(span["gen_ai.usage.input_tokens"], "26");
+    assert_eq!(
+        span["gen_ai.usage.output_tokens"],
+        SAMPLE_COMPLETION.len().to_string()
+    );
+    assert_eq!(span["http.request.method"], "POST");
+    assert_eq!(span["http.response.status_code"], "200");
+    assert_eq!(span["url.path"], "/api/chat");
+    assert_eq!(span["ollama.response.eval_duration"], "2.232");
+    assert!(span.contains_key("ollama.response.time_to_first_token"));
+    assert!(!span.contains_key("gen_ai.input.messages"));
+    assert!(!span.contains_key("gen_ai.output.messages"));
+}
+
+#[tokio::test]
+async fn content_is_recorded_only_when_enabled() {
+    let collector = Collector::default();
+    let _guard = tracing::subscriber::set_default(collector.clone());
+    let server = StubServer::start().await.unwrap();
+
+    chat(&server.client().with_content_capture(true)).await;
+
+    let span = collector.span("chat llama3.2");
+    assert!(span["gen_ai.input.messages"].contains("Why is the sky blue?"));
+    assert!(span["gen_ai.output.messages"].contains(&SAMPLE_COMPLETION.concat()));
+}
+
+#[tokio::test]
+async fn failed_call_records_error() {
+    let collector = Collector::default();
+    let _guard = tracing::subscriber::set_default(collector.clone());
+    let server = StubServer::start().await.unwrap();
+    server.respond(
+        "/api/show",
+        StubResponse::error(404, "model 'nope' not found"),
+    );
+
+    server.client().show_model("nope").await.unwrap_err();
+
+    let span = collector.span("show_model nope");
+    assert_eq!(span["otel.status_code"], "ERROR");
+    assert_eq!(span["error.type"], "api_error");
+    assert_eq!(span["http.response.status_code"], "404");
+}
@@ -0,0 +1,172 @@
+use ollama_oxide::error::OllamaError;
+use ollama_oxide::models::{ChatMessage, FunctionCall, GenerateRequest, ToolCall};
+use ollama_oxide::template::Template;
+use ollama_oxide::testing::StubServer;
+use serde_json::json;
+
+const LLAMA3: &str = r#"{{- if .System }}<|start_header_id|>system<|end_header_id|>
+
+{{ .System }}<|eot_id|>
+{{- end }}
+{{- range $i, $_ := .Messages }}
+{{- $last := eq (len (slice $.Messages $i)) 1 }}
+{{- if eq .Role "user" }}<|start_header_id|>user<|end_header_id|>
+
+{{ .Content }}<|eot_id|>{{ if $last }}<|start_header_id|>assistant<|end_header_id|>
+
+{{ end }}
+{{- else if eq .Role "assistant" }}<|start_header_id|>assistant<|end_header_id|>
+
+{{ .Content }}{{ if not $last }}<|eot_id|>{{ end }}
+{{- end }}
+{{- end }}"#;
+
+const CHATML: &str = "{{ if .System }}<|im_start|>system
+{{ .System }}<|im_end|>
+{{ end }}{{ if .Prompt }}<|im_start|>user
+{{ .Prompt }}<|im_end|>
+{{ end }}<|im_start|>assistant
+{{ .Response }}<|im_end|>
+";
+
+fn message(role: &str, content: &str) -> ChatMessage {
+    ChatMessage {
+        role: role.to_string(),
+        content: content.to_string(),
+        ..Default::default()
+    }
+}
+
+fn conversation() -> Vec<ChatMessage> {
+    vec![
+        message("system", "Be brief."),
+        message("user", "Hi"),
+        message("assistant", "Hello!"),
+        message("user", "Why is the sky blue?"),
+    ]
+}
+
+#[test]
+fn renders_messages_templates() {
+    let template = Template::parse(LLAMA3).unwrap();
+    assert!(template.uses_messages());
+
+    assert_eq!(
+        template.render(&conversation(), &[]).unwrap(),
+        "<|start_header_id|>system<|end_header_id|>\n\nBe brief.<|eot_id|>\
+         <|start_header_id|>user<|end_header_id|>\n\nHi<|eot_id|>\
+         <|start_header_id|>assistant<|end_header_id|>\n\nHello!<|eot_id|>\
+         <|start_header_id|>user<|end_header_id|>\n\nWhy is the sky blue?<|eot_id|>\
+         <|start_header_id|>assistant<|end_header_id|>\n\n"
+    );
+}
+
+#[tokio::test]
+async fn renders_legacy_templates_for_raw_generation() {
+    let template = Template::parse(CHATML).unwrap();
+    assert!(!template.uses_messages());
+    let prompt = template.render(&conversation(), &[]).unwrap();
+    assert_eq!(
+        prompt,
+        "<|im_start|>system\nBe brief.<|im_end|>\n\
+         <|im_start|>user\nHi<|im_end|>\n\
+         <|im_start|>assistant\nHello!<|im_end|>\n\
+         <|im_start|>user\nWhy is the sky blue?<|im_end|>\n\
+         <|im_start|>assistant\n"
+    );
+
+    let server = StubServer::start().await.unwrap();
+    let request = GenerateRequest {
+        model: "qwen2.5".to_string(),
+        prompt: prompt.clone(),
+        stream: Some(false),
+        raw: Some(true),
+        ..Default::default()
+    };
+    let _ = server.client().generate(request).await.unwrap();
+    let body = server.requests()[0].json();
+    assert_eq!(body["raw"], true);
+    assert_eq!(body["prompt"], prompt.as_str());
+}
+
+#[test]
+fn renders_tools_and_tool_calls() {
+    let template = Template::parse(
+        r#"{{- if .Tools }}[TOOLS]{{ range .Tools }}{{ . }}{{ end }}[/TOOLS]
+{{ end }}
+{{- range .Messages }}
+{{- if .ToolCalls }}[CALLS]{{ range .ToolCalls }}{"name": "{{ .Function.Name }}", "arguments": {{ .Function.Arguments }}}{{ end }}[/CALLS]
+{{ else if eq .Role "tool" }}[RESULT]{{ .Content }}[/RESULT]
+{{ else }}{{ .Role }}: {{ .Content }}
+{{ end }}
+{{- end }}"#,
+    )
+    .unwrap();
+    let call = ChatMessage {
+        tool_calls: Some(vec![ToolCall {
+            function: FunctionCall {
+                name: "weather".to_string(),
+                arguments: json!({ "city": "Paris" }),
+            },
+        }]),
+        ..message("assistant", "")
+    };
+    let messages = vec![
+        message("user", "Weather in Paris?"),
+        call,
+        message("tool", "22C"),
+        message("tool", "sunny"),
+        message("user", "Thanks."),
+        message("user", "Bye."),
+    ];
+    let tools = [json!({ "type": "function", "function": { "name": "weather" } })];
+
+    assert_eq!(
+        template.render(&messages, &tools).unwrap(),
+        "[TOOLS]{\"function\":{\"name\":\"weather\"},\"type\":\"function\"}[/TOOLS]\n\
+         user: Weather in Paris?\n\
+         [CALLS]{\"name\": \"weather\", \"arguments\": {\"city\":\"Paris\"}}[/CALLS]\n\
+         [RESULT]22C[/RESULT]\n\
+         [RESULT]sunny[/RESULT]\n\
+         user: Thanks.\n\nBye.\n"
+    );
+}
+
+#[test]
+fn evaluates_functions_and_reports_errors() {
+    let template = Template::parse(
+        r#"{{ $n := 0 }}{{ range $i, $v := .Items }}{{ if gt $v 2 }}{{ break }}{{ end }}{{ $n = $v }}{{ $v }},{{ end }}{{ $n }}
+{{- /* maps iterate in key order */ -}}
+|{{ range $k, $v := .Map }}{{ $k }}={{ $v }};{{ end }}
+|{{ with .Name }}{{ print . 1 2 }}{{ end }}|{{ len "héllo" }}|{{ slice "hello" 1 3 }}|{{ (index .Items 1) }}
+|{{ and 1 0 }}|{{ or "" "y" }}|{{ .Name | len }}|{{ .Missing }}|{{ if .Empty }}no{{ else }}yes{{ end }}"#,
+    )
+    .unwrap();
+    let data =
+        json!({ "Items": [1, 2, 3, 4], "Map": { "b": 2, "a": 1 }, "Name": "x", "Empty": [] });
+    assert_eq!(
+        template.execute(&data).unwrap(),
+        "1,2,2|a=1;b=2;\n|x1 2|6|el|2\n|0|y|1|<no value>|yes"
+    );
+
+    for (source, message) in [
+        ("{{ if .X }}a", "missing {{end}} for {{if}}"),
+        ("{{ end }}", "unexpected {{end}}"),
+        ("{{ upper .X }}", "function \"upper\" not defined"),
+        ("{{ break }}", "{{break}} outside {{range}}"),
+        ("{{ .X", "unclosed action"),
+    ] {
+        let err = Template::parse(source).unwrap_err();
+        assert!(
+            matches!(&err, OllamaError::Template(m) if m == message),
+            "{}: {}",
+            source,
+            err
+        );
+    }
+    let err = Template::parse("{{ len 3 }}")
+        .unwrap()
+        .execute(&json!({}))
+        .unwrap_err();
+    assert!(matches!(err, OllamaError::Template(m) if m == "len of 3"));
+}